@@ -33,7 +33,9 @@ class Ffi {
     _ => throw UnimplementedError('Unsupported platform'),
   };
 
-  static void init(String databasePath, List<Repository> repositories) {
+  /// Opens the database at [databasePath] with the constraints of
+  /// [repositories], returning its handle.
+  static int open(String databasePath, List<Repository> repositories) {
     for (final repository in repositories) {
       final schema = repository.init();
       for (final label in schema.stickyNodes) {
@@ -50,7 +52,10 @@ class Ffi {
       }
     }
     final ptr = databasePath.toNativeUtf8(allocator: malloc);
-    bindings.dust_open(ptr.length, ptr.cast<Uint8>());
-    malloc.free(ptr);
+    try {
+      return bindings.dust_open(ptr.length, ptr.cast<Uint8>());
+    } finally {
+      malloc.free(ptr);
+    }
   }
 }
@@ -48,6 +48,19 @@ class NativeBindings {
     throw NativeError(message);
   }
 
+  CUnit dust_acknowledge(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_acknowledge(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_acknowledgePtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_acknowledge');
+  late final _dust_acknowledge = _dust_acknowledgePtr
+      .asFunction<CResultUnit Function(int, int, Pointer<Uint8>)>();
+
   void dust_add_acyclic_edge(int label) {
     return _dust_add_acyclic_edge(label);
   }
@@ -57,6 +70,18 @@ class NativeBindings {
   late final _dust_add_acyclic_edge =
       _dust_add_acyclic_edgePtr.asFunction<void Function(int)>(isLeaf: true);
 
+  CUnit dust_add_peer(int handle, int peer) {
+    final res = _dust_add_peer(handle, peer);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_add_peerPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64)>>(
+          'dust_add_peer');
+  late final _dust_add_peer = _dust_add_peerPtr
+      .asFunction<CResultUnit Function(int, int)>(isLeaf: true);
+
   void dust_add_sticky_atom(int label) {
     return _dust_add_sticky_atom(label);
   }
@@ -84,21 +109,21 @@ class NativeBindings {
   late final _dust_add_sticky_node =
       _dust_add_sticky_nodePtr.asFunction<void Function(int)>(isLeaf: true);
 
-  COptionAtom dust_atom(int idh, int idl) {
-    final res = _dust_atom(idh, idl);
+  COptionAtom dust_atom(int handle, int idh, int idl) {
+    final res = _dust_atom(handle, idh, idl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_atomPtr =
-      _lookup<NativeFunction<CResultOptionAtom Function(Uint64, Uint64)>>(
-          'dust_atom');
+  late final _dust_atomPtr = _lookup<
+      NativeFunction<
+          CResultOptionAtom Function(Uint64, Uint64, Uint64)>>('dust_atom');
   late final _dust_atom = _dust_atomPtr
-      .asFunction<CResultOptionAtom Function(int, int)>(isLeaf: true);
+      .asFunction<CResultOptionAtom Function(int, int, int)>();
 
   CArrayTripleIdUint64ArrayUint8 dust_atom_id_label_value_by_src(
-      int srch, int srcl) {
-    final res = _dust_atom_id_label_value_by_src(srch, srcl);
+      int handle, int srch, int srcl) {
+    final res = _dust_atom_id_label_value_by_src(handle, srch, srcl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
@@ -106,90 +131,203 @@ class NativeBindings {
   late final _dust_atom_id_label_value_by_srcPtr = _lookup<
       NativeFunction<
           CResultArrayTripleIdUint64ArrayUint8 Function(
-              Uint64, Uint64)>>('dust_atom_id_label_value_by_src');
+              Uint64, Uint64, Uint64)>>('dust_atom_id_label_value_by_src');
   late final _dust_atom_id_label_value_by_src =
-      _dust_atom_id_label_value_by_srcPtr
-          .asFunction<CResultArrayTripleIdUint64ArrayUint8 Function(int, int)>(
-              isLeaf: true);
+      _dust_atom_id_label_value_by_srcPtr.asFunction<
+          CResultArrayTripleIdUint64ArrayUint8 Function(
+              int, int, int)>(isLeaf: true);
 
   CArrayPairIdId dust_atom_id_src_by_label_value(
-      int label, int len, Pointer<Uint8> ptr) {
-    final res = _dust_atom_id_src_by_label_value(label, len, ptr);
+      int handle, int label, int len, Pointer<Uint8> ptr) {
+    final res = _dust_atom_id_src_by_label_value(handle, label, len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_atom_id_src_by_label_valuePtr = _lookup<
       NativeFunction<
-          CResultArrayPairIdId Function(Uint64, Uint64,
+          CResultArrayPairIdId Function(Uint64, Uint64, Uint64,
               Pointer<Uint8>)>>('dust_atom_id_src_by_label_value');
   late final _dust_atom_id_src_by_label_value =
-      _dust_atom_id_src_by_label_valuePtr
-          .asFunction<CResultArrayPairIdId Function(int, int, Pointer<Uint8>)>(
-              isLeaf: true);
+      _dust_atom_id_src_by_label_valuePtr.asFunction<
+          CResultArrayPairIdId Function(
+              int, int, int, Pointer<Uint8>)>(isLeaf: true);
 
-  CArrayTripleIdIdArrayUint8 dust_atom_id_src_value_by_label(int label) {
-    final res = _dust_atom_id_src_value_by_label(label);
+  CArrayTripleIdIdArrayUint8 dust_atom_id_src_value_by_label(
+      int handle, int label) {
+    final res = _dust_atom_id_src_value_by_label(handle, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_atom_id_src_value_by_labelPtr = _lookup<
-          NativeFunction<CResultArrayTripleIdIdArrayUint8 Function(Uint64)>>(
-      'dust_atom_id_src_value_by_label');
+      NativeFunction<
+          CResultArrayTripleIdIdArrayUint8 Function(
+              Uint64, Uint64)>>('dust_atom_id_src_value_by_label');
   late final _dust_atom_id_src_value_by_label =
       _dust_atom_id_src_value_by_labelPtr
-          .asFunction<CResultArrayTripleIdIdArrayUint8 Function(int)>(
+          .asFunction<CResultArrayTripleIdIdArrayUint8 Function(int, int)>(
               isLeaf: true);
 
+  /// Queries atoms by a prefix of values, ordered by value. A `limit` of
+  /// `u64::MAX` means no limit.
+  CArrayTripleIdIdArrayUint8 dust_atom_id_src_value_by_label_prefix(int handle,
+      int label, int len, Pointer<Uint8> ptr, int offset, int limit) {
+    final res = _dust_atom_id_src_value_by_label_prefix(handle, label, len, ptr,
+        offset, limit);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_atom_id_src_value_by_label_prefixPtr = _lookup<
+      NativeFunction<
+          CResultArrayTripleIdIdArrayUint8 Function(Uint64, Uint64, Uint64,
+              Pointer<Uint8>, Uint64,
+              Uint64)>>('dust_atom_id_src_value_by_label_prefix');
+  late final _dust_atom_id_src_value_by_label_prefix =
+      _dust_atom_id_src_value_by_label_prefixPtr.asFunction<
+          CResultArrayTripleIdIdArrayUint8 Function(
+              int, int, int, Pointer<Uint8>, int, int)>(isLeaf: true);
+
+  /// Queries atoms by a range of values, ordered by value. A `limit` of
+  /// `u64::MAX` means no limit.
+  CArrayTripleIdIdArrayUint8 dust_atom_id_src_value_by_label_range(int handle,
+      int label, int lowerKind, int lowerLen, Pointer<Uint8> lowerPtr,
+      int upperKind, int upperLen, Pointer<Uint8> upperPtr, int offset,
+      int limit) {
+    final res = _dust_atom_id_src_value_by_label_range(handle, label, lowerKind,
+        lowerLen, lowerPtr, upperKind, upperLen, upperPtr, offset, limit);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_atom_id_src_value_by_label_rangePtr = _lookup<
+      NativeFunction<
+          CResultArrayTripleIdIdArrayUint8 Function(Uint64, Uint64, Uint8,
+              Uint64, Pointer<Uint8>, Uint8, Uint64, Pointer<Uint8>, Uint64,
+              Uint64)>>('dust_atom_id_src_value_by_label_range');
+  late final _dust_atom_id_src_value_by_label_range =
+      _dust_atom_id_src_value_by_label_rangePtr.asFunction<
+          CResultArrayTripleIdIdArrayUint8 Function(int, int, int, int,
+              Pointer<Uint8>, int, int, Pointer<Uint8>, int,
+              int)>(isLeaf: true);
+
   CArrayPairIdArrayUint8 dust_atom_id_value_by_src_label(
-      int srch, int srcl, int label) {
-    final res = _dust_atom_id_value_by_src_label(srch, srcl, label);
+      int handle, int srch, int srcl, int label) {
+    final res = _dust_atom_id_value_by_src_label(handle, srch, srcl, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_atom_id_value_by_src_labelPtr = _lookup<
       NativeFunction<
-          CResultArrayPairIdArrayUint8 Function(
-              Uint64, Uint64, Uint64)>>('dust_atom_id_value_by_src_label');
+          CResultArrayPairIdArrayUint8 Function(Uint64, Uint64, Uint64,
+              Uint64)>>('dust_atom_id_value_by_src_label');
   late final _dust_atom_id_value_by_src_label =
-      _dust_atom_id_value_by_src_labelPtr
-          .asFunction<CResultArrayPairIdArrayUint8 Function(int, int, int)>(
-              isLeaf: true);
+      _dust_atom_id_value_by_src_labelPtr.asFunction<
+          CResultArrayPairIdArrayUint8 Function(
+              int, int, int, int)>(isLeaf: true);
+
+  /// Looks up many atoms and edges in one call. Ids which are neither are
+  /// skipped, so each entry carries its id.
+  CArrayItemData dust_atoms_edges(int handle, int len, Pointer<CId> ptr) {
+    final res = _dust_atoms_edges(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_atoms_edgesPtr = _lookup<
+      NativeFunction<
+          CResultArrayItemData Function(
+              Uint64, Uint64, Pointer<CId>)>>('dust_atoms_edges');
+  late final _dust_atoms_edges = _dust_atoms_edgesPtr
+      .asFunction<CResultArrayItemData Function(int, int, Pointer<CId>)>();
+
+  /// Backs up the last committed state to the database at given path, which is
+  /// overwritten. See [`Store::backup_to`].
+  CUnit dust_backup(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_backup(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_backupPtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(Uint64, Uint64, Pointer<Uint8>)>>('dust_backup');
+  late final _dust_backup = _dust_backupPtr
+      .asFunction<CResultUnit Function(int, int, Pointer<Uint8>)>();
 
-  CArrayEventData dust_barrier() {
-    final res = _dust_barrier();
+  CArrayEventData dust_barrier(int handle) {
+    final res = _dust_barrier(handle);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_barrierPtr =
-      _lookup<NativeFunction<CResultArrayEventData Function()>>('dust_barrier');
+      _lookup<NativeFunction<CResultArrayEventData Function(Uint64)>>(
+          'dust_barrier');
   late final _dust_barrier = _dust_barrierPtr
-      .asFunction<CResultArrayEventData Function()>(isLeaf: true);
+      .asFunction<CResultArrayEventData Function(int)>();
 
-  CUnit dust_close() {
-    final res = _dust_close();
+  /// See [`Workspace::check`].
+  CArrayViolationData dust_check(int handle) {
+    final res = _dust_check(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_checkPtr =
+      _lookup<NativeFunction<CResultArrayViolationData Function(Uint64)>>(
+          'dust_check');
+  late final _dust_check = _dust_checkPtr
+      .asFunction<CResultArrayViolationData Function(int)>();
+
+  /// Commits and closes the database. The handle is stale afterwards, even if
+  /// committing fails.
+  CUnit dust_close(int handle) {
+    final res = _dust_close(handle);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_closePtr =
-      _lookup<NativeFunction<CResultUnit Function()>>('dust_close');
+      _lookup<NativeFunction<CResultUnit Function(Uint64)>>('dust_close');
   late final _dust_close =
-      _dust_closePtr.asFunction<CResultUnit Function()>(isLeaf: true);
+      _dust_closePtr.asFunction<CResultUnit Function(int)>(isLeaf: true);
 
-  CUnit dust_commit() {
-    final res = _dust_commit();
+  CUnit dust_commit(int handle) {
+    final res = _dust_commit(handle);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_commitPtr =
-      _lookup<NativeFunction<CResultUnit Function()>>('dust_commit');
+      _lookup<NativeFunction<CResultUnit Function(Uint64)>>('dust_commit');
   late final _dust_commit =
-      _dust_commitPtr.asFunction<CResultUnit Function()>(isLeaf: true);
+      _dust_commitPtr.asFunction<CResultUnit Function(int)>(isLeaf: true);
+
+  int dust_compact(int handle) {
+    final res = _dust_compact(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_compactPtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64)>>('dust_compact');
+  late final _dust_compact =
+      _dust_compactPtr.asFunction<CResultUint64 Function(int)>(isLeaf: true);
+
+  /// See [`Workspace::discard`].
+  CUnit dust_discard(int handle) {
+    final res = _dust_discard(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_discardPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64)>>('dust_discard');
+  late final _dust_discard =
+      _dust_discardPtr.asFunction<CResultUnit Function(int)>(isLeaf: true);
 
   /// Drops the return value of [`barrier`].
   void dust_drop_array_event_data(CArrayEventData value) {
@@ -202,6 +340,17 @@ class NativeBindings {
   late final _dust_drop_array_event_data = _dust_drop_array_event_dataPtr
       .asFunction<void Function(CArrayEventData)>(isLeaf: true);
 
+  /// Drops the return value of [`sync_join`].
+  void dust_drop_array_conflict_data(CArrayConflictData value) {
+    return _dust_drop_array_conflict_data(value);
+  }
+
+  late final _dust_drop_array_conflict_dataPtr =
+      _lookup<NativeFunction<Void Function(CArrayConflictData)>>(
+          'dust_drop_array_conflict_data');
+  late final _dust_drop_array_conflict_data = _dust_drop_array_conflict_dataPtr
+      .asFunction<void Function(CArrayConflictData)>(isLeaf: true);
+
   /// Drops the return value of [`node_id_by_label`].
   void dust_drop_array_id(CArrayId value) {
     return _dust_drop_array_id(value);
@@ -235,7 +384,9 @@ class NativeBindings {
   late final _dust_drop_array_id_id = _dust_drop_array_id_idPtr
       .asFunction<void Function(CArrayPairIdId)>(isLeaf: true);
 
-  /// Drops the return value of [`atom_id_src_value_by_label`].
+  /// Drops the return value of [`atom_id_src_value_by_label`],
+  /// [`atom_id_src_value_by_label_range`] and
+  /// [`atom_id_src_value_by_label_prefix`].
   void dust_drop_array_id_id_array_u8(CArrayTripleIdIdArrayUint8 value) {
     return _dust_drop_array_id_id_array_u8(value);
   }
@@ -282,6 +433,28 @@ class NativeBindings {
   late final _dust_drop_array_id_u64_id = _dust_drop_array_id_u64_idPtr
       .asFunction<void Function(CArrayTripleIdUint64Id)>(isLeaf: true);
 
+  /// Drops the return value of [`atoms_edges`].
+  void dust_drop_array_item_data(CArrayItemData value) {
+    return _dust_drop_array_item_data(value);
+  }
+
+  late final _dust_drop_array_item_dataPtr =
+      _lookup<NativeFunction<Void Function(CArrayItemData)>>(
+          'dust_drop_array_item_data');
+  late final _dust_drop_array_item_data = _dust_drop_array_item_dataPtr
+      .asFunction<void Function(CArrayItemData)>(isLeaf: true);
+
+  /// Drops the return value of [`stats`].
+  void dust_drop_array_stats_data(CArrayStatsData value) {
+    return _dust_drop_array_stats_data(value);
+  }
+
+  late final _dust_drop_array_stats_dataPtr =
+      _lookup<NativeFunction<Void Function(CArrayStatsData)>>(
+          'dust_drop_array_stats_data');
+  late final _dust_drop_array_stats_data = _dust_drop_array_stats_dataPtr
+      .asFunction<void Function(CArrayStatsData)>(isLeaf: true);
+
   /// Drops the return value of [`sync_version`] and [`sync_actions`]
   /// and all error results.
   void dust_drop_array_u8(CArrayUint8 value) {
@@ -293,6 +466,18 @@ class NativeBindings {
   late final _dust_drop_array_u8 = _dust_drop_array_u8Ptr
       .asFunction<void Function(CArrayUint8)>(isLeaf: true);
 
+  /// Drops the return value of [`check`] and [`repair`].
+  void dust_drop_array_violation_data(CArrayViolationData value) {
+    return _dust_drop_array_violation_data(value);
+  }
+
+  late final _dust_drop_array_violation_dataPtr =
+      _lookup<NativeFunction<Void Function(CArrayViolationData)>>(
+          'dust_drop_array_violation_data');
+  late final _dust_drop_array_violation_data =
+      _dust_drop_array_violation_dataPtr
+          .asFunction<void Function(CArrayViolationData)>(isLeaf: true);
+
   /// Drops the return value of [`atom`].
   void dust_drop_option_atom(COptionAtom value) {
     return _dust_drop_option_atom(value);
@@ -304,33 +489,47 @@ class NativeBindings {
   late final _dust_drop_option_atom = _dust_drop_option_atomPtr
       .asFunction<void Function(COptionAtom)>(isLeaf: true);
 
-  COptionEdge dust_edge(int idh, int idl) {
-    final res = _dust_edge(idh, idl);
+  /// Drops the return value of [`sync_actions_page`].
+  void dust_drop_pair_array_u8_array_u8(CPairArrayUint8ArrayUint8 value) {
+    return _dust_drop_pair_array_u8_array_u8(value);
+  }
+
+  late final _dust_drop_pair_array_u8_array_u8Ptr =
+      _lookup<NativeFunction<Void Function(CPairArrayUint8ArrayUint8)>>(
+          'dust_drop_pair_array_u8_array_u8');
+  late final _dust_drop_pair_array_u8_array_u8 =
+      _dust_drop_pair_array_u8_array_u8Ptr
+          .asFunction<void Function(CPairArrayUint8ArrayUint8)>(isLeaf: true);
+
+  COptionEdge dust_edge(int handle, int idh, int idl) {
+    final res = _dust_edge(handle, idh, idl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_edgePtr =
-      _lookup<NativeFunction<CResultOptionEdge Function(Uint64, Uint64)>>(
-          'dust_edge');
+  late final _dust_edgePtr = _lookup<
+      NativeFunction<
+          CResultOptionEdge Function(Uint64, Uint64, Uint64)>>('dust_edge');
   late final _dust_edge = _dust_edgePtr
-      .asFunction<CResultOptionEdge Function(int, int)>(isLeaf: true);
+      .asFunction<CResultOptionEdge Function(int, int, int)>();
 
-  CArrayPairIdId dust_edge_id_dst_by_src_label(int srch, int srcl, int label) {
-    final res = _dust_edge_id_dst_by_src_label(srch, srcl, label);
+  CArrayPairIdId dust_edge_id_dst_by_src_label(
+      int handle, int srch, int srcl, int label) {
+    final res = _dust_edge_id_dst_by_src_label(handle, srch, srcl, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_edge_id_dst_by_src_labelPtr = _lookup<
       NativeFunction<
-          CResultArrayPairIdId Function(
-              Uint64, Uint64, Uint64)>>('dust_edge_id_dst_by_src_label');
+          CResultArrayPairIdId Function(Uint64, Uint64, Uint64,
+              Uint64)>>('dust_edge_id_dst_by_src_label');
   late final _dust_edge_id_dst_by_src_label = _dust_edge_id_dst_by_src_labelPtr
-      .asFunction<CResultArrayPairIdId Function(int, int, int)>(isLeaf: true);
+      .asFunction<CResultArrayPairIdId Function(int, int, int, int)>();
 
-  CArrayTripleIdUint64Id dust_edge_id_label_dst_by_src(int srch, int srcl) {
-    final res = _dust_edge_id_label_dst_by_src(srch, srcl);
+  CArrayTripleIdUint64Id dust_edge_id_label_dst_by_src(
+      int handle, int srch, int srcl) {
+    final res = _dust_edge_id_label_dst_by_src(handle, srch, srcl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
@@ -338,26 +537,27 @@ class NativeBindings {
   late final _dust_edge_id_label_dst_by_srcPtr = _lookup<
       NativeFunction<
           CResultArrayTripleIdUint64Id Function(
-              Uint64, Uint64)>>('dust_edge_id_label_dst_by_src');
+              Uint64, Uint64, Uint64)>>('dust_edge_id_label_dst_by_src');
   late final _dust_edge_id_label_dst_by_src = _dust_edge_id_label_dst_by_srcPtr
-      .asFunction<CResultArrayTripleIdUint64Id Function(int, int)>(
-          isLeaf: true);
+      .asFunction<CResultArrayTripleIdUint64Id Function(int, int, int)>();
 
-  CArrayPairIdId dust_edge_id_src_by_dst_label(int dsth, int dstl, int label) {
-    final res = _dust_edge_id_src_by_dst_label(dsth, dstl, label);
+  CArrayPairIdId dust_edge_id_src_by_dst_label(
+      int handle, int dsth, int dstl, int label) {
+    final res = _dust_edge_id_src_by_dst_label(handle, dsth, dstl, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_edge_id_src_by_dst_labelPtr = _lookup<
       NativeFunction<
-          CResultArrayPairIdId Function(
-              Uint64, Uint64, Uint64)>>('dust_edge_id_src_by_dst_label');
+          CResultArrayPairIdId Function(Uint64, Uint64, Uint64,
+              Uint64)>>('dust_edge_id_src_by_dst_label');
   late final _dust_edge_id_src_by_dst_label = _dust_edge_id_src_by_dst_labelPtr
-      .asFunction<CResultArrayPairIdId Function(int, int, int)>(isLeaf: true);
+      .asFunction<CResultArrayPairIdId Function(int, int, int, int)>();
 
-  CArrayTripleIdIdUint64 dust_edge_id_src_label_by_dst(int dsth, int dstl) {
-    final res = _dust_edge_id_src_label_by_dst(dsth, dstl);
+  CArrayTripleIdIdUint64 dust_edge_id_src_label_by_dst(
+      int handle, int dsth, int dstl) {
+    final res = _dust_edge_id_src_label_by_dst(handle, dsth, dstl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
@@ -365,46 +565,152 @@ class NativeBindings {
   late final _dust_edge_id_src_label_by_dstPtr = _lookup<
       NativeFunction<
           CResultArrayTripleIdIdUint64 Function(
-              Uint64, Uint64)>>('dust_edge_id_src_label_by_dst');
+              Uint64, Uint64, Uint64)>>('dust_edge_id_src_label_by_dst');
   late final _dust_edge_id_src_label_by_dst = _dust_edge_id_src_label_by_dstPtr
-      .asFunction<CResultArrayTripleIdIdUint64 Function(int, int)>(
-          isLeaf: true);
+      .asFunction<CResultArrayTripleIdIdUint64 Function(int, int, int)>();
+
+  CArrayUint8 dust_export_bundle(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_export_bundle(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_export_bundlePtr = _lookup<
+      NativeFunction<
+          CResultArrayUint8 Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_export_bundle');
+  late final _dust_export_bundle = _dust_export_bundlePtr
+      .asFunction<CResultArrayUint8 Function(int, int, Pointer<Uint8>)>();
+
+  CArrayConflictData dust_import_bundle(
+      int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_import_bundle(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
 
-  COptionNode dust_node(int idh, int idl) {
-    final res = _dust_node(idh, idl);
+  late final _dust_import_bundlePtr = _lookup<
+      NativeFunction<
+          CResultArrayConflictData Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_import_bundle');
+  late final _dust_import_bundle = _dust_import_bundlePtr
+      .asFunction<
+          CResultArrayConflictData Function(int, int, Pointer<Uint8>)>();
+
+  CUnit dust_insert_list_item(int handle, int idh, int idl, int srch, int srcl,
+      int label, int index, int len, Pointer<Uint8> ptr) {
+    final res = _dust_insert_list_item(handle, idh, idl, srch, srcl, label,
+        index, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_insert_list_itemPtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(Uint64, Uint64, Uint64, Uint64, Uint64, Uint64,
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_insert_list_item');
+  late final _dust_insert_list_item =
+      _dust_insert_list_itemPtr.asFunction<
+          CResultUnit Function(int, int, int, int, int, int, int, int,
+              Pointer<Uint8>)>(isLeaf: true);
+
+  CUnit dust_install_snapshot(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_install_snapshot(handle, len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_nodePtr =
-      _lookup<NativeFunction<CResultOptionNode Function(Uint64, Uint64)>>(
-          'dust_node');
+  late final _dust_install_snapshotPtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_install_snapshot');
+  late final _dust_install_snapshot = _dust_install_snapshotPtr
+      .asFunction<CResultUnit Function(int, int, Pointer<Uint8>)>();
+
+  CArrayPairIdArrayUint8 dust_list_id_value_by_src_label(
+      int handle, int srch, int srcl, int label) {
+    final res = _dust_list_id_value_by_src_label(handle, srch, srcl, label);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_list_id_value_by_src_labelPtr = _lookup<
+      NativeFunction<
+          CResultArrayPairIdArrayUint8 Function(Uint64, Uint64, Uint64,
+              Uint64)>>('dust_list_id_value_by_src_label');
+  late final _dust_list_id_value_by_src_label =
+      _dust_list_id_value_by_src_labelPtr.asFunction<
+          CResultArrayPairIdArrayUint8 Function(
+              int, int, int, int)>(isLeaf: true);
+
+  /// Runs maintenance for at most `budget` milliseconds, releasing at most
+  /// `max_pages` free pages. See [`Store::maintain`].
+  int dust_maintain(int handle, int budget, int maxPages) {
+    final res = _dust_maintain(handle, budget, maxPages);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_maintainPtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64, Uint64, Uint64)>>(
+          'dust_maintain');
+  late final _dust_maintain = _dust_maintainPtr
+      .asFunction<CResultUint64 Function(int, int, int)>();
+
+  COptionNode dust_node(int handle, int idh, int idl) {
+    final res = _dust_node(handle, idh, idl);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_nodePtr = _lookup<
+      NativeFunction<
+          CResultOptionNode Function(Uint64, Uint64, Uint64)>>('dust_node');
   late final _dust_node = _dust_nodePtr
-      .asFunction<CResultOptionNode Function(int, int)>(isLeaf: true);
+      .asFunction<CResultOptionNode Function(int, int, int)>();
 
-  CArrayId dust_node_id_by_label(int label) {
-    final res = _dust_node_id_by_label(label);
+  CArrayId dust_node_id_by_label(int handle, int label) {
+    final res = _dust_node_id_by_label(handle, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_node_id_by_labelPtr =
-      _lookup<NativeFunction<CResultArrayId Function(Uint64)>>(
+      _lookup<NativeFunction<CResultArrayId Function(Uint64, Uint64)>>(
           'dust_node_id_by_label');
   late final _dust_node_id_by_label = _dust_node_id_by_labelPtr
-      .asFunction<CResultArrayId Function(int)>(isLeaf: true);
-
-  CUnit dust_open(int len, Pointer<Uint8> ptr) {
+      .asFunction<CResultArrayId Function(int, int)>();
+
+  /// Opens the database at given path and returns a handle to it, which every
+  /// other function taking a `handle` accepts. The constraints added since the
+  /// previous call are bound to the returned handle and then reset, whether or
+  /// not opening succeeds.
+  ///
+  /// If the same path is already open (e.g. after a hot restart), the old
+  /// handle is closed first and becomes stale.
+  int dust_open(int len, Pointer<Uint8> ptr) {
     final res = _dust_open(len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_openPtr =
-      _lookup<NativeFunction<CResultUnit Function(Uint64, Pointer<Uint8>)>>(
+      _lookup<NativeFunction<CResultUint64 Function(Uint64, Pointer<Uint8>)>>(
           'dust_open');
   late final _dust_open = _dust_openPtr
-      .asFunction<CResultUnit Function(int, Pointer<Uint8>)>(isLeaf: true);
+      .asFunction<CResultUint64 Function(int, Pointer<Uint8>)>();
+
+  /// Like [`dust_open`], but opens a private, temporary database.
+  int dust_open_in_memory() {
+    final res = _dust_open_in_memory();
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_open_in_memoryPtr =
+      _lookup<NativeFunction<CResultUint64 Function()>>('dust_open_in_memory');
+  late final _dust_open_in_memory = _dust_open_in_memoryPtr
+      .asFunction<CResultUint64 Function()>();
 
   CId dust_random_id() {
     return _dust_random_id();
@@ -415,21 +721,125 @@ class NativeBindings {
   late final _dust_random_id =
       _dust_random_idPtr.asFunction<CId Function()>(isLeaf: true);
 
-  CUnit dust_set_atom_none(int idh, int idl) {
-    final res = _dust_set_atom_none(idh, idl);
+  /// Returns the number of open savepoints. See [`Store::release`].
+  int dust_release(int handle) {
+    final res = _dust_release(handle);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_set_atom_nonePtr =
+  late final _dust_releasePtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64)>>('dust_release');
+  late final _dust_release =
+      _dust_releasePtr.asFunction<CResultUint64 Function(int)>(isLeaf: true);
+
+  CUnit dust_remove_list_item(int handle, int idh, int idl) {
+    final res = _dust_remove_list_item(handle, idh, idl);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_remove_list_itemPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64, Uint64)>>(
+          'dust_remove_list_item');
+  late final _dust_remove_list_item = _dust_remove_list_itemPtr
+      .asFunction<CResultUnit Function(int, int, int)>();
+
+  CUnit dust_remove_peer(int handle, int peer) {
+    final res = _dust_remove_peer(handle, peer);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_remove_peerPtr =
       _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64)>>(
+          'dust_remove_peer');
+  late final _dust_remove_peer = _dust_remove_peerPtr
+      .asFunction<CResultUnit Function(int, int)>();
+
+  /// Stages removals which fix the reported violations. Call [`dust_barrier`]
+  /// next. See [`Workspace::repair`].
+  CArrayViolationData dust_repair(int handle) {
+    final res = _dust_repair(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_repairPtr =
+      _lookup<NativeFunction<CResultArrayViolationData Function(Uint64)>>(
+          'dust_repair');
+  late final _dust_repair = _dust_repairPtr
+      .asFunction<CResultArrayViolationData Function(int)>();
+
+  /// Replaces the contents of the database with the backup at given path. See
+  /// [`Store::restore_from`].
+  CUnit dust_restore(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_restore(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_restorePtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_restore');
+  late final _dust_restore = _dust_restorePtr
+      .asFunction<CResultUnit Function(int, int, Pointer<Uint8>)>();
+
+  /// See [`Store::rollback`].
+  CUnit dust_rollback(int handle) {
+    final res = _dust_rollback(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_rollbackPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64)>>('dust_rollback');
+  late final _dust_rollback =
+      _dust_rollbackPtr.asFunction<CResultUnit Function(int)>(isLeaf: true);
+
+  /// Returns the number of open savepoints. See
+  /// [`Store::rollback_to_savepoint`].
+  int dust_rollback_to_savepoint(int handle) {
+    final res = _dust_rollback_to_savepoint(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_rollback_to_savepointPtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64)>>(
+          'dust_rollback_to_savepoint');
+  late final _dust_rollback_to_savepoint = _dust_rollback_to_savepointPtr
+      .asFunction<CResultUint64 Function(int)>();
+
+  /// Returns the number of open savepoints. See [`Store::savepoint`].
+  int dust_savepoint(int handle) {
+    final res = _dust_savepoint(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_savepointPtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64)>>('dust_savepoint');
+  late final _dust_savepoint =
+      _dust_savepointPtr.asFunction<CResultUint64 Function(int)>(isLeaf: true);
+
+  CUnit dust_set_atom_none(int handle, int idh, int idl) {
+    final res = _dust_set_atom_none(handle, idh, idl);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_set_atom_nonePtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64, Uint64)>>(
           'dust_set_atom_none');
   late final _dust_set_atom_none = _dust_set_atom_nonePtr
-      .asFunction<CResultUnit Function(int, int)>(isLeaf: true);
+      .asFunction<CResultUnit Function(int, int, int)>();
 
-  CUnit dust_set_atom_some(int idh, int idl, int srch, int srcl, int label,
-      int len, Pointer<Uint8> ptr) {
-    final res = _dust_set_atom_some(idh, idl, srch, srcl, label, len, ptr);
+  CUnit dust_set_atom_some(int handle, int idh, int idl, int srch, int srcl,
+      int label, int len, Pointer<Uint8> ptr) {
+    final res =
+        _dust_set_atom_some(handle, idh, idl, srch, srcl, label, len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
@@ -437,26 +847,62 @@ class NativeBindings {
   late final _dust_set_atom_somePtr = _lookup<
       NativeFunction<
           CResultUnit Function(Uint64, Uint64, Uint64, Uint64, Uint64, Uint64,
-              Pointer<Uint8>)>>('dust_set_atom_some');
-  late final _dust_set_atom_some = _dust_set_atom_somePtr.asFunction<
-      CResultUnit Function(
-          int, int, int, int, int, int, Pointer<Uint8>)>(isLeaf: true);
+              Uint64, Pointer<Uint8>)>>('dust_set_atom_some');
+  late final _dust_set_atom_some =
+      _dust_set_atom_somePtr.asFunction<
+          CResultUnit Function(
+              int, int, int, int, int, int, int, Pointer<Uint8>)>(isLeaf: true);
+
+  /// Applies a serialized `Vec<Write>` (see [`crate::workspace::Write`]) in one
+  /// call, and returns the number of writes.
+  int dust_set_batch(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_set_batch(handle, len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_set_batchPtr = _lookup<
+      NativeFunction<
+          CResultUint64 Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_set_batch');
+  late final _dust_set_batch = _dust_set_batchPtr
+      .asFunction<CResultUint64 Function(int, int, Pointer<Uint8>)>();
+
+  /// Sets the busy timeout, in milliseconds.
+  void dust_set_busy_timeout(int busyTimeout) {
+    return _dust_set_busy_timeout(busyTimeout);
+  }
+
+  late final _dust_set_busy_timeoutPtr =
+      _lookup<NativeFunction<Void Function(Uint64)>>('dust_set_busy_timeout');
+  late final _dust_set_busy_timeout =
+      _dust_set_busy_timeoutPtr.asFunction<void Function(int)>(isLeaf: true);
+
+  void dust_set_cache_size(int cacheSize) {
+    return _dust_set_cache_size(cacheSize);
+  }
 
-  CUnit dust_set_edge_none(int idh, int idl) {
-    final res = _dust_set_edge_none(idh, idl);
+  late final _dust_set_cache_sizePtr =
+      _lookup<NativeFunction<Void Function(Int64)>>('dust_set_cache_size');
+  late final _dust_set_cache_size =
+      _dust_set_cache_sizePtr.asFunction<void Function(int)>(isLeaf: true);
+
+  CUnit dust_set_edge_none(int handle, int idh, int idl) {
+    final res = _dust_set_edge_none(handle, idh, idl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_set_edge_nonePtr =
-      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64)>>(
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64, Uint64)>>(
           'dust_set_edge_none');
   late final _dust_set_edge_none = _dust_set_edge_nonePtr
-      .asFunction<CResultUnit Function(int, int)>(isLeaf: true);
+      .asFunction<CResultUnit Function(int, int, int)>();
 
-  CUnit dust_set_edge_some(
-      int idh, int idl, int srch, int srcl, int label, int dsth, int dstl) {
-    final res = _dust_set_edge_some(idh, idl, srch, srcl, label, dsth, dstl);
+  CUnit dust_set_edge_some(int handle, int idh, int idl, int srch, int srcl,
+      int label, int dsth, int dstl) {
+    final res =
+        _dust_set_edge_some(handle, idh, idl, srch, srcl, label, dsth, dstl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
@@ -464,69 +910,267 @@ class NativeBindings {
   late final _dust_set_edge_somePtr = _lookup<
       NativeFunction<
           CResultUnit Function(Uint64, Uint64, Uint64, Uint64, Uint64, Uint64,
-              Uint64)>>('dust_set_edge_some');
+              Uint64, Uint64)>>('dust_set_edge_some');
   late final _dust_set_edge_some = _dust_set_edge_somePtr
-      .asFunction<CResultUnit Function(int, int, int, int, int, int, int)>(
-          isLeaf: true);
+      .asFunction<
+          CResultUnit Function(int, int, int, int, int, int, int, int)>();
 
-  CUnit dust_set_node_none(int idh, int idl) {
-    final res = _dust_set_node_none(idh, idl);
+  /// Takes `DELETE`, `TRUNCATE`, `PERSIST`, `MEMORY`, `WAL` or `OFF` as 0 to 5.
+  CUnit dust_set_journal_mode(int journalMode) {
+    final res = _dust_set_journal_mode(journalMode);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_set_journal_modePtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint8)>>(
+          'dust_set_journal_mode');
+  late final _dust_set_journal_mode = _dust_set_journal_modePtr
+      .asFunction<CResultUnit Function(int)>(isLeaf: true);
+
+  void dust_set_max_drift(int maxDrift) {
+    return _dust_set_max_drift(maxDrift);
+  }
+
+  late final _dust_set_max_driftPtr =
+      _lookup<NativeFunction<Void Function(Uint64)>>('dust_set_max_drift');
+  late final _dust_set_max_drift =
+      _dust_set_max_driftPtr.asFunction<void Function(int)>(isLeaf: true);
+
+  CUnit dust_set_node_none(int handle, int idh, int idl) {
+    final res = _dust_set_node_none(handle, idh, idl);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_set_node_nonePtr =
-      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64)>>(
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64, Uint64)>>(
           'dust_set_node_none');
   late final _dust_set_node_none = _dust_set_node_nonePtr
-      .asFunction<CResultUnit Function(int, int)>(isLeaf: true);
+      .asFunction<CResultUnit Function(int, int, int)>();
 
-  CUnit dust_set_node_some(int idh, int idl, int label) {
-    final res = _dust_set_node_some(idh, idl, label);
+  CUnit dust_set_node_some(int handle, int idh, int idl, int label) {
+    final res = _dust_set_node_some(handle, idh, idl, label);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_set_node_somePtr =
-      _lookup<NativeFunction<CResultUnit Function(Uint64, Uint64, Uint64)>>(
-          'dust_set_node_some');
+  late final _dust_set_node_somePtr = _lookup<
+      NativeFunction<
+          CResultUnit Function(
+              Uint64, Uint64, Uint64, Uint64)>>('dust_set_node_some');
   late final _dust_set_node_some = _dust_set_node_somePtr
-      .asFunction<CResultUnit Function(int, int, int)>(isLeaf: true);
+      .asFunction<CResultUnit Function(int, int, int, int)>();
+
+  /// Sets the workspace prefix for the next store opened on this thread.
+  CUnit dust_set_prefix(int len, Pointer<Uint8> ptr) {
+    final res = _dust_set_prefix(len, ptr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_set_prefixPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint64, Pointer<Uint8>)>>(
+          'dust_set_prefix');
+  late final _dust_set_prefix = _dust_set_prefixPtr
+      .asFunction<CResultUnit Function(int, Pointer<Uint8>)>(isLeaf: true);
+
+  void dust_set_read_only(bool readOnly) {
+    return _dust_set_read_only(readOnly);
+  }
+
+  late final _dust_set_read_onlyPtr =
+      _lookup<NativeFunction<Void Function(Bool)>>('dust_set_read_only');
+  late final _dust_set_read_only =
+      _dust_set_read_onlyPtr.asFunction<void Function(bool)>(isLeaf: true);
+
+  void dust_set_retention(int retention) {
+    return _dust_set_retention(retention);
+  }
+
+  late final _dust_set_retentionPtr =
+      _lookup<NativeFunction<Void Function(Uint64)>>('dust_set_retention');
+  late final _dust_set_retention =
+      _dust_set_retentionPtr.asFunction<void Function(int)>(isLeaf: true);
+
+  void dust_set_staged_ttl(int stagedTtl) {
+    return _dust_set_staged_ttl(stagedTtl);
+  }
+
+  late final _dust_set_staged_ttlPtr =
+      _lookup<NativeFunction<Void Function(Uint64)>>('dust_set_staged_ttl');
+  late final _dust_set_staged_ttl =
+      _dust_set_staged_ttlPtr.asFunction<void Function(int)>(isLeaf: true);
+
+  /// Takes `OFF`, `NORMAL`, `FULL` or `EXTRA` as 0 to 3.
+  CUnit dust_set_synchronous(int synchronous) {
+    final res = _dust_set_synchronous(synchronous);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_set_synchronousPtr =
+      _lookup<NativeFunction<CResultUnit Function(Uint8)>>(
+          'dust_set_synchronous');
+  late final _dust_set_synchronous = _dust_set_synchronousPtr
+      .asFunction<CResultUnit Function(int)>(isLeaf: true);
+
+  void dust_set_wal_autocheckpoint(int pages) {
+    return _dust_set_wal_autocheckpoint(pages);
+  }
+
+  late final _dust_set_wal_autocheckpointPtr =
+      _lookup<NativeFunction<Void Function(Uint64)>>(
+          'dust_set_wal_autocheckpoint');
+  late final _dust_set_wal_autocheckpoint = _dust_set_wal_autocheckpointPtr
+      .asFunction<void Function(int)>(isLeaf: true);
+
+  CArrayUint8 dust_snapshot(int handle) {
+    final res = _dust_snapshot(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_snapshotPtr =
+      _lookup<NativeFunction<CResultArrayUint8 Function(Uint64)>>(
+          'dust_snapshot');
+  late final _dust_snapshot = _dust_snapshotPtr
+      .asFunction<CResultArrayUint8 Function(int)>();
+
+  /// Returns a page of the snapshot and the cursor for the next page, which is
+  /// empty if there are no more pages. Pass an empty cursor for the first page.
+  CPairArrayUint8ArrayUint8 dust_snapshot_page(
+      int handle, int cursorLen, Pointer<Uint8> cursorPtr, int budget) {
+    final res = _dust_snapshot_page(handle, cursorLen, cursorPtr, budget);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_snapshot_pagePtr = _lookup<
+      NativeFunction<
+          CResultPairArrayUint8ArrayUint8 Function(Uint64, Uint64,
+              Pointer<Uint8>, Uint64)>>('dust_snapshot_page');
+  late final _dust_snapshot_page = _dust_snapshot_pagePtr.asFunction<
+      CResultPairArrayUint8ArrayUint8 Function(
+          int, int, Pointer<Uint8>, int)>();
+
+  /// Returns what is stored in each structure, for diagnostics. See
+  /// [`Workspace::stats`].
+  CArrayStatsData dust_stats(int handle) {
+    final res = _dust_stats(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
 
-  CArrayUint8 dust_sync_actions(int len, Pointer<Uint8> ptr) {
-    final res = _dust_sync_actions(len, ptr);
+  late final _dust_statsPtr =
+      _lookup<NativeFunction<CResultArrayStatsData Function(Uint64)>>(
+          'dust_stats');
+  late final _dust_stats = _dust_statsPtr
+      .asFunction<CResultArrayStatsData Function(int)>();
+
+  /// See [`Store::storage_stats`].
+  CStorageStats dust_storage_stats(int handle) {
+    final res = _dust_storage_stats(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_storage_statsPtr =
+      _lookup<NativeFunction<CResultStorageStats Function(Uint64)>>(
+          'dust_storage_stats');
+  late final _dust_storage_stats = _dust_storage_statsPtr
+      .asFunction<CResultStorageStats Function(int)>();
+
+  CArrayUint8 dust_sync_actions(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_sync_actions(handle, len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_sync_actionsPtr = _lookup<
-          NativeFunction<CResultArrayUint8 Function(Uint64, Pointer<Uint8>)>>(
-      'dust_sync_actions');
+      NativeFunction<
+          CResultArrayUint8 Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_sync_actions');
   late final _dust_sync_actions = _dust_sync_actionsPtr
-      .asFunction<CResultArrayUint8 Function(int, Pointer<Uint8>)>(
-          isLeaf: true);
+      .asFunction<CResultArrayUint8 Function(int, int, Pointer<Uint8>)>();
+
+  /// Returns a page of actions and the cursor for the next page, which is
+  /// empty if there are no more pages. Pass an empty cursor for the first page.
+  CPairArrayUint8ArrayUint8 dust_sync_actions_page(int handle, int len,
+      Pointer<Uint8> ptr, int cursorLen, Pointer<Uint8> cursorPtr, int budget) {
+    final res =
+        _dust_sync_actions_page(handle, len, ptr, cursorLen, cursorPtr, budget);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
 
-  CUnit dust_sync_join(int len, Pointer<Uint8> ptr) {
-    final res = _dust_sync_join(len, ptr);
+  late final _dust_sync_actions_pagePtr = _lookup<
+      NativeFunction<
+          CResultPairArrayUint8ArrayUint8 Function(Uint64, Uint64,
+              Pointer<Uint8>, Uint64, Pointer<Uint8>,
+              Uint64)>>('dust_sync_actions_page');
+  late final _dust_sync_actions_page =
+      _dust_sync_actions_pagePtr.asFunction<
+          CResultPairArrayUint8ArrayUint8 Function(int, int, Pointer<Uint8>,
+              int, Pointer<Uint8>, int)>(isLeaf: true);
+
+  CArrayConflictData dust_sync_join(int handle, int len, Pointer<Uint8> ptr) {
+    final res = _dust_sync_join(handle, len, ptr);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
-  late final _dust_sync_joinPtr =
-      _lookup<NativeFunction<CResultUnit Function(Uint64, Pointer<Uint8>)>>(
-          'dust_sync_join');
+  late final _dust_sync_joinPtr = _lookup<
+      NativeFunction<
+          CResultArrayConflictData Function(
+              Uint64, Uint64, Pointer<Uint8>)>>('dust_sync_join');
   late final _dust_sync_join = _dust_sync_joinPtr
-      .asFunction<CResultUnit Function(int, Pointer<Uint8>)>(isLeaf: true);
+      .asFunction<
+          CResultArrayConflictData Function(int, int, Pointer<Uint8>)>();
 
-  CArrayUint8 dust_sync_version() {
-    final res = _dust_sync_version();
+  CArrayUint8 dust_sync_version(int handle) {
+    final res = _dust_sync_version(handle);
     if (res.tag != 0) _err(res.body.err);
     return res.body.ok;
   }
 
   late final _dust_sync_versionPtr =
-      _lookup<NativeFunction<CResultArrayUint8 Function()>>(
+      _lookup<NativeFunction<CResultArrayUint8 Function(Uint64)>>(
           'dust_sync_version');
   late final _dust_sync_version = _dust_sync_versionPtr
-      .asFunction<CResultArrayUint8 Function()>(isLeaf: true);
+      .asFunction<CResultArrayUint8 Function(int)>();
+
+  /// Like [`dust_sync_version`], but only requests nodes, atoms, edges and list
+  /// items with given labels.
+  CArrayUint8 dust_sync_version_filtered(int handle, int nodesLen,
+      Pointer<Uint64> nodesPtr, int atomsLen, Pointer<Uint64> atomsPtr,
+      int edgesLen, Pointer<Uint64> edgesPtr, int listsLen,
+      Pointer<Uint64> listsPtr) {
+    final res = _dust_sync_version_filtered(handle, nodesLen, nodesPtr,
+        atomsLen, atomsPtr, edgesLen, edgesPtr, listsLen, listsPtr);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_sync_version_filteredPtr = _lookup<
+      NativeFunction<
+          CResultArrayUint8 Function(Uint64, Uint64, Pointer<Uint64>, Uint64,
+              Pointer<Uint64>, Uint64, Pointer<Uint64>, Uint64,
+              Pointer<Uint64>)>>('dust_sync_version_filtered');
+  late final _dust_sync_version_filtered =
+      _dust_sync_version_filteredPtr.asFunction<
+          CResultArrayUint8 Function(int, int, Pointer<Uint64>, int,
+              Pointer<Uint64>, int, Pointer<Uint64>, int,
+              Pointer<Uint64>)>(isLeaf: true);
+
+  int dust_this(int handle) {
+    final res = _dust_this(handle);
+    if (res.tag != 0) _err(res.body.err);
+    return res.body.ok;
+  }
+
+  late final _dust_thisPtr =
+      _lookup<NativeFunction<CResultUint64 Function(Uint64)>>('dust_this');
+  late final _dust_this =
+      _dust_thisPtr.asFunction<CResultUint64 Function(int)>(isLeaf: true);
 }
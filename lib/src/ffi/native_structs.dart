@@ -184,6 +184,12 @@ final class EdgeBody extends Struct {
   external COptionEdge curr;
 }
 
+final class ListBody extends Struct {
+  external CId id;
+  external COptionAtom prev;
+  external COptionAtom curr;
+}
+
 final class CEventData extends Struct {
   @Uint8()
   external int tag;
@@ -194,6 +200,7 @@ final class UnnamedUnion6 extends Union {
   external NodeBody node;
   external AtomBody atom;
   external EdgeBody edge;
+  external ListBody list;
 }
 
 final class CArrayEventData extends Struct {
@@ -326,3 +333,295 @@ final class UnnamedUnion14 extends Union {
   external CArrayUint8 ok;
   external CArrayUint8 err;
 }
+
+final class CTripleUint64Uint64OptionNode extends Struct {
+  @Uint64()
+  external int first;
+  @Uint64()
+  external int second;
+  external COptionNode third;
+}
+
+final class CTripleUint64Uint64OptionAtom extends Struct {
+  @Uint64()
+  external int first;
+  @Uint64()
+  external int second;
+  external COptionAtom third;
+}
+
+final class CTripleUint64Uint64OptionEdge extends Struct {
+  @Uint64()
+  external int first;
+  @Uint64()
+  external int second;
+  external COptionEdge third;
+}
+
+final class NodeConflictBody extends Struct {
+  external CId id;
+  external CTripleUint64Uint64OptionNode winner;
+  external CTripleUint64Uint64OptionNode loser;
+  @Bool()
+  external bool overwritten;
+}
+
+final class AtomConflictBody extends Struct {
+  external CId id;
+  external CTripleUint64Uint64OptionAtom winner;
+  external CTripleUint64Uint64OptionAtom loser;
+  @Bool()
+  external bool overwritten;
+}
+
+final class EdgeConflictBody extends Struct {
+  external CId id;
+  external CTripleUint64Uint64OptionEdge winner;
+  external CTripleUint64Uint64OptionEdge loser;
+  @Bool()
+  external bool overwritten;
+}
+
+final class RejectedBody extends Struct {
+  @Uint8()
+  external int structure;
+  external CId id;
+  @Uint64()
+  external int bucket;
+  @Uint64()
+  external int clock;
+}
+
+final class ForkedBody extends Struct {
+  @Uint64()
+  external int bucket;
+}
+
+final class CConflictData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion15 body;
+}
+
+final class UnnamedUnion15 extends Union {
+  external NodeConflictBody node;
+  external AtomConflictBody atom;
+  external EdgeConflictBody edge;
+  external RejectedBody rejected;
+  external ForkedBody forked;
+}
+
+final class CArrayConflictData extends Struct {
+  @Uint64()
+  external int len;
+  external Pointer<CConflictData> ptr;
+}
+
+final class CResultArrayConflictData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion16 body;
+}
+
+final class UnnamedUnion16 extends Union {
+  external CArrayConflictData ok;
+  external CArrayUint8 err;
+}
+
+final class CResultUint64 extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion17 body;
+}
+
+final class UnnamedUnion17 extends Union {
+  @Uint64()
+  external int ok;
+  external CArrayUint8 err;
+}
+
+final class CStorageStats extends Struct {
+  @Uint64()
+  external int fileSize;
+  @Uint64()
+  external int pageSize;
+  @Uint64()
+  external int freePages;
+  @Uint64()
+  external int walSize;
+}
+
+final class CResultStorageStats extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion18 body;
+}
+
+final class UnnamedUnion18 extends Union {
+  external CStorageStats ok;
+  external CArrayUint8 err;
+}
+
+final class LabelBody extends Struct {
+  @Uint8()
+  external int structure;
+  @Uint64()
+  external int label;
+  @Uint64()
+  external int items;
+  @Uint64()
+  external int bytes;
+}
+
+final class TombstonesBody extends Struct {
+  @Uint8()
+  external int structure;
+  @Uint64()
+  external int items;
+}
+
+final class BucketBody extends Struct {
+  @Uint8()
+  external int structure;
+  @Uint64()
+  external int bucket;
+  @Uint64()
+  external int clock;
+}
+
+final class PendingBody extends Struct {
+  @Uint8()
+  external int structure;
+  @Uint64()
+  external int items;
+}
+
+final class CStatsData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion19 body;
+}
+
+final class UnnamedUnion19 extends Union {
+  external LabelBody label;
+  external TombstonesBody tombstones;
+  external BucketBody bucket;
+  external PendingBody pending;
+}
+
+final class CArrayStatsData extends Struct {
+  @Uint64()
+  external int len;
+  external Pointer<CStatsData> ptr;
+}
+
+final class CResultArrayStatsData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion20 body;
+}
+
+final class UnnamedUnion20 extends Union {
+  external CArrayStatsData ok;
+  external CArrayUint8 err;
+}
+
+final class DanglingAtomBody extends Struct {
+  external CId id;
+  external CId src;
+  @Uint64()
+  external int label;
+}
+
+final class DanglingEdgeBody extends Struct {
+  external CId id;
+  external CEdge edge;
+}
+
+final class CyclicEdgeBody extends Struct {
+  external CId id;
+  external CEdge edge;
+}
+
+final class CViolationData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion21 body;
+}
+
+final class UnnamedUnion21 extends Union {
+  external DanglingAtomBody danglingAtom;
+  external DanglingEdgeBody danglingEdge;
+  external CyclicEdgeBody cyclicEdge;
+}
+
+final class CArrayViolationData extends Struct {
+  @Uint64()
+  external int len;
+  external Pointer<CViolationData> ptr;
+}
+
+final class CResultArrayViolationData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion22 body;
+}
+
+final class UnnamedUnion22 extends Union {
+  external CArrayViolationData ok;
+  external CArrayUint8 err;
+}
+
+final class AtomItemBody extends Struct {
+  external CId id;
+  external CAtom atom;
+}
+
+final class EdgeItemBody extends Struct {
+  external CId id;
+  external CEdge edge;
+}
+
+final class CItemData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion23 body;
+}
+
+final class UnnamedUnion23 extends Union {
+  external AtomItemBody atom;
+  external EdgeItemBody edge;
+}
+
+final class CArrayItemData extends Struct {
+  @Uint64()
+  external int len;
+  external Pointer<CItemData> ptr;
+}
+
+final class CResultArrayItemData extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion24 body;
+}
+
+final class UnnamedUnion24 extends Union {
+  external CArrayItemData ok;
+  external CArrayUint8 err;
+}
+
+final class CPairArrayUint8ArrayUint8 extends Struct {
+  external CArrayUint8 first;
+  external CArrayUint8 second;
+}
+
+final class CResultPairArrayUint8ArrayUint8 extends Struct {
+  @Uint8()
+  external int tag;
+  external UnnamedUnion25 body;
+}
+
+final class UnnamedUnion25 extends Union {
+  external CPairArrayUint8ArrayUint8 ok;
+  external CArrayUint8 err;
+}
@@ -19,6 +19,7 @@ export 'serializers/int.dart';
 export 'serializers/list.dart';
 export 'serializers/map.dart';
 export 'serializers/option.dart';
+export 'serializers/ordered.dart';
 export 'serializers/record.dart';
 export 'serializers/set.dart';
 export 'serializers/string.dart';
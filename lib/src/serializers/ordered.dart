@@ -0,0 +1,109 @@
+// Copyright 2024 ParkourLabs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import 'dart:convert';
+import 'dart:typed_data';
+
+import '../serializers.dart';
+
+// Serializers in this file write bytes which compare in the same order as the
+// values themselves, so that atoms can be queried by value ranges and
+// prefixes in the database.
+
+const _kSignBit = 1 << 63;
+
+/// Like [Int64Serializer], with the sign bit flipped so that negative values
+/// come first.
+class OrderedIntSerializer implements Serializer<int> {
+  const OrderedIntSerializer();
+
+  @override
+  void serialize(int object, BytesBuilder builder) =>
+      builder.writeInt64(object ^ _kSignBit);
+
+  @override
+  int deserialize(BytesReader reader) => reader.readInt64() ^ _kSignBit;
+}
+
+/// Like [Float64Serializer], with the sign bit flipped for positive values
+/// and all bits flipped for negative values.
+class OrderedDoubleSerializer implements Serializer<double> {
+  const OrderedDoubleSerializer();
+
+  @override
+  void serialize(double object, BytesBuilder builder) {
+    final bits = (ByteData(8)..setFloat64(0, object)).getInt64(0);
+    builder.writeInt64(bits < 0 ? ~bits : bits ^ _kSignBit);
+  }
+
+  @override
+  double deserialize(BytesReader reader) {
+    final bits = reader.readInt64();
+    return (ByteData(8)..setInt64(0, bits < 0 ? bits ^ _kSignBit : ~bits))
+        .getFloat64(0);
+  }
+}
+
+/// Like [DateTimeSerializer], ordered by time.
+class OrderedDateTimeSerializer implements Serializer<DateTime> {
+  const OrderedDateTimeSerializer();
+
+  @override
+  void serialize(DateTime object, BytesBuilder builder) =>
+      const OrderedIntSerializer()
+          .serialize(object.microsecondsSinceEpoch, builder);
+
+  @override
+  DateTime deserialize(BytesReader reader) =>
+      DateTime.fromMicrosecondsSinceEpoch(
+          const OrderedIntSerializer().deserialize(reader));
+}
+
+/// Writes UTF-8 followed by a terminator instead of a length, so strings are
+/// ordered by code point. Zero bytes are escaped as `00 ff`, and the
+/// terminator is `00 01`.
+class OrderedStringSerializer implements Serializer<String> {
+  const OrderedStringSerializer();
+
+  /// Returns the bytes which all serialized strings starting with [prefix]
+  /// start with, for prefix queries.
+  static Uint8List prefix(String prefix) {
+    final builder = BytesBuilder();
+    for (final byte in utf8.encode(prefix)) {
+      builder.add(byte == 0 ? const [0x00, 0xff] : [byte]);
+    }
+    return builder.takeBytes();
+  }
+
+  @override
+  void serialize(String object, BytesBuilder builder) {
+    builder.add(prefix(object));
+    builder.add(const [0x00, 0x01]);
+  }
+
+  @override
+  String deserialize(BytesReader reader) {
+    final bytes = <int>[];
+    while (true) {
+      final byte = reader.readUint8();
+      if (byte != 0) {
+        bytes.add(byte);
+      } else if (reader.readUint8() == 0xff) {
+        bytes.add(0);
+      } else {
+        return utf8.decode(bytes);
+      }
+    }
+  }
+}
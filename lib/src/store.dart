@@ -36,9 +36,128 @@ export 'store/backlinks.dart';
 
 class AlreadyDeletedException<T> implements Exception {}
 
+enum ViolationKind { danglingAtom, danglingEdge, cyclicEdge }
+
+/// An atom or edge which breaks an invariant of the store, see [Dust.check].
+class Violation {
+  final ViolationKind kind;
+  final Id id;
+  final Id src;
+  final int label;
+
+  /// The destination node, for edges.
+  final Id? dst;
+
+  const Violation(this.kind, this.id, this.src, this.label, this.dst);
+}
+
+/// What is stored in one structure, see [Dust.stats].
+class StructureStats {
+  /// Label -> (items, value bytes) for saved items. Value bytes are only
+  /// counted for atoms and list items.
+  final labels = <int, (int, int)>{};
+
+  /// Saved removals which have not been compacted. They keep no label.
+  int tombstones = 0;
+
+  /// Bucket -> clock.
+  final buckets = <int, int>{};
+
+  /// Modifications not yet saved by a barrier.
+  int pending = 0;
+}
+
+/// What a [Dust.syncJoin] or [Dust.importBundle] did besides applying writes.
+class JoinReport {
+  /// IDs of items where a concurrent write lost, together with whether it was
+  /// the local write (i.e. the local change was overwritten).
+  final conflicts = <(Id, bool)>[];
+
+  /// `(structure, id)` of remote writes which were rejected for being too far
+  /// in the future, where the structure is 0 for nodes, 1 for atoms, 2 for
+  /// edges and 3 for list items. They are sent again by later syncs, and
+  /// accepted once the local clock has caught up.
+  final rejected = <(int, Id)>[];
+
+  /// The previous bucket of this replica, if it found out that another
+  /// replica writes to it too, e.g. a copy of the database, and moved to a
+  /// fresh one. See [Dust.bucket].
+  int? forked;
+}
+
+/// Node, atom and edge writes to be applied in one call, see [Dust.setBatch].
+///
+/// Writes are encoded as the native `Vec<Write>`: a count, then for each
+/// write its kind, ID and optional value, all integers big-endian.
+class WriteBatch {
+  final _builder = BytesBuilder();
+  int _count = 0;
+
+  int get length => _count;
+
+  void setNode(Id id, int? l) {
+    _write(0, id, l != null);
+    if (l != null) _int(l);
+  }
+
+  void setAtom<T>(Id id, (Id, int, T, Serializer<T>)? slv) {
+    _write(1, id, slv != null);
+    if (slv != null) {
+      final (src, label, value, serializer) = slv;
+      final builder = BytesBuilder();
+      serializer.serialize(value, builder);
+      _id(src);
+      _int(label);
+      _int(builder.length);
+      _builder.add(builder.takeBytes());
+    }
+  }
+
+  void setEdge(Id id, (Id, int, Id)? sld) {
+    _write(2, id, sld != null);
+    if (sld != null) {
+      final (src, label, dst) = sld;
+      _id(src);
+      _int(label);
+      _id(dst);
+    }
+  }
+
+  void _write(int kind, Id id, bool some) {
+    _count++;
+    _builder.add([0, 0, 0, kind]);
+    _id(id);
+    _builder.addByte(some ? 1 : 0);
+  }
+
+  void _id(Id id) {
+    _int(id.high);
+    _int(id.low);
+  }
+
+  void _int(int value) {
+    _builder.add((ByteData(8)..setInt64(0, value)).buffer.asUint8List());
+  }
+
+  Uint8List _takeBytes() {
+    final res = BytesBuilder(copy: false)
+      ..add((ByteData(8)..setInt64(0, _count)).buffer.asUint8List())
+      ..add(_builder.takeBytes());
+    _count = 0;
+    return res.takeBytes();
+  }
+}
+
 ByteData _view(CArrayUint8 array) =>
     array.ptr.asTypedList(array.len).buffer.asByteData();
 
+// See: https://github.com/dart-lang/sdk/issues/44589
+Pointer<Uint8> _copy(List<int> bytes) {
+  final ptr = malloc.allocate<Uint8>(bytes.length);
+  for (var i = 0; i < bytes.length; i++) (ptr + i).value = bytes[i];
+  return ptr;
+}
+
 typedef NodeByIdSubscription = void Function(int? l);
 typedef NodeByLabelSubscription = (void Function(Id id), void Function(Id id));
 typedef AtomByIdSubscription = void Function((Id, int, ByteData)? slv);
@@ -75,14 +194,24 @@ typedef EdgeByDstLabelSubscription = (
   void Function(Id id, Id src),
   void Function(Id id)
 );
+typedef ListBySrcLabelSubscription = (
+  void Function(Id id, ByteData value),
+  void Function()
+);
 
 /// The main wrapper class around FFI functions.
 ///
 /// Also responsible for subscriptions and reactivity.
 class Dust {
   final NativeBindings bindings;
+
+  /// The native handle of the database.
+  final int handle;
   Timer? committer;
 
+  /// The number of open savepoints, during which nothing is committed.
+  int savepoints = 0;
+
   final nodeById = MultiMap<Id, NodeByIdSubscription>();
   final nodeByLabel = MultiMap<int, NodeByLabelSubscription>();
   final atomById = MultiMap<Id, AtomByIdSubscription>();
@@ -95,6 +224,7 @@ class Dust {
   final edgeBySrcLabel = MultiMap<(Id, int), EdgeBySrcLabelSubscription>();
   final edgeByDst = MultiMap<Id, EdgeByDstSubscription>();
   final edgeByDstLabel = MultiMap<(Id, int), EdgeByDstLabelSubscription>();
+  final listBySrcLabel = MultiMap<(Id, int), ListBySrcLabelSubscription>();
 
   late final _nodeByIdFinalizer =
       Finalizer<(Id, NodeByIdSubscription)>(_unsubscribeNodeById);
@@ -122,25 +252,38 @@ class Dust {
   late final _edgeByDstLabelFinalizer =
       Finalizer<((Id, int), EdgeByDstLabelSubscription)>(
           _unsubscribeEdgeByDstLabel);
+  late final _listBySrcLabelFinalizer =
+      Finalizer<((Id, int), ListBySrcLabelSubscription)>(
+          _unsubscribeListBySrcLabel);
 
-  Dust._(this.bindings);
+  Dust._(this.bindings, this.handle);
 
   /// The global [Dust] instance.
   static Dust? _instance;
 
   /// Initialises the global [Dust] instance.
   static void open(String databasePath, List<Repository> repositories) {
-    Ffi.init(databasePath, repositories);
-    _instance = Dust._(Ffi.bindings);
+    _instance = connect(databasePath, repositories);
   }
 
   /// Disconnects the global [Dust] instance.
   static void close() {
-    instance.committer?.cancel();
-    instance.bindings.dust_close();
+    instance.disconnect();
     _instance = null;
   }
 
+  /// Opens a separate [Dust] instance, e.g. for a per-account database. Opening
+  /// a path that is already open disconnects the previous instance.
+  static Dust connect(String databasePath, List<Repository> repositories) {
+    return Dust._(Ffi.bindings, Ffi.open(databasePath, repositories));
+  }
+
+  /// Commits and disconnects this instance. It must not be used afterwards.
+  void disconnect() {
+    committer?.cancel();
+    bindings.dust_close(handle);
+  }
+
   /// Obtains the global [Dust] instance. Must be called after [open] has been called once.
   static Dust get instance {
     if (_instance == null)
@@ -157,13 +300,13 @@ class Dust {
 
   /// Obtains node value.
   void getNodeById(Id id, void Function(int?) fn) {
-    final data = bindings.dust_node(id.high, id.low);
+    final data = bindings.dust_node(handle, id.high, id.low);
     fn(data.tag == 0 ? null : data.some.label);
   }
 
   /// Queries the reverse index.
   void getNodeByLabel(int label, void Function(Id) fn) {
-    final data = bindings.dust_node_id_by_label(label);
+    final data = bindings.dust_node_id_by_label(handle, label);
     for (var i = 0; i < data.len; i++) {
       final elem = (data.ptr + i).ref;
       fn(Id.fromNative(elem));
@@ -173,7 +316,7 @@ class Dust {
 
   /// Obtains atom value.
   void getAtomById(Id id, void Function((Id, int, ByteData)?) fn) {
-    final data = bindings.dust_atom(id.high, id.low);
+    final data = bindings.dust_atom(handle, id.high, id.low);
     fn(data.tag == 0
         ? null
         : (
@@ -186,7 +329,8 @@ class Dust {
 
   /// Queries the forward index.
   void getAtomLabelValueBySrc(Id src, void Function(Id, int, ByteData) fn) {
-    final data = bindings.dust_atom_id_label_value_by_src(src.high, src.low);
+    final data =
+        bindings.dust_atom_id_label_value_by_src(handle, src.high, src.low);
     for (var i = 0; i < data.len; i++) {
       final elem = (data.ptr + i).ref;
       fn(Id.fromNative(elem.first), elem.second, _view(elem.third));
@@ -197,8 +341,8 @@ class Dust {
   /// Queries the forward index.
   void getAtomValueBySrcLabel(
       Id src, int label, void Function(Id, ByteData) fn) {
-    final data =
-        bindings.dust_atom_id_value_by_src_label(src.high, src.low, label);
+    final data = bindings.dust_atom_id_value_by_src_label(
+        handle, src.high, src.low, label);
     for (var i = 0; i < data.len; i++) {
       final elem = (data.ptr + i).ref;
       fn(Id.fromNative(elem.first), _view(elem.second));
@@ -208,7 +352,71 @@ class Dust {
 
   /// Queries the reverse index.
   void getAtomSrcValueByLabel(int label, void Function(Id, Id, ByteData) fn) {
-    final data = bindings.dust_atom_id_src_value_by_label(label);
+    final data = bindings.dust_atom_id_src_value_by_label(handle, label);
+    for (var i = 0; i < data.len; i++) {
+      final elem = (data.ptr + i).ref;
+      fn(Id.fromNative(elem.first), Id.fromNative(elem.second),
+          _view(elem.third));
+    }
+    bindings.dust_drop_array_id_id_array_u8(data);
+  }
+
+  /// Queries the reverse index for values within a range, ordered by value
+  /// as bytes (see [OrderedIntSerializer] and the like). Each bound is a
+  /// serialized value together with whether it is included. A negative
+  /// [limit] means no limit.
+  void getAtomSrcValueByLabelRange(int label, (Uint8List, bool)? lower,
+      (Uint8List, bool)? upper, void Function(Id, Id, ByteData) fn,
+      {int offset = 0, int limit = -1}) {
+    final (lowerKind, lowerBytes) = switch (lower) {
+      null => (0, Uint8List(0)),
+      (final bytes, final included) => (included ? 1 : 2, bytes),
+    };
+    final (upperKind, upperBytes) = switch (upper) {
+      null => (0, Uint8List(0)),
+      (final bytes, final included) => (included ? 1 : 2, bytes),
+    };
+    final lowerPtr = _copy(lowerBytes);
+    final upperPtr = _copy(upperBytes);
+    final CArrayTripleIdIdArrayUint8 data;
+    try {
+      data = bindings.dust_atom_id_src_value_by_label_range(
+          handle,
+          label,
+          lowerKind,
+          lowerBytes.length,
+          lowerPtr,
+          upperKind,
+          upperBytes.length,
+          upperPtr,
+          offset,
+          limit);
+    } finally {
+      malloc.free(lowerPtr);
+      malloc.free(upperPtr);
+    }
+    for (var i = 0; i < data.len; i++) {
+      final elem = (data.ptr + i).ref;
+      fn(Id.fromNative(elem.first), Id.fromNative(elem.second),
+          _view(elem.third));
+    }
+    bindings.dust_drop_array_id_id_array_u8(data);
+  }
+
+  /// Queries the reverse index for values starting with [prefix], ordered by
+  /// value as bytes (see [OrderedStringSerializer.prefix]). A negative
+  /// [limit] means no limit.
+  void getAtomSrcValueByLabelPrefix(
+      int label, Uint8List prefix, void Function(Id, Id, ByteData) fn,
+      {int offset = 0, int limit = -1}) {
+    final ptr = _copy(prefix);
+    final CArrayTripleIdIdArrayUint8 data;
+    try {
+      data = bindings.dust_atom_id_src_value_by_label_prefix(
+          handle, label, prefix.length, ptr, offset, limit);
+    } finally {
+      malloc.free(ptr);
+    }
     for (var i = 0; i < data.len; i++) {
       final elem = (data.ptr + i).ref;
       fn(Id.fromNative(elem.first), Id.fromNative(elem.second),
@@ -226,7 +434,7 @@ class Dust {
 
   /// Obtains edge value.
   void getEdgeById(Id id, void Function((Id, int, Id)?) fn) {
-    final data = bindings.dust_edge(id.high, id.low);
+    final data = bindings.dust_edge(handle, id.high, id.low);
     fn(data.tag == 0
         ? null
         : (
@@ -238,7 +446,8 @@ class Dust {
 
   /// Queries the forward index.
   void getEdgeLabelDstBySrc(Id src, void Function(Id, int, Id) fn) {
-    final data = bindings.dust_edge_id_label_dst_by_src(src.high, src.low);
+    final data =
+        bindings.dust_edge_id_label_dst_by_src(handle, src.high, src.low);
     for (var i = 0; i < data.len; i++) {
       final elem = (data.ptr + i).ref;
       fn(Id.fromNative(elem.first), elem.second, Id.fromNative(elem.third));
@@ -248,8 +457,8 @@ class Dust {
 
   /// Queries the forward index.
   void getEdgeDstBySrcLabel(Id src, int label, void Function(Id, Id) fn) {
-    final data =
-        bindings.dust_edge_id_dst_by_src_label(src.high, src.low, label);
+    final data = bindings.dust_edge_id_dst_by_src_label(
+        handle, src.high, src.low, label);
     for (var i = 0; i < data.len; i++) {
       final item = (data.ptr + i).ref;
       fn(Id.fromNative(item.first), Id.fromNative(item.second));
@@ -259,7 +468,8 @@ class Dust {
 
   /// Queries the reverse index.
   void getEdgeSrcLabelByDst(Id dst, void Function(Id, Id, int) fn) {
-    final data = bindings.dust_edge_id_src_label_by_dst(dst.high, dst.low);
+    final data =
+        bindings.dust_edge_id_src_label_by_dst(handle, dst.high, dst.low);
     for (var i = 0; i < data.len; i++) {
       final item = (data.ptr + i).ref;
       fn(Id.fromNative(item.first), Id.fromNative(item.second), item.third);
@@ -269,8 +479,8 @@ class Dust {
 
   /// Queries the reverse index.
   void getEdgeSrcByDstLabel(Id dst, int label, void Function(Id, Id) fn) {
-    final data =
-        bindings.dust_edge_id_src_by_dst_label(dst.high, dst.low, label);
+    final data = bindings.dust_edge_id_src_by_dst_label(
+        handle, dst.high, dst.low, label);
     for (var i = 0; i < data.len; i++) {
       final item = (data.ptr + i).ref;
       fn(Id.fromNative(item.first), Id.fromNative(item.second));
@@ -278,20 +488,67 @@ class Dust {
     bindings.dust_drop_array_id_id(data);
   }
 
+  /// Queries the items of a list, in order.
+  void getListValueBySrcLabel(
+      Id src, int label, void Function(Id, ByteData) fn) {
+    final data = bindings.dust_list_id_value_by_src_label(
+        handle, src.high, src.low, label);
+    for (var i = 0; i < data.len; i++) {
+      final elem = (data.ptr + i).ref;
+      fn(Id.fromNative(elem.first), _view(elem.second));
+    }
+    bindings.dust_drop_array_id_array_u8(data);
+  }
+
+  /// Obtains the values of many atoms and edges in one call. [atom] or [edge]
+  /// is called for each of [ids] which exists.
+  void getAtomsEdges(
+      List<Id> ids,
+      void Function(Id, Id, int, ByteData) atom,
+      void Function(Id, Id, int, Id) edge) {
+    final len = ids.length;
+    final ptr = malloc.allocate<CId>(len * sizeOf<CId>());
+    for (var i = 0; i < len; i++) {
+      (ptr + i).ref
+        ..high = ids[i].high
+        ..low = ids[i].low;
+    }
+    final CArrayItemData data;
+    try {
+      data = bindings.dust_atoms_edges(handle, len, ptr);
+    } finally {
+      malloc.free(ptr);
+    }
+    for (var i = 0; i < data.len; i++) {
+      final elem = (data.ptr + i).ref;
+      switch (elem.tag) {
+        case 0:
+          final body = elem.body.atom;
+          atom(Id.fromNative(body.id), Id.fromNative(body.atom.src),
+              body.atom.label, _view(body.atom.value));
+        case 1:
+          final body = elem.body.edge;
+          edge(Id.fromNative(body.id), Id.fromNative(body.edge.src),
+              body.edge.label, Id.fromNative(body.edge.dst));
+      }
+    }
+    bindings.dust_drop_array_item_data(data);
+  }
+
   /// Modifies node value. Requires a [barrier] call to come into effect.
   void setNode(Id id, int? l) {
     if (l == null) {
-      bindings.dust_set_node_none(id.high, id.low);
+      bindings.dust_set_node_none(handle, id.high, id.low);
     } else {
       final label = l;
-      bindings.dust_set_node_some(id.high, id.low, label);
+      bindings.dust_set_node_some(handle, id.high, id.low, label);
     }
   }
 
   /// Modifies atom value. Requires a [barrier] call to come into effect.
   void setAtom<T>(Id id, (Id, int, T, Serializer<T>)? slv) {
     if (slv == null) {
-      bindings.dust_set_atom_none(id.high, id.low);
+      bindings.dust_set_atom_none(handle, id.high, id.low);
     } else {
       final (src, label, value, serializer) = slv;
       final builder = BytesBuilder();
@@ -302,7 +559,7 @@ class Dust {
       final ptr = malloc.allocate<Uint8>(len);
       for (var i = 0; i < len; i++) (ptr + i).value = bytes[i];
       bindings.dust_set_atom_some(
-          id.high, id.low, src.high, src.low, label, len, ptr);
+          handle, id.high, id.low, src.high, src.low, label, len, ptr);
       malloc.free(ptr);
     }
   }
@@ -310,16 +567,82 @@ class Dust {
   /// Modifies edge value. Requires a [barrier] call to come into effect.
   void setEdge(Id id, (Id, int, Id)? sld) {
     if (sld == null) {
-      bindings.dust_set_edge_none(id.high, id.low);
+      bindings.dust_set_edge_none(handle, id.high, id.low);
     } else {
       final (src, label, dst) = sld;
       bindings.dust_set_edge_some(
-          id.high, id.low, src.high, src.low, label, dst.high, dst.low);
+          handle, id.high, id.low, src.high, src.low, label, dst.high, dst.low);
+    }
+  }
+
+  /// Inserts an item into the list `(src, label)` at [index], counting only
+  /// present items. Requires a [barrier] call to come into effect.
+  void insertListItem<T>(
+      Id id, Id src, int label, int index, T value, Serializer<T> serializer) {
+    final builder = BytesBuilder();
+    serializer.serialize(value, builder);
+    final bytes = builder.takeBytes();
+    final ptr = _copy(bytes);
+    try {
+      bindings.dust_insert_list_item(handle, id.high, id.low, src.high,
+          src.low, label, index, bytes.length, ptr);
+    } finally {
+      malloc.free(ptr);
+    }
+  }
+
+  /// Removes an item from its list. Requires a [barrier] call to come into
+  /// effect.
+  void removeListItem(Id id) {
+    bindings.dust_remove_list_item(handle, id.high, id.low);
+  }
+
+  /// Applies all writes of [batch] in one call, which leaves it empty.
+  /// Requires a [barrier] call to come into effect.
+  void setBatch(WriteBatch batch) {
+    final bytes = batch._takeBytes();
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = bytes.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = bytes[i];
+    try {
+      bindings.dust_set_batch(handle, len, ptr);
+    } finally {
+      malloc.free(ptr);
     }
   }
 
   Uint8List syncVersion() {
-    final data = bindings.dust_sync_version();
+    final data = bindings.dust_sync_version(handle);
+    final res =
+        Uint8List.fromList(data.ptr.asTypedList(data.len)); // Makes copy.
+    bindings.dust_drop_array_u8(data);
+    return res;
+  }
+
+  /// Like [syncVersion], but only requests nodes, atoms, edges and list items
+  /// with given labels.
+  Uint8List syncVersionFiltered(
+      {List<int> nodes = const [],
+      List<int> atoms = const [],
+      List<int> edges = const [],
+      List<int> lists = const []}) {
+    final ptrs = [nodes, atoms, edges, lists].map((labels) {
+      final ptr = malloc.allocate<Uint64>(labels.length * sizeOf<Uint64>());
+      for (var i = 0; i < labels.length; i++) (ptr + i).value = labels[i];
+      return ptr;
+    }).toList();
+    final data = bindings.dust_sync_version_filtered(
+        handle,
+        nodes.length,
+        ptrs[0],
+        atoms.length,
+        ptrs[1],
+        edges.length,
+        ptrs[2],
+        lists.length,
+        ptrs[3]);
+    ptrs.forEach(malloc.free);
     final res =
         Uint8List.fromList(data.ptr.asTypedList(data.len)); // Makes copy.
     bindings.dust_drop_array_u8(data);
@@ -331,7 +654,7 @@ class Dust {
     final len = version.length;
     final ptr = malloc.allocate<Uint8>(len);
     for (var i = 0; i < len; i++) (ptr + i).value = version[i];
-    final data = bindings.dust_sync_actions(len, ptr);
+    final data = bindings.dust_sync_actions(handle, len, ptr);
     malloc.free(ptr);
     final res =
         Uint8List.fromList(data.ptr.asTypedList(data.len)); // Makes copy.
@@ -339,14 +662,321 @@ class Dust {
     return res;
   }
 
+  /// Returns a page of at most [budget] actions, together with the cursor to
+  /// pass for the next page, or null if there are no more pages.
+  (Uint8List, Uint8List?) syncActionsPage(
+      Uint8List version, Uint8List? cursor, int budget) {
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = version.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = version[i];
+    final cursorLen = cursor?.length ?? 0;
+    final cursorPtr = malloc.allocate<Uint8>(cursorLen);
+    for (var i = 0; i < cursorLen; i++) (cursorPtr + i).value = cursor![i];
+    final data = bindings.dust_sync_actions_page(
+        handle, len, ptr, cursorLen, cursorPtr, budget);
+    malloc.free(ptr);
+    malloc.free(cursorPtr);
+    final page = Uint8List.fromList(
+        data.first.ptr.asTypedList(data.first.len)); // Makes copy.
+    final next = data.second.len == 0
+        ? null
+        : Uint8List.fromList(data.second.ptr.asTypedList(data.second.len));
+    bindings.dust_drop_pair_array_u8_array_u8(data);
+    return (page, next);
+  }
+
   /// Requires a [barrier] call to come into effect.
-  void syncJoin(Uint8List actions) {
+  ///
+  /// Returns the conflicts which were decided and the writes which were
+  /// rejected.
+  JoinReport syncJoin(Uint8List actions) {
     // See: https://github.com/dart-lang/sdk/issues/44589
     final len = actions.length;
     final ptr = malloc.allocate<Uint8>(len);
     for (var i = 0; i < len; i++) (ptr + i).value = actions[i];
-    final _ = bindings.dust_sync_join(len, ptr);
+    final data = bindings.dust_sync_join(handle, len, ptr);
+    malloc.free(ptr);
+    return _joinReport(data);
+  }
+
+  /// Returns the live state together with the current version, for
+  /// bootstrapping a new replica with [installSnapshot].
+  Uint8List snapshot() {
+    final data = bindings.dust_snapshot(handle);
+    final res =
+        Uint8List.fromList(data.ptr.asTypedList(data.len)); // Makes copy.
+    bindings.dust_drop_array_u8(data);
+    return res;
+  }
+
+  /// Returns a page of around [budget] bytes of the snapshot, together with
+  /// the cursor to pass for the next page, or null if there are no more
+  /// pages. Each page is installed with [installSnapshot].
+  (Uint8List, Uint8List?) snapshotPage(Uint8List? cursor, int budget) {
+    final cursorLen = cursor?.length ?? 0;
+    final cursorPtr = malloc.allocate<Uint8>(cursorLen);
+    for (var i = 0; i < cursorLen; i++) (cursorPtr + i).value = cursor![i];
+    final data =
+        bindings.dust_snapshot_page(handle, cursorLen, cursorPtr, budget);
+    malloc.free(cursorPtr);
+    final page = Uint8List.fromList(
+        data.first.ptr.asTypedList(data.first.len)); // Makes copy.
+    final next = data.second.len == 0
+        ? null
+        : Uint8List.fromList(data.second.ptr.asTypedList(data.second.len));
+    bindings.dust_drop_pair_array_u8_array_u8(data);
+    return (page, next);
+  }
+
+  /// Installs a snapshot made by [snapshot], or a page of one made by
+  /// [snapshotPage], into an empty store. No events are emitted, so this
+  /// should be done before any subscriptions.
+  void installSnapshot(Uint8List snapshot) {
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = snapshot.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = snapshot[i];
+    bindings.dust_install_snapshot(handle, len, ptr);
+    malloc.free(ptr);
+  }
+
+  /// Exports all actions later than [version] as a self-describing bundle,
+  /// e.g. to be carried over to a device without network access.
+  Uint8List exportBundle(Uint8List version) {
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = version.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = version[i];
+    final data = bindings.dust_export_bundle(handle, len, ptr);
+    malloc.free(ptr);
+    final res =
+        Uint8List.fromList(data.ptr.asTypedList(data.len)); // Makes copy.
+    bindings.dust_drop_array_u8(data);
+    return res;
+  }
+
+  /// Like [syncJoin], for a bundle made by [exportBundle]. Nothing is applied
+  /// unless the whole bundle is intact.
+  JoinReport importBundle(Uint8List bundle) {
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = bundle.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = bundle[i];
+    final data = bindings.dust_import_bundle(handle, len, ptr);
     malloc.free(ptr);
+    return _joinReport(data);
+  }
+
+  /// The bucket of this replica, which other replicas register as a peer.
+  int get bucket => bindings.dust_this(handle);
+
+  /// Registers a peer, so that removals are kept until it has acknowledged
+  /// them.
+  void addPeer(int peer) => bindings.dust_add_peer(handle, peer);
+
+  /// Unregisters a peer, e.g. one which has not synced for a long time. If it
+  /// comes back, it gets a full resync.
+  void removePeer(int peer) => bindings.dust_remove_peer(handle, peer);
+
+  /// Records a version received from a registered peer as acknowledged.
+  void acknowledge(Uint8List version) {
+    // See: https://github.com/dart-lang/sdk/issues/44589
+    final len = version.length;
+    final ptr = malloc.allocate<Uint8>(len);
+    for (var i = 0; i < len; i++) (ptr + i).value = version[i];
+    try {
+      bindings.dust_acknowledge(handle, len, ptr);
+    } finally {
+      malloc.free(ptr);
+    }
+  }
+
+  /// Drops removals which all peers have acknowledged. Returns the number of
+  /// dropped items.
+  int compact() => bindings.dust_compact(handle);
+
+  /// Backs up the last committed state to [path], which is overwritten.
+  void backup(String path) {
+    final ptr = path.toNativeUtf8(allocator: malloc);
+    try {
+      bindings.dust_backup(handle, ptr.length, ptr.cast<Uint8>());
+    } finally {
+      malloc.free(ptr);
+    }
+  }
+
+  /// Replaces all data with the backup at [path]. Uncommitted changes are
+  /// lost and no events are emitted, so all data should be read again.
+  void restore(String path) {
+    committer?.cancel();
+    savepoints = 0;
+    final ptr = path.toNativeUtf8(allocator: malloc);
+    try {
+      bindings.dust_restore(handle, ptr.length, ptr.cast<Uint8>());
+    } finally {
+      malloc.free(ptr);
+    }
+  }
+
+  /// Returns the sizes of the database files in bytes as of the last commit,
+  /// and the number of free pages which [maintain] can release.
+  ({int fileSize, int pageSize, int freePages, int walSize}) storageStats() {
+    final stats = bindings.dust_storage_stats(handle);
+    return (
+      fileSize: stats.fileSize,
+      pageSize: stats.pageSize,
+      freePages: stats.freePages,
+      walSize: stats.walSize,
+    );
+  }
+
+  /// Commits, then releases up to [maxPages] free pages (all by default) and
+  /// checkpoints the write-ahead log, stopping once [budget] has elapsed.
+  /// Meant to be run while the app is idle. Returns the number of released
+  /// pages.
+  int maintain(Duration budget, {int maxPages = -1}) {
+    committer?.cancel();
+    return bindings.dust_maintain(handle, budget.inMilliseconds, maxPages);
+  }
+
+  /// Drops all writes since the last [barrier]. Observers have not seen them
+  /// yet, so they are not notified. Throws if a sync was joined since then,
+  /// as it saved part of its state right away; call [barrier] instead, or
+  /// [rollback] to undo the sync as well.
+  void discard() => bindings.dust_discard(handle);
+
+  /// Drops everything written since the last commit and closes all
+  /// savepoints. Observers are not notified, so data changed by barriers
+  /// since then should be read again.
+  void rollback() {
+    committer?.cancel();
+    bindings.dust_rollback(handle);
+    savepoints = 0;
+  }
+
+  /// Opens a savepoint, which can be nested. Nothing is committed until all
+  /// savepoints are closed by [release] or [rollbackToSavepoint].
+  void savepoint() {
+    committer?.cancel();
+    savepoints = bindings.dust_savepoint(handle);
+  }
+
+  /// Closes the innermost savepoint, keeping everything written since.
+  void release() {
+    savepoints = bindings.dust_release(handle);
+    _commitLater();
+  }
+
+  /// Drops everything written since the innermost savepoint and closes it.
+  /// Observers are not notified, like with [rollback].
+  void rollbackToSavepoint() {
+    savepoints = bindings.dust_rollback_to_savepoint(handle);
+    _commitLater();
+  }
+
+  /// Scans all data for atoms and edges which break an invariant of the
+  /// store, e.g. ones written by older versions.
+  List<Violation> check() => _violations(bindings.dust_check(handle));
+
+  /// Removes what [check] reports, as well as anything which depends on it,
+  /// and notifies observers. Returns the violations which were found.
+  List<Violation> repair() {
+    final res = _violations(bindings.dust_repair(handle));
+    barrier();
+    return res;
+  }
+
+  List<Violation> _violations(CArrayViolationData data) {
+    final res = <Violation>[];
+    for (var i = 0; i < data.len; i++) {
+      final item = (data.ptr + i).ref;
+      switch (item.tag) {
+        case 0:
+          final body = item.body.danglingAtom;
+          res.add(Violation(ViolationKind.danglingAtom, Id.fromNative(body.id),
+              Id.fromNative(body.src), body.label, null));
+        case 1:
+          final body = item.body.danglingEdge;
+          res.add(Violation(
+              ViolationKind.danglingEdge,
+              Id.fromNative(body.id),
+              Id.fromNative(body.edge.src),
+              body.edge.label,
+              Id.fromNative(body.edge.dst)));
+        case 2:
+          final body = item.body.cyclicEdge;
+          res.add(Violation(
+              ViolationKind.cyclicEdge,
+              Id.fromNative(body.id),
+              Id.fromNative(body.edge.src),
+              body.edge.label,
+              Id.fromNative(body.edge.dst)));
+        default:
+          throw UnimplementedError();
+      }
+    }
+    bindings.dust_drop_array_violation_data(data);
+    return res;
+  }
+
+  /// Returns what is stored in nodes, atoms, edges and list items, for
+  /// diagnostics. This scans all saved data, so it may be slow on large
+  /// stores.
+  ({
+    StructureStats nodes,
+    StructureStats atoms,
+    StructureStats edges,
+    StructureStats lists
+  }) stats() {
+    final res = List.generate(4, (_) => StructureStats());
+    final data = bindings.dust_stats(handle);
+    for (var i = 0; i < data.len; i++) {
+      final entry = (data.ptr + i).ref;
+      switch (entry.tag) {
+        case 0:
+          final body = entry.body.label;
+          res[body.structure].labels[body.label] = (body.items, body.bytes);
+        case 1:
+          final body = entry.body.tombstones;
+          res[body.structure].tombstones = body.items;
+        case 2:
+          final body = entry.body.bucket;
+          res[body.structure].buckets[body.bucket] = body.clock;
+        case 3:
+          final body = entry.body.pending;
+          res[body.structure].pending = body.items;
+        default:
+          throw UnimplementedError();
+      }
+    }
+    bindings.dust_drop_array_stats_data(data);
+    return (nodes: res[0], atoms: res[1], edges: res[2], lists: res[3]);
+  }
+
+  JoinReport _joinReport(CArrayConflictData data) {
+    final res = JoinReport();
+    for (var i = 0; i < data.len; i++) {
+      final item = (data.ptr + i).ref;
+      switch (item.tag) {
+        case 0:
+          final body = item.body.node;
+          res.conflicts.add((Id.fromNative(body.id), body.overwritten));
+        case 1:
+          final body = item.body.atom;
+          res.conflicts.add((Id.fromNative(body.id), body.overwritten));
+        case 2:
+          final body = item.body.edge;
+          res.conflicts.add((Id.fromNative(body.id), body.overwritten));
+        case 3:
+          final body = item.body.rejected;
+          res.rejected.add((body.structure, Id.fromNative(body.id)));
+        case 4:
+          res.forked = item.body.forked.bucket;
+      }
+    }
+    bindings.dust_drop_array_conflict_data(data);
+    return res;
   }
 
   /// Subscribes to node value changes.
@@ -490,6 +1120,21 @@ class Dust {
     getEdgeSrcByDstLabel(dst, label, insert);
   }
 
+  /// Subscribes to the items of a list. Since any change can move items
+  /// around, [clear] is called before all items are passed to [insert] again.
+  void subscribeListBySrcLabel(
+      Id src,
+      int label,
+      void Function(Id id, ByteData value) insert,
+      void Function() clear,
+      Object owner) {
+    final key = (src, label);
+    final value = (insert, clear);
+    listBySrcLabel.add(key, value);
+    _listBySrcLabelFinalizer.attach(owner, (key, value));
+    getListValueBySrcLabel(src, label, insert);
+  }
+
   void _unsubscribeNodeById((Id, NodeByIdSubscription) kv) =>
       nodeById.remove(kv.$1, kv.$2);
   void _unsubscribeNodeByLabel((int, NodeByLabelSubscription) kv) =>
@@ -513,10 +1158,13 @@ class Dust {
       edgeByDst.remove(kv.$1, kv.$2);
   void _unsubscribeEdgeByDstLabel(((Id, int), EdgeByDstLabelSubscription) kv) =>
       edgeByDstLabel.remove(kv.$1, kv.$2);
+  void _unsubscribeListBySrcLabel(((Id, int), ListBySrcLabelSubscription) kv) =>
+      listBySrcLabel.remove(kv.$1, kv.$2);
 
   /// Processes all events and invokes relevant observers.
   void barrier() {
-    final data = bindings.dust_barrier();
+    final data = bindings.dust_barrier(handle);
+    final lists = <(Id, int)>{};
     for (var i = 0; i < data.len; i++) {
       final event = (data.ptr + i).ref;
       switch (event.tag) {
@@ -587,14 +1235,36 @@ class Dust {
           } else {
             for (final update in edgeById[id]) update(null);
           }
+        case 3:
+          final prev = event.body.list.prev;
+          final curr = event.body.list.curr;
+          if (prev.tag != 0)
+            lists.add((Id.fromNative(prev.some.src), prev.some.label));
+          if (curr.tag != 0)
+            lists.add((Id.fromNative(curr.some.src), curr.some.label));
         default:
           throw UnimplementedError();
       }
     }
     bindings.dust_drop_array_event_data(data);
 
+    // Items are not ordered on their own, so changed lists are queried again.
+    for (final key in lists) {
+      for (final (insert, clear) in listBySrcLabel[key]) {
+        clear();
+        getListValueBySrcLabel(key.$1, key.$2, insert);
+      }
+    }
+
     // Debounced commit after each barrier.
+    _commitLater();
+  }
+
+  void _commitLater() {
     committer?.cancel();
-    committer = Timer(const Duration(milliseconds: 200), bindings.dust_commit);
+    if (savepoints == 0) {
+      committer = Timer(const Duration(milliseconds: 200),
+          () => bindings.dust_commit(handle));
+    }
   }
 }
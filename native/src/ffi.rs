@@ -18,98 +18,550 @@ pub mod drop;
 pub mod store;
 pub mod structs;
 
-use rusqlite::Connection;
-use std::cell::RefCell;
+use std::{
+  cell::RefCell,
+  path::Path,
+  sync::{Arc, Mutex, MutexGuard, PoisonError},
+  time::Duration,
+};
 
-use self::structs::{CArray, CAtom, CEdge, CEventData, CId, CNode, COption, CPair, CResult, CTriple, CUnit};
+use self::structs::{
+  CArray, CAtom, CConflictData, CEdge, CEventData, CId, CItemData, CNode, COption, CPair, CResult, CStatsData,
+  CStorageStats, CTriple, CUnit, CViolationData,
+};
 use crate::{
-  store::Store,
-  workspace::{Constraints, Workspace},
+  store::{JournalMode, OpenOptions, Store, Synchronous},
+  workspace::{ReplicationFilter, Workspace},
   StoreError, Transactor,
 };
 
+/// `(path, store)`, where the path is empty for in-memory databases.
+type Entry = (String, Arc<Store>);
+
+/// Open stores, indexed by the low half of their handles. The high half is the
+/// generation of the slot, which is bumped on close so that stale handles are
+/// never mistaken for a store later opened in the same slot. Handles are valid
+/// on all threads.
+struct Registry {
+  slots: Vec<(u32, Option<Entry>)>,
+}
+
+impl Registry {
+  fn insert(&mut self, path: String, store: Arc<Store>) -> u64 {
+    let index = match self.slots.iter().position(|(_, entry)| entry.is_none()) {
+      Some(index) => index,
+      None => {
+        self.slots.push((1, None));
+        self.slots.len() - 1
+      }
+    };
+    let (generation, entry) = &mut self.slots[index];
+    *entry = Some((path, store));
+    (*generation as u64) << 32 | index as u64
+  }
+
+  fn slot(&mut self, handle: u64) -> Result<&mut Option<Entry>, StoreError> {
+    let (index, generation) = (handle as u32 as usize, (handle >> 32) as u32);
+    match self.slots.get_mut(index) {
+      Some((current, entry)) if *current == generation && entry.is_some() => Ok(entry),
+      Some((current, _)) if *current > generation && generation > 0 => Err(StoreError::Closed(handle)),
+      _ => Err(StoreError::InvalidHandle(handle)),
+    }
+  }
+
+  fn get(&mut self, handle: u64) -> Result<Arc<Store>, StoreError> {
+    Ok(self.slot(handle)?.as_ref().unwrap().1.clone())
+  }
+
+  fn remove(&mut self, handle: u64) -> Result<Arc<Store>, StoreError> {
+    let (_, store) = self.slot(handle)?.take().unwrap();
+    self.slots[handle as u32 as usize].0 += 1;
+    Ok(store)
+  }
+
+  fn find(&self, path: &str) -> Option<u64> {
+    self.slots.iter().enumerate().find_map(|(index, (generation, entry))| match entry {
+      Some((other, _)) if !other.is_empty() && other == path => Some((*generation as u64) << 32 | index as u64),
+      _ => None,
+    })
+  }
+}
+
 thread_local! {
-  static CONSTRAINTS: RefCell<Constraints> = RefCell::new(Constraints::new());
-  static STORE: RefCell<Option<Store>> = RefCell::new(None);
+  static OPTIONS: RefCell<OpenOptions> = RefCell::new(OpenOptions::new());
+}
+
+static STORES: Mutex<Registry> = Mutex::new(Registry { slots: Vec::new() });
+
+/// Nothing is left half-updated in the registry if a thread panics.
+fn stores() -> MutexGuard<'static, Registry> {
+  STORES.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 pub fn convert_result<T>(f: impl FnOnce() -> Result<T, StoreError>) -> CResult<T> {
   f().map_err(|err| err.to_string()).into()
 }
 
-pub fn access_workspace<T>(f: impl FnOnce(&mut Transactor, &mut Workspace) -> Result<T, StoreError>) -> CResult<T> {
-  STORE
-    .with(|cell| {
-      let mut borrow = cell.borrow_mut();
-      let store = borrow.as_mut().ok_or(StoreError::Uninitialised)?;
-      let (txr, ws) = store.as_mut()?;
-      f(txr, ws)
-    })
-    .map_err(|err| err.to_string())
-    .into()
+pub fn access_workspace<T>(
+  handle: u64,
+  f: impl FnOnce(&mut Transactor, &mut Workspace) -> Result<T, StoreError>,
+) -> CResult<T> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.access(f)
+  })
 }
 
+/// Like [`access_workspace`], but on the last committed state, without waiting
+/// for the writer. See [`Store`] for which writes are visible. Used for
+/// background reads such as syncs.
+pub fn read_workspace<T>(handle: u64, f: impl FnOnce(&Transactor, &Workspace) -> Result<T, StoreError>) -> CResult<T> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.read(f)
+  })
+}
+
+/// Like [`read_workspace`], but through the writer while it holds uncommitted
+/// writes or open savepoints, see [`Store::read_latest`]. All data getters go
+/// through here.
+pub fn latest_workspace<T>(
+  handle: u64,
+  f: impl FnOnce(&Transactor, &Workspace) -> Result<T, StoreError>,
+) -> CResult<T> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.read_latest(f)
+  })
+}
+
+/// Adds a constraint to the next store opened on this thread.
 #[no_mangle]
 pub extern "C" fn dust_add_sticky_node(label: u64) {
-  CONSTRAINTS.with(|cell| cell.borrow_mut().add_sticky_node(label));
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().add_sticky_node(label));
 }
 
+/// Adds a constraint to the next store opened on this thread.
 #[no_mangle]
 pub extern "C" fn dust_add_sticky_atom(label: u64) {
-  CONSTRAINTS.with(|cell| cell.borrow_mut().add_sticky_atom(label));
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().add_sticky_atom(label));
 }
 
+/// Adds a constraint to the next store opened on this thread.
 #[no_mangle]
 pub extern "C" fn dust_add_sticky_edge(label: u64) {
-  CONSTRAINTS.with(|cell| cell.borrow_mut().add_sticky_edge(label));
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().add_sticky_edge(label));
 }
 
+/// Adds a constraint to the next store opened on this thread.
 #[no_mangle]
 pub extern "C" fn dust_add_acyclic_edge(label: u64) {
-  CONSTRAINTS.with(|cell| cell.borrow_mut().add_acyclic_edge(label));
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().add_acyclic_edge(label));
+}
+
+/// Sets how far ahead of the local clock, in nanoseconds, remote writes may be,
+/// for the next store opened on this thread.
+#[no_mangle]
+pub extern "C" fn dust_set_max_drift(max_drift: u64) {
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().set_max_drift(max_drift));
+}
+
+/// Sets how long, in nanoseconds, removals are kept before compaction if there
+/// are no registered peers, for the next store opened on this thread.
+#[no_mangle]
+pub extern "C" fn dust_set_retention(retention: u64) {
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().set_retention(retention));
+}
+
+/// Sets how long, in nanoseconds, items of transactions which have not been
+/// fully received are staged, for the next store opened on this thread.
+#[no_mangle]
+pub extern "C" fn dust_set_staged_ttl(staged_ttl: u64) {
+  OPTIONS.with(|cell| cell.borrow_mut().constraints_mut().set_staged_ttl(staged_ttl));
+}
+
+/// Sets the workspace prefix for the next store opened on this thread.
+#[no_mangle]
+pub unsafe extern "C" fn dust_set_prefix(len: u64, ptr: *mut u8) -> CResult<CUnit> {
+  convert_result(|| {
+    let prefix = CArray(len, ptr).as_ref();
+    let prefix = std::str::from_utf8(prefix).map_err(|_| StoreError::InvalidUtf8)?;
+    OPTIONS.with(|cell| {
+      cell.borrow_mut().prefix(prefix);
+    });
+    Ok(CUnit(0))
+  })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dust_open(len: u64, ptr: *mut u8) -> CResult<CUnit> {
+pub extern "C" fn dust_set_read_only(read_only: bool) {
+  OPTIONS.with(|cell| {
+    cell.borrow_mut().read_only(read_only);
+  });
+}
+
+/// Takes `DELETE`, `TRUNCATE`, `PERSIST`, `MEMORY`, `WAL` or `OFF` as 0 to 5.
+#[no_mangle]
+pub extern "C" fn dust_set_journal_mode(journal_mode: u8) -> CResult<CUnit> {
   convert_result(|| {
-    if STORE.with(|cell| cell.borrow().is_some()) {
-      // FIXME: This is a hack to avoid double-initialisation in flutter's hot reload, but this will
-      // cause new databases unable to be opened.
-      return Ok(CUnit(0));
+    let journal_mode = JournalMode::try_from(journal_mode)?;
+    OPTIONS.with(|cell| {
+      cell.borrow_mut().journal_mode(journal_mode);
+    });
+    Ok(CUnit(0))
+  })
+}
+
+/// Takes `OFF`, `NORMAL`, `FULL` or `EXTRA` as 0 to 3.
+#[no_mangle]
+pub extern "C" fn dust_set_synchronous(synchronous: u8) -> CResult<CUnit> {
+  convert_result(|| {
+    let synchronous = Synchronous::try_from(synchronous)?;
+    OPTIONS.with(|cell| {
+      cell.borrow_mut().synchronous(synchronous);
+    });
+    Ok(CUnit(0))
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_set_wal_autocheckpoint(pages: u64) {
+  OPTIONS.with(|cell| {
+    cell.borrow_mut().wal_autocheckpoint(pages);
+  });
+}
+
+#[no_mangle]
+pub extern "C" fn dust_set_cache_size(cache_size: i64) {
+  OPTIONS.with(|cell| {
+    cell.borrow_mut().cache_size(cache_size);
+  });
+}
+
+/// Sets the busy timeout, in milliseconds.
+#[no_mangle]
+pub extern "C" fn dust_set_busy_timeout(busy_timeout: u64) {
+  OPTIONS.with(|cell| {
+    cell.borrow_mut().busy_timeout(Duration::from_millis(busy_timeout));
+  });
+}
+
+/// Opens the database at given path and returns a handle to it, which every
+/// other function taking a `handle` accepts. The options set since the
+/// previous call are bound to the returned handle and then reset, whether or
+/// not opening succeeds.
+///
+/// If the same file is already open (e.g. after a hot restart), even through
+/// another spelling of its path, the old handle is closed first and becomes
+/// stale, even if closing it fails.
+#[no_mangle]
+pub unsafe extern "C" fn dust_open(len: u64, ptr: *mut u8) -> CResult<u64> {
+  let options = OPTIONS.with(|cell| cell.take());
+  convert_result(|| {
+    let path = CArray(len, ptr).as_ref();
+    let path = std::str::from_utf8(path).map_err(|_| StoreError::InvalidUtf8)?;
+    let key = canonical(path);
+    // The registry stays locked until the new store is in, so that concurrent
+    // opens of the same file run one after the other.
+    let mut stores = stores();
+    if let Some(handle) = stores.find(&key) {
+      // The old store is dropped either way, so failing to close it, e.g.
+      // after a storage error, must not keep the file from being opened.
+      let _ = stores.remove(handle)?.close();
     }
+    let store = options.open(path)?;
+    Ok(stores.insert(key, Arc::new(store)))
+  })
+}
+
+/// Resolves `path` to an absolute path without `.`, `..` or symbolic links, so
+/// that different spellings of the same file are recognised. The file itself
+/// may not exist yet. Paths which cannot be resolved, such as SQLite URIs, are
+/// kept as they are.
+fn canonical(path: &str) -> String {
+  let raw = Path::new(path);
+  let resolved = if raw.exists() {
+    raw.canonicalize().ok()
+  } else {
+    match (raw.parent(), raw.file_name()) {
+      (Some(parent), Some(name)) if !path.starts_with("file:") && path != ":memory:" => {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        parent.canonicalize().ok().map(|parent| parent.join(name))
+      }
+      _ => None,
+    }
+  };
+  resolved.and_then(|resolved| resolved.into_os_string().into_string().ok()).unwrap_or_else(|| path.to_owned())
+}
+
+/// Like [`dust_open`], but opens a private, temporary database.
+#[no_mangle]
+pub extern "C" fn dust_open_in_memory() -> CResult<u64> {
+  let options = OPTIONS.with(|cell| cell.take());
+  convert_result(|| {
+    let store = options.open_in_memory()?;
+    Ok(stores().insert(String::new(), Arc::new(store)))
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_commit(handle: u64) -> CResult<CUnit> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.commit()?;
+    Ok(CUnit(0))
+  })
+}
+
+/// See [`Store::rollback`].
+#[no_mangle]
+pub extern "C" fn dust_rollback(handle: u64) -> CResult<CUnit> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.rollback()?;
+    Ok(CUnit(0))
+  })
+}
+
+/// Returns the number of open savepoints. See [`Store::savepoint`].
+#[no_mangle]
+pub extern "C" fn dust_savepoint(handle: u64) -> CResult<u64> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    Ok(store.savepoint()? as u64)
+  })
+}
+
+/// Returns the number of open savepoints. See [`Store::release`].
+#[no_mangle]
+pub extern "C" fn dust_release(handle: u64) -> CResult<u64> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    Ok(store.release()? as u64)
+  })
+}
+
+/// Returns the number of open savepoints. See [`Store::rollback_to_savepoint`].
+#[no_mangle]
+pub extern "C" fn dust_rollback_to_savepoint(handle: u64) -> CResult<u64> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    Ok(store.rollback_to_savepoint()? as u64)
+  })
+}
+
+/// Backs up the last committed state to the database at given path, which is
+/// overwritten. See [`Store::backup_to`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_backup(handle: u64, len: u64, ptr: *mut u8) -> CResult<CUnit> {
+  convert_result(|| {
     let path = CArray(len, ptr).as_ref();
     let path = std::str::from_utf8(path).map_err(|_| StoreError::InvalidUtf8)?;
-    let conn = Connection::open(path)?;
-    conn.execute_batch(
-      "
-      PRAGMA auto_vacuum = INCREMENTAL;
-      PRAGMA journal_mode = WAL;
-      PRAGMA synchronous = NORMAL;
-      PRAGMA wal_autocheckpoint = 2000;
-      PRAGMA cache_size = 2000;
-      PRAGMA busy_timeout = 1000;
-      ",
-    )?;
-    let store = Store::new(conn, CONSTRAINTS.with(|cell| cell.borrow().clone()))?;
-    STORE.with(|cell| cell.replace(Some(store)));
+    let store = stores().get(handle)?;
+    store.backup_to(path)?;
     Ok(CUnit(0))
   })
 }
 
+/// Replaces the contents of the database with the backup at given path. See
+/// [`Store::restore_from`].
 #[no_mangle]
-pub extern "C" fn dust_commit() -> CResult<CUnit> {
+pub unsafe extern "C" fn dust_restore(handle: u64, len: u64, ptr: *mut u8) -> CResult<CUnit> {
   convert_result(|| {
-    STORE.with(|cell| {
-      cell.borrow_mut().as_mut().ok_or(StoreError::Uninitialised)?.commit()?;
-      Ok(CUnit(0))
-    })
+    let path = CArray(len, ptr).as_ref();
+    let path = std::str::from_utf8(path).map_err(|_| StoreError::InvalidUtf8)?;
+    let store = stores().get(handle)?;
+    store.restore_from(path)?;
+    Ok(CUnit(0))
+  })
+}
+
+/// See [`Store::storage_stats`].
+#[no_mangle]
+pub extern "C" fn dust_storage_stats(handle: u64) -> CResult<CStorageStats> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    Ok(store.storage_stats()?.into())
+  })
+}
+
+/// Runs maintenance for at most `budget` milliseconds, releasing at most
+/// `max_pages` free pages. See [`Store::maintain`].
+#[no_mangle]
+pub extern "C" fn dust_maintain(handle: u64, budget: u64, max_pages: u64) -> CResult<u64> {
+  convert_result(|| {
+    let store = stores().get(handle)?;
+    store.maintain(Duration::from_millis(budget), max_pages)
   })
 }
 
+/// Commits and closes the database. The handle is stale afterwards, even if
+/// committing fails.
 #[no_mangle]
-pub extern "C" fn dust_close() -> CResult<CUnit> {
+pub extern "C" fn dust_close(handle: u64) -> CResult<CUnit> {
   convert_result(|| {
-    STORE.with(|cell| cell.take()).ok_or(StoreError::Uninitialised)?.close()?;
+    let store = stores().remove(handle)?;
+    store.close()?;
     Ok(CUnit(0))
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{drop::*, store::*, *};
+
+  fn result<T>(value: CResult<T>) -> Result<T, String> {
+    match value {
+      CResult::Ok(ok) => Ok(ok),
+      CResult::Err(err) => Err(String::from_utf8(unsafe { err.into_boxed() }.into_vec()).unwrap()),
+    }
+  }
+
+  fn barrier(handle: u64) {
+    unsafe { dust_drop_array_event_data(result(dust_barrier(handle)).unwrap()) };
+  }
+
+  fn has_atom(handle: u64, id: u64) -> Result<bool, String> {
+    let atom = result(dust_atom(handle, 0, id))?;
+    let res = matches!(atom, COption::Some(_));
+    unsafe { dust_drop_option_atom(atom) };
+    Ok(res)
+  }
+
+  #[test]
+  fn handles() {
+    let dir = std::env::temp_dir().join(format!("dust-ffi-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let open = |name: &str| {
+      let mut path = dir.join(name).into_os_string().into_string().unwrap().into_bytes();
+      result(unsafe { dust_open(path.len() as u64, path.as_mut_ptr()) }).unwrap()
+    };
+    let mut value = [0u8];
+    let (len, ptr) = (value.len() as u64, value.as_mut_ptr());
+
+    dust_add_sticky_node(1);
+    let a = open("a.db");
+    let b = open("b.db");
+    assert_ne!(a, b);
+    for handle in [a, b] {
+      result(dust_set_node_some(handle, 0, 1, 1)).unwrap();
+      result(unsafe { dust_set_atom_some(handle, 0, 2, 0, 1, 2, len, ptr) }).unwrap();
+      barrier(handle);
+      assert!(has_atom(handle, 2).unwrap());
+    }
+
+    // Constraints only apply to the store opened right after them.
+    for handle in [a, b] {
+      result(dust_set_node_some(handle, 0, 1, 3)).unwrap();
+      barrier(handle);
+    }
+    assert!(!has_atom(a, 2).unwrap());
+    assert!(has_atom(b, 2).unwrap());
+
+    // Closed handles stay invalid after their slot is reused.
+    result(dust_close(b)).unwrap();
+    assert!(has_atom(b, 2).unwrap_err().contains("closed"));
+    let c = open("c.db");
+    assert_ne!(b, c);
+    assert!(has_atom(b, 2).unwrap_err().contains("closed"));
+    assert!(result(dust_close(b)).is_err_and(|err| err.contains("closed")));
+    assert!(has_atom(c ^ 1 << 40, 2).unwrap_err().contains("invalid"));
+    assert!(has_atom(0, 2).unwrap_err().contains("invalid"));
+
+    // Reopening a path closes the old handle.
+    result(dust_commit(a)).unwrap();
+    let d = open("a.db");
+    assert!(has_atom(a, 2).unwrap_err().contains("closed"));
+    assert!(result(dust_node(d, 0, 1)).is_ok());
+
+    // Even if the old store fails to close.
+    stores().get(d).unwrap().close().unwrap();
+    let d = open("a.db");
+    assert!(result(dust_node(d, 0, 1)).is_ok());
+
+    // Another spelling of the same path, or two threads opening it at once,
+    // leave a single live handle.
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let e = open("sub/../a.db");
+    assert!(has_atom(d, 2).unwrap_err().contains("closed"));
+    let handles: Vec<u64> = std::thread::scope(|scope| {
+      let threads: Vec<_> = (0..2).map(|_| scope.spawn(|| open("a.db"))).collect();
+      threads.into_iter().map(|thread| thread.join().unwrap()).collect()
+    });
+    assert!(has_atom(e, 2).unwrap_err().contains("closed"));
+    let live: Vec<u64> = handles.into_iter().filter(|&handle| result(dust_node(handle, 0, 1)).is_ok()).collect();
+    assert_eq!(live.len(), 1);
+    let d = live[0];
+
+    result(dust_close(c)).unwrap();
+    result(dust_close(d)).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn savepoints() {
+    let dir = std::env::temp_dir().join(format!("dust-ffi-savepoints-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut path = dir.join("a.db").into_os_string().into_string().unwrap().into_bytes();
+    let handle = result(unsafe { dust_open(path.len() as u64, path.as_mut_ptr()) }).unwrap();
+    let mut value = [0u8];
+    let (len, ptr) = (value.len() as u64, value.as_mut_ptr());
+
+    result(dust_set_node_some(handle, 0, 1, 1)).unwrap();
+    barrier(handle);
+    result(dust_commit(handle)).unwrap();
+    assert!(!has_atom(handle, 2).unwrap());
+
+    // Getters see writes made inside a savepoint, until it is rolled back.
+    assert_eq!(result(dust_savepoint(handle)), Ok(1));
+    result(unsafe { dust_set_atom_some(handle, 0, 2, 0, 1, 2, len, ptr) }).unwrap();
+    barrier(handle);
+    assert!(has_atom(handle, 2).unwrap());
+    assert_eq!(result(dust_rollback_to_savepoint(handle)), Ok(0));
+    assert!(!has_atom(handle, 2).unwrap());
+
+    result(dust_close(handle)).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn batches() {
+    use crate::{serialize, workspace::Write};
+
+    let handle = result(dust_open_in_memory()).unwrap();
+    let writes = vec![
+      Write::Node(1, Some(10)),
+      Write::Atom(2, Some((1, 20, vec![1, 2, 3].into()))),
+      Write::Edge(3, Some((1, 30, 1))),
+      Write::Atom(4, None),
+    ];
+    let mut batch = serialize(&writes).unwrap();
+    // The layout relied upon by the Dart encoder: big-endian length, variant
+    // index, id and option tag.
+    let mut head = vec![0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0];
+    head.extend(1u128.to_be_bytes());
+    head.push(1);
+    head.extend(10u64.to_be_bytes());
+    assert_eq!(batch[..head.len()], head);
+
+    let (len, ptr) = (batch.len() as u64, batch.as_mut_ptr());
+    assert!(result(unsafe { dust_set_batch(handle, len - 1, ptr) }).is_err_and(|err| err.contains("batch")));
+    assert_eq!(result(unsafe { dust_set_batch(handle, len, ptr) }), Ok(4));
+    barrier(handle);
+
+    let mut ids = [CId(0, 3), CId(0, 4), CId(0, 2)];
+    let items = result(unsafe { dust_atoms_edges(handle, ids.len() as u64, ids.as_mut_ptr()) }).unwrap();
+    let slice = unsafe { items.as_ref() };
+    assert_eq!(slice.len(), 2);
+    assert!(matches!(slice[0], CItemData::Edge { id: CId(0, 3), edge } if edge == (1, 30, 1).into()));
+    match &slice[1] {
+      CItemData::Atom { id, atom } => {
+        assert_eq!(*id, CId(0, 2));
+        assert_eq!((atom.src, atom.label, unsafe { atom.value.as_ref() }), (CId(0, 1), 20, &[1, 2, 3][..]));
+      }
+      _ => panic!("expected atom"),
+    }
+    unsafe { dust_drop_array_item_data(items) };
+    result(dust_close(handle)).unwrap();
+  }
+}
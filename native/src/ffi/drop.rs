@@ -28,6 +28,16 @@ pub unsafe extern "C" fn dust_drop_option_atom(value: COption<CAtom>) {
   }
 }
 
+/// Drops the return value of [`atoms_edges`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_drop_array_item_data(value: CArray<CItemData>) {
+  for elem in value.into_boxed().into_vec().into_iter() {
+    if let CItemData::Atom { atom, .. } = elem {
+      atom.value.into_boxed();
+    }
+  }
+}
+
 /// Drops the return value of [`atom_id_label_value_by_src`].
 #[no_mangle]
 pub unsafe extern "C" fn dust_drop_array_id_u64_array_u8(value: CArray<CTriple<CId, u64, CArray<u8>>>) {
@@ -36,7 +46,7 @@ pub unsafe extern "C" fn dust_drop_array_id_u64_array_u8(value: CArray<CTriple<C
   }
 }
 
-/// Drops the return value of [`atom_id_value_by_src_label`].
+/// Drops the return value of [`atom_id_value_by_src_label`] and [`list_id_value_by_src_label`].
 #[no_mangle]
 pub unsafe extern "C" fn dust_drop_array_id_array_u8(value: CArray<CPair<CId, CArray<u8>>>) {
   for elem in value.into_boxed().into_vec().into_iter() {
@@ -44,7 +54,8 @@ pub unsafe extern "C" fn dust_drop_array_id_array_u8(value: CArray<CPair<CId, CA
   }
 }
 
-/// Drops the return value of [`atom_id_src_value_by_label`].
+/// Drops the return value of [`atom_id_src_value_by_label`], [`atom_id_src_value_by_label_range`] and
+/// [`atom_id_src_value_by_label_prefix`].
 #[no_mangle]
 pub unsafe extern "C" fn dust_drop_array_id_id_array_u8(value: CArray<CTriple<CId, CId, CArray<u8>>>) {
   for elem in value.into_boxed().into_vec().into_iter() {
@@ -70,17 +81,25 @@ pub unsafe extern "C" fn dust_drop_array_id_id_u64(value: CArray<CTriple<CId, CI
   value.into_boxed();
 }
 
-/// Drops the return value of [`sync_version`] and [`sync_actions`] and all error results.
+/// Drops the return value of [`sync_version`], [`sync_version_filtered`], [`sync_actions`], [`snapshot`] and
+/// [`export_bundle`] and all error results.
 #[no_mangle]
 pub unsafe extern "C" fn dust_drop_array_u8(value: CArray<u8>) {
   value.into_boxed();
 }
 
+/// Drops the return value of [`sync_actions_page`] and [`snapshot_page`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_drop_pair_array_u8_array_u8(value: CPair<CArray<u8>, CArray<u8>>) {
+  value.0.into_boxed();
+  value.1.into_boxed();
+}
+
 /// Drops the return value of [`barrier`].
 #[no_mangle]
 pub unsafe extern "C" fn dust_drop_array_event_data(value: CArray<CEventData>) {
   for elem in value.into_boxed().into_vec().into_iter() {
-    if let CEventData::Atom { id: _, prev, curr } = elem {
+    if let CEventData::Atom { id: _, prev, curr } | CEventData::List { id: _, prev, curr } = elem {
       if let COption::Some(inner) = prev {
         inner.value.into_boxed();
       }
@@ -90,3 +109,30 @@ pub unsafe extern "C" fn dust_drop_array_event_data(value: CArray<CEventData>) {
     }
   }
 }
+
+/// Drops the return value of [`sync_join`] and [`import_bundle`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_drop_array_conflict_data(value: CArray<CConflictData>) {
+  for elem in value.into_boxed().into_vec().into_iter() {
+    if let CConflictData::Atom { id: _, winner, loser, overwritten: _ } = elem {
+      if let COption::Some(inner) = winner.2 {
+        inner.value.into_boxed();
+      }
+      if let COption::Some(inner) = loser.2 {
+        inner.value.into_boxed();
+      }
+    }
+  }
+}
+
+/// Drops the return value of [`stats`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_drop_array_stats_data(value: CArray<CStatsData>) {
+  value.into_boxed();
+}
+
+/// Drops the return value of [`check`] and [`repair`].
+#[no_mangle]
+pub unsafe extern "C" fn dust_drop_array_violation_data(value: CArray<CViolationData>) {
+  value.into_boxed();
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use rand::Rng;
+use std::ops::Bound;
 
 use super::*;
 
@@ -22,37 +23,38 @@ pub extern "C" fn dust_random_id() -> CId {
 }
 
 #[no_mangle]
-pub extern "C" fn dust_node(idh: u64, idl: u64) -> CResult<COption<CNode>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_node(handle: u64, idh: u64, idl: u64) -> CResult<COption<CNode>> {
+  latest_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    Ok(ws.node(txr, id).map(Into::into).into())
+    Ok(ws.node(txr, id)?.map(Into::into).into())
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_node_id_by_label(label: u64) -> CResult<CArray<CId>> {
-  access_workspace(|txr, ws| {
-    Ok(ws.node_id_by_label(txr, label).into_keys().map(|id| id.into()).collect::<Box<[_]>>().into())
+pub extern "C" fn dust_node_id_by_label(handle: u64, label: u64) -> CResult<CArray<CId>> {
+  latest_workspace(handle, |txr, ws| {
+    Ok(ws.node_id_by_label(txr, label)?.into_keys().map(|id| id.into()).collect::<Box<[_]>>().into())
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_atom(idh: u64, idl: u64) -> CResult<COption<CAtom>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_atom(handle: u64, idh: u64, idl: u64) -> CResult<COption<CAtom>> {
+  latest_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    Ok(ws.atom(txr, id).map(Into::into).into())
+    Ok(ws.atom(txr, id)?.map(Into::into).into())
   })
 }
 
 #[no_mangle]
 pub extern "C" fn dust_atom_id_label_value_by_src(
+  handle: u64,
   srch: u64,
   srcl: u64,
 ) -> CResult<CArray<CTriple<CId, u64, CArray<u8>>>> {
-  access_workspace(|txr, ws| {
+  latest_workspace(handle, |txr, ws| {
     let src = CId(srch, srcl).into();
     Ok(
-      ws.atom_id_label_value_by_src(txr, src)
+      ws.atom_id_label_value_by_src(txr, src)?
         .into_iter()
         .map(|(id, (label, value))| CTriple(id.into(), label, value.into()))
         .collect::<Box<[_]>>()
@@ -63,14 +65,15 @@ pub extern "C" fn dust_atom_id_label_value_by_src(
 
 #[no_mangle]
 pub extern "C" fn dust_atom_id_value_by_src_label(
+  handle: u64,
   srch: u64,
   srcl: u64,
   label: u64,
 ) -> CResult<CArray<CPair<CId, CArray<u8>>>> {
-  access_workspace(|txr, ws| {
+  latest_workspace(handle, |txr, ws| {
     let src = CId(srch, srcl).into();
     Ok(
-      ws.atom_id_value_by_src_label(txr, src, label)
+      ws.atom_id_value_by_src_label(txr, src, label)?
         .into_iter()
         .map(|(id, value)| CPair(id.into(), value.into()))
         .collect::<Box<[_]>>()
@@ -80,10 +83,13 @@ pub extern "C" fn dust_atom_id_value_by_src_label(
 }
 
 #[no_mangle]
-pub extern "C" fn dust_atom_id_src_value_by_label(label: u64) -> CResult<CArray<CTriple<CId, CId, CArray<u8>>>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_atom_id_src_value_by_label(
+  handle: u64,
+  label: u64,
+) -> CResult<CArray<CTriple<CId, CId, CArray<u8>>>> {
+  latest_workspace(handle, |txr, ws| {
     Ok(
-      ws.atom_id_src_value_by_label(txr, label)
+      ws.atom_id_src_value_by_label(txr, label)?
         .into_iter()
         .map(|(id, (src, value))| CTriple(id.into(), src.into(), value.into()))
         .collect::<Box<[_]>>()
@@ -94,14 +100,15 @@ pub extern "C" fn dust_atom_id_src_value_by_label(label: u64) -> CResult<CArray<
 
 #[no_mangle]
 pub unsafe extern "C" fn dust_atom_id_src_by_label_value(
+  handle: u64,
   label: u64,
   len: u64,
   ptr: *mut u8,
 ) -> CResult<CArray<CPair<CId, CId>>> {
-  access_workspace(|txr, ws| {
+  latest_workspace(handle, |txr, ws| {
     let value = CArray(len, ptr).as_ref();
     Ok(
-      ws.atom_id_src_by_label_value(txr, label, value)
+      ws.atom_id_src_by_label_value(txr, label, value)?
         .into_iter()
         .map(|(id, src)| CPair(id.into(), src.into()))
         .collect::<Box<[_]>>()
@@ -110,20 +117,106 @@ pub unsafe extern "C" fn dust_atom_id_src_by_label_value(
   })
 }
 
+/// Reads a range bound, where `kind` is 0 for unbounded, 1 for inclusive and 2
+/// for exclusive.
+unsafe fn bound(kind: u8, len: u64, ptr: *mut u8) -> Result<Bound<&'static [u8]>, StoreError> {
+  match kind {
+    0 => Ok(Bound::Unbounded),
+    1 => Ok(Bound::Included(CArray(len, ptr).as_ref())),
+    2 => Ok(Bound::Excluded(CArray(len, ptr).as_ref())),
+    _ => Err(StoreError::InvalidOption(format!("bound kind {kind}"))),
+  }
+}
+
+/// Queries atoms by a range of values, ordered by value. A `limit` of
+/// `u64::MAX` means no limit.
+#[no_mangle]
+pub unsafe extern "C" fn dust_atom_id_src_value_by_label_range(
+  handle: u64,
+  label: u64,
+  lower_kind: u8,
+  lower_len: u64,
+  lower_ptr: *mut u8,
+  upper_kind: u8,
+  upper_len: u64,
+  upper_ptr: *mut u8,
+  offset: u64,
+  limit: u64,
+) -> CResult<CArray<CTriple<CId, CId, CArray<u8>>>> {
+  latest_workspace(handle, |txr, ws| {
+    let lower = bound(lower_kind, lower_len, lower_ptr)?;
+    let upper = bound(upper_kind, upper_len, upper_ptr)?;
+    let limit = (limit != u64::MAX).then_some(limit);
+    Ok(
+      ws.atom_id_src_value_by_label_range(txr, label, lower, upper, offset, limit)?
+        .into_iter()
+        .map(|(id, (src, value))| CTriple(id.into(), src.into(), value.into()))
+        .collect::<Box<[_]>>()
+        .into(),
+    )
+  })
+}
+
+/// Queries atoms by a prefix of values, ordered by value. A `limit` of
+/// `u64::MAX` means no limit.
+#[no_mangle]
+pub unsafe extern "C" fn dust_atom_id_src_value_by_label_prefix(
+  handle: u64,
+  label: u64,
+  len: u64,
+  ptr: *mut u8,
+  offset: u64,
+  limit: u64,
+) -> CResult<CArray<CTriple<CId, CId, CArray<u8>>>> {
+  latest_workspace(handle, |txr, ws| {
+    let prefix = CArray(len, ptr).as_ref();
+    let limit = (limit != u64::MAX).then_some(limit);
+    Ok(
+      ws.atom_id_src_value_by_label_prefix(txr, label, prefix, offset, limit)?
+        .into_iter()
+        .map(|(id, (src, value))| CTriple(id.into(), src.into(), value.into()))
+        .collect::<Box<[_]>>()
+        .into(),
+    )
+  })
+}
+
 #[no_mangle]
-pub extern "C" fn dust_edge(idh: u64, idl: u64) -> CResult<COption<CEdge>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_edge(handle: u64, idh: u64, idl: u64) -> CResult<COption<CEdge>> {
+  latest_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    Ok(ws.edge(txr, id).map(Into::into).into())
+    Ok(ws.edge(txr, id)?.map(Into::into).into())
   })
 }
 
+/// Looks up many atoms and edges in one call. Ids which are neither are
+/// skipped, so each entry carries its id.
 #[no_mangle]
-pub extern "C" fn dust_edge_id_label_dst_by_src(srch: u64, srcl: u64) -> CResult<CArray<CTriple<CId, u64, CId>>> {
-  access_workspace(|txr, ws| {
+pub unsafe extern "C" fn dust_atoms_edges(handle: u64, len: u64, ptr: *mut CId) -> CResult<CArray<CItemData>> {
+  latest_workspace(handle, |txr, ws| {
+    let mut res = Vec::new();
+    for &CId(idh, idl) in CArray(len, ptr).as_ref() {
+      let id = CId(idh, idl);
+      if let Some(slv) = ws.atom(txr, id.into())? {
+        res.push(CItemData::Atom { id, atom: slv.into() });
+      } else if let Some(sld) = ws.edge(txr, id.into())? {
+        res.push(CItemData::Edge { id, edge: sld.into() });
+      }
+    }
+    Ok(res.into())
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_edge_id_label_dst_by_src(
+  handle: u64,
+  srch: u64,
+  srcl: u64,
+) -> CResult<CArray<CTriple<CId, u64, CId>>> {
+  latest_workspace(handle, |txr, ws| {
     let src = CId(srch, srcl).into();
     Ok(
-      ws.edge_id_label_dst_by_src(txr, src)
+      ws.edge_id_label_dst_by_src(txr, src)?
         .into_iter()
         .map(|(id, (label, dst))| CTriple(id.into(), label, dst.into()))
         .collect::<Box<[_]>>()
@@ -133,11 +226,16 @@ pub extern "C" fn dust_edge_id_label_dst_by_src(srch: u64, srcl: u64) -> CResult
 }
 
 #[no_mangle]
-pub extern "C" fn dust_edge_id_dst_by_src_label(srch: u64, srcl: u64, label: u64) -> CResult<CArray<CPair<CId, CId>>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_edge_id_dst_by_src_label(
+  handle: u64,
+  srch: u64,
+  srcl: u64,
+  label: u64,
+) -> CResult<CArray<CPair<CId, CId>>> {
+  latest_workspace(handle, |txr, ws| {
     let src = CId(srch, srcl).into();
     Ok(
-      ws.edge_id_dst_by_src_label(txr, src, label)
+      ws.edge_id_dst_by_src_label(txr, src, label)?
         .into_iter()
         .map(|(id, dst)| CPair(id.into(), dst.into()))
         .collect::<Box<[_]>>()
@@ -147,11 +245,15 @@ pub extern "C" fn dust_edge_id_dst_by_src_label(srch: u64, srcl: u64, label: u64
 }
 
 #[no_mangle]
-pub extern "C" fn dust_edge_id_src_label_by_dst(dsth: u64, dstl: u64) -> CResult<CArray<CTriple<CId, CId, u64>>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_edge_id_src_label_by_dst(
+  handle: u64,
+  dsth: u64,
+  dstl: u64,
+) -> CResult<CArray<CTriple<CId, CId, u64>>> {
+  latest_workspace(handle, |txr, ws| {
     let dst = CId(dsth, dstl).into();
     Ok(
-      ws.edge_id_src_label_by_dst(txr, dst)
+      ws.edge_id_src_label_by_dst(txr, dst)?
         .into_iter()
         .map(|(id, (src, label))| CTriple(id.into(), src.into(), label))
         .collect::<Box<[_]>>()
@@ -161,11 +263,16 @@ pub extern "C" fn dust_edge_id_src_label_by_dst(dsth: u64, dstl: u64) -> CResult
 }
 
 #[no_mangle]
-pub extern "C" fn dust_edge_id_src_by_dst_label(dsth: u64, dstl: u64, label: u64) -> CResult<CArray<CPair<CId, CId>>> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_edge_id_src_by_dst_label(
+  handle: u64,
+  dsth: u64,
+  dstl: u64,
+  label: u64,
+) -> CResult<CArray<CPair<CId, CId>>> {
+  latest_workspace(handle, |txr, ws| {
     let dst = CId(dsth, dstl).into();
     Ok(
-      ws.edge_id_src_by_dst_label(txr, dst, label)
+      ws.edge_id_src_by_dst_label(txr, dst, label)?
         .into_iter()
         .map(|(id, src)| CPair(id.into(), src.into()))
         .collect::<Box<[_]>>()
@@ -175,34 +282,54 @@ pub extern "C" fn dust_edge_id_src_by_dst_label(dsth: u64, dstl: u64, label: u64
 }
 
 #[no_mangle]
-pub extern "C" fn dust_set_node_none(idh: u64, idl: u64) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_list_id_value_by_src_label(
+  handle: u64,
+  srch: u64,
+  srcl: u64,
+  label: u64,
+) -> CResult<CArray<CPair<CId, CArray<u8>>>> {
+  latest_workspace(handle, |txr, ws| {
+    let src = CId(srch, srcl).into();
+    Ok(
+      ws.list_id_value_by_src_label(txr, src, label)?
+        .into_iter()
+        .map(|(id, value)| CPair(id.into(), value.into()))
+        .collect::<Box<[_]>>()
+        .into(),
+    )
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_set_node_none(handle: u64, idh: u64, idl: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    ws.set_node(txr, id, None);
+    ws.set_node(txr, id, None)?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_set_node_some(idh: u64, idl: u64, label: u64) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_set_node_some(handle: u64, idh: u64, idl: u64, label: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    ws.set_node(txr, id, Some(label));
+    ws.set_node(txr, id, Some(label))?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_set_atom_none(idh: u64, idl: u64) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_set_atom_none(handle: u64, idh: u64, idl: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    ws.set_atom(txr, id, None);
+    ws.set_atom(txr, id, None)?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn dust_set_atom_some(
+  handle: u64,
   idh: u64,
   idl: u64,
   srch: u64,
@@ -211,26 +338,27 @@ pub unsafe extern "C" fn dust_set_atom_some(
   len: u64,
   ptr: *mut u8,
 ) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
     let src = CId(srch, srcl).into();
     let value = CArray(len, ptr).as_ref();
-    ws.set_atom(txr, id, Some((src, label, Vec::from(value).into())));
+    ws.set_atom(txr, id, Some((src, label, Vec::from(value).into())))?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_set_edge_none(idh: u64, idl: u64) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+pub extern "C" fn dust_set_edge_none(handle: u64, idh: u64, idl: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
-    ws.set_edge(txr, id, None);
+    ws.set_edge(txr, id, None)?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
 pub extern "C" fn dust_set_edge_some(
+  handle: u64,
   idh: u64,
   idl: u64,
   srch: u64,
@@ -239,38 +367,244 @@ pub extern "C" fn dust_set_edge_some(
   dsth: u64,
   dstl: u64,
 ) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+  access_workspace(handle, |txr, ws| {
     let id = CId(idh, idl).into();
     let src = CId(srch, srcl).into();
     let dst = CId(dsth, dstl).into();
-    ws.set_edge(txr, id, Some((src, label, dst)));
+    ws.set_edge(txr, id, Some((src, label, dst)))?;
+    Ok(CUnit(0))
+  })
+}
+
+/// Applies a serialized `Vec<Write>` (see [`crate::workspace::Write`]) in one
+/// call, and returns the number of writes.
+#[no_mangle]
+pub unsafe extern "C" fn dust_set_batch(handle: u64, len: u64, ptr: *mut u8) -> CResult<u64> {
+  access_workspace(handle, |txr, ws| {
+    let batch = CArray(len, ptr).as_ref();
+    Ok(ws.set_batch(txr, batch)? as u64)
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_insert_list_item(
+  handle: u64,
+  idh: u64,
+  idl: u64,
+  srch: u64,
+  srcl: u64,
+  label: u64,
+  index: u64,
+  len: u64,
+  ptr: *mut u8,
+) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    let id = CId(idh, idl).into();
+    let src = CId(srch, srcl).into();
+    let value = CArray(len, ptr).as_ref();
+    let left = match index {
+      0 => None,
+      _ => {
+        let items = ws.list_id_value_by_src_label(txr, src, label)?;
+        Some(items.get(index as usize - 1).ok_or(StoreError::OutOfBounds)?.0)
+      }
+    };
+    ws.insert_list_item(txr, id, src, label, left, Vec::from(value).into())?;
+    Ok(CUnit(0))
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_remove_list_item(handle: u64, idh: u64, idl: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    let id = CId(idh, idl).into();
+    ws.remove_list_item(txr, id)?;
     Ok(CUnit(0))
   })
 }
 
+/// Like all functions producing sync payloads, this reads the last committed
+/// state without waiting for the writer, so peers never receive writes that
+/// may still be lost.
 #[no_mangle]
-pub extern "C" fn dust_sync_version() -> CResult<CArray<u8>> {
-  access_workspace(|txr, ws| Ok(ws.sync_version(txr).into()))
+pub extern "C" fn dust_sync_version(handle: u64) -> CResult<CArray<u8>> {
+  read_workspace(handle, |txr, ws| Ok(ws.sync_version(txr).into()))
 }
 
+/// Like [`dust_sync_version`], but only requests nodes, atoms, edges and list
+/// items with given labels.
 #[no_mangle]
-pub unsafe extern "C" fn dust_sync_actions(len: u64, ptr: *mut u8) -> CResult<CArray<u8>> {
-  access_workspace(|txr, ws| {
+pub unsafe extern "C" fn dust_sync_version_filtered(
+  handle: u64,
+  nodes_len: u64,
+  nodes_ptr: *mut u64,
+  atoms_len: u64,
+  atoms_ptr: *mut u64,
+  edges_len: u64,
+  edges_ptr: *mut u64,
+  lists_len: u64,
+  lists_ptr: *mut u64,
+) -> CResult<CArray<u8>> {
+  read_workspace(handle, |txr, ws| {
+    let labels = |len, ptr| if len > 0 { CArray(len, ptr).as_ref() } else { &[] };
+    let mut filter = ReplicationFilter::new();
+    labels(nodes_len, nodes_ptr).iter().for_each(|&label| filter.add_node(label));
+    labels(atoms_len, atoms_ptr).iter().for_each(|&label| filter.add_atom(label));
+    labels(edges_len, edges_ptr).iter().for_each(|&label| filter.add_edge(label));
+    labels(lists_len, lists_ptr).iter().for_each(|&label| filter.add_list(label));
+    Ok(ws.sync_version_filtered(txr, &filter).into())
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_sync_actions(handle: u64, len: u64, ptr: *mut u8) -> CResult<CArray<u8>> {
+  read_workspace(handle, |txr, ws| {
+    let version = CArray(len, ptr).as_ref();
+    Ok(ws.sync_actions(txr, version)?.into())
+  })
+}
+
+/// Returns a page of actions and the cursor for the next page, which is
+/// empty if there are no more pages. Pass an empty cursor for the first page.
+#[no_mangle]
+pub unsafe extern "C" fn dust_sync_actions_page(
+  handle: u64,
+  len: u64,
+  ptr: *mut u8,
+  cursor_len: u64,
+  cursor_ptr: *mut u8,
+  budget: u64,
+) -> CResult<CPair<CArray<u8>, CArray<u8>>> {
+  read_workspace(handle, |txr, ws| {
     let version = CArray(len, ptr).as_ref();
-    Ok(ws.sync_actions(txr, version).into())
+    let cursor = (cursor_len > 0).then(|| CArray(cursor_len, cursor_ptr).as_ref());
+    let (page, next) = ws.sync_actions_page(txr, version, cursor, budget)?;
+    Ok((page.into(), next.unwrap_or_default().into()).into())
   })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dust_sync_join(len: u64, ptr: *mut u8) -> CResult<CUnit> {
-  access_workspace(|txr, ws| {
+pub unsafe extern "C" fn dust_sync_join(handle: u64, len: u64, ptr: *mut u8) -> CResult<CArray<CConflictData>> {
+  access_workspace(handle, |txr, ws| {
     let actions = CArray(len, ptr).as_ref();
-    ws.sync_join(txr, actions);
+    Ok(ws.sync_join(txr, actions)?.into())
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_snapshot(handle: u64) -> CResult<CArray<u8>> {
+  read_workspace(handle, |txr, ws| Ok(ws.snapshot(txr)?.into()))
+}
+
+/// Returns a page of the snapshot and the cursor for the next page, which is
+/// empty if there are no more pages. Pass an empty cursor for the first page.
+#[no_mangle]
+pub unsafe extern "C" fn dust_snapshot_page(
+  handle: u64,
+  cursor_len: u64,
+  cursor_ptr: *mut u8,
+  budget: u64,
+) -> CResult<CPair<CArray<u8>, CArray<u8>>> {
+  read_workspace(handle, |txr, ws| {
+    let cursor = (cursor_len > 0).then(|| CArray(cursor_len, cursor_ptr).as_ref());
+    let (page, next) = ws.snapshot_page(txr, cursor, budget)?;
+    Ok((page.into(), next.unwrap_or_default().into()).into())
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_install_snapshot(handle: u64, len: u64, ptr: *mut u8) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    let snapshot = CArray(len, ptr).as_ref();
+    Ok(ws.install_snapshot(txr, snapshot)?.into())
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_export_bundle(handle: u64, len: u64, ptr: *mut u8) -> CResult<CArray<u8>> {
+  read_workspace(handle, |txr, ws| {
+    let version = CArray(len, ptr).as_ref();
+    Ok(ws.export_bundle(txr, version)?.into())
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_import_bundle(handle: u64, len: u64, ptr: *mut u8) -> CResult<CArray<CConflictData>> {
+  access_workspace(handle, |txr, ws| {
+    let bundle = CArray(len, ptr).as_ref();
+    Ok(ws.import_bundle(txr, bundle)?.into())
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_this(handle: u64) -> CResult<u64> {
+  latest_workspace(handle, |_, ws| Ok(ws.this()))
+}
+
+#[no_mangle]
+pub extern "C" fn dust_add_peer(handle: u64, peer: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    ws.add_peer(txr, peer)?;
+    Ok(CUnit(0))
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_remove_peer(handle: u64, peer: u64) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    ws.remove_peer(txr, peer)?;
+    Ok(CUnit(0))
+  })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dust_acknowledge(handle: u64, len: u64, ptr: *mut u8) -> CResult<CUnit> {
+  access_workspace(handle, |txr, ws| {
+    let version = CArray(len, ptr).as_ref();
+    ws.acknowledge(txr, version)?;
+    Ok(CUnit(0))
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn dust_compact(handle: u64) -> CResult<u64> {
+  access_workspace(handle, |txr, ws| ws.compact(txr))
+}
+
+/// Returns what is stored in each structure, for diagnostics. See
+/// [`Workspace::stats`].
+#[no_mangle]
+pub extern "C" fn dust_stats(handle: u64) -> CResult<CArray<CStatsData>> {
+  latest_workspace(handle, |txr, ws| Ok(ws.stats(txr)?.into()))
+}
+
+/// See [`Workspace::check`].
+#[no_mangle]
+pub extern "C" fn dust_check(handle: u64) -> CResult<CArray<CViolationData>> {
+  latest_workspace(handle, |txr, ws| {
+    Ok(ws.check(txr)?.into_iter().map(Into::into).collect::<Vec<CViolationData>>().into())
+  })
+}
+
+/// Stages removals which fix the reported violations. Call [`dust_barrier`]
+/// next. See [`Workspace::repair`].
+#[no_mangle]
+pub extern "C" fn dust_repair(handle: u64) -> CResult<CArray<CViolationData>> {
+  access_workspace(handle, |txr, ws| {
+    Ok(ws.repair(txr)?.into_iter().map(Into::into).collect::<Vec<CViolationData>>().into())
+  })
+}
+
+/// See [`Workspace::discard`].
+#[no_mangle]
+pub extern "C" fn dust_discard(handle: u64) -> CResult<CUnit> {
+  access_workspace(handle, |_, ws| {
+    ws.discard()?;
     Ok(CUnit(0))
   })
 }
 
 #[no_mangle]
-pub extern "C" fn dust_barrier() -> CResult<CArray<CEventData>> {
-  access_workspace(|txr, ws| Ok(ws.barrier(txr).into()))
+pub extern "C" fn dust_barrier(handle: u64) -> CResult<CArray<CEventData>> {
+  access_workspace(handle, |txr, ws| Ok(ws.barrier(txr)?.into()))
 }
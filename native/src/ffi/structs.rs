@@ -12,6 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::{
+  store::StorageStats,
+  workspace::{
+    Conflict, JoinReport, StructureStats, Violation, WorkspaceStats, ATOMS_NAME, EDGES_NAME, LISTS_NAME, NODES_NAME,
+  },
+};
+
 /// See: https://github.com/rust-lang/rust/issues/20660
 #[repr(C)]
 pub struct CUnit(pub u8);
@@ -72,12 +79,86 @@ pub struct CEdge {
   pub dst: CId,
 }
 
+/// One entry of the return value of [`super::store::dust_atoms_edges`].
+#[derive(Debug)]
+#[repr(C, u8)]
+pub enum CItemData {
+  Atom { id: CId, atom: CAtom },
+  Edge { id: CId, edge: CEdge },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct CStorageStats {
+  pub file_size: u64,
+  pub page_size: u64,
+  pub free_pages: u64,
+  pub wal_size: u64,
+}
+
+/// List items are passed as [`CAtom`]s, which have the same `(src, label,
+/// value)` shape. Their order is not included and should be queried again.
 #[derive(Debug)]
 #[repr(C, u8)]
 pub enum CEventData {
   Node { id: CId, prev: COption<CNode>, curr: COption<CNode> },
   Atom { id: CId, prev: COption<CAtom>, curr: COption<CAtom> },
   Edge { id: CId, prev: COption<CEdge>, curr: COption<CEdge> },
+  List { id: CId, prev: COption<CAtom>, curr: COption<CAtom> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, u8)]
+pub enum CViolationData {
+  DanglingAtom { id: CId, src: CId, label: u64 },
+  DanglingEdge { id: CId, edge: CEdge },
+  CyclicEdge { id: CId, edge: CEdge },
+}
+
+/// One entry of [`WorkspaceStats`]. `structure` is 0 for nodes, 1 for atoms,
+/// 2 for edges and 3 for list items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, u8)]
+pub enum CStatsData {
+  Label { structure: u8, label: u64, items: u64, bytes: u64 },
+  Tombstones { structure: u8, items: u64 },
+  Bucket { structure: u8, bucket: u64, clock: u64 },
+  Pending { structure: u8, items: u64 },
+}
+
+/// One entry of a [`JoinReport`]: a conflict, or a remote write rejected for
+/// being too far in the future. `structure` is 0 for nodes, 1 for atoms, 2 for
+/// edges and 3 for list items.
+#[derive(Debug)]
+#[repr(C, u8)]
+pub enum CConflictData {
+  Node {
+    id: CId,
+    winner: CTriple<u64, u64, COption<CNode>>,
+    loser: CTriple<u64, u64, COption<CNode>>,
+    overwritten: bool,
+  },
+  Atom {
+    id: CId,
+    winner: CTriple<u64, u64, COption<CAtom>>,
+    loser: CTriple<u64, u64, COption<CAtom>>,
+    overwritten: bool,
+  },
+  Edge {
+    id: CId,
+    winner: CTriple<u64, u64, COption<CEdge>>,
+    loser: CTriple<u64, u64, COption<CEdge>>,
+    overwritten: bool,
+  },
+  Rejected {
+    structure: u8,
+    id: CId,
+    bucket: u64,
+    clock: u64,
+  },
+  Forked {
+    bucket: u64,
+  },
 }
 
 impl From<()> for CUnit {
@@ -119,6 +200,13 @@ impl From<(u128, u64, u128)> for CEdge {
   }
 }
 
+impl From<StorageStats> for CStorageStats {
+  fn from(value: StorageStats) -> Self {
+    let StorageStats { file_size, page_size, free_pages, wal_size } = value;
+    Self { file_size, page_size, free_pages, wal_size }
+  }
+}
+
 impl<T, U> From<(T, U)> for CPair<T, U> {
   fn from(value: (T, U)) -> Self {
     let (first, second) = value;
@@ -141,6 +229,81 @@ impl<T> From<Vec<T>> for CArray<T> {
   }
 }
 
+impl<T, U: Into<T>> From<(u64, u64, Option<U>)> for CTriple<u64, u64, COption<T>> {
+  fn from(value: (u64, u64, Option<U>)) -> Self {
+    let (bucket, clock, inner) = value;
+    Self(bucket, clock, inner.map(Into::into).into())
+  }
+}
+
+impl From<Conflict> for CConflictData {
+  fn from(value: Conflict) -> Self {
+    match value {
+      Conflict::Node { id, winner, loser, overwritten } => {
+        CConflictData::Node { id: id.into(), winner: winner.into(), loser: loser.into(), overwritten }
+      }
+      Conflict::Atom { id, winner, loser, overwritten } => {
+        CConflictData::Atom { id: id.into(), winner: winner.into(), loser: loser.into(), overwritten }
+      }
+      Conflict::Edge { id, winner, loser, overwritten } => {
+        CConflictData::Edge { id: id.into(), winner: winner.into(), loser: loser.into(), overwritten }
+      }
+    }
+  }
+}
+
+impl From<JoinReport> for CArray<CConflictData> {
+  fn from(value: JoinReport) -> Self {
+    let JoinReport { conflicts, rejected, forked } = value;
+    let mut res: Vec<CConflictData> = conflicts.into_iter().map(Into::into).collect();
+    for (name, id, bucket, clock) in rejected {
+      let structure = [NODES_NAME, ATOMS_NAME, EDGES_NAME, LISTS_NAME].iter().position(|&n| n == name);
+      let structure = structure.map_or(u8::MAX, |structure| structure as u8);
+      res.push(CConflictData::Rejected { structure, id: id.into(), bucket, clock });
+    }
+    if let Some(bucket) = forked {
+      res.push(CConflictData::Forked { bucket });
+    }
+    res.into()
+  }
+}
+
+impl From<Violation> for CViolationData {
+  fn from(value: Violation) -> Self {
+    match value {
+      Violation::DanglingAtom { id, src, label } => {
+        CViolationData::DanglingAtom { id: id.into(), src: src.into(), label }
+      }
+      Violation::DanglingEdge { id, src, label, dst } => {
+        CViolationData::DanglingEdge { id: id.into(), edge: (src, label, dst).into() }
+      }
+      Violation::CyclicEdge { id, src, label, dst } => {
+        CViolationData::CyclicEdge { id: id.into(), edge: (src, label, dst).into() }
+      }
+    }
+  }
+}
+
+impl From<WorkspaceStats> for CArray<CStatsData> {
+  fn from(value: WorkspaceStats) -> Self {
+    let WorkspaceStats { nodes, atoms, edges, lists } = value;
+    let mut res = Vec::new();
+    for (structure, stats) in [nodes, atoms, edges, lists].into_iter().enumerate() {
+      let structure = structure as u8;
+      let StructureStats { labels, tombstones, buckets, pending } = stats;
+      for (label, (items, bytes)) in labels {
+        res.push(CStatsData::Label { structure, label, items, bytes });
+      }
+      res.push(CStatsData::Tombstones { structure, items: tombstones });
+      for (bucket, clock) in buckets {
+        res.push(CStatsData::Bucket { structure, bucket, clock });
+      }
+      res.push(CStatsData::Pending { structure, items: pending });
+    }
+    res.into()
+  }
+}
+
 impl<T> From<Option<T>> for COption<T> {
   fn from(value: Option<T>) -> Self {
     match value {
@@ -165,6 +328,6 @@ impl<T> CArray<T> {
   }
 
   pub unsafe fn into_boxed(self) -> Box<[T]> {
-    Box::from_raw(std::slice::from_raw_parts_mut(self.1, self.0 as usize))
+    Box::from_raw(std::ptr::slice_from_raw_parts_mut(self.1, self.0 as usize))
   }
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod ffi;
+pub mod memory;
 pub mod store;
 pub mod workspace;
 
@@ -32,10 +33,50 @@ pub enum StoreError {
   Sqlite(#[from] rusqlite::Error),
   #[error("string is not valid UTF8")]
   InvalidUtf8,
-  #[error("data store not initialised")]
-  Uninitialised,
+  #[error("invalid store handle {0:#x}")]
+  InvalidHandle(u64),
+  #[error("store handle {0:#x} already closed")]
+  Closed(u64),
   #[error("data store disconnected due to previous error")]
   Disconnected,
+  #[error("data store opened read-only")]
+  ReadOnly,
+  #[error("invalid option: {0}")]
+  InvalidOption(String),
+  #[error("backup failed: {0}")]
+  Backup(String),
+  #[error("no open savepoint")]
+  NoSavepoint,
+  #[error("{0} savepoints still open")]
+  SavepointsOpen(usize),
+  #[error("list index out of bounds")]
+  OutOfBounds,
+  #[error("list item {0:032x} not found")]
+  UnknownListItem(u128),
+  #[error("list item {0:032x} already exists")]
+  DuplicateListItem(u128),
+  #[error("local write rejected, clock {0} is not ahead of its bucket")]
+  StaleClock(u64),
+  #[error("malformed sync payload: {0}")]
+  MalformedPayload(String),
+  #[error("malformed sync bundle: {0}")]
+  MalformedBundle(String),
+  #[error("malformed write batch: {0}")]
+  MalformedBatch(String),
+  #[error("sync protocol version mismatch: local {0}, remote {1}")]
+  ProtocolMismatch(u64, u64),
+  #[error("schema version mismatch: local {0}, remote {1}")]
+  SchemaMismatch(u64, u64),
+  #[error("workspace mismatch: local {0:032x}, remote {1:032x}")]
+  WorkspaceMismatch(u128, u128),
+  #[error("snapshots can only be installed into an empty workspace")]
+  NotEmpty,
+  #[error("unsupported database schema version {0}, latest supported is {1}")]
+  UnsupportedSchema(u64, u64),
+  #[error("corrupt stored data: {0}")]
+  Corrupt(String),
+  #[error("pending modifications include a join whose sync state is saved already, run a barrier or roll back first")]
+  JoinPending,
 }
 
 /// A wrapper around `bincode`.
@@ -66,6 +107,14 @@ impl DerefMut for Transactor {
   }
 }
 
+impl Transactor {
+  /// Starts a read transaction, which sees the database as of its first read.
+  pub fn read_only(conn: Connection) -> rusqlite::Result<Self> {
+    conn.execute_batch("BEGIN DEFERRED")?;
+    Ok(Self { conn })
+  }
+}
+
 impl TryFrom<Connection> for Transactor {
   type Error = rusqlite::Error;
   fn try_from(value: Connection) -> rusqlite::Result<Self> {
@@ -102,11 +151,11 @@ fn remove<K: Eq + Hash, V: Eq>(map: &mut BTreeMap<K, Vec<V>>, key: K, value: &V)
 */
 
 /// Hashes the string `s` to a value of desired.
-pub fn fnv64_hash(s: impl AsRef<str>) -> u64 {
+pub fn fnv64_hash(s: impl AsRef<[u8]>) -> u64 {
   const PRIME: Wrapping<u64> = Wrapping(1099511628211);
   const BASIS: Wrapping<u64> = Wrapping(14695981039346656037);
   let mut res = BASIS;
-  for c in s.as_ref().as_bytes() {
+  for c in s.as_ref() {
     res = (res * PRIME) ^ Wrapping(*c as u64);
   }
   res.0
@@ -0,0 +1,126 @@
+// Copyright 2024 ParkourLabs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{deserialize, serialize, StoreError};
+
+/// An in-memory storage backend, as an alternative to [`crate::Transactor`].
+///
+/// Data is kept in ordered tables of serialized keys and values. Keys are
+/// tuples encoded with [`serialize`], which preserves the order of unsigned
+/// integers, so a table keyed by `(a, b, id)` doubles as an index on `a` and
+/// `(a, b)`. Nothing is written to disk.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransactor {
+  tables: BTreeMap<String, BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryTransactor {
+  /// Creates an empty backend.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns the value at `key` in `table`.
+  pub(crate) fn read<V: DeserializeOwned>(&self, table: &str, key: &impl Serialize) -> Result<Option<V>, StoreError> {
+    let Some(value) = self.tables.get(table).and_then(|entries| entries.get(&serialize(key).unwrap())) else {
+      return Ok(None);
+    };
+    Ok(Some(load(table, value)?))
+  }
+
+  /// Returns the value at `key` in `table`, which an index refers to.
+  pub(crate) fn read_indexed<V: DeserializeOwned>(&self, table: &str, key: &impl Serialize) -> Result<V, StoreError> {
+    self.read(table, key)?.ok_or_else(|| StoreError::Corrupt(format!("{table}: missing indexed entry")))
+  }
+
+  /// Sets the value at `key` in `table`, creating the table if needed.
+  pub(crate) fn write(&mut self, table: &str, key: &impl Serialize, value: &impl Serialize) {
+    let table = self.tables.entry(table.to_owned()).or_default();
+    table.insert(serialize(key).unwrap(), serialize(value).unwrap());
+  }
+
+  /// Removes the value at `key` in `table`. Returns whether it existed.
+  pub(crate) fn remove(&mut self, table: &str, key: &impl Serialize) -> bool {
+    self.tables.get_mut(table).is_some_and(|table| table.remove(&serialize(key).unwrap()).is_some())
+  }
+
+  /// Removes all values in `table` whose key starts with `prefix`. Returns the
+  /// number of removed values.
+  pub(crate) fn remove_prefix(&mut self, table: &str, prefix: &impl Serialize) -> u64 {
+    let prefix = serialize(prefix).unwrap();
+    let Some(table) = self.tables.get_mut(table) else { return 0 };
+    let keys: Vec<Vec<u8>> =
+      table.range(prefix.clone()..).map(|(k, _)| k).take_while(|k| k.starts_with(&prefix)).cloned().collect();
+    for key in &keys {
+      table.remove(key);
+    }
+    keys.len() as u64
+  }
+
+  /// Returns all entries in `table` whose key starts with `prefix`, in key
+  /// order.
+  pub(crate) fn scan<'a, K: DeserializeOwned, V: DeserializeOwned>(
+    &'a self,
+    table: &str,
+    prefix: &impl Serialize,
+  ) -> impl Iterator<Item = Result<(K, V), StoreError>> + 'a {
+    self.range(table, prefix, prefix)
+  }
+
+  /// Returns all entries in `table` whose key starts with `prefix` and is not
+  /// less than `start`, in key order. `start` must itself start with `prefix`.
+  pub(crate) fn range<'a, K: DeserializeOwned, V: DeserializeOwned>(
+    &'a self,
+    table: &str,
+    prefix: &impl Serialize,
+    start: &impl Serialize,
+  ) -> impl Iterator<Item = Result<(K, V), StoreError>> + 'a {
+    let prefix = serialize(prefix).unwrap();
+    let start = serialize(start).unwrap();
+    let name = table.to_owned();
+    self
+      .tables
+      .get(table)
+      .into_iter()
+      .flat_map(move |table| table.range(start.clone()..))
+      .take_while(move |(k, _)| k.starts_with(&prefix))
+      .map(move |(k, v)| Ok((load(&name, k)?, load(&name, v)?)))
+  }
+
+  /// Returns IDs from an index keyed by `(bucket, clock, id)`, for entries in
+  /// `bucket` strictly later than `lower`, in clock order.
+  pub(crate) fn by_bucket_clock(
+    &self,
+    table: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<u128>, StoreError> {
+    let Some(start) = lower.map_or(Some(0), |lower| lower.checked_add(1)) else { return Ok(Vec::new()) };
+    let limit = limit.and_then(|limit| usize::try_from(limit).ok()).unwrap_or(usize::MAX);
+    self
+      .range(table, &bucket, &(bucket, start))
+      .take(limit)
+      .map(|entry| entry.map(|((_, _, id), ()): ((u64, u64, u128), ())| id))
+      .collect()
+  }
+}
+
+/// Deserializes a key or value stored in `table`.
+fn load<V: DeserializeOwned>(table: &str, bytes: &[u8]) -> Result<V, StoreError> {
+  deserialize(bytes).map_err(|err| StoreError::Corrupt(format!("{table}: {err}")))
+}
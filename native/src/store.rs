@@ -12,42 +12,867 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use rusqlite::Connection;
+use rusqlite::{
+  backup::{Backup, StepResult},
+  Connection, OpenFlags, OptionalExtension,
+};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  time::{Duration, Instant},
+};
 
-use crate::workspace::{Constraints, Workspace};
+use crate::workspace::{
+  metadata::{WorkspaceMetadataTransactor, CURRENT_VERSION},
+  Constraints, Workspace,
+};
 use crate::{StoreError, Transactor};
 
+/// Read-only connections kept open for reuse.
+const MAX_IDLE_READERS: usize = 4;
+
+/// Name of all savepoints. SQLite resolves it to the innermost one.
+const SAVEPOINT_NAME: &str = "dust";
+
+/// Pages released per step of [`Store::maintain`], between budget checks.
+const VACUUM_STEP: u64 = 64;
+
+/// See <https://www.sqlite.org/pragma.html#pragma_journal_mode>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+  Delete,
+  Truncate,
+  Persist,
+  Memory,
+  Wal,
+  Off,
+}
+
+/// See <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+  Off,
+  Normal,
+  Full,
+  Extra,
+}
+
+impl TryFrom<u8> for JournalMode {
+  type Error = StoreError;
+  fn try_from(value: u8) -> Result<Self, StoreError> {
+    match value {
+      0 => Ok(Self::Delete),
+      1 => Ok(Self::Truncate),
+      2 => Ok(Self::Persist),
+      3 => Ok(Self::Memory),
+      4 => Ok(Self::Wal),
+      5 => Ok(Self::Off),
+      _ => Err(StoreError::InvalidOption(format!("journal mode {value}"))),
+    }
+  }
+}
+
+impl TryFrom<u8> for Synchronous {
+  type Error = StoreError;
+  fn try_from(value: u8) -> Result<Self, StoreError> {
+    match value {
+      0 => Ok(Self::Off),
+      1 => Ok(Self::Normal),
+      2 => Ok(Self::Full),
+      3 => Ok(Self::Extra),
+      _ => Err(StoreError::InvalidOption(format!("synchronous mode {value}"))),
+    }
+  }
+}
+
+/// Configures how a [`Store`] is opened. The defaults suit an app database:
+/// WAL with `synchronous = NORMAL`, which may lose the last commits on power
+/// failure but never corrupts the database.
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+  prefix: String,
+  constraints: Constraints,
+  read_only: bool,
+  journal_mode: JournalMode,
+  synchronous: Synchronous,
+  wal_autocheckpoint: u64,
+  cache_size: i64,
+  busy_timeout: Duration,
+}
+
+impl Default for OpenOptions {
+  fn default() -> Self {
+    Self {
+      prefix: String::new(),
+      constraints: Constraints::new(),
+      read_only: false,
+      journal_mode: JournalMode::Wal,
+      synchronous: Synchronous::Normal,
+      wal_autocheckpoint: 2000,
+      cache_size: 2000,
+      busy_timeout: Duration::from_secs(1),
+    }
+  }
+}
+
+impl OpenOptions {
+  pub fn new() -> Self {
+    Default::default()
+  }
+  /// Sets the workspace prefix, so that several workspaces can share a file.
+  pub fn prefix(&mut self, prefix: &str) -> &mut Self {
+    self.prefix = prefix.to_owned();
+    self
+  }
+  pub fn constraints(&mut self, constraints: Constraints) -> &mut Self {
+    self.constraints = constraints;
+    self
+  }
+  pub fn constraints_mut(&mut self) -> &mut Constraints {
+    &mut self.constraints
+  }
+  /// Opens without a writer: only [`Store::read`] is allowed. The database
+  /// must already be initialised. If it is at an older schema version, it is
+  /// copied to memory and migrated there, so later writes to the file are not
+  /// seen. Ignored for in-memory databases.
+  pub fn read_only(&mut self, read_only: bool) -> &mut Self {
+    self.read_only = read_only;
+    self
+  }
+  /// Only takes effect when not read-only.
+  pub fn journal_mode(&mut self, journal_mode: JournalMode) -> &mut Self {
+    self.journal_mode = journal_mode;
+    self
+  }
+  pub fn synchronous(&mut self, synchronous: Synchronous) -> &mut Self {
+    self.synchronous = synchronous;
+    self
+  }
+  /// Sets the WAL size, in pages, after which commits checkpoint it. Zero
+  /// disables automatic checkpoints.
+  pub fn wal_autocheckpoint(&mut self, pages: u64) -> &mut Self {
+    self.wal_autocheckpoint = pages;
+    self
+  }
+  /// Sets the page cache size of each connection: in pages if positive, or in
+  /// KiB if negative, as in SQLite.
+  pub fn cache_size(&mut self, cache_size: i64) -> &mut Self {
+    self.cache_size = cache_size;
+    self
+  }
+  /// Sets how long to wait for locks held by other connections.
+  pub fn busy_timeout(&mut self, busy_timeout: Duration) -> &mut Self {
+    self.busy_timeout = busy_timeout;
+    self
+  }
+
+  pub fn open(&self, path: &str) -> Result<Store, StoreError> {
+    let conn = match self.read_only {
+      true => Connection::open_with_flags(path, reader_flags())?,
+      false => Connection::open(path)?,
+    };
+    self.configure(&conn)?;
+    Store::with_options(conn, self)
+  }
+
+  /// Opens a private, temporary database, e.g. for tests.
+  pub fn open_in_memory(&self) -> Result<Store, StoreError> {
+    let conn = Connection::open_in_memory()?;
+    let options = Self { read_only: false, ..self.clone() };
+    options.configure(&conn)?;
+    Store::with_options(conn, &options)
+  }
+
+  fn configure(&self, conn: &Connection) -> Result<(), StoreError> {
+    if !self.read_only {
+      let journal_mode = match self.journal_mode {
+        JournalMode::Delete => "DELETE",
+        JournalMode::Truncate => "TRUNCATE",
+        JournalMode::Persist => "PERSIST",
+        JournalMode::Memory => "MEMORY",
+        JournalMode::Wal => "WAL",
+        JournalMode::Off => "OFF",
+      };
+      conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL")?;
+      conn.pragma_update_and_check(None, "journal_mode", journal_mode, |_| Ok(()))?;
+    }
+    let synchronous = match self.synchronous {
+      Synchronous::Off => "OFF",
+      Synchronous::Normal => "NORMAL",
+      Synchronous::Full => "FULL",
+      Synchronous::Extra => "EXTRA",
+    };
+    conn.pragma_update(None, "synchronous", synchronous)?;
+    conn.pragma_update_and_check(None, "wal_autocheckpoint", self.wal_autocheckpoint, |_| Ok(()))?;
+    conn.pragma_update(None, "cache_size", self.cache_size)?;
+    conn.busy_timeout(self.busy_timeout)?;
+    Ok(())
+  }
+}
+
+/// Copies the whole database in one step, so that it is consistent.
+fn copy(src: &Connection, dst: &mut Connection) -> Result<(), StoreError> {
+  match Backup::new(src, dst)?.step(-1)? {
+    StepResult::Done => Ok(()),
+    res => Err(StoreError::Backup(format!("copy interrupted: {res:?}"))),
+  }
+}
+
+/// Checks that `conn` holds an intact workspace which can be opened.
+fn check_backup(conn: &Connection, prefix: &str) -> Result<(), StoreError> {
+  let integrity: String = conn.query_row("PRAGMA integrity_check", (), |row| row.get(0))?;
+  if integrity != "ok" {
+    return Err(StoreError::Backup(integrity));
+  }
+  let table = format!("{prefix}.version");
+  let exists =
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?", (&table,), |row| {
+      row.get::<_, u64>(0)
+    })?;
+  if exists == 0 {
+    return Err(StoreError::Backup(format!("no workspace with prefix {prefix:?}")));
+  }
+  let version = conn
+    .query_row(&format!("SELECT version FROM \"{table}\""), (), |row| Ok(u64::from_be_bytes(row.get(0)?)))
+    .optional()?;
+  match version {
+    None => Err(StoreError::Backup(format!("no workspace with prefix {prefix:?}"))),
+    Some(version) if version == 0 || version > CURRENT_VERSION => {
+      Err(StoreError::UnsupportedSchema(version, CURRENT_VERSION))
+    }
+    Some(_) => Ok(()),
+  }
+}
+
+/// Identifies the database file at `path`. Copies of it, e.g. on another
+/// device, have a different one, see [`Workspace::check_fingerprint`].
+#[cfg(unix)]
+fn fingerprint(path: &str) -> Option<u64> {
+  use std::os::unix::fs::MetadataExt;
+  std::fs::metadata(path).ok().map(|metadata| metadata.ino())
+}
+
+/// File identities are not available here, so copies are only detected
+/// when syncing.
+#[cfg(not(unix))]
+fn fingerprint(_: &str) -> Option<u64> {
+  None
+}
+
+fn reader_flags() -> OpenFlags {
+  OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI
+}
+
+/// Sizes of the database files, see [`Store::storage_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageStats {
+  /// Size of the main database file in bytes.
+  pub file_size: u64,
+  pub page_size: u64,
+  /// Unused pages in the main database file, which [`Store::maintain`]
+  /// releases.
+  pub free_pages: u64,
+  /// Size of the write-ahead log in bytes, zero unless in WAL mode.
+  pub wal_size: u64,
+}
+
+/// A store with one writer and a pool of read-only connections, which can be
+/// shared between threads.
+///
+/// Reads through [`Store::access`] observe everything written so far,
+/// including modifications not yet applied by a barrier. Reads through
+/// [`Store::read`] only observe the state as of the last [`Store::commit`]:
+/// neither pending modifications nor barriers since then. They never wait for
+/// the writer, except on in-memory databases, which cannot be opened twice and
+/// are read through the writer connection instead (still without pending
+/// modifications, but including uncommitted barriers). Reads through
+/// [`Store::read_latest`] go through the writer only while it holds anything
+/// not yet committed, so the writing thread sees its own writes.
 pub struct Store {
-  txr: Option<Transactor>,
-  workspace: Workspace,
+  path: Option<String>,
+  options: OpenOptions,
+  /// `(connection, workspace, workspace at each open savepoint)`.
+  writer: Mutex<(Option<Transactor>, Workspace, Vec<Workspace>)>,
+  committed: RwLock<Arc<Workspace>>,
+  readers: Mutex<Option<Vec<Connection>>>,
+  /// Whether the writer may hold anything newer than the last commit: pending
+  /// modifications, barriers or open savepoints.
+  uncommitted: AtomicBool,
 }
 
 impl Store {
+  /// Opens a store on a connection configured by the caller.
   pub fn new(conn: Connection, constraints: Constraints) -> Result<Self, StoreError> {
-    let mut txr = conn.try_into()?;
-    let workspace = Workspace::new("", constraints, &mut txr);
-    Ok(Self { txr: Some(txr), workspace })
+    Self::with_options(conn, OpenOptions::new().constraints(constraints))
   }
 
-  pub fn as_mut(&mut self) -> Result<(&mut Transactor, &mut Workspace), StoreError> {
-    let txr = self.txr.as_mut().ok_or(StoreError::Disconnected)?;
-    Ok((txr, &mut self.workspace))
+  fn with_options(conn: Connection, options: &OpenOptions) -> Result<Self, StoreError> {
+    let mut path = conn.path().filter(|path| !path.is_empty()).map(str::to_owned);
+    let (txr, workspace, readers) = match options.read_only {
+      true => {
+        let mut txr = Transactor::read_only(conn)?;
+        if matches!(txr.get_version(&options.prefix), Ok(Some(version)) if version < CURRENT_VERSION) {
+          // Older schemas cannot be migrated through a read-only connection,
+          // so a private copy is migrated and read like an in-memory database.
+          let mut conn = Connection::open_in_memory()?;
+          copy(&txr, &mut conn)?;
+          let mut txr: Transactor = conn.try_into()?;
+          let workspace = Workspace::new(&options.prefix, options.constraints.clone(), &mut txr)?;
+          let conn: Connection = txr.try_into()?;
+          path = None;
+          (Some(Transactor::read_only(conn)?), workspace, Vec::new())
+        } else {
+          let workspace = Workspace::new(&options.prefix, options.constraints.clone(), &mut txr)?;
+          (None, workspace, vec![txr.try_into()?])
+        }
+      }
+      false => {
+        let mut txr: Transactor = conn.try_into()?;
+        let mut workspace = Workspace::new(&options.prefix, options.constraints.clone(), &mut txr)?;
+        if let Some(fingerprint) = path.as_deref().and_then(fingerprint) {
+          if workspace.check_fingerprint(&mut txr, fingerprint)?.is_some() {
+            workspace.barrier(&mut txr)?;
+          }
+        }
+        // Commits the initialisation so that readers can see the tables.
+        let conn: Connection = txr.try_into()?;
+        (Some(conn.try_into()?), workspace, Vec::new())
+      }
+    };
+    Ok(Self {
+      path,
+      options: options.clone(),
+      committed: RwLock::new(Arc::new(workspace.committed())),
+      writer: Mutex::new((txr, workspace, Vec::new())),
+      readers: Mutex::new(Some(readers)),
+      uncommitted: AtomicBool::new(false),
+    })
+  }
+
+  /// Returns whether the store has no writer.
+  pub fn is_read_only(&self) -> bool {
+    self.options.read_only
+  }
+
+  /// Runs `f` on the workspace. After a storage error, the workspace may hold
+  /// modifications which were only partly written, so the connection is
+  /// dropped, rolling back the open transaction, and every later call on this
+  /// store returns [`StoreError::Disconnected`]. Open the file again as a new
+  /// store to continue.
+  pub fn access<T>(
+    &self,
+    f: impl FnOnce(&mut Transactor, &mut Workspace) -> Result<T, StoreError>,
+  ) -> Result<T, StoreError> {
+    self.access_writer(|txr, ws, _| f(txr, ws))
   }
 
-  pub fn commit(&mut self) -> Result<(), StoreError> {
-    let txr = self.txr.take().ok_or(StoreError::Disconnected)?;
+  /// Like [`Store::access`], with the workspaces saved by open savepoints.
+  fn access_writer<T>(
+    &self,
+    f: impl FnOnce(&mut Transactor, &mut Workspace, &mut Vec<Workspace>) -> Result<T, StoreError>,
+  ) -> Result<T, StoreError> {
+    if self.options.read_only {
+      return Err(StoreError::ReadOnly);
+    }
+    let mut writer = self.writer.lock().map_err(|_| StoreError::Disconnected)?;
+    self.uncommitted.store(true, Ordering::Release);
+    let (txr, ws, savepoints) = &mut *writer;
+    let res = f(txr.as_mut().ok_or(StoreError::Disconnected)?, ws, savepoints);
+    if let Err(StoreError::Sqlite(_)) = res {
+      *txr = None;
+    }
+    res
+  }
+
+  /// Runs `f` on the latest state of the workspace: through the writer, like
+  /// [`Store::access`], while it holds anything not yet committed, and
+  /// otherwise like [`Store::read`], without waiting for it.
+  pub fn read_latest<T>(
+    &self,
+    f: impl FnOnce(&Transactor, &Workspace) -> Result<T, StoreError>,
+  ) -> Result<T, StoreError> {
+    match self.uncommitted.load(Ordering::Acquire) {
+      true => self.access(|txr, ws| f(txr, ws)),
+      false => self.read(f),
+    }
+  }
+
+  /// Runs `f` on the last committed state of the workspace, in a read
+  /// transaction on a pooled connection.
+  pub fn read<T>(&self, f: impl FnOnce(&Transactor, &Workspace) -> Result<T, StoreError>) -> Result<T, StoreError> {
+    let Some(path) = &self.path else {
+      let committed = self.committed.read().map_err(|_| StoreError::Disconnected)?.clone();
+      let writer = self.writer.lock().map_err(|_| StoreError::Disconnected)?;
+      return f(writer.0.as_ref().ok_or(StoreError::Disconnected)?, &committed);
+    };
+    let conn = match self.idle_readers(|readers| readers.pop())? {
+      Some(conn) => conn,
+      None => {
+        let conn = Connection::open_with_flags(path, reader_flags())?;
+        conn.pragma_update(None, "cache_size", self.options.cache_size)?;
+        conn.busy_timeout(self.options.busy_timeout)?;
+        conn
+      }
+    };
+    // The snapshot is taken on the first read, which must not race with a
+    // commit replacing the matching workspace.
+    let (txr, committed) = {
+      let committed = self.committed.read().map_err(|_| StoreError::Disconnected)?;
+      let txr = Transactor::read_only(conn)?;
+      txr.query_row("SELECT COUNT(*) FROM sqlite_master", (), |_| Ok(()))?;
+      (txr, committed.clone())
+    };
+    let res = f(&txr, &committed)?;
     let conn: Connection = txr.try_into()?;
-    let txr: Transactor = conn.try_into()?;
-    self.txr = Some(txr);
+    self.idle_readers(|readers| {
+      if readers.len() < MAX_IDLE_READERS {
+        readers.push(conn);
+      }
+    })?;
+    Ok(res)
+  }
+
+  /// Copies the last committed state (see [`Store::read`]) to a database at
+  /// `path`, overwriting it, with SQLite's online backup. The copy is checked
+  /// before returning. Writes may proceed meanwhile.
+  pub fn backup_to(&self, path: &str) -> Result<(), StoreError> {
+    let mut dst = Connection::open(path)?;
+    self.read(|txr, _| copy(txr, &mut dst))?;
+    check_backup(&dst, &self.options.prefix)
+  }
+
+  /// Replaces the contents of the store with the backup at `path`, which is
+  /// checked first and migrated if needed. Pending modifications are lost. If
+  /// restoring fails, the store keeps its contents and stays usable.
+  ///
+  /// Peers may have received writes from this replica made after the backup,
+  /// so it moves to a fresh bucket (see [`Workspace::fork`]). Data read before
+  /// should be read again.
+  pub fn restore_from(&self, path: &str) -> Result<(), StoreError> {
+    if self.options.read_only {
+      return Err(StoreError::ReadOnly);
+    }
+    let src = Connection::open_with_flags(path, reader_flags())?;
+    check_backup(&src, &self.options.prefix)?;
+    // Migrations and the fork run on a private, temporary copy, so that the
+    // store is left as it was if they fail.
+    let mut tmp = Connection::open("")?;
+    copy(&src, &mut tmp)?;
+    let mut txr: Transactor = tmp.try_into()?;
+    let mut workspace = Workspace::new(&self.options.prefix, self.options.constraints.clone(), &mut txr)?;
+    // The backup records the identity of the file it was taken from, which
+    // may fork already.
+    let forked = match self.path.as_deref().and_then(fingerprint) {
+      Some(fingerprint) => workspace.check_fingerprint(&mut txr, fingerprint)?.is_some(),
+      None => false,
+    };
+    if !forked {
+      workspace.fork(&mut txr)?;
+    }
+    workspace.barrier(&mut txr)?;
+    let tmp: Connection = txr.try_into()?;
+    let mut writer = self.writer.lock().map_err(|_| StoreError::Disconnected)?;
+    let mut committed = self.committed.write().map_err(|_| StoreError::Disconnected)?;
+    let mut conn: Connection = writer.0.take().ok_or(StoreError::Disconnected)?.try_into()?;
+    *committed = Arc::new(writer.1.committed());
+    // A failed copy leaves the database unchanged, so the writer is put back
+    // either way.
+    let res = copy(&tmp, &mut conn);
+    writer.0 = Some(conn.try_into()?);
+    res?;
+    *committed = Arc::new(workspace.committed());
+    writer.1 = workspace;
+    writer.2.clear();
+    self.uncommitted.store(false, Ordering::Release);
     Ok(())
   }
 
-  pub fn close(self) -> Result<(), StoreError> {
-    let txr = self.txr.ok_or(StoreError::Disconnected)?;
-    let conn: Connection = txr.try_into()?;
+  /// Commits the open transaction. Fails with
+  /// [`StoreError::SavepointsOpen`] while there are open savepoints.
+  pub fn commit(&self) -> Result<(), StoreError> {
+    self.outside_transaction(|_| Ok(()))
+  }
+
+  /// Rolls back the open transaction, including all savepoints, and drops
+  /// pending modifications, returning to the state of the last commit.
+  /// Observers of data changed by barriers since then should read it again.
+  pub fn rollback(&self) -> Result<(), StoreError> {
+    self.access_writer(|txr, ws, savepoints| {
+      txr.execute_batch("ROLLBACK; BEGIN IMMEDIATE")?;
+      *ws = Workspace::new(&self.options.prefix, self.options.constraints.clone(), txr)?;
+      savepoints.clear();
+      self.uncommitted.store(false, Ordering::Release);
+      Ok(())
+    })
+  }
+
+  /// Opens a savepoint inside the open transaction. Savepoints can be nested.
+  /// Returns the number of open savepoints.
+  pub fn savepoint(&self) -> Result<usize, StoreError> {
+    self.access_writer(|txr, ws, savepoints| {
+      txr.execute_batch(&format!("SAVEPOINT {SAVEPOINT_NAME}"))?;
+      savepoints.push(ws.clone());
+      Ok(savepoints.len())
+    })
+  }
+
+  /// Closes the innermost savepoint, keeping everything written since.
+  /// Returns the number of open savepoints.
+  pub fn release(&self) -> Result<usize, StoreError> {
+    self.access_writer(|txr, _, savepoints| {
+      savepoints.pop().ok_or(StoreError::NoSavepoint)?;
+      txr.execute_batch(&format!("RELEASE {SAVEPOINT_NAME}"))?;
+      Ok(savepoints.len())
+    })
+  }
+
+  /// Returns to the state at the innermost savepoint, including pending
+  /// modifications, and closes it. Returns the number of open savepoints.
+  pub fn rollback_to_savepoint(&self) -> Result<usize, StoreError> {
+    self.access_writer(|txr, ws, savepoints| {
+      let saved = savepoints.pop().ok_or(StoreError::NoSavepoint)?;
+      txr.execute_batch(&format!("ROLLBACK TO {SAVEPOINT_NAME}; RELEASE {SAVEPOINT_NAME}"))?;
+      *ws = saved;
+      Ok(savepoints.len())
+    })
+  }
+
+  /// Returns the sizes of the database files as of the last commit.
+  pub fn storage_stats(&self) -> Result<StorageStats, StoreError> {
+    let pragma =
+      |txr: &Transactor, name: &str| txr.query_row(&format!("PRAGMA {name}"), (), |row| row.get::<_, u64>(0));
+    let (page_size, page_count, free_pages) = self
+      .read(|txr, _| Ok((pragma(txr, "page_size")?, pragma(txr, "page_count")?, pragma(txr, "freelist_count")?)))?;
+    let wal_size = match &self.path {
+      Some(path) => std::fs::metadata(format!("{path}-wal")).map_or(0, |meta| meta.len()),
+      None => 0,
+    };
+    Ok(StorageStats { file_size: page_size * page_count, page_size, free_pages, wal_size })
+  }
+
+  /// Commits, then releases free pages to the file system, at most
+  /// `max_pages` of them, and checkpoints the write-ahead log without waiting
+  /// for readers. Stops early once `budget` has elapsed, so that this can run
+  /// whenever the app is idle. Writers wait meanwhile.
+  ///
+  /// Returns the number of released pages. Databases created before incremental
+  /// auto-vacuum was enabled never release pages.
+  pub fn maintain(&self, budget: Duration, max_pages: u64) -> Result<u64, StoreError> {
+    let deadline = Instant::now().checked_add(budget);
+    let elapsed = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    self.outside_transaction(|conn| {
+      let free_pages = || conn.query_row("PRAGMA freelist_count", (), |row| row.get::<_, u64>(0));
+      let mut released = 0;
+      let mut before = free_pages()?;
+      while released < max_pages && before > 0 && !elapsed() {
+        conn.execute_batch(&format!("PRAGMA incremental_vacuum({})", VACUUM_STEP.min(max_pages - released)))?;
+        let after = free_pages()?;
+        if after >= before {
+          break;
+        }
+        released += before - after;
+        before = after;
+      }
+      if !elapsed() {
+        conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", (), |_| Ok(()))?;
+      }
+      Ok(released)
+    })
+  }
+
+  /// Commits, runs `f` on the writer connection in autocommit mode, then
+  /// begins the next transaction.
+  fn outside_transaction<T>(&self, f: impl FnOnce(&Connection) -> Result<T, StoreError>) -> Result<T, StoreError> {
+    if self.options.read_only {
+      return Err(StoreError::ReadOnly);
+    }
+    let mut writer = self.writer.lock().map_err(|_| StoreError::Disconnected)?;
+    let (txr, ws, savepoints) = &mut *writer;
+    if !savepoints.is_empty() {
+      return Err(StoreError::SavepointsOpen(savepoints.len()));
+    }
+    let conn: Connection = {
+      let mut committed = self.committed.write().map_err(|_| StoreError::Disconnected)?;
+      let conn = txr.take().ok_or(StoreError::Disconnected)?.try_into()?;
+      *committed = Arc::new(ws.committed());
+      self.uncommitted.store(ws.is_modified(), Ordering::Release);
+      conn
+    };
+    let res = f(&conn);
+    *txr = Some(conn.try_into()?);
+    res
+  }
+
+  /// Commits and closes the writer and all idle readers. Later calls fail with
+  /// [`StoreError::Disconnected`], but reads already in progress complete.
+  pub fn close(&self) -> Result<(), StoreError> {
+    let mut writer = self.writer.lock().map_err(|_| StoreError::Disconnected)?;
+    self.readers.lock().map_err(|_| StoreError::Disconnected)?.take().ok_or(StoreError::Disconnected)?;
+    if self.options.read_only {
+      return Ok(());
+    }
+    let conn: Connection = writer.0.take().ok_or(StoreError::Disconnected)?.try_into()?;
     conn.close().map_err(|(_, err)| err)?;
     Ok(())
   }
+
+  fn idle_readers<T>(&self, f: impl FnOnce(&mut Vec<Connection>) -> T) -> Result<T, StoreError> {
+    let mut readers = self.readers.lock().map_err(|_| StoreError::Disconnected)?;
+    Ok(f(readers.as_mut().ok_or(StoreError::Disconnected)?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn disconnect_on_error() {
+    let conn = Connection::open_in_memory().unwrap();
+    let store = Store::new(conn, Constraints::new()).unwrap();
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+
+    // Other errors leave the store usable.
+    assert!(matches!(store.access(|txr, ws| ws.sync_actions(txr, &[])), Err(StoreError::MalformedPayload(_))));
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+
+    store.access(|txr, _| Ok(txr.execute("DROP TABLE \".nodes.data\"", ())?)).unwrap();
+    assert!(matches!(store.access(|txr, ws| ws.node(txr, 2)), Err(StoreError::Sqlite(_))));
+    assert!(matches!(store.access(|txr, ws| ws.node(txr, 1)), Err(StoreError::Disconnected)));
+    assert!(matches!(store.commit(), Err(StoreError::Disconnected)));
+  }
+
+  #[test]
+  fn committed_reads() {
+    let dir = std::env::temp_dir().join(format!("dust-store-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let conn = Connection::open(dir.join("test.db")).unwrap();
+    conn.execute_batch("PRAGMA journal_mode = WAL").unwrap();
+    let store = Arc::new(Store::new(conn, Constraints::new()).unwrap());
+
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 1)).unwrap(), None);
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 1)).unwrap(), None);
+    store.commit().unwrap();
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    let version = store.read(|txr, ws| Ok(ws.sync_version(txr))).unwrap();
+
+    // Readers on other threads proceed while the writer is busy.
+    store
+      .access(|txr, ws| {
+        ws.set_node(txr, 2, Some(0))?;
+        ws.barrier(txr)?;
+        let reader = store.clone();
+        let res = std::thread::spawn(move || reader.read(|txr, ws| Ok((ws.node(txr, 2)?, ws.sync_version(txr)))));
+        assert_eq!(res.join().unwrap()?, (None, version.clone()));
+        Ok(())
+      })
+      .unwrap();
+    store.commit().unwrap();
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 2)).unwrap(), Some(0));
+    assert_ne!(store.read(|txr, ws| Ok(ws.sync_version(txr))).unwrap(), version);
+
+    store.close().unwrap();
+    assert!(matches!(store.read(|txr, ws| ws.node(txr, 1)), Err(StoreError::Disconnected)));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn backup_restore() {
+    let dir = std::env::temp_dir().join(format!("dust-backup-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).into_os_string().into_string().unwrap();
+    let store = OpenOptions::new().open(&path("test.db")).unwrap();
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+    store.commit().unwrap();
+    store.access(|txr, ws| ws.set_node(txr, 2, Some(0))).unwrap();
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+
+    // Only committed writes are backed up.
+    store.backup_to(&path("backup.db")).unwrap();
+    let backup = OpenOptions::new().read_only(true).open(&path("backup.db")).unwrap();
+    assert_eq!(backup.read(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    assert_eq!(backup.read(|txr, ws| ws.node(txr, 2)).unwrap(), None);
+    backup.close().unwrap();
+
+    // Invalid backups are rejected before anything is changed.
+    std::fs::write(path("garbage.db"), [1; 4096]).unwrap();
+    assert!(store.restore_from(&path("garbage.db")).is_err());
+    Connection::open(path("empty.db")).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+    assert!(matches!(store.restore_from(&path("empty.db")), Err(StoreError::Backup(_))));
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 2)).unwrap(), Some(0));
+
+    // So are backups which cannot be migrated, and the store stays usable.
+    std::fs::copy(path("backup.db"), path("broken.db")).unwrap();
+    Connection::open(path("broken.db"))
+      .unwrap()
+      .execute_batch("DROP TABLE \".nodes.data\"; CREATE TABLE \".nodes.data\" (x)")
+      .unwrap();
+    assert!(matches!(store.restore_from(&path("broken.db")), Err(StoreError::Sqlite(_))));
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 2)).unwrap(), Some(0));
+
+    let this = store.access(|_, ws| Ok(ws.this())).unwrap();
+    store.access(|txr, ws| ws.set_node(txr, 1, None)).unwrap();
+    store.restore_from(&path("backup.db")).unwrap();
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    assert_eq!(store.access(|txr, ws| ws.node(txr, 2)).unwrap(), None);
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    assert_ne!(store.access(|_, ws| Ok(ws.this())).unwrap(), this);
+    store.close().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn copied_database() {
+    let dir = std::env::temp_dir().join(format!("dust-copy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).into_os_string().into_string().unwrap();
+    let store = OpenOptions::new().open(&path("test.db")).unwrap();
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+    store.commit().unwrap();
+    let this = store.access(|_, ws| Ok(ws.this())).unwrap();
+    store.close().unwrap();
+
+    // Reopening the same file keeps the bucket, opening a copy forks.
+    std::fs::copy(path("test.db"), path("copy.db")).unwrap();
+    let store = OpenOptions::new().open(&path("test.db")).unwrap();
+    assert_eq!(store.access(|_, ws| Ok(ws.this())).unwrap(), this);
+    store.close().unwrap();
+    let copy = OpenOptions::new().open(&path("copy.db")).unwrap();
+    assert_ne!(copy.access(|_, ws| Ok(ws.this())).unwrap(), this);
+    assert_eq!(copy.read(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    let forked = copy.access(|_, ws| Ok(ws.this())).unwrap();
+    copy.close().unwrap();
+    let copy = OpenOptions::new().open(&path("copy.db")).unwrap();
+    assert_eq!(copy.access(|_, ws| Ok(ws.this())).unwrap(), forked);
+    copy.close().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn maintenance() {
+    let dir = std::env::temp_dir().join(format!("dust-maintenance-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("test.db").into_os_string().into_string().unwrap();
+    let store = OpenOptions::new().open(&path).unwrap();
+    store
+      .access(|txr, _| {
+        txr.execute("CREATE TABLE scratch (data BLOB)", ())?;
+        for _ in 0..64 {
+          txr.execute("INSERT INTO scratch VALUES (zeroblob(16384))", ())?;
+        }
+        Ok(())
+      })
+      .unwrap();
+    store.commit().unwrap();
+    let stats = store.storage_stats().unwrap();
+    assert_eq!(stats.file_size % stats.page_size, 0);
+    assert!(stats.wal_size > 0);
+    store.access(|txr, _| Ok(txr.execute("DROP TABLE scratch", ())?)).unwrap();
+    assert_eq!(store.storage_stats().unwrap().free_pages, stats.free_pages);
+    store.commit().unwrap();
+    let free_pages = store.storage_stats().unwrap().free_pages;
+    assert!(free_pages > 2 * VACUUM_STEP);
+
+    assert_eq!(store.maintain(Duration::ZERO, u64::MAX).unwrap(), 0);
+    assert_eq!(store.maintain(Duration::MAX, 10).unwrap(), 10);
+    assert_eq!(store.storage_stats().unwrap().free_pages, free_pages - 10);
+    assert_eq!(store.maintain(Duration::MAX, u64::MAX).unwrap(), free_pages - 10);
+    let after = store.storage_stats().unwrap();
+    assert_eq!(after.free_pages, 0);
+    assert!(after.file_size < stats.file_size);
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    store.close().unwrap();
+
+    let store = OpenOptions::new().read_only(true).open(&path).unwrap();
+    assert!(matches!(store.maintain(Duration::MAX, u64::MAX), Err(StoreError::ReadOnly)));
+    assert_eq!(store.storage_stats().unwrap().free_pages, 0);
+    store.close().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rollback_savepoints() {
+    let store = OpenOptions::new().open_in_memory().unwrap();
+    let node = |id| store.access(|txr, ws| ws.node(txr, id)).unwrap();
+    let set_node = |id| store.access(|txr, ws| ws.set_node(txr, id, Some(0))).unwrap();
+    let barrier = || drop(store.access(|txr, ws| ws.barrier(txr)).unwrap());
+    let version = || store.access(|txr, ws| Ok(ws.sync_version(txr))).unwrap();
+    set_node(1);
+    barrier();
+    store.commit().unwrap();
+    let committed = version();
+
+    set_node(2);
+    store.access(|_, ws| ws.discard()).unwrap();
+    barrier();
+    assert_eq!(node(2), None);
+    assert_eq!(version(), committed);
+
+    set_node(2);
+    barrier();
+    set_node(3);
+    assert_eq!(store.savepoint().unwrap(), 1);
+    set_node(4);
+    barrier();
+    assert_eq!(store.savepoint().unwrap(), 2);
+    set_node(5);
+    barrier();
+    assert!(matches!(store.commit(), Err(StoreError::SavepointsOpen(2))));
+    assert_eq!(store.rollback_to_savepoint().unwrap(), 1);
+    assert_eq!((node(4), node(5)), (Some(0), None));
+    assert_eq!(store.release().unwrap(), 0);
+    assert!(matches!(store.release(), Err(StoreError::NoSavepoint)));
+    assert_eq!(node(3), Some(0));
+
+    store.savepoint().unwrap();
+    set_node(6);
+    store.rollback().unwrap();
+    assert_eq!((2..=6).map(node).collect::<Vec<_>>(), [None; 5]);
+    assert_eq!(node(1), Some(0));
+    assert_eq!(version(), committed);
+    store.commit().unwrap();
+  }
+
+  #[test]
+  fn open_options() {
+    let dir = std::env::temp_dir().join(format!("dust-options-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("test.db").into_os_string().into_string().unwrap();
+    let mut options = OpenOptions::new();
+    options.prefix("test").journal_mode(JournalMode::Delete).synchronous(Synchronous::Full).cache_size(-1024);
+
+    let journal_mode = |txr: &Transactor| Ok(txr.query_row("PRAGMA journal_mode", (), |row| row.get::<_, String>(0))?);
+    let store = options.open(&path).unwrap();
+    assert_eq!(store.access(|txr, _| journal_mode(txr)).unwrap(), "delete");
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    store.access(|txr, ws| ws.barrier(txr)).unwrap();
+    store.close().unwrap();
+    // Workspaces are not created in read-only mode.
+    assert!(OpenOptions::new().read_only(true).open(&path).is_err());
+
+    let store = options.read_only(true).open(&path).unwrap();
+    assert!(store.is_read_only());
+    assert_eq!(store.read(|txr, ws| ws.node(txr, 1)).unwrap(), Some(0));
+    assert!(matches!(store.access(|txr, ws| ws.node(txr, 1)), Err(StoreError::ReadOnly)));
+    assert!(matches!(store.commit(), Err(StoreError::ReadOnly)));
+    store.close().unwrap();
+    assert!(options.open(&dir.join("missing.db").into_os_string().into_string().unwrap()).is_err());
+
+    let store = options.open_in_memory().unwrap();
+    assert!(!store.is_read_only());
+    assert_eq!(store.access(|txr, _| journal_mode(txr)).unwrap(), "memory");
+    store.access(|txr, ws| ws.set_node(txr, 1, Some(0))).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
 }
 
 /*
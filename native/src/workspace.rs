@@ -15,25 +15,132 @@
 #![allow(clippy::type_complexity)]
 
 pub mod atom_set;
+pub mod bundle;
 pub mod edge_set;
+pub mod list_set;
 pub mod metadata;
 pub mod node_set;
+pub mod txn_set;
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  ops::Bound,
+};
 
-use self::{atom_set::AtomSet, edge_set::EdgeSet, metadata::WorkspaceMetadata, node_set::NodeSet};
-use crate::{deserialize, ffi::structs::CEventData, serialize, Transactor};
+use self::{
+  atom_set::{AtomSet, AtomSetTransactor},
+  bundle::BundleHeader,
+  edge_set::{EdgeSet, EdgeSetTransactor},
+  list_set::{ListSet, ListSetTransactor},
+  metadata::{
+    drift_limit, now, WorkspaceMetadata, WorkspaceMetadataTransactor, CURRENT_VERSION, OLDEST_COMPATIBLE_VERSION,
+  },
+  node_set::{NodeSet, NodeSetTransactor},
+  txn_set::{TxnSet, TxnSetTransactor},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{deserialize, ffi::structs::CEventData, fnv64_hash, serialize, StoreError};
 
 pub const NODES_NAME: &str = "nodes";
 pub const ATOMS_NAME: &str = "atoms";
 pub const EDGES_NAME: &str = "edges";
+pub const LISTS_NAME: &str = "lists";
+pub const HANDSHAKE_NAME: &str = "handshake";
+pub const RANGES_NAME: &str = "ranges";
+pub const FILTER_NAME: &str = "filter";
+pub const PEER_NAME: &str = "peer";
+pub const RESET_NAME: &str = "reset";
+pub const CLOCKS_NAME: &str = "clocks";
+pub const TXNS_NAME: &str = "txns";
+pub const COMPATIBLE_NAME: &str = "compatible";
+pub const PRISTINE_NAME: &str = "pristine";
+pub const UNFILTERED_NAME: &str = "unfiltered";
+pub const SEEN_NAME: &str = "seen";
+pub const FORKS_NAME: &str = "forks";
+pub const REMOVED_NAME: &str = "removed";
+pub const COMPACTED_NAME: &str = "compacted";
+
+/// Sync payload format version. Peers speaking different versions refuse to sync.
+pub const PROTOCOL_VERSION: u64 = 2;
+
+/// Number of rows fetched at a time when building pages of actions.
+const PAGE_CHUNK: u64 = 256;
+
+/// Default maximum clock drift allowed for remote writes: one hour, in nanoseconds.
+pub const DEFAULT_MAX_DRIFT: u64 = 60 * 60 * 1_000_000_000;
+
+/// Default time for which removals are kept if there are no registered peers:
+/// thirty days, in nanoseconds.
+pub const DEFAULT_RETENTION: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Default time for which items of incomplete transactions are staged: one
+/// day, in nanoseconds.
+pub const DEFAULT_STAGED_TTL: u64 = 24 * 60 * 60 * 1_000_000_000;
 
+/// `bucket -> (lower, upper)`: for each bucket, the range of clock values
+/// whose actions are all included in a page.
+type Ranges = BTreeMap<u64, (Option<u64>, u64)>;
+
+/// `(name, bucket, clock)`: position of the last action included in a page.
+type Cursor = (String, u64, Option<u64>);
+
+/// `(bucket clocks, compacted clocks)`: sent with every page of all actions,
+/// rather than incremental ones, to a peer which may have missed removals that
+/// are no longer stored.
+type Reset = (BTreeMap<u64, u64>, BTreeMap<u64, u64>);
+
+/// Pages of a reset of one structure received so far, see [`Reset`].
 #[derive(Debug, Clone, Default)]
+struct ResetProgress {
+  /// `bucket -> lower -> upper`: clock ranges covered so far.
+  ranges: BTreeMap<u64, BTreeMap<Option<u64>, u64>>,
+  /// `id -> (bucket, clock)`: present local items not received so far.
+  missing: BTreeMap<u128, (u64, u64)>,
+}
+
+impl ResetProgress {
+  /// Returns whether the ranges covered so far include all of `clocks`.
+  fn covers(&self, clocks: &BTreeMap<u64, u64>) -> bool {
+    clocks.iter().all(|(bucket, &upper)| {
+      let mut reached = None;
+      for (&lower, &upper) in self.ranges.get(bucket).into_iter().flatten() {
+        if lower > reached {
+          break;
+        }
+        reached = reached.max(Some(upper));
+      }
+      reached >= Some(upper)
+    })
+  }
+}
+
+/// `(bucket, txn, name, lower, upper)`: one manifest entry of a transaction.
+type TxnEntry = (u64, u64, String, u64, u64);
+
+#[derive(Debug, Clone)]
 pub struct Constraints {
   sticky_nodes: BTreeSet<u64>,
   sticky_atoms: BTreeSet<u64>,
   sticky_edges: BTreeSet<u64>,
   acyclic_edges: BTreeSet<u64>,
+  max_drift: u64,
+  retention: u64,
+  staged_ttl: u64,
+}
+
+impl Default for Constraints {
+  fn default() -> Self {
+    Self {
+      sticky_nodes: BTreeSet::new(),
+      sticky_atoms: BTreeSet::new(),
+      sticky_edges: BTreeSet::new(),
+      acyclic_edges: BTreeSet::new(),
+      max_drift: DEFAULT_MAX_DRIFT,
+      retention: DEFAULT_RETENTION,
+      staged_ttl: DEFAULT_STAGED_TTL,
+    }
+  }
 }
 
 impl Constraints {
@@ -52,79 +159,438 @@ impl Constraints {
   pub fn add_acyclic_edge(&mut self, label: u64) {
     self.acyclic_edges.insert(label);
   }
+  /// Sets how far ahead of the local clock, in nanoseconds, remote writes may
+  /// be. `u64::MAX` disables the check.
+  pub fn set_max_drift(&mut self, max_drift: u64) {
+    self.max_drift = max_drift;
+  }
+  /// Sets how long, in nanoseconds, removals are kept before compaction if
+  /// there are no registered peers. `u64::MAX` keeps them until there are.
+  pub fn set_retention(&mut self, retention: u64) {
+    self.retention = retention;
+  }
+  /// Sets how long, in nanoseconds after it was made, a transaction which has
+  /// not been fully received is staged. After that, the items received so far
+  /// are applied on the next join. `u64::MAX` stages them until complete.
+  pub fn set_staged_ttl(&mut self, staged_ttl: u64) {
+    self.staged_ttl = staged_ttl;
+  }
+}
+
+/// Restricts replication to items with given labels. Removed items are always
+/// replicated, since their labels are no longer known. Labels are assumed to
+/// never change for a given ID.
+///
+/// Filters need not be closed under references: once a replica has synced
+/// with a filter, barriers keep atoms and edges whose endpoint nodes are
+/// missing, since those may have been filtered out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationFilter {
+  nodes: BTreeSet<u64>,
+  atoms: BTreeSet<u64>,
+  edges: BTreeSet<u64>,
+  lists: BTreeSet<u64>,
+}
+
+impl ReplicationFilter {
+  pub fn new() -> Self {
+    Default::default()
+  }
+  pub fn add_node(&mut self, label: u64) {
+    self.nodes.insert(label);
+  }
+  pub fn add_atom(&mut self, label: u64) {
+    self.atoms.insert(label);
+  }
+  pub fn add_edge(&mut self, label: u64) {
+    self.edges.insert(label);
+  }
+  pub fn add_list(&mut self, label: u64) {
+    self.lists.insert(label);
+  }
+  /// Returns the key under which bucket clocks for this filter are tracked.
+  pub fn id(&self) -> u64 {
+    fnv64_hash(serialize(self).unwrap())
+  }
+}
+
+/// A write which lost to a concurrent write to the same item, neither of which
+/// was seen by the author of the other. Each side is `(bucket, clock, value)`,
+/// where a `None` value is a removal. `overwritten` is set if the local write lost.
+///
+/// Each replica reports the conflict when it receives the other write, so the
+/// replica whose write was overwritten learns about it too. Concurrency is
+/// judged from when the author of the winning write had received the other
+/// one, which is only known for writes sent by their author; relayed writes
+/// are reported unless their bucket wrote both.
+///
+/// List items are not covered: they are only inserted and removed, so two
+/// writes to the same item never decide between different values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+  Node {
+    id: u128,
+    winner: (u64, u64, Option<u64>),
+    loser: (u64, u64, Option<u64>),
+    overwritten: bool,
+  },
+  Atom {
+    id: u128,
+    winner: (u64, u64, Option<(u128, u64, Box<[u8]>)>),
+    loser: (u64, u64, Option<(u128, u64, Box<[u8]>)>),
+    overwritten: bool,
+  },
+  Edge {
+    id: u128,
+    winner: (u64, u64, Option<(u128, u64, u128)>),
+    loser: (u64, u64, Option<(u128, u64, u128)>),
+    overwritten: bool,
+  },
+}
+
+/// Summary of a [`Workspace::sync_join`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JoinReport {
+  pub conflicts: Vec<Conflict>,
+  /// `(structure, id, bucket, clock)` of remote writes which were rejected
+  /// for being too far in the future. They are sent again by later syncs, and
+  /// accepted once the local clock has caught up.
+  pub rejected: Vec<(&'static str, u128, u64, u64)>,
+  /// The previous bucket of this replica, if the sender had writes in it which
+  /// were never made here, or knew of another replica writing to it. See
+  /// [`Workspace::fork`].
+  pub forked: Option<u64>,
+}
+
+/// An item which breaks one of the invariants maintained by
+/// [`Workspace::barrier`], see [`Workspace::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+  /// The source node of the atom does not exist (`atom_implies_node`).
+  DanglingAtom { id: u128, src: u128, label: u64 },
+  /// The source or destination node of the edge does not exist
+  /// (`edge_implies_node`).
+  DanglingEdge { id: u128, src: u128, label: u64, dst: u128 },
+  /// The acyclic edge closes a cycle (`acyclic_or_none`).
+  CyclicEdge { id: u128, src: u128, label: u64, dst: u128 },
+}
+
+/// Contents of one structure of a [`Workspace`], see [`Workspace::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructureStats {
+  /// `label -> (items, value bytes)` for saved items. Value bytes are only
+  /// counted for atoms and list items.
+  pub labels: BTreeMap<u64, (u64, u64)>,
+  /// Saved removals which have not been compacted. They keep no label.
+  pub tombstones: u64,
+  /// `bucket -> clock`.
+  pub buckets: BTreeMap<u64, u64>,
+  /// Modifications not yet saved by a barrier.
+  pub pending: u64,
+}
+
+/// Summary of a [`Workspace::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceStats {
+  pub nodes: StructureStats,
+  pub atoms: StructureStats,
+  pub edges: StructureStats,
+  pub lists: StructureStats,
+}
+
+/// One entry of a write batch, see [`Workspace::set_batch`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Write {
+  Node(u128, Option<u64>),
+  Atom(u128, Option<(u128, u64, Box<[u8]>)>),
+  Edge(u128, Option<(u128, u64, u128)>),
 }
 
-#[derive(Debug)]
+/// Database interface for [`Workspace`]. This is implemented by every storage
+/// backend which implements the interfaces of all structures, namely
+/// [`crate::Transactor`] (SQLite) and [`crate::memory::MemoryTransactor`].
+pub trait WorkspaceTransactor:
+  WorkspaceMetadataTransactor
+  + NodeSetTransactor
+  + AtomSetTransactor
+  + EdgeSetTransactor
+  + ListSetTransactor
+  + TxnSetTransactor
+{
+}
+
+impl<T> WorkspaceTransactor for T where
+  T: WorkspaceMetadataTransactor
+    + NodeSetTransactor
+    + AtomSetTransactor
+    + EdgeSetTransactor
+    + ListSetTransactor
+    + TxnSetTransactor
+{
+}
+
+#[derive(Debug, Clone)]
 pub struct Workspace {
   metadata: WorkspaceMetadata,
   constraints: Constraints,
   nodes: NodeSet,
   atoms: AtomSet,
   edges: EdgeSet,
+  lists: ListSet,
+  txns: TxnSet,
+  resets: BTreeMap<&'static str, ResetProgress>,
+  /// Whether pending modifications include a join or a fork, which save
+  /// transaction manifests, staged items and bucket state right away.
+  joined: bool,
 }
 
 impl Workspace {
-  pub fn new(prefix: &'static str, constraints: Constraints, txr: &mut Transactor) -> Self {
-    let metadata = WorkspaceMetadata::new(prefix, txr);
-    let nodes = NodeSet::new(prefix, NODES_NAME, txr);
-    let atoms = AtomSet::new(prefix, ATOMS_NAME, txr);
-    let edges = EdgeSet::new(prefix, EDGES_NAME, txr);
-    Self { metadata, constraints, nodes, atoms, edges }
+  pub fn new(prefix: &str, constraints: Constraints, txr: &mut impl WorkspaceTransactor) -> Result<Self, StoreError> {
+    let metadata = WorkspaceMetadata::new(prefix, txr)?;
+    let nodes = NodeSet::new(prefix, NODES_NAME, txr)?;
+    let atoms = AtomSet::new(prefix, ATOMS_NAME, txr)?;
+    let edges = EdgeSet::new(prefix, EDGES_NAME, txr)?;
+    let lists = ListSet::new(prefix, LISTS_NAME, txr)?;
+    let txns = TxnSet::new(prefix, txr)?;
+    Ok(Self { metadata, constraints, nodes, atoms, edges, lists, txns, resets: BTreeMap::new(), joined: false })
+  }
+
+  /// Returns a copy without pending modifications, which sees the saved state
+  /// when read through another connection. Writing through it is not allowed.
+  pub fn committed(&self) -> Self {
+    Self {
+      metadata: self.metadata.clone(),
+      constraints: self.constraints.clone(),
+      nodes: self.nodes.committed(),
+      atoms: self.atoms.committed(),
+      edges: self.edges.committed(),
+      lists: self.lists.committed(),
+      txns: self.txns.clone(),
+      resets: BTreeMap::new(),
+      joined: false,
+    }
+  }
+
+  /// Drops all pending modifications, i.e. local writes since the last
+  /// barrier.
+  ///
+  /// Fails with [`StoreError::JoinPending`] if a join (see [`Self::sync_join`])
+  /// or a fork (see [`Self::fork`]) ran since the last barrier: they save
+  /// transaction manifests, staged items and bucket state right away, so
+  /// dropping their items would lose them for good. Roll back the transaction
+  /// (see [`crate::store::Store::rollback`]) to undo them instead.
+  pub fn discard(&mut self) -> Result<(), StoreError> {
+    if self.joined {
+      return Err(StoreError::JoinPending);
+    }
+    self.nodes = self.nodes.committed();
+    self.atoms = self.atoms.committed();
+    self.edges = self.edges.committed();
+    self.lists = self.lists.committed();
+    self.resets.clear();
+    Ok(())
   }
 
-  pub fn node(&self, txr: &Transactor, id: u128) -> Option<u64> {
-    self.nodes.get(txr, id).and_then(|(_, _, label)| label)
+  pub fn node(&self, txr: &impl WorkspaceTransactor, id: u128) -> Result<Option<u64>, StoreError> {
+    Ok(self.nodes.get(txr, id)?.and_then(|(_, _, label)| label))
   }
-  pub fn node_id_by_label(&self, txr: &Transactor, label: u64) -> BTreeMap<u128, ()> {
+  pub fn node_id_by_label(&self, txr: &impl WorkspaceTransactor, label: u64) -> Result<BTreeMap<u128, ()>, StoreError> {
     self.nodes.id_by_label(txr, label)
   }
-  pub fn atom(&self, txr: &Transactor, id: u128) -> Option<(u128, u64, Box<[u8]>)> {
-    self.atoms.get(txr, id).and_then(|(_, _, slv)| slv)
+  pub fn atom(&self, txr: &impl WorkspaceTransactor, id: u128) -> Result<Option<(u128, u64, Box<[u8]>)>, StoreError> {
+    Ok(self.atoms.get(txr, id)?.and_then(|(_, _, slv)| slv))
   }
-  pub fn atom_id_label_value_by_src(&self, txr: &Transactor, src: u128) -> BTreeMap<u128, (u64, Box<[u8]>)> {
+  pub fn atom_id_label_value_by_src(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, Box<[u8]>)>, StoreError> {
     self.atoms.id_label_value_by_src(txr, src)
   }
-  pub fn atom_id_value_by_src_label(&self, txr: &Transactor, src: u128, label: u64) -> BTreeMap<u128, Box<[u8]>> {
+  pub fn atom_id_value_by_src_label(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, Box<[u8]>>, StoreError> {
     self.atoms.id_value_by_src_label(txr, src, label)
   }
-  pub fn atom_id_src_value_by_label(&self, txr: &Transactor, label: u64) -> BTreeMap<u128, (u128, Box<[u8]>)> {
+  pub fn atom_id_src_value_by_label(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    label: u64,
+  ) -> Result<BTreeMap<u128, (u128, Box<[u8]>)>, StoreError> {
     self.atoms.id_src_value_by_label(txr, label)
   }
-  pub fn atom_id_src_by_label_value(&self, txr: &Transactor, label: u64, value: &[u8]) -> BTreeMap<u128, u128> {
+  pub fn atom_id_src_by_label_value(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    label: u64,
+    value: &[u8],
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
     self.atoms.id_src_by_label_value(txr, label, value)
   }
-  pub fn edge(&self, txr: &Transactor, id: u128) -> Option<(u128, u64, u128)> {
-    self.edges.get(txr, id).and_then(|(_, _, sld)| sld)
+  /// Returns atoms with `label` and a value between `lower` and `upper`,
+  /// ordered by value and then ID, see
+  /// [`AtomSet::id_src_value_by_label_range`]. Values are compared as bytes.
+  pub fn atom_id_src_value_by_label_range(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    label: u64,
+    lower: Bound<&[u8]>,
+    upper: Bound<&[u8]>,
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError> {
+    self.atoms.id_src_value_by_label_range(txr, label, lower, upper, offset, limit)
+  }
+  /// Like [`Self::atom_id_src_value_by_label_range`], for values starting with `prefix`.
+  pub fn atom_id_src_value_by_label_prefix(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    label: u64,
+    prefix: &[u8],
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError> {
+    let upper = prefix_end(prefix);
+    let upper = upper.as_deref().map_or(Bound::Unbounded, Bound::Excluded);
+    self.atoms.id_src_value_by_label_range(txr, label, Bound::Included(prefix), upper, offset, limit)
   }
-  pub fn edge_id_label_dst_by_src(&self, txr: &Transactor, src: u128) -> BTreeMap<u128, (u64, u128)> {
+  pub fn edge(&self, txr: &impl WorkspaceTransactor, id: u128) -> Result<Option<(u128, u64, u128)>, StoreError> {
+    Ok(self.edges.get(txr, id)?.and_then(|(_, _, sld)| sld))
+  }
+  pub fn edge_id_label_dst_by_src(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, u128)>, StoreError> {
     self.edges.id_label_dst_by_src(txr, src)
   }
-  pub fn edge_id_dst_by_src_label(&self, txr: &Transactor, src: u128, label: u64) -> BTreeMap<u128, u128> {
+  pub fn edge_id_dst_by_src_label(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
     self.edges.id_dst_by_src_label(txr, src, label)
   }
-  pub fn edge_id_src_label_by_dst(&self, txr: &Transactor, dst: u128) -> BTreeMap<u128, (u128, u64)> {
+  pub fn edge_id_src_label_by_dst(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    dst: u128,
+  ) -> Result<BTreeMap<u128, (u128, u64)>, StoreError> {
     self.edges.id_src_label_by_dst(txr, dst)
   }
-  pub fn edge_id_src_by_dst_label(&self, txr: &Transactor, dst: u128, label: u64) -> BTreeMap<u128, u128> {
+  pub fn edge_id_src_by_dst_label(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    dst: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
     self.edges.id_src_by_dst_label(txr, dst, label)
   }
+  pub fn list_id_value_by_src_label(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    src: u128,
+    label: u64,
+  ) -> Result<Vec<(u128, Box<[u8]>)>, StoreError> {
+    self.lists.id_value_by_src_label(txr, src, label)
+  }
 
-  pub fn set_node(&mut self, txr: &Transactor, id: u128, label: Option<u64>) {
+  pub fn set_node(&mut self, txr: &impl WorkspaceTransactor, id: u128, label: Option<u64>) -> Result<(), StoreError> {
     let this = self.metadata.this();
     let next = self.nodes.next();
-    assert!(self.nodes.set(txr, id, this, next, label));
+    if !self.nodes.set(txr, id, this, next, label)? {
+      return Err(StoreError::StaleClock(next));
+    }
+    Ok(())
   }
 
-  pub fn set_atom(&mut self, txr: &Transactor, id: u128, slv: Option<(u128, u64, Box<[u8]>)>) {
+  pub fn set_atom(
+    &mut self,
+    txr: &impl WorkspaceTransactor,
+    id: u128,
+    slv: Option<(u128, u64, Box<[u8]>)>,
+  ) -> Result<(), StoreError> {
     let this = self.metadata.this();
     let next = self.atoms.next();
-    assert!(self.atoms.set(txr, id, this, next, slv));
+    if !self.atoms.set(txr, id, this, next, slv)? {
+      return Err(StoreError::StaleClock(next));
+    }
+    Ok(())
   }
 
-  pub fn set_edge(&mut self, txr: &Transactor, id: u128, sld: Option<(u128, u64, u128)>) {
+  pub fn set_edge(
+    &mut self,
+    txr: &impl WorkspaceTransactor,
+    id: u128,
+    sld: Option<(u128, u64, u128)>,
+  ) -> Result<(), StoreError> {
     let this = self.metadata.this();
     let next = self.edges.next();
-    assert!(self.edges.set(txr, id, this, next, sld));
+    if !self.edges.set(txr, id, this, next, sld)? {
+      return Err(StoreError::StaleClock(next));
+    }
+    Ok(())
+  }
+
+  /// Applies a serialized `Vec<Write>` as if by the corresponding `set_*`
+  /// calls, and returns the number of writes. Nothing is applied if the batch
+  /// is malformed.
+  pub fn set_batch(&mut self, txr: &impl WorkspaceTransactor, batch: &[u8]) -> Result<usize, StoreError> {
+    let writes: Vec<Write> = deserialize(batch).map_err(|err| StoreError::MalformedBatch(err.to_string()))?;
+    let len = writes.len();
+    for write in writes {
+      match write {
+        Write::Node(id, label) => self.set_node(txr, id, label)?,
+        Write::Atom(id, slv) => self.set_atom(txr, id, slv)?,
+        Write::Edge(id, sld) => self.set_edge(txr, id, sld)?,
+      }
+    }
+    Ok(len)
+  }
+
+  /// Inserts a new item into the list `(src, label)`, right after the item
+  /// `left` (or at the beginning if `left` is `None`).
+  pub fn insert_list_item(
+    &mut self,
+    txr: &impl WorkspaceTransactor,
+    id: u128,
+    src: u128,
+    label: u64,
+    left: Option<u128>,
+    value: Box<[u8]>,
+  ) -> Result<(), StoreError> {
+    if self.lists.get(txr, id)?.is_some() {
+      return Err(StoreError::DuplicateListItem(id));
+    }
+    let order = self.lists.id_by_src_label(txr, src, label)?;
+    let index = match left {
+      None => 0,
+      Some(left) => order.iter().position(|x| *x == left).ok_or(StoreError::UnknownListItem(left))? + 1,
+    };
+    let right = order.get(index).copied();
+    let this = self.metadata.this();
+    let next = self.lists.next();
+    if !self.lists.set(txr, id, this, next, (src, label, left, right), Some(value))? {
+      return Err(StoreError::StaleClock(next));
+    }
+    Ok(())
+  }
+
+  /// Removes an item from its list. The item is kept as a placeholder, so that
+  /// concurrent insertions around it can still be ordered.
+  pub fn remove_list_item(&mut self, txr: &impl WorkspaceTransactor, id: u128) -> Result<(), StoreError> {
+    if let Some((_, _, pos, Some(_))) = self.lists.get(txr, id)? {
+      let this = self.metadata.this();
+      let next = self.lists.next();
+      if !self.lists.set(txr, id, this, next, pos, None)? {
+        return Err(StoreError::StaleClock(next));
+      }
+    }
+    Ok(())
   }
 
   /// Issues write-read barrier: goes through all recent modifications,
@@ -133,11 +599,17 @@ impl Workspace {
   /// 1. `atom_implies_node`: all atoms must start from a node.
   /// 2. `edge_implies_node`: all edges must start from and ends at nodes.
   /// 3. `sticky_or_none`: for each node, if it has "sticky" atoms or edges
-  ///     attached to it at the previous barrier, those must be preserved,
-  ///     otherwise the node must be removed.
+  ///    attached to it at the previous barrier, those must be preserved,
+  ///    otherwise the node must be removed.
   /// 4. `acyclic_or_none`: edges marked as "acyclic" cannot form cycles,
-  ///     otherwise some edges must be removed to break the cycle.
-  pub fn barrier(&mut self, txr: &mut Transactor) -> Vec<CEventData> {
+  ///    otherwise some edges must be removed to break the cycle.
+  ///
+  /// List items are not required to start from a node, but are removed
+  /// together with the node they start from.
+  ///
+  /// On replicas which have synced with a filter, atoms and edges whose nodes
+  /// are missing are kept, since those nodes may have been filtered out.
+  pub fn barrier(&mut self, txr: &mut impl WorkspaceTransactor) -> Result<Vec<CEventData>, StoreError> {
     // Assuming all conditions were true before any of the modifications,
     // we only need to focus on changes which cause violations.
 
@@ -147,6 +619,8 @@ impl Workspace {
     let mut atoms = BTreeSet::<u128>::new();
     // The set of edges which definitely violate (2) or (4).
     let mut edges = BTreeSet::<u128>::new();
+    // Whether missing nodes may have been filtered out, rather than removed.
+    let filtered = self.nodes.is_filtered();
 
     for (id, prev, curr) in self.nodes.mods() {
       if let Some(label) = prev {
@@ -168,7 +642,7 @@ impl Workspace {
         }
       }
       if let Some((src, _, _)) = curr {
-        if !self.nodes.exists(txr, src) {
+        if !filtered && !self.nodes.exists(txr, src)? {
           atoms.insert(id); // `curr` exists, `src` node does not exist (1)
         }
       }
@@ -183,9 +657,9 @@ impl Workspace {
         }
       }
       if let Some((src, label, dst)) = curr {
-        if !(self.nodes.exists(txr, src) && self.nodes.exists(txr, dst))
+        if !(filtered || (self.nodes.exists(txr, src)? && self.nodes.exists(txr, dst)?))
           || (self.constraints.acyclic_edges.contains(&label)
-            && self.reachable(txr, label, dst, src, &mut BTreeSet::new()))
+            && self.reachable(txr, label, dst, src, &mut BTreeSet::new())?)
         {
           edges.insert(id); // `curr` exists, `src` or `dst` node does not exist (2) or cyclic (4)
           if self.constraints.sticky_edges.contains(&label) {
@@ -196,27 +670,32 @@ impl Workspace {
     }
 
     while let Some(id) = atoms.pop_first() {
-      self.set_atom(txr, id, None);
+      self.set_atom(txr, id, None)?;
     }
     while let Some(id) = edges.pop_first() {
-      self.set_edge(txr, id, None);
+      self.set_edge(txr, id, None)?;
     }
     while let Some(id) = nodes.pop_first() {
-      if self.nodes.exists(txr, id) {
-        self.set_node(txr, id, None);
+      if self.nodes.exists(txr, id)? {
+        self.set_node(txr, id, None)?;
       }
-      for (atom, _) in self.atom_id_label_value_by_src(txr, id) {
-        self.set_atom(txr, atom, None);
+      for (atom, _) in self.atom_id_label_value_by_src(txr, id)? {
+        self.set_atom(txr, atom, None)?;
       }
-      for (edge, _) in self.edge_id_label_dst_by_src(txr, id) {
-        self.set_edge(txr, edge, None);
+      for (edge, _) in self.edge_id_label_dst_by_src(txr, id)? {
+        self.set_edge(txr, edge, None)?;
       }
-      for (edge, (src, label)) in self.edge_id_src_label_by_dst(txr, id) {
-        self.set_edge(txr, edge, None);
+      for (edge, (src, label)) in self.edge_id_src_label_by_dst(txr, id)? {
+        self.set_edge(txr, edge, None)?;
         if self.constraints.sticky_edges.contains(&label) {
           nodes.insert(src); // `curr` is sticky, `curr` is removed
         }
       }
+      for (item, (_, _, _, value)) in self.lists.by_src(txr, id)? {
+        if value.is_some() {
+          self.remove_list_item(txr, item)?;
+        }
+      }
     }
 
     // Collect all modifications.
@@ -230,104 +709,1584 @@ impl Workspace {
     for (id, prev, curr) in self.edges.mods() {
       res.push(CEventData::Edge { id: id.into(), prev: prev.map(Into::into).into(), curr: curr.map(Into::into).into() })
     }
+    for (id, prev, curr) in self.lists.mods() {
+      res.push(CEventData::List { id: id.into(), prev: prev.map(Into::into).into(), curr: curr.map(Into::into).into() })
+    }
+
+    // Record which of our own writes belong together, so that peers apply them atomically.
+    let this = self.metadata.this();
+    let pending = [
+      (NODES_NAME, self.nodes.pending(this)),
+      (ATOMS_NAME, self.atoms.pending(this)),
+      (EDGES_NAME, self.edges.pending(this)),
+      (LISTS_NAME, self.lists.pending(this)),
+    ];
+    if pending.iter().map(|(_, clocks)| clocks.len()).sum::<usize>() > 1 {
+      let txn = pending.iter().flat_map(|(_, clocks)| clocks).copied().max().unwrap();
+      for (name, clocks) in pending {
+        if let (Some(&lower), Some(&upper)) = (clocks.iter().min(), clocks.iter().max()) {
+          self.txns.record(txr, this, txn, name, lower, upper)?;
+        }
+      }
+    }
 
     // Apply and save all modifications.
-    self.nodes.save(txr);
-    self.atoms.save(txr);
-    self.edges.save(txr);
+    self.nodes.save(txr)?;
+    self.atoms.save(txr)?;
+    self.edges.save(txr)?;
+    self.lists.save(txr)?;
+    self.joined = false;
 
-    res
+    Ok(res)
   }
 
   /// Used in checking acyclicity constraints.
-  fn reachable(&self, txr: &Transactor, label: u64, src: u128, dst: u128, v: &mut BTreeSet<u128>) -> bool {
+  fn reachable(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    label: u64,
+    src: u128,
+    dst: u128,
+    v: &mut BTreeSet<u128>,
+  ) -> Result<bool, StoreError> {
     if src == dst {
-      return true;
+      return Ok(true);
     }
     v.insert(src);
-    for (_, next) in self.edge_id_dst_by_src_label(txr, src, label) {
-      if !v.contains(&next) && self.reachable(txr, label, next, dst, v) {
-        return true;
+    for (_, next) in self.edge_id_dst_by_src_label(txr, src, label)? {
+      if !v.contains(&next) && self.reachable(txr, label, next, dst, v)? {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+
+  /// Returns whether nothing has been written to or joined into this replica yet.
+  /// A pristine replica adopts the workspace ID of the first peer it joins.
+  pub fn is_pristine(&self) -> bool {
+    self.nodes.is_pristine() && self.atoms.is_pristine() && self.edges.is_pristine() && self.lists.is_pristine()
+  }
+
+  /// Returns whether there are pending modifications.
+  pub fn is_modified(&self) -> bool {
+    !(self.nodes.mods().is_empty()
+      && self.atoms.mods().is_empty()
+      && self.edges.mods().is_empty()
+      && self.lists.mods().is_empty())
+  }
+
+  /// Returns this replica's ID, which is also the bucket of its own writes.
+  pub fn this(&self) -> u64 {
+    self.metadata.this()
+  }
+
+  /// Returns the registered peers.
+  pub fn peers(&self) -> Vec<u64> {
+    self.metadata.peers()
+  }
+
+  /// Registers a peer. Removed items are only compacted after all registered
+  /// peers have acknowledged them.
+  pub fn add_peer(&mut self, txr: &mut impl WorkspaceTransactor, peer: u64) -> Result<(), StoreError> {
+    self.metadata.add_peer(txr, peer)
+  }
+
+  /// Unregisters a peer, e.g. one which has not synced for a long time. If it
+  /// comes back, it gets a full resync.
+  pub fn remove_peer(&mut self, txr: &mut impl WorkspaceTransactor, peer: u64) -> Result<(), StoreError> {
+    self.metadata.remove_peer(txr, peer)
+  }
+
+  /// Records the version sent by a remote peer as acknowledged, if the peer is
+  /// registered. Call this with versions received from peers, e.g. together
+  /// with [`Self::sync_actions`].
+  pub fn acknowledge(&mut self, txr: &mut impl WorkspaceTransactor, version: &[u8]) -> Result<(), StoreError> {
+    let all: BTreeMap<String, &[u8]> = decode(version, "version")?;
+    let id = Self::check_handshake(&all)?;
+    let peer: Option<u64> = all.get(PEER_NAME).map(|m| decode(m, PEER_NAME)).transpose()?;
+    let nodes_version: BTreeMap<u64, u64> = decode_entry(&all, NODES_NAME)?;
+    let atoms_version: BTreeMap<u64, u64> = decode_entry(&all, ATOMS_NAME)?;
+    let edges_version: BTreeMap<u64, u64> = decode_entry(&all, EDGES_NAME)?;
+    let lists_version: BTreeMap<u64, u64> = decode_entry(&all, LISTS_NAME)?;
+    if let Some(peer) = peer.filter(|_| id == self.metadata.id()) {
+      self.metadata.acknowledge(txr, peer, NODES_NAME, &nodes_version)?;
+      self.metadata.acknowledge(txr, peer, ATOMS_NAME, &atoms_version)?;
+      self.metadata.acknowledge(txr, peer, EDGES_NAME, &edges_version)?;
+      self.metadata.acknowledge(txr, peer, LISTS_NAME, &lists_version)?;
+    }
+    Ok(())
+  }
+
+  /// Physically deletes removed nodes, atoms, edges and list items which all
+  /// registered peers have acknowledged. If there are no registered peers,
+  /// removals older than the retention period are deleted instead, see
+  /// [`Constraints::set_retention`]. Removed list items are only deleted once
+  /// no other item refers to them as an origin, since they are needed for
+  /// ordering. Only saved modifications are compacted, so call this after a
+  /// barrier. Transaction manifests which all registered peers have
+  /// acknowledged are dropped as well.
+  ///
+  /// Returns the number of deleted items.
+  pub fn compact(&mut self, txr: &mut impl WorkspaceTransactor) -> Result<u64, StoreError> {
+    let mut res = 0;
+    let retained = now().saturating_sub(self.constraints.retention);
+    let horizon = |name, buckets: BTreeMap<u64, u64>| -> BTreeMap<u64, u64> {
+      let Some(mut horizon) = self.metadata.horizon(name) else {
+        return buckets.into_iter().map(|(bucket, clock)| (bucket, clock.min(retained))).collect();
+      };
+      horizon.retain(|bucket, clock| {
+        buckets.get(bucket).is_some_and(|&own| {
+          *clock = (*clock).min(own);
+          true
+        })
+      });
+      horizon
+    };
+    let nodes_horizon = horizon(NODES_NAME, self.nodes.buckets());
+    let atoms_horizon = horizon(ATOMS_NAME, self.atoms.buckets());
+    let edges_horizon = horizon(EDGES_NAME, self.edges.buckets());
+    let lists_horizon = horizon(LISTS_NAME, self.lists.buckets());
+    // Peers have all of our writes up to the horizon, so they no longer need
+    // to know what was seen before them.
+    let this = self.metadata.this();
+    if let Some(&clock) = nodes_horizon.get(&this) {
+      self.nodes.forget(txr, clock)?;
+    }
+    if let Some(&clock) = atoms_horizon.get(&this) {
+      self.atoms.forget(txr, clock)?;
+    }
+    if let Some(&clock) = edges_horizon.get(&this) {
+      self.edges.forget(txr, clock)?;
+    }
+    if let Some(&clock) = lists_horizon.get(&this) {
+      self.lists.forget(txr, clock)?;
+    }
+    for (bucket, clock) in nodes_horizon {
+      res += self.nodes.compact(txr, bucket, clock)?;
+      self.txns.prune(txr, bucket, NODES_NAME, clock)?;
+    }
+    for (bucket, clock) in atoms_horizon {
+      res += self.atoms.compact(txr, bucket, clock)?;
+      self.txns.prune(txr, bucket, ATOMS_NAME, clock)?;
+    }
+    for (bucket, clock) in edges_horizon {
+      res += self.edges.compact(txr, bucket, clock)?;
+      self.txns.prune(txr, bucket, EDGES_NAME, clock)?;
+    }
+    for (bucket, clock) in lists_horizon {
+      res += self.lists.compact(txr, bucket, clock)?;
+      self.txns.prune(txr, bucket, LISTS_NAME, clock)?;
+    }
+    Ok(res)
+  }
+
+  /// Scans all nodes, atoms and edges, including pending modifications, for
+  /// violations of the invariants which [`Self::barrier`] maintains, e.g. in
+  /// data written by older versions. Sticky constraints only restrict changes,
+  /// so they cannot be checked on a given state.
+  ///
+  /// Of each cycle of acyclic edges, only the edges which [`Self::repair`]
+  /// would remove are reported.
+  pub fn check(&self, txr: &impl WorkspaceTransactor) -> Result<Vec<Violation>, StoreError> {
+    let mut nodes = BTreeSet::new();
+    for bucket in self.nodes.buckets().into_keys() {
+      let mut lower = None;
+      loop {
+        let chunk = self.nodes.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+        let Some((_, last)) = chunk.last() else { break };
+        lower = Some(last.1);
+        for (id, (_, _, l)) in chunk {
+          if l.is_some() {
+            nodes.insert(id);
+          }
+        }
+      }
+    }
+    let mut res = Vec::new();
+    for bucket in self.atoms.buckets().into_keys() {
+      let mut lower = None;
+      loop {
+        let chunk = self.atoms.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+        let Some((_, last)) = chunk.last() else { break };
+        lower = Some(last.1);
+        for (id, (_, _, slv)) in chunk {
+          if let Some((src, label, _)) = slv.filter(|(src, _, _)| !nodes.contains(src)) {
+            res.push(Violation::DanglingAtom { id, src, label });
+          }
+        }
+      }
+    }
+    // `label -> src -> [(id, dst)]`, for acyclic edges between existing nodes.
+    let mut graphs = BTreeMap::<u64, BTreeMap<u128, Vec<(u128, u128)>>>::new();
+    for bucket in self.edges.buckets().into_keys() {
+      let mut lower = None;
+      loop {
+        let chunk = self.edges.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+        let Some((_, last)) = chunk.last() else { break };
+        lower = Some(last.1);
+        for (id, (_, _, sld)) in chunk {
+          let Some((src, label, dst)) = sld else { continue };
+          if !(nodes.contains(&src) && nodes.contains(&dst)) {
+            res.push(Violation::DanglingEdge { id, src, label, dst });
+          } else if self.constraints.acyclic_edges.contains(&label) {
+            graphs.entry(label).or_default().entry(src).or_default().push((id, dst));
+          }
+        }
+      }
+    }
+    for (label, graph) in graphs {
+      for (id, src, dst) in back_edges(&graph) {
+        res.push(Violation::CyclicEdge { id, src, label, dst });
+      }
+    }
+    Ok(res)
+  }
+
+  /// Removes the items reported by [`Self::check`], together with the source
+  /// nodes of removed sticky edges, as a barrier would have done. The removals
+  /// are pending modifications, so call [`Self::barrier`] next, which also
+  /// removes whatever was attached to the removed nodes.
+  ///
+  /// Returns the violations which were found.
+  pub fn repair(&mut self, txr: &impl WorkspaceTransactor) -> Result<Vec<Violation>, StoreError> {
+    let res = self.check(txr)?;
+    for violation in &res {
+      match *violation {
+        Violation::DanglingAtom { id, .. } => self.set_atom(txr, id, None)?,
+        Violation::DanglingEdge { id, src, label, .. } | Violation::CyclicEdge { id, src, label, .. } => {
+          self.set_edge(txr, id, None)?;
+          if self.constraints.sticky_edges.contains(&label) && self.nodes.exists(txr, src)? {
+            self.set_node(txr, src, None)?;
+          }
+        }
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns what is stored in each structure, for diagnostics. This scans all
+  /// saved nodes, atoms and edges.
+  pub fn stats(&self, txr: &impl WorkspaceTransactor) -> Result<WorkspaceStats, StoreError> {
+    let stats = |mut labels: BTreeMap<Option<u64>, (u64, u64)>, buckets, pending: usize| StructureStats {
+      tombstones: labels.remove(&None).map_or(0, |(items, _)| items),
+      labels: labels.into_iter().filter_map(|(label, counts)| Some((label?, counts))).collect(),
+      buckets,
+      pending: pending as u64,
+    };
+    Ok(WorkspaceStats {
+      nodes: stats(self.nodes.count_by_label(txr)?, self.nodes.buckets(), self.nodes.mods().len()),
+      atoms: stats(self.atoms.count_by_label(txr)?, self.atoms.buckets(), self.atoms.mods().len()),
+      edges: stats(self.edges.count_by_label(txr)?, self.edges.buckets(), self.edges.mods().len()),
+      lists: stats(self.lists.count_by_label(txr)?, self.lists.buckets(), self.lists.mods().len()),
+    })
+  }
+
+  /// Returns the live state, without removed nodes, atoms and edges, together
+  /// with the bucket clocks. A new replica can install this with
+  /// [`Self::install_snapshot`] instead of replaying the whole history, then
+  /// continue with incremental syncs.
+  pub fn snapshot(&self, txr: &impl WorkspaceTransactor) -> Result<Box<[u8]>, StoreError> {
+    Ok(self.snapshot_page(txr, None, u64::MAX)?.0)
+  }
+
+  /// Returns a page of [`Self::snapshot`] starting after `cursor`, of around
+  /// `budget` bytes (at least one item is always included). Also returns the
+  /// cursor for the next page, or `None` if this is the last one.
+  ///
+  /// Removals which have not been compacted yet are included, so that peers
+  /// behind this replica can still receive them from the new one. Bucket
+  /// clocks are taken when the first page is made, carried along in the
+  /// cursor and only sent with the last page, so that writes made while
+  /// paging are received by the incremental syncs which follow.
+  ///
+  /// To keep backward compatibility, do not change existing strings and type
+  /// annotations below. Additional entries may be added.
+  pub fn snapshot_page(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    cursor: Option<&[u8]>,
+    budget: u64,
+  ) -> Result<(Box<[u8]>, Option<Box<[u8]>>), StoreError> {
+    let (cursor, clocks): (Option<Cursor>, BTreeMap<String, BTreeMap<u64, u64>>) = match cursor {
+      Some(cursor) => decode::<(Cursor, _)>(cursor, "cursor").map(|(cursor, clocks)| (Some(cursor), clocks))?,
+      None => (None, self.snapshot_clocks(txr)?),
+    };
+
+    let mut page = Page::new(cursor, budget);
+    let handshake: (u64, u64, u128) = self.handshake();
+    let uncompacted = |compacted: &BTreeMap<u64, u64>, bucket: u64, clock: u64| Some(&clock) > compacted.get(&bucket);
+    let nodes_items: BTreeMap<u128, (u64, u64, Option<u64>)> = page.collect(
+      NODES_NAME,
+      self.nodes.buckets(),
+      BTreeMap::new(),
+      |bucket, lower, limit| self.nodes.bucket_actions(txr, bucket, lower, limit),
+      |item| item.1,
+      |item| item.2.is_some() || uncompacted(self.nodes.compacted(), item.0, item.1),
+    )?;
+    let atoms_items: BTreeMap<u128, (u64, u64, Option<(u128, u64, Box<[u8]>)>)> = page.collect(
+      ATOMS_NAME,
+      self.atoms.buckets(),
+      BTreeMap::new(),
+      |bucket, lower, limit| self.atoms.bucket_actions(txr, bucket, lower, limit),
+      |item| item.1,
+      |item| item.2.is_some() || uncompacted(self.atoms.compacted(), item.0, item.1),
+    )?;
+    let edges_items: BTreeMap<u128, (u64, u64, Option<(u128, u64, u128)>)> = page.collect(
+      EDGES_NAME,
+      self.edges.buckets(),
+      BTreeMap::new(),
+      |bucket, lower, limit| self.edges.bucket_actions(txr, bucket, lower, limit),
+      |item| item.1,
+      |item| item.2.is_some() || uncompacted(self.edges.compacted(), item.0, item.1),
+    )?;
+    // Removed list items are kept, since they are needed for ordering.
+    let lists: BTreeMap<u128, (u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>)> = page.collect(
+      LISTS_NAME,
+      self.lists.buckets(),
+      BTreeMap::new(),
+      |bucket, lower, limit| self.lists.bucket_actions(txr, bucket, lower, limit),
+      |item| item.1,
+      |_| true,
+    )?;
+
+    let mut removed: BTreeMap<&str, BTreeMap<u128, (u64, u64)>> = BTreeMap::new();
+    let mut nodes: BTreeMap<u128, (u64, u64, u64)> = BTreeMap::new();
+    for (id, (bucket, clock, l)) in nodes_items {
+      match l {
+        Some(l) => {
+          nodes.insert(id, (bucket, clock, l));
+        }
+        None => {
+          removed.entry(NODES_NAME).or_default().insert(id, (bucket, clock));
+        }
+      }
+    }
+    let mut atoms: BTreeMap<u128, (u64, u64, (u128, u64, Box<[u8]>))> = BTreeMap::new();
+    for (id, (bucket, clock, slv)) in atoms_items {
+      match slv {
+        Some(slv) => {
+          atoms.insert(id, (bucket, clock, slv));
+        }
+        None => {
+          removed.entry(ATOMS_NAME).or_default().insert(id, (bucket, clock));
+        }
+      }
+    }
+    let mut edges: BTreeMap<u128, (u64, u64, (u128, u64, u128))> = BTreeMap::new();
+    for (id, (bucket, clock, sld)) in edges_items {
+      match sld {
+        Some(sld) => {
+          edges.insert(id, (bucket, clock, sld));
+        }
+        None => {
+          removed.entry(EDGES_NAME).or_default().insert(id, (bucket, clock));
+        }
+      }
+    }
+
+    let mut all: BTreeMap<&str, Vec<u8>> = BTreeMap::from([
+      (NODES_NAME, serialize(&nodes).unwrap()),
+      (ATOMS_NAME, serialize(&atoms).unwrap()),
+      (EDGES_NAME, serialize(&edges).unwrap()),
+      (LISTS_NAME, serialize(&lists).unwrap()),
+      (HANDSHAKE_NAME, serialize(&handshake).unwrap()),
+      (COMPATIBLE_NAME, serialize(&OLDEST_COMPATIBLE_VERSION).unwrap()),
+      (REMOVED_NAME, serialize(&removed).unwrap()),
+    ]);
+    match page.cursor {
+      Some(cursor) => Ok((serialize(&all).unwrap().into(), Some(serialize(&(cursor, clocks)).unwrap().into()))),
+      None => {
+        let compacted: BTreeMap<&str, &BTreeMap<u64, u64>> = BTreeMap::from([
+          (NODES_NAME, self.nodes.compacted()),
+          (ATOMS_NAME, self.atoms.compacted()),
+          (EDGES_NAME, self.edges.compacted()),
+          (LISTS_NAME, self.lists.compacted()),
+        ]);
+        all.insert(CLOCKS_NAME, serialize(&clocks).unwrap());
+        all.insert(COMPACTED_NAME, serialize(&compacted).unwrap());
+        Ok((serialize(&all).unwrap().into(), None))
+      }
+    }
+  }
+
+  /// Returns the bucket clocks sent with a snapshot. Staged items are not
+  /// included in snapshots, so they are received again by incremental syncs.
+  fn snapshot_clocks(
+    &self,
+    txr: &impl WorkspaceTransactor,
+  ) -> Result<BTreeMap<String, BTreeMap<u64, u64>>, StoreError> {
+    let clocks = |name, mut buckets: BTreeMap<u64, u64>| -> Result<BTreeMap<u64, u64>, StoreError> {
+      for (bucket, lower) in self.txns.staged_lower(txr, name)? {
+        buckets.entry(bucket).and_modify(|clock| *clock = (*clock).min(lower.saturating_sub(1)));
+      }
+      Ok(buckets)
+    };
+    Ok(BTreeMap::from([
+      (NODES_NAME.to_owned(), clocks(NODES_NAME, self.nodes.buckets())?),
+      (ATOMS_NAME.to_owned(), clocks(ATOMS_NAME, self.atoms.buckets())?),
+      (EDGES_NAME.to_owned(), clocks(EDGES_NAME, self.edges.buckets())?),
+      (LISTS_NAME.to_owned(), clocks(LISTS_NAME, self.lists.buckets())?),
+    ]))
+  }
+
+  /// Installs a snapshot made by [`Self::snapshot`], or one page of it, into
+  /// this replica, which must be pristine. Items are written directly,
+  /// without a barrier, so no events are emitted. Pages may be installed in
+  /// any order; the replica stays pristine until the last one is installed.
+  ///
+  /// The compaction horizon of the sender is taken over, so peers which are
+  /// behind it get a full resync from this replica, like from the sender.
+  pub fn install_snapshot(&mut self, txr: &mut impl WorkspaceTransactor, snapshot: &[u8]) -> Result<(), StoreError> {
+    let all: BTreeMap<String, &[u8]> = decode(snapshot, "snapshot")?;
+    let id = Self::check_handshake(&all)?;
+    let nodes: BTreeMap<u128, (u64, u64, u64)> = decode_entry(&all, NODES_NAME)?;
+    let atoms: BTreeMap<u128, (u64, u64, (u128, u64, Box<[u8]>))> = decode_entry(&all, ATOMS_NAME)?;
+    let edges: BTreeMap<u128, (u64, u64, (u128, u64, u128))> = decode_entry(&all, EDGES_NAME)?;
+    let lists: BTreeMap<u128, (u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>)> =
+      decode_entry(&all, LISTS_NAME)?;
+    let mut removed: BTreeMap<String, BTreeMap<u128, (u64, u64)>> = decode_entry(&all, REMOVED_NAME)?;
+    let clocks: Option<BTreeMap<String, BTreeMap<u64, u64>>> =
+      all.get(CLOCKS_NAME).map(|m| decode(m, CLOCKS_NAME)).transpose()?;
+    let compacted: Option<BTreeMap<String, BTreeMap<u64, u64>>> =
+      all.get(COMPACTED_NAME).map(|m| decode(m, COMPACTED_NAME)).transpose()?;
+
+    if !self.is_pristine() {
+      return Err(StoreError::NotEmpty);
+    }
+    if id != self.metadata.id() {
+      self.metadata.set_id(txr, id)?;
+    }
+
+    for (id, (bucket, clock, l)) in nodes {
+      self.nodes.merge(txr, id, bucket, clock, Some(l))?;
+    }
+    for (id, (bucket, clock)) in removed.remove(NODES_NAME).unwrap_or_default() {
+      self.nodes.merge(txr, id, bucket, clock, None)?;
+    }
+    for (id, (bucket, clock, slv)) in atoms {
+      self.atoms.merge(txr, id, bucket, clock, Some(slv))?;
+    }
+    for (id, (bucket, clock)) in removed.remove(ATOMS_NAME).unwrap_or_default() {
+      self.atoms.merge(txr, id, bucket, clock, None)?;
+    }
+    for (id, (bucket, clock, sld)) in edges {
+      self.edges.merge(txr, id, bucket, clock, Some(sld))?;
+    }
+    for (id, (bucket, clock)) in removed.remove(EDGES_NAME).unwrap_or_default() {
+      self.edges.merge(txr, id, bucket, clock, None)?;
+    }
+    for (id, (bucket, clock, pos, value)) in lists {
+      self.lists.merge(txr, id, bucket, clock, pos, value)?;
+    }
+    if let Some(mut clocks) = clocks {
+      // Older snapshots hold no removed nodes, atoms or edges at all, so
+      // everything they cover counts as compacted.
+      let mut compacted = compacted.unwrap_or_else(|| {
+        let mut compacted = clocks.clone();
+        compacted.remove(LISTS_NAME);
+        compacted
+      });
+      for (bucket, clock) in clocks.remove(NODES_NAME).unwrap_or_default() {
+        self.nodes.extend(bucket, None, clock);
+      }
+      for (bucket, clock) in compacted.remove(NODES_NAME).unwrap_or_default() {
+        self.nodes.mark_compacted(txr, bucket, clock)?;
+      }
+      for (bucket, clock) in clocks.remove(ATOMS_NAME).unwrap_or_default() {
+        self.atoms.extend(bucket, None, clock);
+      }
+      for (bucket, clock) in compacted.remove(ATOMS_NAME).unwrap_or_default() {
+        self.atoms.mark_compacted(txr, bucket, clock)?;
+      }
+      for (bucket, clock) in clocks.remove(EDGES_NAME).unwrap_or_default() {
+        self.edges.extend(bucket, None, clock);
       }
+      for (bucket, clock) in compacted.remove(EDGES_NAME).unwrap_or_default() {
+        self.edges.mark_compacted(txr, bucket, clock)?;
+      }
+      for (bucket, clock) in clocks.remove(LISTS_NAME).unwrap_or_default() {
+        self.lists.extend(bucket, None, clock);
+      }
+      for (bucket, clock) in compacted.remove(LISTS_NAME).unwrap_or_default() {
+        self.lists.mark_compacted(txr, bucket, clock)?;
+      }
+    }
+    self.nodes.save(txr)?;
+    self.atoms.save(txr)?;
+    self.edges.save(txr)?;
+    self.lists.save(txr)?;
+    Ok(())
+  }
+
+  /// Exports all actions later than the given remote version as an offline
+  /// sync bundle, which can be imported with [`Self::import_bundle`].
+  pub fn export_bundle(&self, txr: &impl WorkspaceTransactor, version: &[u8]) -> Result<Box<[u8]>, StoreError> {
+    let payload = self.sync_actions(txr, version)?;
+    let header = BundleHeader {
+      protocol: PROTOCOL_VERSION,
+      schema: CURRENT_VERSION,
+      oldest: OLDEST_COMPATIBLE_VERSION,
+      workspace: self.metadata.id(),
+      sender: self.metadata.this(),
+      clocks: BTreeMap::from([
+        (NODES_NAME.to_owned(), self.nodes.buckets()),
+        (ATOMS_NAME.to_owned(), self.atoms.buckets()),
+        (EDGES_NAME.to_owned(), self.edges.buckets()),
+        (LISTS_NAME.to_owned(), self.lists.buckets()),
+      ]),
+    };
+    Ok(bundle::encode(&header, &payload))
+  }
+
+  /// Joins an offline sync bundle made by [`Self::export_bundle`]. The whole
+  /// bundle is verified first, so a corrupted or incompatible bundle leaves
+  /// the workspace unchanged.
+  pub fn import_bundle(&mut self, txr: &mut impl WorkspaceTransactor, bundle: &[u8]) -> Result<JoinReport, StoreError> {
+    let (header, payload) = bundle::decode(bundle)?;
+    if header.protocol != PROTOCOL_VERSION {
+      return Err(StoreError::ProtocolMismatch(PROTOCOL_VERSION, header.protocol));
+    }
+    if !schema_compatible(header.schema, header.oldest) {
+      return Err(StoreError::SchemaMismatch(CURRENT_VERSION, header.schema));
+    }
+    self.check_workspace(header.workspace, false)?;
+    self.sync_join(txr, payload)
+  }
+
+  /// Returns the clock value of `bucket` in structure `name`.
+  fn bucket_clock(&self, name: &str, bucket: u64) -> Option<u64> {
+    match name {
+      NODES_NAME => self.nodes.buckets().get(&bucket).copied(),
+      ATOMS_NAME => self.atoms.buckets().get(&bucket).copied(),
+      EDGES_NAME => self.edges.buckets().get(&bucket).copied(),
+      LISTS_NAME => self.lists.buckets().get(&bucket).copied(),
+      _ => None,
+    }
+  }
+
+  /// Switches to a fresh bucket after another replica has been found writing
+  /// to ours, i.e. the database was copied to another device or restored from
+  /// a backup. Both replicas may then hold different items under the same
+  /// clock values, which peers would treat as already received, so all items
+  /// in the previous bucket are moved to the new one. Their clock values are
+  /// kept, so they still lose to later writes made elsewhere.
+  ///
+  /// The previous bucket is recorded and sent to peers, which pass it on, so
+  /// that the other replica forks as well once it hears of it.
+  ///
+  /// Returns the new bucket.
+  pub fn fork(&mut self, txr: &mut impl WorkspaceTransactor) -> Result<u64, StoreError> {
+    let prev = self.metadata.this();
+    let this = self.metadata.rotate(txr)?;
+    self.metadata.add_fork(txr, prev)?;
+    let mut lower = None;
+    loop {
+      let chunk = self.nodes.bucket_actions(txr, prev, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, _) in chunk {
+        self.nodes.relocate(txr, id, this)?;
+      }
+    }
+    let mut lower = None;
+    loop {
+      let chunk = self.atoms.bucket_actions(txr, prev, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, _) in chunk {
+        self.atoms.relocate(txr, id, this)?;
+      }
+    }
+    let mut lower = None;
+    loop {
+      let chunk = self.edges.bucket_actions(txr, prev, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, _) in chunk {
+        self.edges.relocate(txr, id, this)?;
+      }
+    }
+    let mut lower = None;
+    loop {
+      let chunk = self.lists.bucket_actions(txr, prev, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, _) in chunk {
+        self.lists.relocate(txr, id, this)?;
+      }
+    }
+    self.joined = true;
+    Ok(this)
+  }
+
+  /// Records the identity of the database file, e.g. its inode, which is not
+  /// kept when the file is copied. If a different one was recorded before,
+  /// the database is a copy, which forks right away (see [`Workspace::fork`]).
+  ///
+  /// Returns the previous bucket if this replica forked.
+  pub fn check_fingerprint(
+    &mut self,
+    txr: &mut impl WorkspaceTransactor,
+    fingerprint: u64,
+  ) -> Result<Option<u64>, StoreError> {
+    let recorded = self.metadata.fingerprint();
+    if recorded == Some(fingerprint) {
+      return Ok(None);
+    }
+    self.metadata.set_fingerprint(txr, fingerprint)?;
+    if recorded.is_none() {
+      return Ok(None);
+    }
+    let prev = self.metadata.this();
+    self.fork(txr)?;
+    Ok(Some(prev))
+  }
+
+  /// Adds at most `limit` items in `bucket` strictly later than `lower` which
+  /// are staged until their transaction is complete, so that they are passed
+  /// on to other peers like applied ones.
+  fn with_staged<I: DeserializeOwned>(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+    mut actions: Vec<(u128, I)>,
+  ) -> Result<Vec<(u128, I)>, StoreError> {
+    for (id, item) in self.txns.staged(txr, name, bucket, lower, limit)? {
+      actions.push((id, load(&item, name)?));
+    }
+    Ok(actions)
+  }
+
+  /// Returns whether all items of a transaction have been received, applied or not.
+  fn is_complete(&self, txr: &impl WorkspaceTransactor, bucket: u64, txn: u64) -> Result<bool, StoreError> {
+    Ok(self.txns.manifest(txr, bucket, txn)?.into_iter().all(|(name, (_, upper))| match name.as_str() {
+      NODES_NAME => self.nodes.covers(bucket, Some(upper)),
+      ATOMS_NAME => self.atoms.covers(bucket, Some(upper)),
+      EDGES_NAME => self.edges.covers(bucket, Some(upper)),
+      LISTS_NAME => self.lists.covers(bucket, Some(upper)),
+      _ => true,
+    }))
+  }
+
+  /// Records a page of a reset of structure `name`: the ranges it covers, the
+  /// items it contains, and the present local items in those ranges which it
+  /// does not contain. Once the pages received so far cover all of the remote
+  /// bucket `clocks`, returns the items none of them contained, since the
+  /// remote peer no longer has their removals. Pages may arrive in any order.
+  fn reset_progress(
+    &mut self,
+    name: &'static str,
+    clocks: &BTreeMap<u64, u64>,
+    ranges: &Ranges,
+    live: &BTreeSet<u128>,
+    missing: Vec<(u128, (u64, u64))>,
+  ) -> BTreeMap<u128, (u64, u64)> {
+    let progress = self.resets.entry(name).or_default();
+    progress.missing.retain(|id, _| !live.contains(id));
+    progress.missing.extend(missing);
+    for (&bucket, &(lower, upper)) in ranges {
+      let entry = progress.ranges.entry(bucket).or_default().entry(lower).or_insert(upper);
+      *entry = (*entry).max(upper);
+    }
+    if !progress.covers(clocks) {
+      return BTreeMap::new();
+    }
+    self.resets.remove(name).unwrap_or_default().missing
+  }
+
+  /// Returns the transaction to stage an incoming item for, if it belongs to
+  /// one which has not been fully received yet.
+  fn pending_txn(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    name: &str,
+    bucket: u64,
+    clock: u64,
+  ) -> Result<Option<u64>, StoreError> {
+    let Some(txn) = self.txns.find(txr, bucket, name, clock)? else { return Ok(None) };
+    Ok((!self.is_complete(txr, bucket, txn)?).then_some(txn))
+  }
+
+  /// `(protocol version, schema version, workspace ID)`.
+  fn handshake(&self) -> (u64, u64, u128) {
+    (PROTOCOL_VERSION, CURRENT_VERSION, self.metadata.id())
+  }
+
+  /// Checks the handshake sent by a remote peer, returning its workspace ID.
+  /// Peers which do not send a handshake are assumed to speak version 0, and
+  /// peers which do not send their oldest compatible schema version are
+  /// assumed to only be compatible with their own.
+  fn check_handshake(all: &BTreeMap<String, &[u8]>) -> Result<u128, StoreError> {
+    let handshake: Option<(u64, u64, u128)> = all.get(HANDSHAKE_NAME).map(|m| decode(m, HANDSHAKE_NAME)).transpose()?;
+    let (protocol, schema, id) = handshake.unwrap_or_default();
+    let oldest: Option<u64> = all.get(COMPATIBLE_NAME).map(|m| decode(m, COMPATIBLE_NAME)).transpose()?;
+    if protocol != PROTOCOL_VERSION {
+      return Err(StoreError::ProtocolMismatch(PROTOCOL_VERSION, protocol));
+    }
+    if !schema_compatible(schema, oldest.unwrap_or(schema)) {
+      return Err(StoreError::SchemaMismatch(CURRENT_VERSION, schema));
+    }
+    Ok(id)
+  }
+
+  /// Checks that a remote peer belongs to the same workspace, unless either
+  /// side is pristine. Peers state whether they are pristine explicitly, since
+  /// an empty version may as well come from a filtered or reset replica.
+  fn check_workspace(&self, id: u128, pristine: bool) -> Result<(), StoreError> {
+    if id != self.metadata.id() && !pristine && !self.is_pristine() {
+      return Err(StoreError::WorkspaceMismatch(self.metadata.id(), id));
     }
-    false
+    Ok(())
   }
 
   /// To keep backward compatibility, do not change existing strings and type
   /// annotations below. Additional entries may be added.
-  pub fn sync_version(&self, _: &Transactor) -> Box<[u8]> {
+  pub fn sync_version(&self, _: &impl WorkspaceTransactor) -> Box<[u8]> {
+    let handshake: (u64, u64, u128) = self.handshake();
     let nodes_version: BTreeMap<u64, u64> = self.nodes.buckets();
     let atoms_version: BTreeMap<u64, u64> = self.atoms.buckets();
     let edges_version: BTreeMap<u64, u64> = self.edges.buckets();
+    let lists_version: BTreeMap<u64, u64> = self.lists.buckets();
+
+    let all: BTreeMap<&str, Vec<u8>> = BTreeMap::from([
+      (NODES_NAME, serialize(&nodes_version).unwrap()),
+      (ATOMS_NAME, serialize(&atoms_version).unwrap()),
+      (EDGES_NAME, serialize(&edges_version).unwrap()),
+      (LISTS_NAME, serialize(&lists_version).unwrap()),
+      (HANDSHAKE_NAME, serialize(&handshake).unwrap()),
+      (COMPATIBLE_NAME, serialize(&OLDEST_COMPATIBLE_VERSION).unwrap()),
+      (PEER_NAME, serialize(&self.metadata.this()).unwrap()),
+      (PRISTINE_NAME, serialize(&self.is_pristine()).unwrap()),
+    ]);
+
+    serialize(&all).unwrap().into()
+  }
+
+  /// Like [`Self::sync_version`], but only requests items matching `filter`.
+  /// Bucket clocks are tracked separately for each filter, so switching to a
+  /// wider filter later backfills everything it newly matches.
+  ///
+  /// Unfiltered bucket clocks only advance until something is filtered out
+  /// of a bucket, so filtered replicas should only sync with replicas holding
+  /// everything they need.
+  pub fn sync_version_filtered(&self, _: &impl WorkspaceTransactor, filter: &ReplicationFilter) -> Box<[u8]> {
+    let handshake: (u64, u64, u128) = self.handshake();
+    let nodes_version: BTreeMap<u64, u64> = self.nodes.filtered_buckets(filter.id());
+    let atoms_version: BTreeMap<u64, u64> = self.atoms.filtered_buckets(filter.id());
+    let edges_version: BTreeMap<u64, u64> = self.edges.filtered_buckets(filter.id());
+    let lists_version: BTreeMap<u64, u64> = self.lists.filtered_buckets(filter.id());
 
     let all: BTreeMap<&str, Vec<u8>> = BTreeMap::from([
       (NODES_NAME, serialize(&nodes_version).unwrap()),
       (ATOMS_NAME, serialize(&atoms_version).unwrap()),
       (EDGES_NAME, serialize(&edges_version).unwrap()),
+      (LISTS_NAME, serialize(&lists_version).unwrap()),
+      (HANDSHAKE_NAME, serialize(&handshake).unwrap()),
+      (COMPATIBLE_NAME, serialize(&OLDEST_COMPATIBLE_VERSION).unwrap()),
+      (FILTER_NAME, serialize(filter).unwrap()),
+      (PEER_NAME, serialize(&self.metadata.this()).unwrap()),
+      (PRISTINE_NAME, serialize(&self.is_pristine()).unwrap()),
     ]);
 
     serialize(&all).unwrap().into()
   }
 
+  /// Returns all actions later than the given remote version.
+  ///
+  /// Fails if the remote peer is incompatible, or belongs to another workspace
+  /// (unless it is pristine).
+  pub fn sync_actions(&self, txr: &impl WorkspaceTransactor, version: &[u8]) -> Result<Box<[u8]>, StoreError> {
+    Ok(self.sync_actions_page(txr, version, None, u64::MAX)?.0)
+  }
+
+  /// Returns a page of actions later than the given remote version, starting
+  /// after `cursor`, of around `budget` bytes (at least one action is always
+  /// included). Also returns the cursor for the next page, or `None` if this
+  /// is the last one.
+  ///
+  /// Pages can be joined in any order: bucket clocks only advance over clock
+  /// ranges without gaps, so resuming from [`Self::sync_version`] after a
+  /// dropped connection fetches whatever is missing. Nodes come before atoms
+  /// and edges, so pages joined in order can be followed by a barrier at any
+  /// point; otherwise, defer the barrier until all pages are joined, or
+  /// dangling atoms and edges will be removed. Writes made between two
+  /// barriers form a transaction, which the receiving peer only applies once
+  /// all of its pages have been joined.
+  ///
   /// To keep backward compatibility, do not change existing strings and type
   /// annotations below. Additional entries may be added.
-  pub fn sync_actions(&self, txr: &Transactor, version: &[u8]) -> Box<[u8]> {
-    let all: BTreeMap<String, &[u8]> = deserialize(version).unwrap();
+  pub fn sync_actions_page(
+    &self,
+    txr: &impl WorkspaceTransactor,
+    version: &[u8],
+    cursor: Option<&[u8]>,
+    budget: u64,
+  ) -> Result<(Box<[u8]>, Option<Box<[u8]>>), StoreError> {
+    let all: BTreeMap<String, &[u8]> = decode(version, "version")?;
+    let id = Self::check_handshake(&all)?;
+    let cursor: Option<Cursor> = cursor.map(|cursor| decode(cursor, "cursor")).transpose()?;
 
-    let nodes_version: BTreeMap<u64, u64> = all.get(NODES_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
-    let atoms_version: BTreeMap<u64, u64> = all.get(ATOMS_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
-    let edges_version: BTreeMap<u64, u64> = all.get(EDGES_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
+    let nodes_version: BTreeMap<u64, u64> = decode_entry(&all, NODES_NAME)?;
+    let atoms_version: BTreeMap<u64, u64> = decode_entry(&all, ATOMS_NAME)?;
+    let edges_version: BTreeMap<u64, u64> = decode_entry(&all, EDGES_NAME)?;
+    let lists_version: BTreeMap<u64, u64> = decode_entry(&all, LISTS_NAME)?;
+    let filter: Option<ReplicationFilter> = all.get(FILTER_NAME).map(|m| decode(m, FILTER_NAME)).transpose()?;
 
-    let nodes_actions: BTreeMap<u128, (u64, u64, Option<u64>)> = self.nodes.actions(txr, nodes_version);
-    let atoms_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, Box<[u8]>)>)> =
-      self.atoms.actions(txr, atoms_version);
-    let edges_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, u128)>)> = self.edges.actions(txr, edges_version);
+    self.check_workspace(id, decode_entry(&all, PRISTINE_NAME)?)?;
 
-    let all: BTreeMap<&str, Vec<u8>> = BTreeMap::from([
+    // Peers which may have missed compacted removals get everything again, page by page.
+    let stale = |compacted: &BTreeMap<u64, u64>, version: &BTreeMap<u64, u64>| {
+      !version.is_empty() && compacted.iter().any(|(bucket, clock)| version.get(bucket) < Some(clock))
+    };
+    let mut resets: BTreeMap<&str, Reset> = BTreeMap::new();
+    if stale(self.nodes.compacted(), &nodes_version) {
+      resets.insert(NODES_NAME, (self.nodes.buckets(), self.nodes.compacted().clone()));
+    }
+    if stale(self.atoms.compacted(), &atoms_version) {
+      resets.insert(ATOMS_NAME, (self.atoms.buckets(), self.atoms.compacted().clone()));
+    }
+    if stale(self.edges.compacted(), &edges_version) {
+      resets.insert(EDGES_NAME, (self.edges.buckets(), self.edges.compacted().clone()));
+    }
+    if stale(self.lists.compacted(), &lists_version) {
+      resets.insert(LISTS_NAME, (self.lists.buckets(), self.lists.compacted().clone()));
+    }
+    let nodes_version = if resets.contains_key(NODES_NAME) { BTreeMap::new() } else { nodes_version };
+    let atoms_version = if resets.contains_key(ATOMS_NAME) { BTreeMap::new() } else { atoms_version };
+    let edges_version = if resets.contains_key(EDGES_NAME) { BTreeMap::new() } else { edges_version };
+    let lists_version = if resets.contains_key(LISTS_NAME) { BTreeMap::new() } else { lists_version };
+
+    // When our writes not yet received by the peer were made, relative to
+    // what we had received, so that it can tell which of them overwrote its
+    // own writes without having seen them.
+    let this = self.metadata.this();
+    let mut seen: (u64, BTreeMap<&str, BTreeMap<u64, BTreeMap<u64, u64>>>) = (
+      this,
+      BTreeMap::from([
+        (NODES_NAME, self.nodes.seen(nodes_version.get(&this).copied())),
+        (ATOMS_NAME, self.atoms.seen(atoms_version.get(&this).copied())),
+        (EDGES_NAME, self.edges.seen(edges_version.get(&this).copied())),
+      ]),
+    );
+    seen.1.retain(|_, seen| !seen.is_empty());
+
+    let mut page = Page::new(cursor, budget);
+    let handshake: (u64, u64, u128) = self.handshake();
+    let nodes_actions: BTreeMap<u128, (u64, u64, Option<u64>)> = page.collect(
+      NODES_NAME,
+      self.nodes.buckets(),
+      nodes_version,
+      |bucket, lower, limit| {
+        self.with_staged(txr, NODES_NAME, bucket, lower, limit, self.nodes.bucket_actions(txr, bucket, lower, limit)?)
+      },
+      |item| item.1,
+      |item| filter.as_ref().map_or(true, |f| item.2.map_or(true, |label| f.nodes.contains(&label))),
+    )?;
+    let atoms_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, Box<[u8]>)>)> = page.collect(
+      ATOMS_NAME,
+      self.atoms.buckets(),
+      atoms_version,
+      |bucket, lower, limit| {
+        self.with_staged(txr, ATOMS_NAME, bucket, lower, limit, self.atoms.bucket_actions(txr, bucket, lower, limit)?)
+      },
+      |item| item.1,
+      |item| filter.as_ref().map_or(true, |f| item.2.as_ref().map_or(true, |(_, label, _)| f.atoms.contains(label))),
+    )?;
+    let edges_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, u128)>)> = page.collect(
+      EDGES_NAME,
+      self.edges.buckets(),
+      edges_version,
+      |bucket, lower, limit| {
+        self.with_staged(txr, EDGES_NAME, bucket, lower, limit, self.edges.bucket_actions(txr, bucket, lower, limit)?)
+      },
+      |item| item.1,
+      |item| filter.as_ref().map_or(true, |f| item.2.map_or(true, |(_, label, _)| f.edges.contains(&label))),
+    )?;
+    let lists_actions: BTreeMap<u128, (u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>)> = page
+      .collect(
+        LISTS_NAME,
+        self.lists.buckets(),
+        lists_version,
+        |bucket, lower, limit| {
+          self.with_staged(txr, LISTS_NAME, bucket, lower, limit, self.lists.bucket_actions(txr, bucket, lower, limit)?)
+        },
+        |item| item.1,
+        |item| filter.as_ref().map_or(true, |f| f.lists.contains(&item.2 .1)),
+      )?;
+    let ranges: BTreeMap<&str, Ranges> = page.ranges;
+    // Buckets whose ranges have nothing filtered out, so filtered peers may advance their unfiltered clocks.
+    let unfiltered: BTreeMap<&str, BTreeSet<u64>> = (ranges.iter())
+      .map(|(&name, ranges)| {
+        let skipped = page.skipped.get(name);
+        (
+          name,
+          ranges.keys().filter(|bucket| !skipped.is_some_and(|skipped| skipped.contains(bucket))).copied().collect(),
+        )
+      })
+      .collect();
+
+    // Manifests of the transactions which actions in this page belong to.
+    let mut pending = BTreeSet::new();
+    if filter.is_none() {
+      let items = (nodes_actions.values().map(|item| (NODES_NAME, item.0, item.1)))
+        .chain(atoms_actions.values().map(|item| (ATOMS_NAME, item.0, item.1)))
+        .chain(edges_actions.values().map(|item| (EDGES_NAME, item.0, item.1)))
+        .chain(lists_actions.values().map(|item| (LISTS_NAME, item.0, item.1)));
+      for (name, bucket, clock) in items {
+        if let Some(txn) = self.txns.find(txr, bucket, name, clock)? {
+          pending.insert((bucket, txn));
+        }
+      }
+    }
+    let mut txns: Vec<TxnEntry> = Vec::new();
+    for (bucket, txn) in pending {
+      for (name, (lower, upper)) in self.txns.manifest(txr, bucket, txn)? {
+        txns.push((bucket, txn, name, lower, upper));
+      }
+    }
+    let clocks: BTreeMap<&str, BTreeMap<u64, u64>> = BTreeMap::from([
+      (NODES_NAME, self.nodes.buckets()),
+      (ATOMS_NAME, self.atoms.buckets()),
+      (EDGES_NAME, self.edges.buckets()),
+      (LISTS_NAME, self.lists.buckets()),
+    ]);
+
+    let mut all: BTreeMap<&str, Vec<u8>> = BTreeMap::from([
       (NODES_NAME, serialize(&nodes_actions).unwrap()),
       (ATOMS_NAME, serialize(&atoms_actions).unwrap()),
       (EDGES_NAME, serialize(&edges_actions).unwrap()),
+      (LISTS_NAME, serialize(&lists_actions).unwrap()),
+      (HANDSHAKE_NAME, serialize(&handshake).unwrap()),
+      (COMPATIBLE_NAME, serialize(&OLDEST_COMPATIBLE_VERSION).unwrap()),
+      (RANGES_NAME, serialize(&ranges).unwrap()),
+      (RESET_NAME, serialize(&resets).unwrap()),
+      (CLOCKS_NAME, serialize(&clocks).unwrap()),
+      (TXNS_NAME, serialize(&txns).unwrap()),
+      (PRISTINE_NAME, serialize(&self.is_pristine()).unwrap()),
+      (SEEN_NAME, serialize(&seen).unwrap()),
+      (FORKS_NAME, serialize(self.metadata.forks()).unwrap()),
     ]);
+    if let Some(filter) = &filter {
+      all.insert(FILTER_NAME, serialize(filter).unwrap());
+      all.insert(UNFILTERED_NAME, serialize(&unfiltered).unwrap());
+    }
 
-    serialize(&all).unwrap().into()
+    Ok((serialize(&all).unwrap().into(), page.cursor.map(|cursor| serialize(&cursor).unwrap().into())))
   }
 
-  /// To keep backward compatibility, do not change existing strings and type
-  /// annotations below. Additional entries may be added.
-  pub fn sync_join(&mut self, txr: &Transactor, actions: &[u8]) {
-    let all: BTreeMap<String, &[u8]> = deserialize(actions).unwrap();
+  /// To keep backward compatibility, do not change existing strings and type
+  /// annotations below. Additional entries may be added.
+  ///
+  /// The whole payload is decoded and checked before anything is applied, so
+  /// a malformed payload or an incompatible peer leaves the workspace unchanged.
+  ///
+  /// Writes made between two barriers on the sending peer are applied, and
+  /// thus checked by the next barrier, only once all of them have been joined.
+  /// Writes too far ahead of the local clock are rejected, see
+  /// [`Constraints::set_max_drift`].
+  ///
+  /// Returns the concurrent writes which lost, on either side.
+  pub fn sync_join(&mut self, txr: &mut impl WorkspaceTransactor, actions: &[u8]) -> Result<JoinReport, StoreError> {
+    let all: BTreeMap<String, &[u8]> = decode(actions, "actions")?;
+    let id = Self::check_handshake(&all)?;
+    // Staged transactions expire by local time, as the sender's clock may be
+    // behind, or its writes may have been made long before this sync.
+    let staged_at = now();
+
+    let nodes_actions: BTreeMap<u128, (u64, u64, Option<u64>)> = decode_entry(&all, NODES_NAME)?;
+    let atoms_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, Box<[u8]>)>)> = decode_entry(&all, ATOMS_NAME)?;
+    let edges_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, u128)>)> = decode_entry(&all, EDGES_NAME)?;
+    let lists_actions: BTreeMap<u128, (u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>)> =
+      decode_entry(&all, LISTS_NAME)?;
+    let mut ranges: BTreeMap<String, Ranges> = decode_entry(&all, RANGES_NAME)?;
+    let mut nodes_ranges = ranges.remove(NODES_NAME).unwrap_or_default();
+    let mut atoms_ranges = ranges.remove(ATOMS_NAME).unwrap_or_default();
+    let mut edges_ranges = ranges.remove(EDGES_NAME).unwrap_or_default();
+    let mut lists_ranges = ranges.remove(LISTS_NAME).unwrap_or_default();
+    let mut resets: BTreeMap<String, Reset> = decode_entry(&all, RESET_NAME)?;
+    let clocks: Option<BTreeMap<String, BTreeMap<u64, u64>>> =
+      all.get(CLOCKS_NAME).map(|m| decode(m, CLOCKS_NAME)).transpose()?;
+    let this = self.metadata.this();
+    let forks: BTreeSet<u64> = all.get(FORKS_NAME).map(|m| decode(m, FORKS_NAME)).transpose()?.unwrap_or_default();
+    // Either the sender has writes in our bucket which were never made here,
+    // or another replica found that out first.
+    let forked = forks.contains(&this)
+      || clocks.as_ref().is_some_and(|clocks| {
+        clocks.iter().any(|(name, clocks)| clocks.get(&this) > self.bucket_clock(name, this).as_ref())
+      });
+    let seen: Option<(u64, BTreeMap<String, BTreeMap<u64, BTreeMap<u64, u64>>>)> =
+      all.get(SEEN_NAME).map(|m| decode(m, SEEN_NAME)).transpose()?;
+    let sequential = |name: &str| {
+      let seen = seen.as_ref().map(|(sender, seen)| (*sender, seen.get(name).cloned().unwrap_or_default()));
+      move |local: (u64, u64), remote: (u64, u64)| sequential(seen.as_ref(), local, remote)
+    };
+    let (nodes_sequential, atoms_sequential, edges_sequential) =
+      (sequential(NODES_NAME), sequential(ATOMS_NAME), sequential(EDGES_NAME));
+    let mut report = JoinReport::default();
+    let filter: Option<ReplicationFilter> = all.get(FILTER_NAME).map(|m| decode(m, FILTER_NAME)).transpose()?;
+    let filter_id = filter.as_ref().map(ReplicationFilter::id);
+    let unfiltered: BTreeMap<String, BTreeSet<u64>> =
+      all.get(UNFILTERED_NAME).map(|m| decode(m, UNFILTERED_NAME)).transpose()?.unwrap_or_default();
+    let complete = |name: &str, bucket: u64| unfiltered.get(name).is_some_and(|buckets| buckets.contains(&bucket));
+    let txns: Vec<TxnEntry> = decode_entry(&all, TXNS_NAME)?;
+
+    self.check_workspace(id, decode_entry(&all, PRISTINE_NAME)?)?;
+    // From here on, the join writes to the database, see `Self::discard`.
+    self.joined = true;
+    if id != self.metadata.id() && self.is_pristine() {
+      self.metadata.set_id(txr, id)?;
+    }
+
+    // Items of transactions which have not been fully received yet are staged,
+    // and applied together once the rest arrives. Filtered replicas may never
+    // receive whole transactions, so they apply items right away.
+    if filter_id.is_none() {
+      for (bucket, txn, name, lower, upper) in txns {
+        self.txns.record(txr, bucket, txn, &name, lower, upper)?;
+      }
+    }
+
+    let mut nodes_actions = nodes_actions.into_iter().collect::<Vec<_>>();
+    nodes_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
+    let mut atoms_actions = atoms_actions.into_iter().collect::<Vec<_>>();
+    atoms_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
+    let mut edges_actions = edges_actions.into_iter().collect::<Vec<_>>();
+    edges_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
+    let mut lists_actions = lists_actions.into_iter().collect::<Vec<_>>();
+    lists_actions.sort_by_key(|(_, (bucket, clock, _, _))| (*bucket, *clock));
+
+    // Actions too far in the future are rejected and left uncovered, so that
+    // they are sent again later. Other peers' clocks cannot push ours further.
+    let limit = drift_limit(self.constraints.max_drift);
+
+    // Actions not connected to what we have, or filtered, are merged without
+    // advancing bucket clocks. Filtered ranges advance the filter's clocks instead.
+    let gaps = |ranges: &Ranges, covers: &dyn Fn(u64, Option<u64>) -> bool| -> BTreeSet<u64> {
+      ranges.iter().filter(|(&bucket, &(lower, _))| !covers(bucket, lower)).map(|(&bucket, _)| bucket).collect()
+    };
+
+    let nodes_live: BTreeSet<u128> =
+      nodes_actions.iter().filter(|(_, item)| item.2.is_some()).map(|(id, _)| *id).collect();
+    reject(NODES_NAME, &mut nodes_actions, &mut nodes_ranges, limit, |item| (item.0, item.1), &mut report.rejected);
+    let nodes_gaps = gaps(&nodes_ranges, &|bucket, lower| self.nodes.covers(bucket, lower));
+    for (id, (bucket, clock, l)) in nodes_actions {
+      if filter_id.is_none() {
+        if let Some(txn) = self.pending_txn(txr, NODES_NAME, bucket, clock)? {
+          self.txns.stage(
+            txr,
+            NODES_NAME,
+            id,
+            (bucket, clock, txn),
+            &serialize(&(bucket, clock, l)).unwrap(),
+            staged_at,
+          )?;
+          continue;
+        }
+      }
+      let local = self.nodes.get(txr, id)?;
+      let seen = self.nodes.covers(bucket, Some(clock));
+      let applied = match filter_id.is_some() || nodes_gaps.contains(&bucket) {
+        false => self.nodes.set(txr, id, bucket, clock, l)?,
+        true => self.nodes.merge(txr, id, bucket, clock, l)?,
+      };
+      if let Some((winner, loser, overwritten)) = conflict(local, (bucket, clock, l), applied, seen, &nodes_sequential)
+      {
+        report.conflicts.push(Conflict::Node { id, winner, loser, overwritten });
+      }
+    }
+    if let Some((clocks, compacted)) = resets.remove(NODES_NAME) {
+      let matches = |item: &(u64, u64, Option<u64>)| {
+        filter.as_ref().map_or(true, |f| item.2.is_some_and(|label| f.nodes.contains(&label)))
+      };
+      let mut missing = Vec::new();
+      for (&bucket, &range) in &nodes_ranges {
+        for (id, clock) in self.nodes.missing(txr, bucket, range, &nodes_live, matches)? {
+          missing.push((id, (bucket, clock)));
+        }
+      }
+      for (id, (bucket, clock)) in self.reset_progress(NODES_NAME, &clocks, &nodes_ranges, &nodes_live, missing) {
+        if self.nodes.get(txr, id)?.is_some_and(|item| (item.0, item.1) == (bucket, clock) && item.2.is_some()) {
+          self.set_node(txr, id, None)?;
+        }
+      }
+      for (bucket, clock) in compacted {
+        self.nodes.mark_compacted(txr, bucket, clock)?;
+      }
+    }
+    for (bucket, (lower, upper)) in nodes_ranges {
+      match filter_id {
+        Some(filter_id) => self.nodes.filtered_extend(filter_id, bucket, lower, upper, complete(NODES_NAME, bucket)),
+        None => self.nodes.extend(bucket, lower, upper),
+      }
+    }
+
+    let atoms_live: BTreeSet<u128> =
+      atoms_actions.iter().filter(|(_, item)| item.2.is_some()).map(|(id, _)| *id).collect();
+    reject(ATOMS_NAME, &mut atoms_actions, &mut atoms_ranges, limit, |item| (item.0, item.1), &mut report.rejected);
+    let atoms_gaps = gaps(&atoms_ranges, &|bucket, lower| self.atoms.covers(bucket, lower));
+    for (id, (bucket, clock, slv)) in atoms_actions {
+      if filter_id.is_none() {
+        if let Some(txn) = self.pending_txn(txr, ATOMS_NAME, bucket, clock)? {
+          self.txns.stage(
+            txr,
+            ATOMS_NAME,
+            id,
+            (bucket, clock, txn),
+            &serialize(&(bucket, clock, slv)).unwrap(),
+            staged_at,
+          )?;
+          continue;
+        }
+      }
+      let local = self.atoms.get(txr, id)?;
+      let seen = self.atoms.covers(bucket, Some(clock));
+      let remote = (bucket, clock, slv.clone());
+      let applied = match filter_id.is_some() || atoms_gaps.contains(&bucket) {
+        false => self.atoms.set(txr, id, bucket, clock, slv)?,
+        true => self.atoms.merge(txr, id, bucket, clock, slv)?,
+      };
+      if let Some((winner, loser, overwritten)) = conflict(local, remote, applied, seen, &atoms_sequential) {
+        report.conflicts.push(Conflict::Atom { id, winner, loser, overwritten });
+      }
+    }
+    if let Some((clocks, compacted)) = resets.remove(ATOMS_NAME) {
+      let matches = |item: &(u64, u64, Option<(u128, u64, Box<[u8]>)>)| {
+        filter.as_ref().map_or(true, |f| item.2.as_ref().is_some_and(|(_, label, _)| f.atoms.contains(label)))
+      };
+      let mut missing = Vec::new();
+      for (&bucket, &range) in &atoms_ranges {
+        for (id, clock) in self.atoms.missing(txr, bucket, range, &atoms_live, matches)? {
+          missing.push((id, (bucket, clock)));
+        }
+      }
+      for (id, (bucket, clock)) in self.reset_progress(ATOMS_NAME, &clocks, &atoms_ranges, &atoms_live, missing) {
+        if self.atoms.get(txr, id)?.is_some_and(|item| (item.0, item.1) == (bucket, clock) && item.2.is_some()) {
+          self.set_atom(txr, id, None)?;
+        }
+      }
+      for (bucket, clock) in compacted {
+        self.atoms.mark_compacted(txr, bucket, clock)?;
+      }
+    }
+    for (bucket, (lower, upper)) in atoms_ranges {
+      match filter_id {
+        Some(filter_id) => self.atoms.filtered_extend(filter_id, bucket, lower, upper, complete(ATOMS_NAME, bucket)),
+        None => self.atoms.extend(bucket, lower, upper),
+      }
+    }
+
+    let edges_live: BTreeSet<u128> =
+      edges_actions.iter().filter(|(_, item)| item.2.is_some()).map(|(id, _)| *id).collect();
+    reject(EDGES_NAME, &mut edges_actions, &mut edges_ranges, limit, |item| (item.0, item.1), &mut report.rejected);
+    let edges_gaps = gaps(&edges_ranges, &|bucket, lower| self.edges.covers(bucket, lower));
+    for (id, (bucket, clock, sld)) in edges_actions {
+      if filter_id.is_none() {
+        if let Some(txn) = self.pending_txn(txr, EDGES_NAME, bucket, clock)? {
+          self.txns.stage(
+            txr,
+            EDGES_NAME,
+            id,
+            (bucket, clock, txn),
+            &serialize(&(bucket, clock, sld)).unwrap(),
+            staged_at,
+          )?;
+          continue;
+        }
+      }
+      let local = self.edges.get(txr, id)?;
+      let seen = self.edges.covers(bucket, Some(clock));
+      let applied = match filter_id.is_some() || edges_gaps.contains(&bucket) {
+        false => self.edges.set(txr, id, bucket, clock, sld)?,
+        true => self.edges.merge(txr, id, bucket, clock, sld)?,
+      };
+      if let Some((winner, loser, overwritten)) =
+        conflict(local, (bucket, clock, sld), applied, seen, &edges_sequential)
+      {
+        report.conflicts.push(Conflict::Edge { id, winner, loser, overwritten });
+      }
+    }
+    if let Some((clocks, compacted)) = resets.remove(EDGES_NAME) {
+      let matches = |item: &(u64, u64, Option<(u128, u64, u128)>)| {
+        filter.as_ref().map_or(true, |f| item.2.is_some_and(|(_, label, _)| f.edges.contains(&label)))
+      };
+      let mut missing = Vec::new();
+      for (&bucket, &range) in &edges_ranges {
+        for (id, clock) in self.edges.missing(txr, bucket, range, &edges_live, matches)? {
+          missing.push((id, (bucket, clock)));
+        }
+      }
+      for (id, (bucket, clock)) in self.reset_progress(EDGES_NAME, &clocks, &edges_ranges, &edges_live, missing) {
+        if self.edges.get(txr, id)?.is_some_and(|item| (item.0, item.1) == (bucket, clock) && item.2.is_some()) {
+          self.set_edge(txr, id, None)?;
+        }
+      }
+      for (bucket, clock) in compacted {
+        self.edges.mark_compacted(txr, bucket, clock)?;
+      }
+    }
+    for (bucket, (lower, upper)) in edges_ranges {
+      match filter_id {
+        Some(filter_id) => self.edges.filtered_extend(filter_id, bucket, lower, upper, complete(EDGES_NAME, bucket)),
+        None => self.edges.extend(bucket, lower, upper),
+      }
+    }
+
+    let lists_live: BTreeSet<u128> =
+      lists_actions.iter().filter(|(_, item)| item.3.is_some()).map(|(id, _)| *id).collect();
+    reject(LISTS_NAME, &mut lists_actions, &mut lists_ranges, limit, |item| (item.0, item.1), &mut report.rejected);
+    let lists_gaps = gaps(&lists_ranges, &|bucket, lower| self.lists.covers(bucket, lower));
+    for (id, (bucket, clock, pos, value)) in lists_actions {
+      if filter_id.is_none() {
+        if let Some(txn) = self.pending_txn(txr, LISTS_NAME, bucket, clock)? {
+          self.txns.stage(
+            txr,
+            LISTS_NAME,
+            id,
+            (bucket, clock, txn),
+            &serialize(&(bucket, clock, pos, value)).unwrap(),
+            staged_at,
+          )?;
+          continue;
+        }
+      }
+      match filter_id.is_some() || lists_gaps.contains(&bucket) {
+        false => self.lists.set(txr, id, bucket, clock, pos, value)?,
+        true => self.lists.merge(txr, id, bucket, clock, pos, value)?,
+      };
+    }
+    if let Some((clocks, compacted)) = resets.remove(LISTS_NAME) {
+      let matches = |item: &(u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>)| {
+        filter.as_ref().map_or(true, |f| f.lists.contains(&item.2 .1))
+      };
+      let mut missing = Vec::new();
+      for (&bucket, &range) in &lists_ranges {
+        for (id, clock) in self.lists.missing(txr, bucket, range, &lists_live, matches)? {
+          missing.push((id, (bucket, clock)));
+        }
+      }
+      for (id, (bucket, clock)) in self.reset_progress(LISTS_NAME, &clocks, &lists_ranges, &lists_live, missing) {
+        if self.lists.get(txr, id)?.is_some_and(|item| (item.0, item.1) == (bucket, clock) && item.3.is_some()) {
+          self.remove_list_item(txr, id)?;
+        }
+      }
+      for (bucket, clock) in compacted {
+        self.lists.mark_compacted(txr, bucket, clock)?;
+      }
+    }
+    for (bucket, (lower, upper)) in lists_ranges {
+      match filter_id {
+        Some(filter_id) => self.lists.filtered_extend(filter_id, bucket, lower, upper, complete(LISTS_NAME, bucket)),
+        None => self.lists.extend(bucket, lower, upper),
+      }
+    }
+
+    for bucket in forks {
+      self.metadata.add_fork(txr, bucket)?;
+    }
+    if forked {
+      report.forked = Some(this);
+      self.fork(txr)?;
+    }
+
+    // Apply staged transactions which are now complete. Those staged for too
+    // long, e.g. because the sender compacted the rest away, are applied as
+    // far as they have been received.
+    let expired = now().saturating_sub(self.constraints.staged_ttl);
+    for (bucket, txn, at) in self.txns.pending(txr)? {
+      if at >= expired && !self.is_complete(txr, bucket, txn)? {
+        continue;
+      }
+      for (name, id, item) in self.txns.release(txr, bucket, txn)? {
+        match name.as_str() {
+          NODES_NAME => {
+            let (bucket, clock, l): (u64, u64, Option<u64>) = load(&item, &name)?;
+            let local = self.nodes.get(txr, id)?;
+            let applied = self.nodes.merge(txr, id, bucket, clock, l)?;
+            if let Some((winner, loser, overwritten)) =
+              conflict(local, (bucket, clock, l), applied, false, &nodes_sequential)
+            {
+              report.conflicts.push(Conflict::Node { id, winner, loser, overwritten });
+            }
+          }
+          ATOMS_NAME => {
+            let (bucket, clock, slv): (u64, u64, Option<(u128, u64, Box<[u8]>)>) = load(&item, &name)?;
+            let local = self.atoms.get(txr, id)?;
+            let remote = (bucket, clock, slv.clone());
+            let applied = self.atoms.merge(txr, id, bucket, clock, slv)?;
+            if let Some((winner, loser, overwritten)) = conflict(local, remote, applied, false, &atoms_sequential) {
+              report.conflicts.push(Conflict::Atom { id, winner, loser, overwritten });
+            }
+          }
+          EDGES_NAME => {
+            let (bucket, clock, sld): (u64, u64, Option<(u128, u64, u128)>) = load(&item, &name)?;
+            let local = self.edges.get(txr, id)?;
+            let applied = self.edges.merge(txr, id, bucket, clock, sld)?;
+            if let Some((winner, loser, overwritten)) =
+              conflict(local, (bucket, clock, sld), applied, false, &edges_sequential)
+            {
+              report.conflicts.push(Conflict::Edge { id, winner, loser, overwritten });
+            }
+          }
+          LISTS_NAME => {
+            let (bucket, clock, pos, value): (u64, u64, (u128, u64, Option<u128>, Option<u128>), Option<Box<[u8]>>) =
+              load(&item, &name)?;
+            self.lists.merge(txr, id, bucket, clock, pos, value)?;
+          }
+          _ => (),
+        }
+      }
+    }
+
+    Ok(report)
+  }
+}
+
+/// `(winner, loser, overwritten)`.
+type Outcome<T> = ((u64, u64, Option<T>), (u64, u64, Option<T>), bool);
+
+/// Checks whether joining `remote` decided between two different concurrent
+/// writes. `seen` is whether `remote` was received before, and `sequential`
+/// tells whether the author of a remote write had received a local one. A
+/// losing remote write is always concurrent, since it would have a later
+/// clock if made after the local one.
+fn conflict<T: PartialEq>(
+  local: Option<(u64, u64, Option<T>)>,
+  remote: (u64, u64, Option<T>),
+  applied: bool,
+  seen: bool,
+  sequential: &impl Fn((u64, u64), (u64, u64)) -> bool,
+) -> Option<Outcome<T>> {
+  let local = local?;
+  if seen || (local.0, local.1) == (remote.0, remote.1) || local.2 == remote.2 {
+    return None;
+  }
+  if applied && sequential((local.0, local.1), (remote.0, remote.1)) {
+    return None; // The remote write was made after receiving the local one.
+  }
+  match applied {
+    true => Some((remote, local, true)),
+    false => Some((local, remote, false)),
+  }
+}
+
+/// Returns whether the `remote` write was made after its author had received
+/// the `local` one, given the `(bucket, seen)` of the sender, see
+/// [`metadata::StructureMetadata::seen`]. Writes of the same bucket are made in clock
+/// order. Writes relayed from other buckets cannot be judged, so they are
+/// taken to be concurrent.
+fn sequential(
+  sender: Option<&(u64, BTreeMap<u64, BTreeMap<u64, u64>>)>,
+  local: (u64, u64),
+  remote: (u64, u64),
+) -> bool {
+  if local.0 == remote.0 {
+    return true;
+  }
+  let Some((_, seen)) = sender.filter(|(bucket, _)| *bucket == remote.0) else {
+    return false;
+  };
+  seen.get(&local.0).and_then(|seen| seen.range(local.1..).next()).is_some_and(|(_, &at)| at <= remote.1)
+}
+
+/// Removes actions with clock values beyond `limit`, recording them in
+/// `rejected`, and shrinks `ranges` so that they are not marked as received.
+fn reject<I>(
+  name: &'static str,
+  actions: &mut Vec<(u128, I)>,
+  ranges: &mut Ranges,
+  limit: u64,
+  key: impl Fn(&I) -> (u64, u64),
+  rejected: &mut Vec<(&'static str, u128, u64, u64)>,
+) {
+  actions.retain(|(id, item)| {
+    let (bucket, clock) = key(item);
+    if clock > limit {
+      rejected.push((name, *id, bucket, clock));
+    }
+    clock <= limit
+  });
+  ranges.retain(|_, (lower, upper)| {
+    *upper = (*upper).min(limit);
+    *lower < Some(*upper)
+  });
+}
+
+/// Helper for collecting a page of actions within a size budget.
+struct Page {
+  start: Option<Cursor>,
+  budget: u64,
+  used: u64,
+  cursor: Option<Cursor>,
+  ranges: BTreeMap<&'static str, Ranges>,
+  skipped: BTreeMap<&'static str, BTreeSet<u64>>,
+}
+
+impl Page {
+  fn new(start: Option<Cursor>, budget: u64) -> Self {
+    Self { start, budget, used: 0, cursor: None, ranges: BTreeMap::new(), skipped: BTreeMap::new() }
+  }
 
-    let nodes_actions: BTreeMap<u128, (u64, u64, Option<u64>)> =
-      all.get(NODES_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
-    let atoms_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, Box<[u8]>)>)> =
-      all.get(ATOMS_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
-    let edges_actions: BTreeMap<u128, (u64, u64, Option<(u128, u64, u128)>)> =
-      all.get(EDGES_NAME).map_or_else(BTreeMap::new, |m| deserialize(m).unwrap());
+  /// Collects actions of one structure satisfying `keep`, bucket by bucket in
+  /// clock order, until the budget runs out. Structures must be visited in the same order for
+  /// every page. Buckets with actions not satisfying `keep` are recorded as skipped.
+  fn collect<I: Serialize>(
+    &mut self,
+    name: &'static str,
+    buckets: BTreeMap<u64, u64>,
+    version: BTreeMap<u64, u64>,
+    fetch: impl Fn(u64, Option<u64>, u64) -> Result<Vec<(u128, I)>, StoreError>,
+    clock: impl Fn(&I) -> u64,
+    keep: impl Fn(&I) -> bool,
+  ) -> Result<BTreeMap<u128, I>, StoreError> {
+    let mut res = BTreeMap::new();
+    if self.cursor.is_some() {
+      return Ok(res);
+    }
+    // Skip structures and buckets already visited.
+    let mut start = None;
+    match self.start.take() {
+      Some((name_, bucket, clock)) if name_ == name => start = Some((bucket, clock)),
+      Some(cursor) => {
+        self.start = Some(cursor);
+        return Ok(res);
+      }
+      None => (),
+    }
+    for (bucket, upper) in buckets {
+      let mut lower = version.get(&bucket).copied();
+      match start {
+        Some((bucket_, _)) if bucket < bucket_ => continue,
+        Some((bucket_, clock)) if bucket == bucket_ => lower = lower.max(clock),
+        _ => (),
+      }
+      if Some(upper) <= lower {
+        continue;
+      }
+      let first = lower;
+      'bucket: loop {
+        // Items beyond `upper` may have been received out of order, with gaps in between.
+        let mut chunk = fetch(bucket, lower, PAGE_CHUNK)?;
+        chunk.sort_by_key(|(_, item)| clock(item));
+        chunk.truncate(PAGE_CHUNK as usize);
+        chunk.retain(|(_, item)| clock(item) <= upper);
+        if chunk.is_empty() {
+          self.ranges.entry(name).or_default().insert(bucket, (first, upper));
+          break;
+        }
+        for (id, item) in chunk {
+          if !keep(&item) {
+            self.skipped.entry(name).or_default().insert(bucket);
+            lower = Some(clock(&item));
+            continue;
+          }
+          let size = serialize(&(id, &item)).unwrap().len() as u64;
+          if self.used > 0 && self.used.saturating_add(size) > self.budget {
+            if let Some(clock) = lower.filter(|_| lower > first) {
+              self.ranges.entry(name).or_default().insert(bucket, (first, clock));
+            }
+            self.cursor = Some((name.to_owned(), bucket, lower));
+            break 'bucket;
+          }
+          self.used += size;
+          lower = Some(clock(&item));
+          res.insert(id, item);
+        }
+      }
+      if self.cursor.is_some() {
+        break;
+      }
+    }
+    Ok(res)
+  }
+}
 
-    let mut nodes_actions = nodes_actions.into_iter().collect::<Vec<_>>();
-    nodes_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
-    let mut atoms_actions = atoms_actions.into_iter().collect::<Vec<_>>();
-    atoms_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
-    let mut edges_actions = edges_actions.into_iter().collect::<Vec<_>>();
-    edges_actions.sort_by_key(|(_, (bucket, clock, _))| (*bucket, *clock));
+/// Returns whether a peer on schema version `remote`, which is compatible
+/// with versions down to `oldest`, can sync with this one. The newer of the
+/// two decides.
+fn schema_compatible(remote: u64, oldest: u64) -> bool {
+  if remote >= CURRENT_VERSION {
+    oldest <= CURRENT_VERSION
+  } else {
+    remote >= OLDEST_COMPATIBLE_VERSION
+  }
+}
 
-    for (id, (bucket, clock, l)) in nodes_actions {
-      self.nodes.set(txr, id, bucket, clock, l);
+/// Returns `(id, src, dst)` of the edges of `graph` (`src -> [(id, dst)]`)
+/// which close a cycle when visited in depth-first order. Removing them leaves
+/// the graph acyclic.
+fn back_edges(graph: &BTreeMap<u128, Vec<(u128, u128)>>) -> Vec<(u128, u128, u128)> {
+  // Nodes on the current path map to `true`, finished nodes to `false`.
+  let mut visited = BTreeMap::<u128, bool>::new();
+  let mut res = Vec::new();
+  for &root in graph.keys() {
+    if visited.contains_key(&root) {
+      continue;
     }
-    for (id, (bucket, clock, slv)) in atoms_actions {
-      self.atoms.set(txr, id, bucket, clock, slv);
+    visited.insert(root, true);
+    let mut stack = vec![(root, 0)];
+    while let Some(&(src, next)) = stack.last() {
+      match graph.get(&src).and_then(|out| out.get(next)) {
+        Some(&(id, dst)) => {
+          stack.last_mut().unwrap().1 += 1;
+          match visited.get(&dst) {
+            None => {
+              visited.insert(dst, true);
+              stack.push((dst, 0));
+            }
+            Some(true) => res.push((id, src, dst)),
+            Some(false) => {}
+          }
+        }
+        None => {
+          visited.insert(src, false);
+          stack.pop();
+        }
+      }
     }
-    for (id, (bucket, clock, sld)) in edges_actions {
-      self.edges.set(txr, id, bucket, clock, sld);
+  }
+  res
+}
+
+/// Returns the smallest byte string greater than all strings starting with
+/// `prefix`, or `None` if there is no such string.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+  let mut res = prefix.to_vec();
+  while let Some(last) = res.pop() {
+    if last < u8::MAX {
+      res.push(last + 1);
+      return Some(res);
     }
   }
+  None
+}
+
+/// Deserializes a sync payload received from a remote peer.
+fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8], what: &str) -> Result<T, StoreError> {
+  deserialize(bytes).map_err(|err| StoreError::MalformedPayload(format!("{what}: {err}")))
+}
+
+/// Deserializes data stored by this replica.
+fn load<'a, T: Deserialize<'a>>(bytes: &'a [u8], what: &str) -> Result<T, StoreError> {
+  deserialize(bytes).map_err(|err| StoreError::Corrupt(format!("{what}: {err}")))
+}
+
+/// Deserializes an entry of a sync payload. Absent entries are assumed to be empty.
+fn decode_entry<'a, T: Deserialize<'a> + Default>(
+  all: &BTreeMap<String, &'a [u8]>,
+  name: &str,
+) -> Result<T, StoreError> {
+  all.get(name).map_or_else(|| Ok(T::default()), |bytes| decode(bytes, name))
 }
 
 #[cfg(test)]
 mod tests {
   use core::panic;
 
-  use super::*;
+  use super::{metadata::WorkspaceMetadataTransactor, *};
+  use crate::{ffi::structs::COption, memory::MemoryTransactor, store::OpenOptions, Transactor};
   use rand::{seq::SliceRandom, Rng};
   use rusqlite::Connection;
 
@@ -339,105 +2298,105 @@ mod tests {
     constraints.add_sticky_node(100);
     constraints.add_sticky_atom(200);
     constraints.add_sticky_edge(300);
-    let mut ws = Workspace::new("", constraints, &mut txr);
+    let mut ws = Workspace::new("", constraints, &mut txr).unwrap();
 
     let node0 = rng.gen();
     let node1 = rng.gen();
     let node2 = rng.gen();
     let node3 = rng.gen();
-    ws.set_node(&txr, node0, Some(0));
-    ws.set_node(&txr, node1, Some(100));
-    ws.set_node(&txr, node2, Some(0));
-    ws.set_node(&txr, node3, Some(100));
-    ws.set_edge(&txr, rng.gen(), Some((node0, 2, node0)));
-    ws.set_edge(&txr, rng.gen(), Some((node0, 3, node1)));
-    ws.set_edge(&txr, rng.gen(), Some((node1, 2, node1)));
-    ws.set_edge(&txr, rng.gen(), Some((node1, 3, node0)));
-    ws.set_edge(&txr, rng.gen(), Some((node1, 2, 2333))); // Invalid
-    ws.set_edge(&txr, rng.gen(), Some((2333, 2, node1))); // Invalid
-    ws.barrier(&mut txr);
-    assert_eq!(ws.node(&txr, node0), Some(0));
-    assert_eq!(ws.node(&txr, node1), Some(100));
-    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node0).len(), 2);
-    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node0).len(), 2);
-    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node1).len(), 2);
-    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node1).len(), 2);
-
-    ws.set_node(&txr, node0, Some(2333));
-    ws.set_node(&txr, node1, Some(2333)); // Invalid
-    ws.set_edge(&txr, rng.gen(), Some((node0, 3, node1))); // Invalid
-    ws.set_edge(&txr, rng.gen(), Some((node1, 3, node0))); // Invalid
-    ws.barrier(&mut txr);
-    assert_eq!(ws.node(&txr, node0), Some(2333));
-    assert_eq!(ws.node(&txr, node1), None);
-    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node0).len(), 1);
-    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node0).len(), 1);
-    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node1).len(), 0);
-    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node1).len(), 0);
+    ws.set_node(&txr, node0, Some(0)).unwrap();
+    ws.set_node(&txr, node1, Some(100)).unwrap();
+    ws.set_node(&txr, node2, Some(0)).unwrap();
+    ws.set_node(&txr, node3, Some(100)).unwrap();
+    ws.set_edge(&txr, rng.gen(), Some((node0, 2, node0))).unwrap();
+    ws.set_edge(&txr, rng.gen(), Some((node0, 3, node1))).unwrap();
+    ws.set_edge(&txr, rng.gen(), Some((node1, 2, node1))).unwrap();
+    ws.set_edge(&txr, rng.gen(), Some((node1, 3, node0))).unwrap();
+    ws.set_edge(&txr, rng.gen(), Some((node1, 2, 2333))).unwrap(); // Invalid
+    ws.set_edge(&txr, rng.gen(), Some((2333, 2, node1))).unwrap(); // Invalid
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(ws.node(&txr, node0).unwrap(), Some(0));
+    assert_eq!(ws.node(&txr, node1).unwrap(), Some(100));
+    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node0).unwrap().len(), 2);
+    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node0).unwrap().len(), 2);
+    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node1).unwrap().len(), 2);
+    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node1).unwrap().len(), 2);
+
+    ws.set_node(&txr, node0, Some(2333)).unwrap();
+    ws.set_node(&txr, node1, Some(2333)).unwrap(); // Invalid
+    ws.set_edge(&txr, rng.gen(), Some((node0, 3, node1))).unwrap(); // Invalid
+    ws.set_edge(&txr, rng.gen(), Some((node1, 3, node0))).unwrap(); // Invalid
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(ws.node(&txr, node0).unwrap(), Some(2333));
+    assert_eq!(ws.node(&txr, node1).unwrap(), None);
+    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node0).unwrap().len(), 1);
+    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node0).unwrap().len(), 1);
+    assert_eq!(ws.edge_id_label_dst_by_src(&txr, node1).unwrap().len(), 0);
+    assert_eq!(ws.edge_id_src_label_by_dst(&txr, node1).unwrap().len(), 0);
 
     let atom0 = rng.gen();
     let atom1 = rng.gen();
     let atom2 = rng.gen();
-    ws.set_atom(&txr, atom0, Some((node0, 1, vec![1, 2, 3, 4].into())));
-    ws.set_atom(&txr, atom1, Some((node0, 200, vec![].into()))); // Overwritten
-    ws.set_atom(&txr, atom1, Some((node0, 0, vec![].into()))); // Overwritten
-    ws.set_atom(&txr, atom1, Some((node0, 200, vec![5, 6, 7].into())));
-    ws.set_atom(&txr, atom2, Some((node2, 2, vec![].into())));
-    ws.barrier(&mut txr);
-    assert!(ws.atom(&txr, atom0).is_some());
-    assert!(ws.atom(&txr, atom1).is_some());
-    assert!(ws.atom(&txr, atom2).is_some());
-
-    ws.set_atom(&txr, atom0, Some((node2, 1, vec![].into())));
-    ws.set_atom(&txr, atom1, Some((node2, 200, vec![].into()))); // Invalid, delete `node0`
-    ws.set_atom(&txr, atom2, Some((node0, 2, vec![].into()))); // Invalid, `node0` deleted
-    ws.barrier(&mut txr);
-    assert!(ws.node(&txr, node0).is_none());
-    assert!(ws.atom(&txr, atom0).is_some());
-    assert!(ws.atom(&txr, atom1).is_some());
-    assert!(ws.atom(&txr, atom2).is_none());
+    ws.set_atom(&txr, atom0, Some((node0, 1, vec![1, 2, 3, 4].into()))).unwrap();
+    ws.set_atom(&txr, atom1, Some((node0, 200, vec![].into()))).unwrap(); // Overwritten
+    ws.set_atom(&txr, atom1, Some((node0, 0, vec![].into()))).unwrap(); // Overwritten
+    ws.set_atom(&txr, atom1, Some((node0, 200, vec![5, 6, 7].into()))).unwrap();
+    ws.set_atom(&txr, atom2, Some((node2, 2, vec![].into()))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.atom(&txr, atom0).unwrap().is_some());
+    assert!(ws.atom(&txr, atom1).unwrap().is_some());
+    assert!(ws.atom(&txr, atom2).unwrap().is_some());
+
+    ws.set_atom(&txr, atom0, Some((node2, 1, vec![].into()))).unwrap();
+    ws.set_atom(&txr, atom1, Some((node2, 200, vec![].into()))).unwrap(); // Invalid, delete `node0`
+    ws.set_atom(&txr, atom2, Some((node0, 2, vec![].into()))).unwrap(); // Invalid, `node0` deleted
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.node(&txr, node0).unwrap().is_none());
+    assert!(ws.atom(&txr, atom0).unwrap().is_some());
+    assert!(ws.atom(&txr, atom1).unwrap().is_some());
+    assert!(ws.atom(&txr, atom2).unwrap().is_none());
 
     let edge0 = rng.gen();
     let edge1 = rng.gen();
     let edge2 = rng.gen();
     let edge3 = rng.gen();
-    ws.set_edge(&txr, edge0, Some((node3, 1, node0))); // Invalid
-    ws.set_edge(&txr, edge1, Some((node3, 2, node1))); // Invalid
-    ws.set_edge(&txr, edge2, Some((node3, 300, node2)));
-    ws.set_edge(&txr, edge3, Some((node3, 300, node3)));
-    ws.barrier(&mut txr);
-    assert!(ws.node(&txr, node2).is_some());
-    assert!(ws.node(&txr, node3).is_some());
-    assert!(ws.edge(&txr, edge0).is_none());
-    assert!(ws.edge(&txr, edge1).is_none());
-    assert!(ws.edge(&txr, edge2).is_some());
-    assert!(ws.edge(&txr, edge3).is_some());
-
-    ws.set_edge(&txr, rng.gen(), Some((node2, 300, node0))); // Invalid, delete `node2` (?) and `node3`
-    ws.barrier(&mut txr);
-    assert!(ws.node(&txr, node2).is_none());
-    assert!(ws.node(&txr, node3).is_none());
+    ws.set_edge(&txr, edge0, Some((node3, 1, node0))).unwrap(); // Invalid
+    ws.set_edge(&txr, edge1, Some((node3, 2, node1))).unwrap(); // Invalid
+    ws.set_edge(&txr, edge2, Some((node3, 300, node2))).unwrap();
+    ws.set_edge(&txr, edge3, Some((node3, 300, node3))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.node(&txr, node2).unwrap().is_some());
+    assert!(ws.node(&txr, node3).unwrap().is_some());
+    assert!(ws.edge(&txr, edge0).unwrap().is_none());
+    assert!(ws.edge(&txr, edge1).unwrap().is_none());
+    assert!(ws.edge(&txr, edge2).unwrap().is_some());
+    assert!(ws.edge(&txr, edge3).unwrap().is_some());
+
+    ws.set_edge(&txr, rng.gen(), Some((node2, 300, node0))).unwrap(); // Invalid, delete `node2` (?) and `node3`
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.node(&txr, node2).unwrap().is_none());
+    assert!(ws.node(&txr, node3).unwrap().is_none());
 
     const N: usize = 2333;
     let nodes: Vec<u128> = (0..N + 1).map(|_| rng.gen()).collect();
     let edges: Vec<u128> = (0..N).map(|_| rng.gen()).collect();
     let atom = rng.gen();
     for i in 0..N {
-      ws.set_node(&txr, nodes[i], Some(0));
-      ws.set_edge(&txr, edges[i], Some((nodes[i], 300, nodes[i + rng.gen_range(1..=(N - i))])));
+      ws.set_node(&txr, nodes[i], Some(0)).unwrap();
+      ws.set_edge(&txr, edges[i], Some((nodes[i], 300, nodes[i + rng.gen_range(1..=(N - i))]))).unwrap();
     }
-    ws.set_node(&txr, nodes[N], Some(0));
-    ws.set_atom(&txr, atom, Some((nodes[N], 200, vec![].into())));
-    ws.barrier(&mut txr);
+    ws.set_node(&txr, nodes[N], Some(0)).unwrap();
+    ws.set_atom(&txr, atom, Some((nodes[N], 200, vec![].into()))).unwrap();
+    ws.barrier(&mut txr).unwrap();
     for i in 0..N {
-      assert!(ws.node(&txr, nodes[i]).is_some());
-      assert!(ws.edge(&txr, edges[i]).is_some());
+      assert!(ws.node(&txr, nodes[i]).unwrap().is_some());
+      assert!(ws.edge(&txr, edges[i]).unwrap().is_some());
     }
-    ws.set_atom(&txr, atom, Some((nodes[N], 2333, vec![].into()))); // Invalid, delete `nodes` and `edges`
-    ws.barrier(&mut txr);
+    ws.set_atom(&txr, atom, Some((nodes[N], 2333, vec![].into()))).unwrap(); // Invalid, delete `nodes` and `edges`
+    ws.barrier(&mut txr).unwrap();
     for i in 0..N {
-      assert!(ws.node(&txr, nodes[i]).is_none());
-      assert!(ws.edge(&txr, edges[i]).is_none());
+      assert!(ws.node(&txr, nodes[i]).unwrap().is_none());
+      assert!(ws.edge(&txr, edges[i]).unwrap().is_none());
     }
   }
 
@@ -454,7 +2413,7 @@ mod tests {
     for round in 50..100 {
       let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
       let mut rng = rand::thread_rng();
-      let mut ws = Workspace::new("", constraints.clone(), &mut txr);
+      let mut ws = Workspace::new("", constraints.clone(), &mut txr).unwrap();
 
       let mut nodes = vec![];
       let mut atoms = vec![];
@@ -464,7 +2423,7 @@ mod tests {
       for _ in 0..300 {
         let node = rng.gen();
         let label = rng.gen_range(0..K * 2);
-        ws.set_node(&txr, node, Some(label));
+        ws.set_node(&txr, node, Some(label)).unwrap();
         nodes.push((node, vec![], vec![]));
       }
 
@@ -473,7 +2432,7 @@ mod tests {
         let atom = rng.gen();
         let i = rng.gen_range(0..nodes.len());
         let label = rng.gen_range(0..K * 2);
-        ws.set_atom(&txr, atom, Some((nodes[i].0, label, vec![].into())));
+        ws.set_atom(&txr, atom, Some((nodes[i].0, label, vec![].into()))).unwrap();
         if label < K {
           nodes[i].1.push((atom, label));
         }
@@ -486,7 +2445,7 @@ mod tests {
         let i = rng.gen_range(0..nodes.len());
         let j = rng.gen_range(0..nodes.len());
         let label = rng.gen_range(0..K * 2);
-        ws.set_edge(&txr, edge, Some((nodes[i].0, label, nodes[j].0)));
+        ws.set_edge(&txr, edge, Some((nodes[i].0, label, nodes[j].0))).unwrap();
         if label < K {
           nodes[i].2.push((edge, label));
         }
@@ -494,7 +2453,7 @@ mod tests {
       }
 
       // Done.
-      ws.barrier(&mut txr);
+      ws.barrier(&mut txr).unwrap();
 
       // Generate operations.
       for _ in 0..round {
@@ -505,7 +2464,7 @@ mod tests {
             if rng.gen_ratio(1, 16) {
               node = rng.gen();
             }
-            let mut value = ws.node(&txr, node);
+            let mut value = ws.node(&txr, node).unwrap();
             if rng.gen_ratio(1, 16) {
               value = None;
             }
@@ -514,7 +2473,7 @@ mod tests {
                 *inner = rng.gen_range(0..K * 2);
               }
             }
-            ws.set_node(&txr, node, value);
+            ws.set_node(&txr, node, value).unwrap();
           }
           1 => {
             // Randomly mutate atom.
@@ -522,7 +2481,7 @@ mod tests {
             if rng.gen_ratio(1, 16) {
               atom = rng.gen();
             }
-            let mut value = ws.atom(&txr, atom);
+            let mut value = ws.atom(&txr, atom).unwrap();
             if rng.gen_ratio(1, 16) {
               value = None;
             }
@@ -540,7 +2499,7 @@ mod tests {
                 inner.1 = rng.gen();
               }
             }
-            ws.set_atom(&txr, atom, value);
+            ws.set_atom(&txr, atom, value).unwrap();
           }
           2 => {
             // Randomly mutate edge.
@@ -548,7 +2507,7 @@ mod tests {
             if rng.gen_ratio(1, 16) {
               edge = rng.gen();
             }
-            let mut value = ws.edge(&txr, edge);
+            let mut value = ws.edge(&txr, edge).unwrap();
             if rng.gen_ratio(1, 16) {
               value = None;
             }
@@ -569,38 +2528,38 @@ mod tests {
                 inner.2 = rng.gen();
               }
             }
-            ws.set_edge(&txr, edge, value);
+            ws.set_edge(&txr, edge, value).unwrap();
           }
           _ => panic!(),
         }
       }
 
       // Done.
-      ws.barrier(&mut txr);
+      ws.barrier(&mut txr).unwrap();
 
       // Check invariants.
       // (1)
       for atom in atoms {
-        if let Some((src, _, _)) = ws.atom(&txr, atom) {
-          assert!(ws.node(&txr, src).is_some());
+        if let Some((src, _, _)) = ws.atom(&txr, atom).unwrap() {
+          assert!(ws.node(&txr, src).unwrap().is_some());
         }
       }
       // (2)
       for edge in edges {
-        if let Some((src, _, dst)) = ws.edge(&txr, edge) {
-          assert!(ws.node(&txr, src).is_some());
-          assert!(ws.node(&txr, dst).is_some());
+        if let Some((src, _, dst)) = ws.edge(&txr, edge).unwrap() {
+          assert!(ws.node(&txr, src).unwrap().is_some());
+          assert!(ws.node(&txr, dst).unwrap().is_some());
         }
       }
       // (3)
       let mut count = 0;
       for (node, ratoms, redges) in nodes {
-        if ws.node(&txr, node).is_some() {
+        if ws.node(&txr, node).unwrap().is_some() {
           for (ratom, label) in ratoms {
-            assert_eq!(ws.atom(&txr, ratom).map(|(src, label, _)| (src, label)), Some((node, label)));
+            assert_eq!(ws.atom(&txr, ratom).unwrap().map(|(src, label, _)| (src, label)), Some((node, label)));
           }
           for (redge, label) in redges {
-            assert_eq!(ws.edge(&txr, redge).map(|(src, label, _)| (src, label)), Some((node, label)));
+            assert_eq!(ws.edge(&txr, redge).unwrap().map(|(src, label, _)| (src, label)), Some((node, label)));
           }
           count += 1;
         }
@@ -616,40 +2575,1129 @@ mod tests {
     let mut constraints = Constraints::new();
     constraints.add_sticky_edge(0);
     constraints.add_acyclic_edge(0);
-    let mut ws = Workspace::new("", constraints, &mut txr);
+    let mut ws = Workspace::new("", constraints, &mut txr).unwrap();
 
     let node0 = rng.gen();
     let node1 = rng.gen();
     let node2 = rng.gen();
     let node3 = rng.gen();
-    ws.set_node(&txr, node0, Some(0));
-    ws.set_node(&txr, node1, Some(0));
-    ws.set_node(&txr, node2, Some(0));
-    ws.set_node(&txr, node3, Some(0));
+    ws.set_node(&txr, node0, Some(0)).unwrap();
+    ws.set_node(&txr, node1, Some(0)).unwrap();
+    ws.set_node(&txr, node2, Some(0)).unwrap();
+    ws.set_node(&txr, node3, Some(0)).unwrap();
     let edge0 = rng.gen();
     let edge1 = rng.gen();
     let edge2 = rng.gen();
     let edge3 = rng.gen();
-    ws.set_edge(&txr, edge0, Some((node0, 0, node1)));
-    ws.set_edge(&txr, edge1, Some((node1, 0, node2)));
-    ws.set_edge(&txr, edge2, Some((node2, 0, node3)));
-    ws.barrier(&mut txr);
-    assert!(ws.node(&txr, node0).is_some());
-    assert!(ws.node(&txr, node1).is_some());
-    assert!(ws.node(&txr, node2).is_some());
-    assert!(ws.node(&txr, node3).is_some());
-    assert!(ws.edge(&txr, edge0).is_some());
-    assert!(ws.edge(&txr, edge1).is_some());
-    assert!(ws.edge(&txr, edge2).is_some());
-
-    ws.set_edge(&txr, edge3, Some((node2, 0, node0)));
-    ws.barrier(&mut txr);
-    assert!(ws.node(&txr, node0).is_none());
-    assert!(ws.node(&txr, node1).is_none());
-    assert!(ws.node(&txr, node2).is_none());
-    assert!(ws.node(&txr, node3).is_some());
-    assert!(ws.edge(&txr, edge0).is_none());
-    assert!(ws.edge(&txr, edge1).is_none());
-    assert!(ws.edge(&txr, edge2).is_none());
+    ws.set_edge(&txr, edge0, Some((node0, 0, node1))).unwrap();
+    ws.set_edge(&txr, edge1, Some((node1, 0, node2))).unwrap();
+    ws.set_edge(&txr, edge2, Some((node2, 0, node3))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.node(&txr, node0).unwrap().is_some());
+    assert!(ws.node(&txr, node1).unwrap().is_some());
+    assert!(ws.node(&txr, node2).unwrap().is_some());
+    assert!(ws.node(&txr, node3).unwrap().is_some());
+    assert!(ws.edge(&txr, edge0).unwrap().is_some());
+    assert!(ws.edge(&txr, edge1).unwrap().is_some());
+    assert!(ws.edge(&txr, edge2).unwrap().is_some());
+
+    ws.set_edge(&txr, edge3, Some((node2, 0, node0))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert!(ws.node(&txr, node0).unwrap().is_none());
+    assert!(ws.node(&txr, node1).unwrap().is_none());
+    assert!(ws.node(&txr, node2).unwrap().is_none());
+    assert!(ws.node(&txr, node3).unwrap().is_some());
+    assert!(ws.edge(&txr, edge0).unwrap().is_none());
+    assert!(ws.edge(&txr, edge1).unwrap().is_none());
+    assert!(ws.edge(&txr, edge2).unwrap().is_none());
+  }
+
+  #[test]
+  fn list_simple() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws = Workspace::new("", Constraints::new(), &mut txr).unwrap();
+
+    let src = rng.gen();
+    let values = |ws: &Workspace, txr: &Transactor| -> Vec<u8> {
+      ws.list_id_value_by_src_label(txr, src, 0).unwrap().into_iter().map(|(_, value)| value[0]).collect()
+    };
+
+    let item0 = rng.gen();
+    let item1 = rng.gen();
+    let item2 = rng.gen();
+    let item3 = rng.gen();
+    ws.insert_list_item(&txr, item0, src, 0, None, vec![0].into()).unwrap();
+    ws.insert_list_item(&txr, item1, src, 0, Some(item0), vec![1].into()).unwrap();
+    ws.insert_list_item(&txr, item2, src, 0, None, vec![2].into()).unwrap();
+    assert_eq!(values(&ws, &txr), [2, 0, 1]);
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(values(&ws, &txr), [2, 0, 1]);
+
+    ws.remove_list_item(&txr, item0).unwrap();
+    assert_eq!(values(&ws, &txr), [2, 1]);
+    ws.insert_list_item(&txr, item3, src, 0, Some(item0), vec![3].into()).unwrap();
+    assert_eq!(values(&ws, &txr), [2, 3, 1]);
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(values(&ws, &txr), [2, 3, 1]);
+    assert!(ws.list_id_value_by_src_label(&txr, src, 1).unwrap().is_empty());
+
+    let res = ws.insert_list_item(&txr, item1, src, 0, None, vec![4].into());
+    assert!(matches!(res, Err(StoreError::DuplicateListItem(id)) if id == item1));
+    let res = ws.insert_list_item(&txr, rng.gen(), src, 1, Some(item1), vec![4].into());
+    assert!(matches!(res, Err(StoreError::UnknownListItem(id)) if id == item1));
+    assert_eq!(values(&ws, &txr), [2, 3, 1]);
+
+    // Removing the node the list starts from removes its items too.
+    ws.set_node(&txr, src, Some(0)).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    ws.set_node(&txr, src, None).unwrap();
+    let events = ws.barrier(&mut txr).unwrap();
+    assert!(values(&ws, &txr).is_empty());
+    let removed = events
+      .iter()
+      .filter(|event| matches!(event, CEventData::List { prev: COption::Some(_), curr: COption::None, .. }));
+    assert_eq!(removed.count(), 3);
+  }
+
+  #[test]
+  fn list_concurrent() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+
+    let src = rng.gen();
+    let mut next = 0u8;
+    for _ in 0..20 {
+      for (ws, txr) in [(&mut ws0, &mut txr0), (&mut ws1, &mut txr1)] {
+        for _ in 0..rng.gen_range(0..5) {
+          let items = ws.list_id_value_by_src_label(txr, src, 0).unwrap();
+          if !items.is_empty() && rng.gen_ratio(1, 4) {
+            let (item, _) = items.choose(&mut rng).unwrap();
+            ws.remove_list_item(txr, *item).unwrap();
+          } else {
+            let left = items.choose(&mut rng).filter(|_| rng.gen_ratio(3, 4)).map(|(item, _)| *item);
+            let item = rng.gen();
+            ws.insert_list_item(txr, item, src, 0, left, vec![next].into()).unwrap();
+            let index = left.map_or(0, |left| items.iter().position(|(item, _)| *item == left).unwrap() + 1);
+            assert_eq!(ws.list_id_value_by_src_label(txr, src, 0).unwrap()[index].0, item);
+            next = next.wrapping_add(1);
+          }
+        }
+        ws.barrier(txr).unwrap();
+      }
+      let version0 = ws0.sync_version(&txr0);
+      let version1 = ws1.sync_version(&txr1);
+      let actions0 = ws0.sync_actions(&txr0, &version1).unwrap();
+      let actions1 = ws1.sync_actions(&txr1, &version0).unwrap();
+      ws0.sync_join(&mut txr0, &actions1).unwrap();
+      ws1.sync_join(&mut txr1, &actions0).unwrap();
+      ws0.barrier(&mut txr0).unwrap();
+      ws1.barrier(&mut txr1).unwrap();
+      assert_eq!(
+        ws0.list_id_value_by_src_label(&txr0, src, 0).unwrap(),
+        ws1.list_id_value_by_src_label(&txr1, src, 0).unwrap()
+      );
+    }
+  }
+
+  #[test]
+  fn sync_malformed() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    let node = rng.gen();
+    ws0.set_node(&txr0, node, Some(0)).unwrap();
+    ws0.set_atom(&txr0, rng.gen(), Some((node, 0, vec![0; 100].into()))).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+
+    assert!(matches!(ws0.sync_actions(&txr0, &[1, 2, 3]), Err(StoreError::MalformedPayload(_))));
+    let version = serialize(&BTreeMap::from([(NODES_NAME, vec![1, 2, 3])])).unwrap();
+    assert!(matches!(ws0.sync_actions(&txr0, &version), Err(StoreError::MalformedPayload(_))));
+
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    for len in 0..actions.len() {
+      assert!(matches!(ws1.sync_join(&mut txr1, &actions[..len]), Err(StoreError::MalformedPayload(_))));
+    }
+    let mut corrupted = actions.to_vec();
+    corrupted.push(0);
+    assert!(matches!(ws1.sync_join(&mut txr1, &corrupted), Err(StoreError::MalformedPayload(_))));
+    assert!(ws1.barrier(&mut txr1).unwrap().is_empty());
+    assert!(ws1.node(&txr1, node).unwrap().is_none());
+
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+    assert_eq!(ws1.barrier(&mut txr1).unwrap().len(), 2);
+    assert_eq!(ws1.node(&txr1, node).unwrap(), Some(0));
+  }
+
+  #[test]
+  fn sync_handshake() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    let mut ws2 = Workspace::new("", Constraints::new(), &mut txr2).unwrap();
+    ws0.set_node(&txr0, rng.gen(), Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    ws2.set_node(&txr2, rng.gen(), Some(0)).unwrap();
+    ws2.barrier(&mut txr2).unwrap();
+
+    // Pristine replicas adopt the workspace ID.
+    assert!(ws1.is_pristine());
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+    assert!(!ws1.is_pristine());
+    let actions = ws1.sync_actions(&txr1, &ws0.sync_version(&txr0)).unwrap();
+    ws0.sync_join(&mut txr0, &actions).unwrap();
+
+    // Unrelated workspaces refuse to merge.
+    let version = ws2.sync_version(&txr2);
+    assert!(matches!(ws0.sync_actions(&txr0, &version), Err(StoreError::WorkspaceMismatch(_, _))));
+    let actions = ws2.sync_actions(&txr2, &ws2.sync_version(&txr2)).unwrap();
+    assert!(matches!(ws0.sync_join(&mut txr0, &actions), Err(StoreError::WorkspaceMismatch(_, _))));
+    // Even when they request nothing.
+    let handshake = serialize(&ws2.handshake()).unwrap();
+    let empty = serialize(&BTreeMap::from([(HANDSHAKE_NAME, handshake)])).unwrap();
+    assert!(matches!(ws0.sync_actions(&txr0, &empty), Err(StoreError::WorkspaceMismatch(_, _))));
+
+    // Pristine replicas match any workspace, in both directions.
+    let mut txr3: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws3 = Workspace::new("", Constraints::new(), &mut txr3).unwrap();
+    let actions = ws3.sync_actions(&txr3, &ws0.sync_version(&txr0)).unwrap();
+    ws0.sync_join(&mut txr0, &actions).unwrap();
+    assert_ne!(ws0.metadata.id(), ws3.metadata.id());
+    let actions = ws0.sync_actions(&txr0, &ws3.sync_version(&txr3)).unwrap();
+    ws3.sync_join(&mut txr3, &actions).unwrap();
+    assert_eq!(ws3.metadata.id(), ws0.metadata.id());
+
+    // Incompatible peers are refused.
+    let legacy = serialize(&BTreeMap::from([(NODES_NAME, serialize(&BTreeMap::<u64, u64>::new()).unwrap())])).unwrap();
+    assert!(matches!(ws0.sync_actions(&txr0, &legacy), Err(StoreError::ProtocolMismatch(PROTOCOL_VERSION, 0))));
+    assert!(matches!(ws0.sync_join(&mut txr0, &legacy), Err(StoreError::ProtocolMismatch(PROTOCOL_VERSION, 0))));
+    let handshake = serialize(&(PROTOCOL_VERSION, CURRENT_VERSION + 1, ws0.metadata.id())).unwrap();
+    let newer = serialize(&BTreeMap::from([(HANDSHAKE_NAME, handshake.clone())])).unwrap();
+    assert!(matches!(ws0.sync_actions(&txr0, &newer), Err(StoreError::SchemaMismatch(CURRENT_VERSION, _))));
+    assert!(matches!(ws0.sync_join(&mut txr0, &newer), Err(StoreError::SchemaMismatch(CURRENT_VERSION, _))));
+    let older = serialize(&(PROTOCOL_VERSION, OLDEST_COMPATIBLE_VERSION - 1, ws0.metadata.id())).unwrap();
+    let older = serialize(&BTreeMap::from([(HANDSHAKE_NAME, older)])).unwrap();
+    assert!(matches!(
+      ws0.sync_actions(&txr0, &older),
+      Err(StoreError::SchemaMismatch(CURRENT_VERSION, version)) if version == OLDEST_COMPATIBLE_VERSION - 1
+    ));
+
+    // Newer peers which are still compatible with this version are accepted.
+    let compatible = serialize(&CURRENT_VERSION).unwrap();
+    let newer = serialize(&BTreeMap::from([(HANDSHAKE_NAME, handshake), (COMPATIBLE_NAME, compatible)])).unwrap();
+    ws0.sync_actions(&txr0, &newer).unwrap();
+    ws0.sync_join(&mut txr0, &newer).unwrap();
+  }
+
+  fn clocks(ws: &Workspace) -> [BTreeMap<u64, u64>; 4] {
+    [ws.nodes.buckets(), ws.atoms.buckets(), ws.edges.buckets(), ws.lists.buckets()]
+  }
+
+  #[test]
+  fn sync_paginated() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    let src = rng.gen();
+    let mut left = None;
+    for i in 0..100u8 {
+      let node = rng.gen();
+      ws0.set_node(&txr0, node, Some(0)).unwrap();
+      ws0.set_atom(&txr0, rng.gen(), Some((node, 0, vec![i; 10].into()))).unwrap();
+      let item = rng.gen();
+      ws0.insert_list_item(&txr0, item, src, 0, left, vec![i].into()).unwrap();
+      left = Some(item);
+      if i % 10 == 0 {
+        ws0.barrier(&mut txr0).unwrap();
+      }
+    }
+
+    // Collect pages, then join them out of order with one page missing.
+    let version = ws1.sync_version(&txr1);
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+      let (page, next) = ws0.sync_actions_page(&txr0, &version, cursor.as_deref(), 500).unwrap();
+      assert!(page.len() < 1500); // Including manifests of the transactions touched.
+      pages.push(page);
+      cursor = match next {
+        Some(next) => Some(next),
+        None => break,
+      };
+    }
+    assert!(pages.len() > 10);
+    let missing = pages.remove(pages.len() / 2);
+    pages.shuffle(&mut rng);
+    for page in &pages {
+      ws1.sync_join(&mut txr1, page).unwrap();
+    }
+    assert_ne!(clocks(&ws0), clocks(&ws1));
+
+    // Resuming from the reported version fills the gap.
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    assert!(actions.len() < pages.iter().map(|page| page.len()).sum());
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+    assert_eq!(
+      ws0.list_id_value_by_src_label(&txr0, src, 0).unwrap(),
+      ws1.list_id_value_by_src_label(&txr1, src, 0).unwrap()
+    );
+    assert_eq!(ws0.atom_id_src_value_by_label(&txr0, 0).unwrap(), ws1.atom_id_src_value_by_label(&txr1, 0).unwrap());
+    assert_eq!(ws0.node_id_by_label(&txr0, 0).unwrap(), ws1.node_id_by_label(&txr1, 0).unwrap());
+
+    // The missing page is now redundant.
+    ws1.sync_join(&mut txr1, &missing).unwrap();
+    assert!(ws1.barrier(&mut txr1).unwrap().is_empty());
+  }
+
+  #[test]
+  fn sync_transactions() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut constraints = Constraints::new();
+    constraints.add_sticky_atom(0);
+    let mut ws0 = Workspace::new("", constraints.clone(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", constraints.clone(), &mut txr1).unwrap();
+    let mut ws2 = Workspace::new("", constraints, &mut txr2).unwrap();
+
+    // One transaction spanning several pages.
+    let node = rng.gen();
+    ws0.set_node(&txr0, node, Some(0)).unwrap();
+    for i in 0..50u8 {
+      ws0.set_atom(&txr0, rng.gen(), Some((node, 0, vec![i; 10].into()))).unwrap();
+    }
+    ws0.barrier(&mut txr0).unwrap();
+
+    let version = ws1.sync_version(&txr1);
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+      let (page, next) = ws0.sync_actions_page(&txr0, &version, cursor.as_deref(), 500).unwrap();
+      pages.push(page);
+      cursor = match next {
+        Some(next) => Some(next),
+        None => break,
+      };
+    }
+    assert!(pages.len() > 2);
+    let last = pages.pop().unwrap();
+
+    // Nothing is visible or checked until the whole transaction has arrived.
+    for page in &pages {
+      ws1.sync_join(&mut txr1, page).unwrap();
+      assert!(ws1.barrier(&mut txr1).unwrap().is_empty());
+      assert!(!ws1.nodes.exists(&txr1, node).unwrap());
+    }
+
+    // Staged items are passed on to other peers.
+    let actions = ws1.sync_actions(&txr1, &ws2.sync_version(&txr2)).unwrap();
+    ws2.sync_join(&mut txr2, &actions).unwrap();
+    assert!(ws2.barrier(&mut txr2).unwrap().is_empty());
+
+    // Transactions are kept staged by the time they arrived here, so that one
+    // written long ago is not applied before its last page.
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let mut constraints = Constraints::new();
+    constraints.set_staged_ttl(100_000_000);
+    let mut txr4: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws4 = Workspace::new("", constraints, &mut txr4).unwrap();
+    for page in &pages {
+      ws4.sync_join(&mut txr4, page).unwrap();
+      assert!(ws4.barrier(&mut txr4).unwrap().is_empty());
+      assert!(!ws4.nodes.exists(&txr4, node).unwrap());
+    }
+    ws4.sync_join(&mut txr4, &last).unwrap();
+    assert_eq!(ws4.barrier(&mut txr4).unwrap().len(), 51);
+
+    // Transactions staged for too long are applied as far as received.
+    let mut txr3: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws3 = Workspace::new("", Constraints::new(), &mut txr3).unwrap();
+    ws3.sync_join(&mut txr3, &pages[0]).unwrap();
+    assert!(ws3.barrier(&mut txr3).unwrap().is_empty());
+    ws3.constraints.set_staged_ttl(0);
+    ws3.sync_join(&mut txr3, &pages[1]).unwrap();
+    assert!(!ws3.barrier(&mut txr3).unwrap().is_empty());
+    assert!(ws3.nodes.exists(&txr3, node).unwrap());
+    assert!(ws3.txns.pending(&txr3).unwrap().is_empty());
+
+    ws1.sync_join(&mut txr1, &last).unwrap();
+    assert_eq!(ws1.barrier(&mut txr1).unwrap().len(), 51);
+    assert_eq!(ws1.atom_id_src_value_by_label(&txr1, 0).unwrap(), ws0.atom_id_src_value_by_label(&txr0, 0).unwrap());
+
+    let actions = ws1.sync_actions(&txr1, &ws2.sync_version(&txr2)).unwrap();
+    ws2.sync_join(&mut txr2, &actions).unwrap();
+    assert_eq!(ws2.barrier(&mut txr2).unwrap().len(), 51);
+    assert_eq!(clocks(&ws0), clocks(&ws2));
+  }
+
+  #[test]
+  fn sync_filtered() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    let mut write = |ws0: &mut Workspace, txr0: &mut Transactor| {
+      for label in 0..2 {
+        let node = rng.gen();
+        ws0.set_node(txr0, node, Some(label)).unwrap();
+        ws0.set_atom(txr0, rng.gen(), Some((node, label, vec![0; 10].into()))).unwrap();
+      }
+      ws0.barrier(txr0).unwrap();
+    };
+    let sync = |ws0: &Workspace, txr0: &Transactor, ws1: &mut Workspace, txr1: &mut Transactor, filter| {
+      let version = ws1.sync_version_filtered(txr1, filter);
+      let actions = ws0.sync_actions(txr0, &version).unwrap();
+      ws1.sync_join(txr1, &actions).unwrap();
+      ws1.barrier(txr1).unwrap();
+      let all: BTreeMap<String, &[u8]> = decode(&actions, "actions").unwrap();
+      let nodes: BTreeMap<u128, (u64, u64, Option<u64>)> = decode_entry(&all, NODES_NAME).unwrap();
+      nodes.len()
+    };
+
+    let mut narrow = ReplicationFilter::new();
+    narrow.add_node(0);
+    narrow.add_atom(0);
+    let mut wide = narrow.clone();
+    wide.add_node(1);
+    wide.add_atom(1);
+
+    for _ in 0..5 {
+      write(&mut ws0, &mut txr0);
+      sync(&ws0, &txr0, &mut ws1, &mut txr1, &narrow);
+      assert_eq!(ws1.node_id_by_label(&txr1, 0).unwrap(), ws0.node_id_by_label(&txr0, 0).unwrap());
+      assert_eq!(ws1.atom_id_src_value_by_label(&txr1, 0).unwrap(), ws0.atom_id_src_value_by_label(&txr0, 0).unwrap());
+      assert!(ws1.node_id_by_label(&txr1, 1).unwrap().is_empty());
+      assert!(ws1.atom_id_src_value_by_label(&txr1, 1).unwrap().is_empty());
+    }
+    assert!(!ws1.is_pristine());
+    assert_ne!(clocks(&ws0), clocks(&ws1));
+    assert_eq!(sync(&ws0, &txr0, &mut ws1, &mut txr1, &narrow), 0);
+
+    // A wider filter backfills items missed earlier.
+    sync(&ws0, &txr0, &mut ws1, &mut txr1, &wide);
+    assert_eq!(ws1.node_id_by_label(&txr1, 1).unwrap(), ws0.node_id_by_label(&txr0, 1).unwrap());
+    assert_eq!(ws1.atom_id_src_value_by_label(&txr1, 1).unwrap(), ws0.atom_id_src_value_by_label(&txr0, 1).unwrap());
+    assert_eq!(sync(&ws0, &txr0, &mut ws1, &mut txr1, &wide), 0);
+
+    // A filter matching everything advances unfiltered clocks as well.
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws2 = Workspace::new("", Constraints::new(), &mut txr2).unwrap();
+    sync(&ws0, &txr0, &mut ws2, &mut txr2, &wide);
+    assert_eq!(clocks(&ws0), clocks(&ws2));
+    let actions = ws0.sync_actions(&txr0, &ws2.sync_version(&txr2)).unwrap();
+    let all: BTreeMap<String, &[u8]> = decode(&actions, "actions").unwrap();
+    let nodes: BTreeMap<u128, (u64, u64, Option<u64>)> = decode_entry(&all, NODES_NAME).unwrap();
+    assert!(nodes.is_empty());
+
+    // Atoms whose nodes were filtered out are kept.
+    let mut txr3: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws3 = Workspace::new("", Constraints::new(), &mut txr3).unwrap();
+    let mut atoms = narrow.clone();
+    atoms.add_atom(1);
+    sync(&ws0, &txr0, &mut ws3, &mut txr3, &atoms);
+    assert!(ws3.node_id_by_label(&txr3, 1).unwrap().is_empty());
+    assert_eq!(ws3.atom_id_src_value_by_label(&txr3, 1).unwrap(), ws0.atom_id_src_value_by_label(&txr0, 1).unwrap());
+  }
+
+  #[test]
+  fn sync_compaction() {
+    let mut txrs: Vec<Transactor> = (0..4).map(|_| Connection::open_in_memory().unwrap().try_into().unwrap()).collect();
+    let mut wss: Vec<Workspace> =
+      txrs.iter_mut().map(|txr| Workspace::new("", Constraints::new(), txr).unwrap()).collect();
+    let mut rng = rand::thread_rng();
+
+    // Pulls from replica 0 into replica `i`, then acknowledges the new version.
+    let sync = |wss: &mut Vec<Workspace>, txrs: &mut Vec<Transactor>, i: usize| {
+      let version = wss[i].sync_version(&txrs[i]);
+      let actions = wss[0].sync_actions(&txrs[0], &version).unwrap();
+      wss[i].sync_join(&mut txrs[i], &actions).unwrap();
+      wss[i].barrier(&mut txrs[i]).unwrap();
+      let version = wss[i].sync_version(&txrs[i]);
+      wss[0].acknowledge(&mut txrs[0], &version).unwrap();
+    };
+
+    let nodes: Vec<u128> = (0..10).map(|_| rng.gen()).collect();
+    for &node in &nodes {
+      wss[0].set_node(&txrs[0], node, Some(0)).unwrap();
+    }
+    wss[0].barrier(&mut txrs[0]).unwrap();
+    let (this1, this2) = (wss[1].this(), wss[2].this());
+    wss[0].add_peer(&mut txrs[0], this1).unwrap();
+    wss[0].add_peer(&mut txrs[0], this2).unwrap();
+    for i in 1..4 {
+      sync(&mut wss, &mut txrs, i);
+    }
+
+    // Replica 3 writes something replica 0 has not seen, and goes offline.
+    let own = rng.gen();
+    wss[3].set_node(&txrs[3], own, Some(0)).unwrap();
+    wss[3].barrier(&mut txrs[3]).unwrap();
+
+    for &node in &nodes[..5] {
+      wss[0].set_node(&txrs[0], node, None).unwrap();
+    }
+    wss[0].barrier(&mut txrs[0]).unwrap();
+    sync(&mut wss, &mut txrs, 1);
+    assert_eq!(wss[0].compact(&mut txrs[0]).unwrap(), 0);
+    sync(&mut wss, &mut txrs, 2);
+    assert_eq!(wss[0].compact(&mut txrs[0]).unwrap(), 5);
+    assert_eq!(wss[0].compact(&mut txrs[0]).unwrap(), 0);
+
+    // Replica 3 missed the removals, which are gone now. It gets everything
+    // again, in pages joined out of order.
+    let version = wss[3].sync_version(&txrs[3]);
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+      let (page, next) = wss[0].sync_actions_page(&txrs[0], &version, cursor.as_deref(), 100).unwrap();
+      pages.push(page);
+      cursor = next;
+      if cursor.is_none() {
+        break;
+      }
+    }
+    assert!(pages.len() > 1);
+    for page in pages.iter().rev() {
+      wss[3].sync_join(&mut txrs[3], page).unwrap();
+      wss[3].barrier(&mut txrs[3]).unwrap();
+    }
+    let mut expected = wss[0].node_id_by_label(&txrs[0], 0).unwrap();
+    assert_eq!(expected.len(), 5);
+    expected.insert(own, ());
+    assert_eq!(wss[3].node_id_by_label(&txrs[3], 0).unwrap(), expected);
+    assert_eq!(wss[2].node_id_by_label(&txrs[2], 0).unwrap(), wss[0].node_id_by_label(&txrs[0], 0).unwrap());
+
+    // The removals are written anew, so that they reach peers which missed them too.
+    let this3 = wss[3].this();
+    for &node in &nodes[..5] {
+      assert_eq!(wss[3].nodes.get(&txrs[3], node).unwrap().map(|item| (item.0, item.2)), Some((this3, None)));
+    }
+  }
+
+  #[test]
+  fn list_compaction() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut constraints = Constraints::new();
+    constraints.set_retention(0);
+    let mut ws = Workspace::new("", constraints, &mut txr).unwrap();
+    let (src, a, b, c) = (1, 2, 3, 4);
+    ws.insert_list_item(&txr, a, src, 0, None, vec![0].into()).unwrap();
+    ws.insert_list_item(&txr, b, src, 0, Some(a), vec![1].into()).unwrap();
+    ws.insert_list_item(&txr, c, src, 0, Some(b), vec![2].into()).unwrap();
+    ws.barrier(&mut txr).unwrap();
+
+    // Removed items which others refer to are kept.
+    ws.remove_list_item(&txr, b).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(ws.compact(&mut txr).unwrap(), 0);
+    assert!(ws.lists.get(&txr, b).unwrap().is_some());
+
+    ws.remove_list_item(&txr, c).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    let mut deleted = 0;
+    loop {
+      match ws.compact(&mut txr).unwrap() {
+        0 => break,
+        n => deleted += n,
+      }
+    }
+    assert_eq!(deleted, 2);
+    assert!(ws.lists.get(&txr, b).unwrap().is_none());
+    assert_eq!(ws.list_id_value_by_src_label(&txr, src, 0).unwrap(), vec![(a, vec![0].into())]);
+  }
+
+  #[test]
+  fn sync_conflicts() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    let sync = |ws0: &mut Workspace, txr0: &mut Transactor, ws1: &mut Workspace, txr1: &mut Transactor| {
+      let actions = ws0.sync_actions(txr0, &ws1.sync_version(txr1)).unwrap();
+      let report = ws1.sync_join(txr1, &actions).unwrap();
+      ws1.barrier(txr1).unwrap();
+      report
+    };
+
+    let node = rng.gen();
+    let atom = rng.gen();
+    ws0.set_node(&txr0, node, Some(0)).unwrap();
+    ws0.set_atom(&txr0, atom, Some((node, 0, vec![0].into()))).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    assert!(sync(&mut ws0, &mut txr0, &mut ws1, &mut txr1).conflicts.is_empty());
+
+    // Sequential writes do not conflict.
+    ws1.set_atom(&txr1, atom, Some((node, 0, vec![1].into()))).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    assert!(sync(&mut ws1, &mut txr1, &mut ws0, &mut txr0).conflicts.is_empty());
+
+    // The replica whose write lost learns that it was overwritten, whether it
+    // receives the winning write first...
+    ws0.set_atom(&txr0, atom, Some((node, 0, vec![2].into()))).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    // Concurrent writes closer than the clock resolution may be ordered either way.
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    ws1.set_atom(&txr1, atom, Some((node, 0, vec![3].into()))).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    let report = sync(&mut ws1, &mut txr1, &mut ws0, &mut txr0);
+    let [Conflict::Atom { id, winner, loser, overwritten }] = &report.conflicts[..] else { panic!() };
+    assert_eq!(*id, atom);
+    assert!(*overwritten);
+    assert_eq!(loser.2.as_ref().unwrap().2[..], [2]);
+    assert_eq!(ws0.atom(&txr0, atom).unwrap(), winner.2);
+    assert!(sync(&mut ws0, &mut txr0, &mut ws1, &mut txr1).conflicts.is_empty());
+    assert_eq!(ws1.atom(&txr1, atom).unwrap(), winner.2);
+
+    // ...or after the winning replica has received its write.
+    ws1.set_atom(&txr1, atom, Some((node, 0, vec![4].into()))).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    ws0.set_atom(&txr0, atom, Some((node, 0, vec![5].into()))).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    let report = sync(&mut ws1, &mut txr1, &mut ws0, &mut txr0);
+    let [Conflict::Atom { overwritten, loser, .. }] = &report.conflicts[..] else { panic!() };
+    assert!(!*overwritten);
+    assert_eq!(loser.2.as_ref().unwrap().2[..], [4]);
+    let report = sync(&mut ws0, &mut txr0, &mut ws1, &mut txr1);
+    let [Conflict::Atom { overwritten, winner, loser, .. }] = &report.conflicts[..] else { panic!() };
+    assert!(*overwritten);
+    assert_eq!(winner.2.as_ref().unwrap().2[..], [5]);
+    assert_eq!(loser.2.as_ref().unwrap().2[..], [4]);
+    assert_eq!(ws1.atom(&txr1, atom).unwrap(), winner.2);
+
+    // Sequential writes still do not conflict once both replicas are in sync.
+    ws0.set_atom(&txr0, atom, Some((node, 0, vec![6].into()))).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    assert!(sync(&mut ws0, &mut txr0, &mut ws1, &mut txr1).conflicts.is_empty());
+    assert!(sync(&mut ws1, &mut txr1, &mut ws0, &mut txr0).conflicts.is_empty());
+  }
+
+  #[test]
+  fn sync_future() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    // A replica with its clock set a year ahead.
+    let (node, future) = (rng.gen(), rng.gen());
+    let clock = now() + 365 * 24 * 60 * 60 * 1_000_000_000;
+    ws0.set_node(&txr0, node, Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    assert!(ws0.nodes.set(&txr0, future, ws0.this(), clock, Some(0)).unwrap());
+    ws0.barrier(&mut txr0).unwrap();
+
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    let report = ws1.sync_join(&mut txr1, &actions).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    assert_eq!(report.rejected, [(NODES_NAME, future, ws0.this(), clock)]);
+    assert!(ws1.nodes.exists(&txr1, node).unwrap());
+    assert!(!ws1.nodes.exists(&txr1, future).unwrap());
+    assert!(ws1.nodes.next() < clock);
+
+    // Rejected writes are sent again, and accepted once within the allowed drift.
+    ws1.constraints.set_max_drift(u64::MAX);
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    assert!(ws1.sync_join(&mut txr1, &actions).unwrap().rejected.is_empty());
+    ws1.barrier(&mut txr1).unwrap();
+    assert!(ws1.nodes.exists(&txr1, future).unwrap());
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+  }
+
+  #[test]
+  fn sync_forked() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws2 = Workspace::new("", Constraints::new(), &mut txr2).unwrap();
+
+    let sync = |ws0: &mut Workspace, txr0: &mut Transactor, ws1: &mut Workspace, txr1: &mut Transactor| {
+      let actions = ws0.sync_actions(txr0, &ws1.sync_version(txr1)).unwrap();
+      let report = ws1.sync_join(txr1, &actions).unwrap();
+      ws1.barrier(txr1).unwrap();
+      report
+    };
+
+    // Make a copy of the database.
+    let (a, b, c) = (rng.gen(), rng.gen(), rng.gen());
+    ws0.set_node(&txr0, a, Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    sync(&mut ws0, &mut txr0, &mut ws1, &mut txr1);
+    txr1.put_this("", ws0.this()).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    let prev = ws0.this();
+
+    // Both copies write under the same bucket, so peers can miss writes.
+    ws0.set_node(&txr0, b, Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    ws1.set_node(&txr1, c, Some(0)).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    sync(&mut ws1, &mut txr1, &mut ws2, &mut txr2);
+    sync(&mut ws0, &mut txr0, &mut ws2, &mut txr2);
+    assert!(!ws2.nodes.exists(&txr2, b).unwrap());
+
+    // A later edit elsewhere, made before the fork is noticed.
+    ws2.set_node(&txr2, a, Some(1)).unwrap();
+    ws2.barrier(&mut txr2).unwrap();
+
+    // The copy which is behind moves to a fresh bucket, taking its items along
+    // with their clock values, so the later edit still wins.
+    let clock = ws0.nodes.get(&txr0, b).unwrap().unwrap().1;
+    assert_eq!(sync(&mut ws1, &mut txr1, &mut ws0, &mut txr0).forked, Some(prev));
+    assert_ne!(ws0.this(), prev);
+    assert_eq!(ws0.nodes.get(&txr0, b).unwrap(), Some((ws0.this(), clock, Some(0))));
+    assert_eq!(sync(&mut ws0, &mut txr0, &mut ws2, &mut txr2).forked, None);
+    assert_eq!(sync(&mut ws2, &mut txr2, &mut ws0, &mut txr0).forked, None);
+    assert_eq!(ws2.node_id_by_label(&txr2, 0).unwrap(), ws0.node_id_by_label(&txr0, 0).unwrap());
+    assert_eq!(ws2.node_id_by_label(&txr2, 0).unwrap().len(), 2);
+    assert_eq!(ws0.nodes.get(&txr0, a).unwrap().unwrap().2, Some(1));
+
+    // The other copy hears of it from a third replica and forks as well. The
+    // moved items cannot be discarded before the barrier saves them.
+    let actions = ws2.sync_actions(&txr2, &ws1.sync_version(&txr1)).unwrap();
+    assert_eq!(ws1.sync_join(&mut txr1, &actions).unwrap().forked, Some(prev));
+    assert!(matches!(ws1.discard(), Err(StoreError::JoinPending)));
+    ws1.barrier(&mut txr1).unwrap();
+    ws1.discard().unwrap();
+    assert_ne!(ws1.this(), prev);
+    assert_ne!(ws1.this(), ws0.this());
+    assert_eq!(sync(&mut ws1, &mut txr1, &mut ws2, &mut txr2).forked, None);
+    assert_eq!(sync(&mut ws2, &mut txr2, &mut ws1, &mut txr1).forked, None);
+    assert_eq!(ws1.node_id_by_label(&txr1, 0).unwrap(), ws0.node_id_by_label(&txr0, 0).unwrap());
+  }
+
+  #[test]
+  fn sync_bundle() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    let mut ws2 = Workspace::new("", Constraints::new(), &mut txr2).unwrap();
+
+    let node = rng.gen();
+    ws0.set_node(&txr0, node, Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    ws2.set_node(&txr2, rng.gen(), Some(0)).unwrap();
+    ws2.barrier(&mut txr2).unwrap();
+    let bundle = ws0.export_bundle(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    let (header, _) = bundle::decode(&bundle).unwrap();
+    assert_eq!(header.workspace, ws0.metadata.id());
+    assert_eq!(header.clocks[NODES_NAME], ws0.nodes.buckets());
+
+    // Damaged bundles are rejected before anything is applied.
+    for index in [0, 12, bundle.len() / 2, bundle.len() - 1] {
+      let mut damaged = bundle.to_vec();
+      damaged[index] ^= 1;
+      assert!(matches!(ws1.import_bundle(&mut txr1, &damaged), Err(StoreError::MalformedBundle(_))));
+    }
+    assert!(matches!(ws1.import_bundle(&mut txr1, &bundle[..bundle.len() - 1]), Err(StoreError::MalformedBundle(_))));
+    assert!(matches!(ws2.import_bundle(&mut txr2, &bundle), Err(StoreError::WorkspaceMismatch(_, _))));
+    assert!(ws1.is_pristine());
+
+    // So are bundles from newer schema versions which dropped compatibility
+    // with this one.
+    let (header, payload) = bundle::decode(&bundle).unwrap();
+    let newer = BundleHeader { schema: CURRENT_VERSION + 1, oldest: CURRENT_VERSION + 1, ..header };
+    let incompatible = bundle::encode(&newer, payload);
+    assert!(matches!(ws1.import_bundle(&mut txr1, &incompatible), Err(StoreError::SchemaMismatch(_, _))));
+    assert!(ws1.is_pristine());
+
+    ws1.import_bundle(&mut txr1, &bundle).unwrap();
+    assert!(matches!(ws1.discard(), Err(StoreError::JoinPending)));
+    ws1.barrier(&mut txr1).unwrap();
+    ws1.discard().unwrap();
+    assert!(ws1.nodes.exists(&txr1, node).unwrap());
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+  }
+
+  #[test]
+  fn sync_snapshot() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr2: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    let mut ws2 = Workspace::new("", Constraints::new(), &mut txr2).unwrap();
+    let mut txr3: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws3 = Workspace::new("", Constraints::new(), &mut txr3).unwrap();
+
+    let src = rng.gen();
+    let mut left = None;
+    for i in 0..100u8 {
+      if i == 50 {
+        let actions = ws0.sync_actions(&txr0, &ws3.sync_version(&txr3)).unwrap();
+        ws3.sync_join(&mut txr3, &actions).unwrap();
+        ws3.barrier(&mut txr3).unwrap();
+      }
+      let node = rng.gen();
+      ws0.set_node(&txr0, node, Some(0)).unwrap();
+      ws0.set_atom(&txr0, rng.gen(), Some((node, 0, vec![i; 10].into()))).unwrap();
+      ws0.set_edge(&txr0, rng.gen(), Some((node, 0, src))).unwrap();
+      let item = rng.gen();
+      ws0.insert_list_item(&txr0, item, src, 0, left, vec![i].into()).unwrap();
+      left = Some(item);
+      ws0.set_node(&txr0, src, Some(0)).unwrap();
+      ws0.barrier(&mut txr0).unwrap();
+      if i % 2 == 0 {
+        ws0.set_node(&txr0, node, None).unwrap();
+        ws0.remove_list_item(&txr0, item).unwrap();
+        ws0.barrier(&mut txr0).unwrap();
+      }
+    }
+
+    // Pages are installed one by one, and clocks only come with the last one.
+    let mut cursor = None;
+    loop {
+      let (page, next) = ws0.snapshot_page(&txr0, cursor.as_deref(), 500).unwrap();
+      ws1.install_snapshot(&mut txr1, &page).unwrap();
+      cursor = next;
+      if cursor.is_none() {
+        break;
+      }
+      assert!(ws1.is_pristine());
+    }
+    let snapshot = ws0.snapshot(&txr0).unwrap();
+    assert!(matches!(ws1.install_snapshot(&mut txr1, &snapshot), Err(StoreError::NotEmpty)));
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+    assert_eq!(ws0.nodes.compacted(), ws1.nodes.compacted());
+    assert_eq!(ws0.node_id_by_label(&txr0, 0).unwrap(), ws1.node_id_by_label(&txr1, 0).unwrap());
+    assert_eq!(ws0.atom_id_src_value_by_label(&txr0, 0).unwrap(), ws1.atom_id_src_value_by_label(&txr1, 0).unwrap());
+    assert_eq!(
+      ws0.edge_id_src_by_dst_label(&txr0, src, 0).unwrap(),
+      ws1.edge_id_src_by_dst_label(&txr1, src, 0).unwrap()
+    );
+    assert_eq!(
+      ws0.list_id_value_by_src_label(&txr0, src, 0).unwrap(),
+      ws1.list_id_value_by_src_label(&txr1, src, 0).unwrap()
+    );
+
+    // Incremental syncs continue from there.
+    ws0.set_node(&txr0, src, None).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+    assert_eq!(ws0.node_id_by_label(&txr0, 0).unwrap(), ws1.node_id_by_label(&txr1, 0).unwrap());
+    assert_eq!(ws0.atom_id_src_value_by_label(&txr0, 0).unwrap(), ws1.atom_id_src_value_by_label(&txr1, 0).unwrap());
+
+    // Peers behind the sender get the removals they missed, without a full resync.
+    let actions = ws1.sync_actions(&txr1, &ws3.sync_version(&txr3)).unwrap();
+    let all: BTreeMap<String, &[u8]> = decode(&actions, "actions").unwrap();
+    assert!(decode_entry::<BTreeMap<String, Reset>>(&all, RESET_NAME).unwrap().is_empty());
+    ws3.sync_join(&mut txr3, &actions).unwrap();
+    ws3.barrier(&mut txr3).unwrap();
+    assert_eq!(ws0.node_id_by_label(&txr0, 0).unwrap(), ws3.node_id_by_label(&txr3, 0).unwrap());
+
+    // And further replicas can sync from the new one.
+    let actions = ws1.sync_actions(&txr1, &ws2.sync_version(&txr2)).unwrap();
+    ws2.sync_join(&mut txr2, &actions).unwrap();
+    ws2.barrier(&mut txr2).unwrap();
+    assert_eq!(clocks(&ws0), clocks(&ws2));
+    assert_eq!(
+      ws0.list_id_value_by_src_label(&txr0, src, 0).unwrap(),
+      ws2.list_id_value_by_src_label(&txr2, src, 0).unwrap()
+    );
+  }
+
+  #[test]
+  fn stats() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws = Workspace::new("", Constraints::new(), &mut txr).unwrap();
+    ws.set_node(&txr, 1, Some(10)).unwrap();
+    ws.set_node(&txr, 2, Some(10)).unwrap();
+    ws.set_node(&txr, 3, Some(20)).unwrap();
+    ws.set_atom(&txr, 4, Some((1, 30, vec![0; 5].into()))).unwrap();
+    ws.set_atom(&txr, 5, Some((2, 30, vec![0; 7].into()))).unwrap();
+    ws.set_edge(&txr, 6, Some((1, 40, 2))).unwrap();
+    ws.insert_list_item(&txr, 7, 1, 50, None, vec![0; 3].into()).unwrap();
+    ws.insert_list_item(&txr, 8, 1, 50, Some(7), vec![0; 4].into()).unwrap();
+    let stats = ws.stats(&txr).unwrap();
+    assert_eq!(stats.nodes.labels, BTreeMap::new());
+    assert_eq!((stats.nodes.pending, stats.atoms.pending, stats.edges.pending, stats.lists.pending), (3, 2, 1, 2));
+
+    ws.barrier(&mut txr).unwrap();
+    ws.set_node(&txr, 3, None).unwrap();
+    ws.remove_list_item(&txr, 8).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    let stats = ws.stats(&txr).unwrap();
+    let this = ws.this();
+    assert_eq!(stats.nodes.labels, BTreeMap::from([(10, (2, 0))]));
+    assert_eq!(stats.nodes.tombstones, 1);
+    assert_eq!(stats.nodes.buckets, BTreeMap::from([(this, ws.nodes.buckets()[&this])]));
+    assert_eq!(stats.atoms.labels, BTreeMap::from([(30, (2, 12))]));
+    assert_eq!(stats.edges.labels, BTreeMap::from([(40, (1, 0))]));
+    assert_eq!(stats.lists.labels, BTreeMap::from([(50, (1, 3))]));
+    assert_eq!(stats.lists.tombstones, 1);
+    assert_eq!((stats.nodes.pending, stats.atoms.pending, stats.edges.pending, stats.lists.pending), (0, 0, 0, 0));
+
+    // Without registered peers, removals are kept for the retention period.
+    ws.compact(&mut txr).unwrap();
+    assert_eq!(ws.stats(&txr).unwrap().nodes.tombstones, 1);
+    ws.constraints.set_retention(0);
+    ws.compact(&mut txr).unwrap();
+    let stats = ws.stats(&txr).unwrap();
+    assert_eq!((stats.nodes.tombstones, stats.lists.tombstones), (0, 0));
+  }
+
+  #[test]
+  fn check_repair() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut ws = Workspace::new("", Constraints::new(), &mut txr).unwrap();
+    for node in 1..=4 {
+      ws.set_node(&txr, node, Some(0)).unwrap();
+    }
+    ws.set_atom(&txr, 10, Some((1, 5, vec![].into()))).unwrap();
+    ws.set_atom(&txr, 11, Some((2, 5, vec![].into()))).unwrap();
+    ws.set_edge(&txr, 20, Some((1, 7, 2))).unwrap();
+    ws.set_edge(&txr, 21, Some((2, 7, 3))).unwrap();
+    ws.set_edge(&txr, 22, Some((3, 7, 1))).unwrap();
+    ws.set_edge(&txr, 23, Some((4, 7, 4))).unwrap();
+    ws.set_edge(&txr, 24, Some((4, 8, 2))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(ws.check(&txr).unwrap(), []);
+
+    // Constraints added later, and a node removed behind the workspace's back.
+    txr.execute("DELETE FROM \".nodes.data\" WHERE id = ?", (2u128.to_be_bytes(),)).unwrap();
+    let mut constraints = Constraints::new();
+    constraints.add_acyclic_edge(7);
+    constraints.add_sticky_edge(8);
+    let mut ws = Workspace::new("", constraints, &mut txr).unwrap();
+    let violations = ws.check(&txr).unwrap();
+    assert_eq!(
+      violations,
+      [
+        Violation::DanglingAtom { id: 11, src: 2, label: 5 },
+        Violation::DanglingEdge { id: 20, src: 1, label: 7, dst: 2 },
+        Violation::DanglingEdge { id: 21, src: 2, label: 7, dst: 3 },
+        Violation::DanglingEdge { id: 24, src: 4, label: 8, dst: 2 },
+        Violation::CyclicEdge { id: 23, src: 4, label: 7, dst: 4 },
+      ]
+    );
+
+    assert_eq!(ws.repair(&txr).unwrap(), violations);
+    ws.barrier(&mut txr).unwrap();
+    assert_eq!(ws.check(&txr).unwrap(), []);
+    assert_eq!(ws.node_id_by_label(&txr, 0).unwrap().into_keys().collect::<Vec<_>>(), [1, 3]);
+    assert!(ws.atom(&txr, 10).unwrap().is_some());
+    assert!(ws.edge(&txr, 22).unwrap().is_some());
+
+    // Buckets larger than a chunk are read in several.
+    for id in 100..100 + PAGE_CHUNK as u128 {
+      ws.set_node(&txr, id, Some(0)).unwrap();
+      ws.set_atom(&txr, id + PAGE_CHUNK as u128, Some((id, 5, vec![].into()))).unwrap();
+    }
+    ws.set_atom(&txr, 99, Some((1, 5, vec![].into()))).unwrap();
+    ws.barrier(&mut txr).unwrap();
+    txr.execute("DELETE FROM \".nodes.data\" WHERE id = ?", (1u128.to_be_bytes(),)).unwrap();
+    let ws = Workspace::new("", Constraints::new(), &mut txr).unwrap();
+    assert_eq!(
+      ws.check(&txr).unwrap(),
+      [
+        Violation::DanglingAtom { id: 10, src: 1, label: 5 },
+        Violation::DanglingAtom { id: 99, src: 1, label: 5 },
+        Violation::DanglingEdge { id: 22, src: 3, label: 7, dst: 1 },
+      ]
+    );
+  }
+
+  #[test]
+  fn value_ranges() {
+    fn check(txr: &mut impl WorkspaceTransactor) {
+      let mut ws = Workspace::new("", Constraints::new(), txr).unwrap();
+      ws.set_node(txr, 0, Some(0)).unwrap();
+      let values: [&[u8]; 6] = [b"a", b"ab", b"abc", b"b", b"\xff", b"\xff\xff"];
+      for (i, value) in values.iter().enumerate() {
+        ws.set_atom(txr, i as u128, Some((0, 1, value.to_vec().into()))).unwrap();
+      }
+      ws.set_atom(txr, 9, Some((0, 2, b"a".to_vec().into()))).unwrap();
+      ws.barrier(txr).unwrap();
+
+      let ids = |res: Vec<(u128, (u128, Box<[u8]>))>| res.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+      let saved = ws.atom_id_src_value_by_label_range(txr, 1, Bound::Unbounded, Bound::Unbounded, 2, Some(3));
+      assert_eq!(ids(saved.unwrap()), [2, 3, 4]);
+      let saved = ws.atom_id_src_value_by_label_range(txr, 1, Bound::Unbounded, Bound::Unbounded, u64::MAX, None);
+      assert_eq!(ids(saved.unwrap()), []);
+
+      // Pending modifications move an item out of and another into the results.
+      ws.set_atom(txr, 2, Some((0, 1, b"c".to_vec().into()))).unwrap();
+      ws.set_atom(txr, 6, Some((0, 1, b"aa".to_vec().into()))).unwrap();
+
+      let range = |lower, upper, offset, limit| {
+        ids(ws.atom_id_src_value_by_label_range(txr, 1, lower, upper, offset, limit).unwrap())
+      };
+      assert_eq!(range(Bound::Unbounded, Bound::Unbounded, 0, None), [0, 6, 1, 3, 2, 4, 5]);
+      assert_eq!(range(Bound::Excluded(b"a"), Bound::Unbounded, 0, Some(3)), [6, 1, 3]);
+      assert_eq!(range(Bound::Included(b"aa"), Bound::Included(b"b"), 1, Some(2)), [1, 3]);
+      assert_eq!(range(Bound::Unbounded, Bound::Excluded(b"b"), 2, None), [1]);
+
+      let prefix = |prefix: &[u8]| ids(ws.atom_id_src_value_by_label_prefix(txr, 1, prefix, 0, None).unwrap());
+      assert_eq!(prefix(b"a"), [0, 6, 1]);
+      assert_eq!(prefix(b"ab"), [1]);
+      assert_eq!(prefix(b"\xff"), [4, 5]);
+      assert_eq!(prefix(b""), [0, 6, 1, 3, 2, 4, 5]);
+    }
+
+    check(&mut TryInto::<Transactor>::try_into(Connection::open_in_memory().unwrap()).unwrap());
+    check(&mut MemoryTransactor::new());
+  }
+
+  #[test]
+  fn memory_backend() {
+    let mut txr0: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    let mut txr1 = MemoryTransactor::new();
+    let mut rng = rand::thread_rng();
+    let mut ws0 = Workspace::new("", Constraints::new(), &mut txr0).unwrap();
+    let mut ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+
+    fn write(ws: &mut Workspace, txr: &mut impl WorkspaceTransactor, rng: &mut impl Rng, src: u128) {
+      for _ in 0..rng.gen_range(0..20) {
+        let node = rng.gen();
+        ws.set_node(txr, node, Some(rng.gen_range(0..3))).unwrap();
+        ws.set_atom(txr, rng.gen(), Some((node, rng.gen_range(0..3), vec![rng.gen_range(0..3)].into()))).unwrap();
+        ws.set_edge(txr, rng.gen(), Some((node, rng.gen_range(0..3), src))).unwrap();
+        let items = ws.list_id_value_by_src_label(txr, src, 0).unwrap();
+        let left = items.choose(rng).map(|(item, _)| *item);
+        ws.insert_list_item(txr, rng.gen(), src, 0, left, vec![0].into()).unwrap();
+        if rng.gen_ratio(1, 3) {
+          ws.set_node(txr, node, None).unwrap();
+        }
+        if let Some((item, _)) = items.choose(rng).filter(|_| rng.gen_ratio(1, 3)) {
+          ws.remove_list_item(txr, *item).unwrap();
+        }
+      }
+      ws.barrier(txr).unwrap();
+    }
+
+    let src = rng.gen();
+    ws0.set_node(&txr0, src, Some(0)).unwrap();
+    ws0.barrier(&mut txr0).unwrap();
+    let actions = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+    ws1.sync_join(&mut txr1, &actions).unwrap();
+    ws1.barrier(&mut txr1).unwrap();
+    for _ in 0..10 {
+      write(&mut ws0, &mut txr0, &mut rng, src);
+      write(&mut ws1, &mut txr1, &mut rng, src);
+      let actions0 = ws0.sync_actions(&txr0, &ws1.sync_version(&txr1)).unwrap();
+      let actions1 = ws1.sync_actions(&txr1, &ws0.sync_version(&txr0)).unwrap();
+      ws0.sync_join(&mut txr0, &actions1).unwrap();
+      ws1.sync_join(&mut txr1, &actions0).unwrap();
+      ws0.barrier(&mut txr0).unwrap();
+      ws1.barrier(&mut txr1).unwrap();
+      ws0.compact(&mut txr0).unwrap();
+      ws1.compact(&mut txr1).unwrap();
+      assert_eq!(clocks(&ws0), clocks(&ws1));
+      assert_eq!(ws0.stats(&txr0).unwrap(), ws1.stats(&txr1).unwrap());
+      for label in 0..3 {
+        assert_eq!(ws0.node_id_by_label(&txr0, label).unwrap(), ws1.node_id_by_label(&txr1, label).unwrap());
+        assert_eq!(
+          ws0.atom_id_src_value_by_label(&txr0, label).unwrap(),
+          ws1.atom_id_src_value_by_label(&txr1, label).unwrap()
+        );
+        assert_eq!(
+          ws0.atom_id_src_by_label_value(&txr0, label, &[1]).unwrap(),
+          ws1.atom_id_src_by_label_value(&txr1, label, &[1]).unwrap()
+        );
+        assert_eq!(
+          ws0.edge_id_src_by_dst_label(&txr0, src, label).unwrap(),
+          ws1.edge_id_src_by_dst_label(&txr1, src, label).unwrap()
+        );
+      }
+      assert_eq!(
+        ws0.list_id_value_by_src_label(&txr0, src, 0).unwrap(),
+        ws1.list_id_value_by_src_label(&txr1, src, 0).unwrap()
+      );
+    }
+
+    // Data outlives the workspace.
+    let this = ws1.this();
+    let nodes = ws1.node_id_by_label(&txr1, 0).unwrap();
+    let ws1 = Workspace::new("", Constraints::new(), &mut txr1).unwrap();
+    assert_eq!(ws1.this(), this);
+    assert_eq!(ws1.node_id_by_label(&txr1, 0).unwrap(), nodes);
+    assert_eq!(clocks(&ws0), clocks(&ws1));
+  }
+
+  #[test]
+  fn open_fixtures() {
+    // Databases written by each schema version, with the same content.
+    let fixtures: [(u64, &str); CURRENT_VERSION as usize] =
+      [(1, include_str!("workspace/fixtures/v1.sql")), (2, include_str!("workspace/fixtures/v2.sql"))];
+    let schema = |txr: &Transactor| -> BTreeSet<String> {
+      let mut stmt = txr.prepare("SELECT name FROM sqlite_master").unwrap();
+      stmt.query_map((), |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+    };
+    let dir = std::env::temp_dir().join(format!("dust-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (version, fixture) in fixtures {
+      // Read-only stores migrate a copy and leave the file as it is.
+      let path = dir.join(format!("v{version}.db")).into_os_string().into_string().unwrap();
+      Connection::open(&path).unwrap().execute_batch(fixture).unwrap();
+      let store = OpenOptions::new().read_only(true).open(&path).unwrap();
+      let read = store.read(|txr, ws| Ok((ws.node(txr, 1)?, ws.atom(txr, 3)?, ws.edge(txr, 4)?))).unwrap();
+      assert_eq!(read, (Some(10), Some((1, 30, vec![1, 2, 3].into())), Some((1, 40, 2))));
+      let stored: u64 = Connection::open(&path)
+        .unwrap()
+        .query_row("SELECT version FROM \".version\"", (), |row| Ok(u64::from_be_bytes(row.get(0)?)))
+        .unwrap();
+      assert_eq!(stored, version);
+
+      let conn = Connection::open_in_memory().unwrap();
+      conn.execute_batch(fixture).unwrap();
+      let mut txr: Transactor = conn.try_into().unwrap();
+      assert_eq!(txr.get_version("").unwrap(), Some(version));
+
+      // Migrations alone bring the tables to the current layout.
+      for version in version..CURRENT_VERSION {
+        txr.migrate("", version).unwrap();
+      }
+      let current = Connection::open_in_memory().unwrap();
+      current.execute_batch(fixtures[CURRENT_VERSION as usize - 1].1).unwrap();
+      assert_eq!(schema(&txr), schema(&current.try_into().unwrap()));
+
+      let mut ws = Workspace::new("", Constraints::new(), &mut txr).unwrap();
+      assert_eq!(txr.get_version("").unwrap(), Some(CURRENT_VERSION));
+      assert_eq!(ws.node(&txr, 1).unwrap(), Some(10));
+      assert_eq!(ws.node(&txr, 2).unwrap(), Some(20));
+      assert_eq!(ws.atom(&txr, 3).unwrap(), Some((1, 30, vec![1, 2, 3].into())));
+      assert_eq!(ws.edge(&txr, 4).unwrap(), Some((1, 40, 2)));
+      assert_eq!(ws.atom(&txr, 5).unwrap(), None);
+
+      ws.set_atom(&txr, 6, Some((2, 30, vec![4].into()))).unwrap();
+      ws.barrier(&mut txr).unwrap();
+      assert_eq!(ws.atom_id_src_value_by_label(&txr, 30).unwrap().len(), 2);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
   }
 }
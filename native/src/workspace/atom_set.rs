@@ -13,13 +13,19 @@
 // limitations under the License.
 
 use rusqlite::{OptionalExtension, Result, Row};
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{
+  collections::{btree_map::Entry, BTreeMap, BTreeSet},
+  ops::{Bound, RangeBounds},
+};
 
-use super::metadata::{StructureMetadata, StructureMetadataTransactor};
-use crate::Transactor;
+use super::{
+  metadata::{StructureMetadata, StructureMetadataTransactor},
+  PAGE_CHUNK,
+};
+use crate::{memory::MemoryTransactor, StoreError, Transactor};
 
 /// A last-writer-wins element set for storing atomic data.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AtomSet {
   metadata: StructureMetadata,
   mods: BTreeMap<u128, (Option<Item>, Item)>,
@@ -34,27 +40,73 @@ fn item_lt(lhs: &Item, rhs: &Item) -> bool {
 
 /// Database interface for [`AtomSet`].
 pub trait AtomSetTransactor: StructureMetadataTransactor {
-  fn init(&mut self, prefix: &str, name: &str);
-  fn get(&self, prefix: &str, name: &str, id: u128) -> Option<Item>;
-  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item);
-  fn id_label_value_by_src(&self, prefix: &str, name: &str, src: u128) -> BTreeMap<u128, (u64, Box<[u8]>)>;
-  fn id_value_by_src_label(&self, prefix: &str, name: &str, src: u128, label: u64) -> BTreeMap<u128, Box<[u8]>>;
-  fn id_src_value_by_label(&self, prefix: &str, name: &str, label: u64) -> BTreeMap<u128, (u128, Box<[u8]>)>;
-  fn id_src_by_label_value(&self, prefix: &str, name: &str, label: u64, value: &[u8]) -> BTreeMap<u128, u128>;
-  fn by_bucket_clock_range(&self, prefix: &str, name: &str, bucket: u64, lower: Option<u64>) -> BTreeMap<u128, Item>;
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError>;
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError>;
+  fn id_label_value_by_src(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, Box<[u8]>)>, StoreError>;
+  fn id_value_by_src_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, Box<[u8]>>, StoreError>;
+  fn id_src_value_by_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+  ) -> Result<BTreeMap<u128, (u128, Box<[u8]>)>, StoreError>;
+  fn id_src_by_label_value(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    value: &[u8],
+  ) -> Result<BTreeMap<u128, u128>, StoreError>;
+  fn id_src_value_by_label_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError>;
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError>;
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError>;
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError>;
 }
 
 impl AtomSet {
   /// Creates or loads data.
-  pub fn new(prefix: &'static str, name: &'static str, txr: &mut impl AtomSetTransactor) -> Self {
-    let metadata = StructureMetadata::new(prefix, name, txr);
+  pub fn new(prefix: &str, name: &'static str, txr: &mut impl AtomSetTransactor) -> Result<Self, StoreError> {
+    let metadata = StructureMetadata::new(prefix, name, txr)?;
     let mods = BTreeMap::new();
-    txr.init(prefix, name);
-    Self { metadata, mods }
+    txr.init(prefix, name)?;
+    Ok(Self { metadata, mods })
+  }
+
+  /// Returns a copy without pending modifications, for reading the saved
+  /// state through another connection.
+  pub fn committed(&self) -> Self {
+    Self { metadata: self.metadata.committed(), mods: BTreeMap::new() }
   }
 
   /// Returns the name of the workspace.
-  pub fn prefix(&self) -> &'static str {
+  pub fn prefix(&self) -> &str {
     self.metadata.prefix()
   }
 
@@ -68,11 +120,47 @@ impl AtomSet {
     self.metadata.buckets()
   }
 
-  /// Returns the largest clock value across all buckets plus one.
+  /// Returns the clock value for the next local write.
   pub fn next(&self) -> u64 {
     self.metadata.next()
   }
 
+  /// Returns whether all items in `bucket` up to clock `lower` have been received.
+  pub fn covers(&self, bucket: u64, lower: Option<u64>) -> bool {
+    self.metadata.covers(bucket, lower)
+  }
+
+  /// Records that all items in `bucket` with clock in `(lower, upper]` have been received.
+  pub fn extend(&mut self, bucket: u64, lower: Option<u64>, upper: u64) {
+    self.metadata.extend(bucket, lower, upper)
+  }
+
+  /// Returns whether no bucket has been received, with or without a filter.
+  pub fn is_pristine(&self) -> bool {
+    self.metadata.is_pristine()
+  }
+
+  /// Returns the current clock values for each bucket, for items matching `filter`.
+  pub fn filtered_buckets(&self, filter: u64) -> BTreeMap<u64, u64> {
+    self.metadata.filtered_buckets(filter)
+  }
+
+  /// Records that all items in `bucket` matching `filter` with clock in `(lower, upper]` have been received.
+  /// If `complete`, no item in the range was filtered out.
+  pub fn filtered_extend(&mut self, filter: u64, bucket: u64, lower: Option<u64>, upper: u64, complete: bool) {
+    self.metadata.filtered_extend(filter, bucket, lower, upper, complete)
+  }
+
+  /// Returns when buckets were received, see [`StructureMetadata::seen`].
+  pub fn seen(&self, since: Option<u64>) -> BTreeMap<u64, BTreeMap<u64, u64>> {
+    self.metadata.seen(since)
+  }
+
+  /// Forgets when buckets were received before local clock value `before`.
+  pub fn forget(&mut self, txr: &mut impl AtomSetTransactor, before: u64) -> Result<(), StoreError> {
+    self.metadata.forget(txr, before)
+  }
+
   /// Returns pending modifications.
   pub fn mods(&self) -> Vec<(u128, Option<(u128, u64, Box<[u8]>)>, Option<(u128, u64, Box<[u8]>)>)> {
     let mut res = Vec::new();
@@ -82,19 +170,34 @@ impl AtomSet {
     res
   }
 
-  pub fn get(&self, txr: &impl AtomSetTransactor, id: u128) -> Option<Item> {
-    self.mods.get(&id).map_or_else(|| txr.get(self.prefix(), self.name(), id), |(_, curr)| Some(curr.clone()))
+  /// Returns clock values of pending modifications in `bucket`.
+  pub fn pending(&self, bucket: u64) -> Vec<u64> {
+    self.mods.values().filter(|(_, curr)| curr.0 == bucket).map(|(_, curr)| curr.1).collect()
   }
 
-  pub fn id_label_value_by_src(&self, txr: &impl AtomSetTransactor, src: u128) -> BTreeMap<u128, (u64, Box<[u8]>)> {
-    let mut res = txr.id_label_value_by_src(self.prefix(), self.name(), src);
+  /// Returns the number of saved items and the total size of their values by label, where removed items
+  /// which have not been compacted are counted under `None`.
+  pub fn count_by_label(&self, txr: &impl AtomSetTransactor) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    txr.count_by_label(self.prefix(), self.name())
+  }
+
+  pub fn get(&self, txr: &impl AtomSetTransactor, id: u128) -> Result<Option<Item>, StoreError> {
+    self.mods.get(&id).map_or_else(|| txr.get(self.prefix(), self.name(), id), |(_, curr)| Ok(Some(curr.clone())))
+  }
+
+  pub fn id_label_value_by_src(
+    &self,
+    txr: &impl AtomSetTransactor,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, Box<[u8]>)>, StoreError> {
+    let mut res = txr.id_label_value_by_src(self.prefix(), self.name(), src)?;
     for (id, (_, (_, _, slv))) in &self.mods {
       match slv {
         Some((src_, label, value)) if src_ == &src => res.insert(*id, (*label, value.clone())),
         _ => res.remove(id),
       };
     }
-    res
+    Ok(res)
   }
 
   pub fn id_value_by_src_label(
@@ -102,46 +205,94 @@ impl AtomSet {
     txr: &impl AtomSetTransactor,
     src: u128,
     label: u64,
-  ) -> BTreeMap<u128, Box<[u8]>> {
-    let mut res = txr.id_value_by_src_label(self.prefix(), self.name(), src, label);
+  ) -> Result<BTreeMap<u128, Box<[u8]>>, StoreError> {
+    let mut res = txr.id_value_by_src_label(self.prefix(), self.name(), src, label)?;
     for (id, (_, (_, _, slv))) in &self.mods {
       match slv {
         Some((src_, label_, value)) if src_ == &src && label_ == &label => res.insert(*id, value.clone()),
         _ => res.remove(id),
       };
     }
-    res
+    Ok(res)
   }
 
-  pub fn id_src_value_by_label(&self, txr: &impl AtomSetTransactor, label: u64) -> BTreeMap<u128, (u128, Box<[u8]>)> {
-    let mut res = txr.id_src_value_by_label(self.prefix(), self.name(), label);
+  pub fn id_src_value_by_label(
+    &self,
+    txr: &impl AtomSetTransactor,
+    label: u64,
+  ) -> Result<BTreeMap<u128, (u128, Box<[u8]>)>, StoreError> {
+    let mut res = txr.id_src_value_by_label(self.prefix(), self.name(), label)?;
     for (id, (_, (_, _, slv))) in &self.mods {
       match slv {
         Some((src, label_, value)) if label_ == &label => res.insert(*id, (*src, value.clone())),
         _ => res.remove(id),
       };
     }
-    res
+    Ok(res)
   }
 
-  pub fn id_src_by_label_value(&self, txr: &impl AtomSetTransactor, label: u64, value: &[u8]) -> BTreeMap<u128, u128> {
-    let mut res = txr.id_src_by_label_value(self.prefix(), self.name(), label, value);
+  pub fn id_src_by_label_value(
+    &self,
+    txr: &impl AtomSetTransactor,
+    label: u64,
+    value: &[u8],
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
+    let mut res = txr.id_src_by_label_value(self.prefix(), self.name(), label, value)?;
     for (id, (_, (_, _, slv))) in &self.mods {
       match slv {
         Some((src, label_, value_)) if label_ == &label && value_.as_ref() == value => res.insert(*id, *src),
         _ => res.remove(id),
       };
     }
-    res
+    Ok(res)
+  }
+
+  /// Returns items with `label` and a value between `lower` and `upper`,
+  /// each of which may be inclusive, exclusive or unbounded. Items are
+  /// ordered by value and then ID. The first `offset` of them are skipped,
+  /// and at most `limit` are returned.
+  pub fn id_src_value_by_label_range(
+    &self,
+    txr: &impl AtomSetTransactor,
+    label: u64,
+    lower: Bound<&[u8]>,
+    upper: Bound<&[u8]>,
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError> {
+    if self.mods.is_empty() {
+      return txr.id_src_value_by_label_range(self.prefix(), self.name(), label, (lower, upper), offset, limit);
+    }
+    // Pending modifications can move items before or past `offset`, so saved
+    // items are fetched from the start. Those with pending modifications are
+    // dropped below, so as many more are fetched.
+    let fetch = limit.map(|limit| offset.saturating_add(limit).saturating_add(self.mods.len() as u64));
+    let mut res = txr.id_src_value_by_label_range(self.prefix(), self.name(), label, (lower, upper), 0, fetch)?;
+    res.retain(|(id, _)| !self.mods.contains_key(id));
+    for (id, (_, (_, _, slv))) in &self.mods {
+      if let Some((src, label_, value)) = slv {
+        if label_ == &label && (lower, upper).contains(&value.as_ref()) {
+          res.push((*id, (*src, value.clone())));
+        }
+      }
+    }
+    res.sort_by(|(lhs, (_, lvalue)), (rhs, (_, rvalue))| (lvalue, lhs).cmp(&(rvalue, rhs)));
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    let limit = limit.and_then(|limit| usize::try_from(limit).ok()).unwrap_or(usize::MAX);
+    Ok(res.into_iter().skip(offset).take(limit).collect())
   }
 
   /// Returns all actions strictly later than given clock values.
   /// Absent entries are assumed to be `None`.
-  pub fn actions(&self, txr: &impl AtomSetTransactor, version: BTreeMap<u64, u64>) -> BTreeMap<u128, Item> {
+  pub fn actions(
+    &self,
+    txr: &impl AtomSetTransactor,
+    version: BTreeMap<u64, u64>,
+  ) -> Result<BTreeMap<u128, Item>, StoreError> {
     let mut res = BTreeMap::new();
     for &bucket in self.buckets().keys() {
       let lower = version.get(&bucket).copied();
-      for (id, item) in txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower) {
+      for (id, item) in txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, None)? {
         res.insert(id, item);
       }
     }
@@ -153,7 +304,28 @@ impl AtomSet {
         res.remove(id);
       }
     }
-    res
+    Ok(res)
+  }
+
+  /// Returns at most `limit` actions in `bucket` strictly later than `lower`,
+  /// in clock order.
+  pub fn bucket_actions(
+    &self,
+    txr: &impl AtomSetTransactor,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let mut res = txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, Some(limit))?;
+    res.retain(|(id, _)| !self.mods.contains_key(id));
+    for (id, (_, item)) in &self.mods {
+      if item.0 == bucket && Some(item.1) > lower {
+        res.push((*id, item.clone()));
+      }
+    }
+    res.sort_by_key(|(_, item)| item.1);
+    res.truncate(limit as usize);
+    Ok(res)
   }
 
   /// Modifies item.
@@ -164,78 +336,157 @@ impl AtomSet {
     bucket: u64,
     clock: u64,
     slv: Option<(u128, u64, Box<[u8]>)>,
-  ) -> bool {
-    if self.metadata.update(bucket, clock) {
-      let item = (bucket, clock, slv);
-      match self.mods.entry(id) {
-        Entry::Vacant(entry) => {
-          let prev = txr.get(self.metadata.prefix(), self.metadata.name(), id);
-          if prev.is_none() || item_lt(prev.as_ref().unwrap(), &item) {
-            entry.insert((prev, item));
-            return true;
-          }
+  ) -> Result<bool, StoreError> {
+    Ok(self.metadata.update(bucket, clock) && self.merge(txr, id, bucket, clock, slv)?)
+  }
+
+  /// Modifies item without checking or advancing the bucket clock. Used for
+  /// items received out of order.
+  pub fn merge(
+    &mut self,
+    txr: &impl AtomSetTransactor,
+    id: u128,
+    bucket: u64,
+    clock: u64,
+    slv: Option<(u128, u64, Box<[u8]>)>,
+  ) -> Result<bool, StoreError> {
+    self.metadata.observe(clock);
+    let item = (bucket, clock, slv);
+    match self.mods.entry(id) {
+      Entry::Vacant(entry) => {
+        let prev = txr.get(self.metadata.prefix(), self.metadata.name(), id)?;
+        if prev.is_none() || item_lt(prev.as_ref().unwrap(), &item) {
+          entry.insert((prev, item));
+          return Ok(true);
         }
-        Entry::Occupied(mut entry) => {
-          if item_lt(&entry.get().1, &item) {
-            entry.get_mut().1 = item;
-            return true;
-          }
+      }
+      Entry::Occupied(mut entry) => {
+        if item_lt(&entry.get().1, &item) {
+          entry.get_mut().1 = item;
+          return Ok(true);
+        }
+      }
+    }
+    Ok(false)
+  }
+
+  /// Moves item to `bucket`, keeping its clock value and contents. Used when
+  /// this replica forks, see [`crate::workspace::Workspace::fork`].
+  pub fn relocate(&mut self, txr: &impl AtomSetTransactor, id: u128, bucket: u64) -> Result<(), StoreError> {
+    let (prev, mut curr) = match self.mods.remove(&id) {
+      Some(entry) => entry,
+      None => match txr.get(self.metadata.prefix(), self.metadata.name(), id)? {
+        Some(item) => (Some(item.clone()), item),
+        None => return Ok(()),
+      },
+    };
+    curr.0 = bucket;
+    self.metadata.update(bucket, curr.1);
+    self.mods.insert(id, (prev, curr));
+    Ok(())
+  }
+
+  /// Returns, for each bucket, the clock value up to which removed items may
+  /// have been physically deleted.
+  pub fn compacted(&self) -> &BTreeMap<u64, u64> {
+    self.metadata.compacted()
+  }
+
+  /// Records that removed items in `bucket` up to clock `clock` may have been
+  /// physically deleted elsewhere.
+  pub fn mark_compacted(
+    &mut self,
+    txr: &mut impl AtomSetTransactor,
+    bucket: u64,
+    clock: u64,
+  ) -> Result<(), StoreError> {
+    self.metadata.compact(txr, bucket, clock)
+  }
+
+  /// Physically deletes saved removed items in `bucket` up to clock `upper`.
+  /// Returns the number of deleted items.
+  pub fn compact(&mut self, txr: &mut impl AtomSetTransactor, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let res = txr.purge(self.prefix(), self.name(), bucket, upper)?;
+    if res > 0 {
+      self.metadata.compact(txr, bucket, upper)?;
+    }
+    Ok(res)
+  }
+
+  /// Returns present items in `bucket` with clock in `(lower, upper]` which
+  /// satisfy `matches` but are not in `live`, together with their clocks. Used
+  /// when a remote peer which sent all of its items in that range no longer
+  /// has the removals of these items.
+  pub fn missing(
+    &self,
+    txr: &impl AtomSetTransactor,
+    bucket: u64,
+    (lower, upper): (Option<u64>, u64),
+    live: &BTreeSet<u128>,
+    matches: impl Fn(&Item) -> bool,
+  ) -> Result<Vec<(u128, u64)>, StoreError> {
+    let mut res = Vec::new();
+    let mut lower = lower;
+    while lower < Some(upper) {
+      let chunk = self.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, item) in chunk {
+        if item.1 <= upper && item.2.is_some() && !live.contains(&id) && matches(&item) {
+          res.push((id, item.1));
         }
       }
     }
-    false
+    Ok(res)
   }
 
   /// Saves all pending modifications.
-  pub fn save(&mut self, txr: &mut impl AtomSetTransactor) {
-    self.metadata.save(txr);
+  pub fn save(&mut self, txr: &mut impl AtomSetTransactor) -> Result<(), StoreError> {
+    self.metadata.save(txr)?;
     for (id, (_, curr)) in std::mem::take(&mut self.mods) {
-      txr.set(self.prefix(), self.name(), id, curr);
+      txr.set(self.prefix(), self.name(), id, curr)?;
     }
+    Ok(())
   }
 }
 
-fn read_row(row: &Row<'_>) -> (u128, Item) {
-  let id = row.get(0).unwrap();
-  let bucket = row.get(1).unwrap();
-  let clock = row.get(2).unwrap();
-  let src: Option<_> = row.get(3).unwrap();
-  let label: Option<_> = row.get(4).unwrap();
-  let value: Option<Vec<u8>> = row.get(5).unwrap();
-  (
-    u128::from_be_bytes(id),
-    (
-      u64::from_be_bytes(bucket),
-      u64::from_be_bytes(clock),
-      value.map(|vec| (u128::from_be_bytes(src.unwrap()), u64::from_be_bytes(label.unwrap()), vec.into())),
-    ),
-  )
+fn read_row(row: &Row<'_>) -> Result<(u128, Item)> {
+  let id = row.get(0)?;
+  let bucket = row.get(1)?;
+  let clock = row.get(2)?;
+  let value: Option<Vec<u8>> = row.get(5)?;
+  // A live row must carry its source and label; reading them as non-null surfaces a corrupt row as an error.
+  let slv = match value {
+    Some(vec) => Some((u128::from_be_bytes(row.get(3)?), u64::from_be_bytes(row.get(4)?), vec.into())),
+    None => None,
+  };
+  Ok((u128::from_be_bytes(id), (u64::from_be_bytes(bucket), u64::from_be_bytes(clock), slv)))
 }
 
-fn read_row_id_label_value(row: &Row<'_>) -> (u128, (u64, Box<[u8]>)) {
-  let id = row.get(0).unwrap();
-  let label = row.get(1).unwrap();
-  let value: Vec<u8> = row.get(2).unwrap();
-  (u128::from_be_bytes(id), (u64::from_be_bytes(label), value.into()))
+fn read_row_id_label_value(row: &Row<'_>) -> Result<(u128, (u64, Box<[u8]>))> {
+  let id = row.get(0)?;
+  let label = row.get(1)?;
+  let value: Vec<u8> = row.get(2)?;
+  Ok((u128::from_be_bytes(id), (u64::from_be_bytes(label), value.into())))
 }
 
-fn read_row_id_value(row: &Row<'_>) -> (u128, Box<[u8]>) {
-  let id = row.get(0).unwrap();
-  let value: Vec<u8> = row.get(1).unwrap();
-  (u128::from_be_bytes(id), value.into())
+fn read_row_id_value(row: &Row<'_>) -> Result<(u128, Box<[u8]>)> {
+  let id = row.get(0)?;
+  let value: Vec<u8> = row.get(1)?;
+  Ok((u128::from_be_bytes(id), value.into()))
 }
 
-fn read_row_id_src_value(row: &Row<'_>) -> (u128, (u128, Box<[u8]>)) {
-  let id = row.get(0).unwrap();
-  let src = row.get(1).unwrap();
-  let value: Vec<u8> = row.get(2).unwrap();
-  (u128::from_be_bytes(id), (u128::from_be_bytes(src), value.into()))
+fn read_row_id_src_value(row: &Row<'_>) -> Result<(u128, (u128, Box<[u8]>))> {
+  let id = row.get(0)?;
+  let src = row.get(1)?;
+  let value: Vec<u8> = row.get(2)?;
+  Ok((u128::from_be_bytes(id), (u128::from_be_bytes(src), value.into())))
 }
 
-fn read_row_id_src(row: &Row<'_>) -> (u128, u128) {
-  let id = row.get(0).unwrap();
-  let src = row.get(1).unwrap();
-  (u128::from_be_bytes(id), u128::from_be_bytes(src))
+fn read_row_id_src(row: &Row<'_>) -> Result<(u128, u128)> {
+  let id = row.get(0)?;
+  let src = row.get(1)?;
+  Ok((u128::from_be_bytes(id), u128::from_be_bytes(src)))
 }
 
 fn make_row(
@@ -250,8 +501,13 @@ fn make_row(
   (id.to_be_bytes(), bucket.to_be_bytes(), clock.to_be_bytes(), src, label, value)
 }
 
+fn read_row_label_count(row: &Row<'_>) -> Result<(Option<u64>, (u64, u64))> {
+  let label: Option<_> = row.get(0)?;
+  Ok((label.map(u64::from_be_bytes), (row.get(1)?, row.get(2)?)))
+}
+
 impl AtomSetTransactor for Transactor {
-  fn init(&mut self, prefix: &str, name: &str) {
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
     self
       .execute_batch(&format!(
         "
@@ -269,93 +525,336 @@ impl AtomSetTransactor for Transactor {
         CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_label_value\" ON \"{prefix}.{name}.data\" (label, value);
         CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_bucket_clock\" ON \"{prefix}.{name}.data\" (bucket, clock);
         "
-      ))
-      .unwrap();
+      ))?;
+    Ok(())
   }
 
-  fn get(&self, prefix: &str, name: &str, id: u128) -> Option<Item> {
-    self
-      .prepare_cached(&format!(
-        "SELECT id, bucket, clock, src, label, value FROM \"{prefix}.{name}.data\"
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, bucket, clock, src, label, value FROM \"{prefix}.{name}.data\"
         WHERE id = ?"
-      ))
-      .unwrap()
-      .query_row((id.to_be_bytes(),), |row| Ok(read_row(row)))
-      .optional()
-      .unwrap()
-      .map(|(_, item)| item)
+        ))?
+        .query_row((id.to_be_bytes(),), read_row)
+        .optional()?
+        .map(|(_, item)| item),
+    )
   }
 
-  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) {
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
     self
-      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.data\" VALUES (?, ?, ?, ?, ?, ?)"))
-      .unwrap()
-      .execute(make_row(id, item))
-      .unwrap();
+      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.data\" VALUES (?, ?, ?, ?, ?, ?)"))?
+      .execute(make_row(id, item))?;
+    Ok(())
   }
 
-  fn id_label_value_by_src(&self, prefix: &str, name: &str, src: u128) -> BTreeMap<u128, (u64, Box<[u8]>)> {
-    self
-      .prepare_cached(&format!(
-        "SELECT id, label, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
+  fn id_label_value_by_src(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, Box<[u8]>)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, label, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
         WHERE src = ?"
-      ))
-      .unwrap()
-      .query_map((src.to_be_bytes(),), |row| Ok(read_row_id_label_value(row)))
-      .unwrap()
-      .map(Result::unwrap)
-      .collect()
+        ))?
+        .query_map((src.to_be_bytes(),), read_row_id_label_value)?
+        .collect::<Result<_>>()?,
+    )
   }
 
-  fn id_value_by_src_label(&self, prefix: &str, name: &str, src: u128, label: u64) -> BTreeMap<u128, Box<[u8]>> {
-    self
-      .prepare_cached(&format!(
-        "SELECT id, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
+  fn id_value_by_src_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, Box<[u8]>>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
         WHERE src = ? AND label = ?"
-      ))
-      .unwrap()
-      .query_map((src.to_be_bytes(), label.to_be_bytes()), |row| Ok(read_row_id_value(row)))
-      .unwrap()
-      .map(Result::unwrap)
+        ))?
+        .query_map((src.to_be_bytes(), label.to_be_bytes()), read_row_id_value)?
+        .collect::<Result<_>>()?,
+    )
+  }
+
+  fn id_src_value_by_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+  ) -> Result<BTreeMap<u128, (u128, Box<[u8]>)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, src, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label_value\"
+        WHERE label = ?"
+        ))?
+        .query_map((label.to_be_bytes(),), read_row_id_src_value)?
+        .collect::<Result<_>>()?,
+    )
+  }
+
+  fn id_src_by_label_value(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    value: &[u8],
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, src FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label_value\"
+        WHERE label = ? AND value = ?"
+        ))?
+        .query_map((label.to_be_bytes(), value), read_row_id_src)?
+        .collect::<Result<_>>()?,
+    )
+  }
+
+  fn id_src_value_by_label_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    (lower, upper): (Bound<&[u8]>, Bound<&[u8]>),
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError> {
+    let (lower_cond, lower) = match lower {
+      Bound::Included(value) => ("value >= ?2", Some(value)),
+      Bound::Excluded(value) => ("value > ?2", Some(value)),
+      Bound::Unbounded => ("?2 IS NULL", None),
+    };
+    let (upper_cond, upper) = match upper {
+      Bound::Included(value) => ("value <= ?3", Some(value)),
+      Bound::Excluded(value) => ("value < ?3", Some(value)),
+      Bound::Unbounded => ("?3 IS NULL", None),
+    };
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, src, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label_value\"
+        WHERE label = ?1 AND {lower_cond} AND {upper_cond} ORDER BY value ASC, id ASC
+        LIMIT IFNULL(?4, -1) OFFSET ?5"
+        ))?
+        .query_map(
+          (
+            label.to_be_bytes(),
+            lower,
+            upper,
+            limit.and_then(|limit| i64::try_from(limit).ok()),
+            i64::try_from(offset).unwrap_or(i64::MAX),
+          ),
+          read_row_id_src_value,
+        )?
+        .collect::<Result<_>>()?,
+    )
+  }
+
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    Ok(self
+      .prepare_cached(&format!(
+        "SELECT id, bucket, clock, src, label, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_bucket_clock\"
+        WHERE bucket = ?1 AND (?2 IS NULL OR clock > ?2) ORDER BY clock ASC LIMIT IFNULL(?3, -1)"
+      ))?
+      .query_map((bucket.to_be_bytes(), lower.map(u64::to_be_bytes), limit.and_then(|limit| i64::try_from(limit).ok())), read_row)?
+      .collect::<Result<_>>()?)
+  }
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "DELETE FROM \"{prefix}.{name}.data\" WHERE bucket = ? AND clock <= ? AND label IS NULL"
+        ))?
+        .execute((bucket.to_be_bytes(), upper.to_be_bytes()))? as u64,
+    )
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT label, COUNT(*), IFNULL(SUM(LENGTH(value)), 0) FROM \"{prefix}.{name}.data\" GROUP BY label"
+        ))?
+        .query_map((), read_row_label_count)?
+        .collect::<Result<_>>()?,
+    )
+  }
+}
+
+/// Removes an item and its index entries, for [`MemoryTransactor`]. Returns
+/// the removed item.
+fn unindex(txr: &mut MemoryTransactor, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+  let Some(item): Option<Item> = txr.read(&format!("{prefix}.{name}.data"), &id)? else { return Ok(None) };
+  let (bucket, clock, slv) = &item;
+  txr.remove(&format!("{prefix}.{name}.data"), &id);
+  if let Some((src, label, value)) = slv {
+    txr.remove(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label, id));
+    txr.remove(&format!("{prefix}.{name}.data.idx_label_value"), &(label, value, id));
+  }
+  txr.remove(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id));
+  Ok(Some(item))
+}
+
+/// Reads the present atom which an index entry refers to, for
+/// [`MemoryTransactor`].
+fn read_indexed(
+  txr: &MemoryTransactor,
+  prefix: &str,
+  name: &str,
+  id: u128,
+) -> Result<(u128, u64, Box<[u8]>), StoreError> {
+  let (_, _, slv): Item = txr.read_indexed(&format!("{prefix}.{name}.data"), &id)?;
+  slv.ok_or_else(|| StoreError::Corrupt(format!("{prefix}.{name}.data: indexed atom {id:032x} is removed")))
+}
+
+impl AtomSetTransactor for MemoryTransactor {
+  fn init(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    self.read(&format!("{prefix}.{name}.data"), &id)
+  }
+
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
+    unindex(self, prefix, name, id)?;
+    let (bucket, clock, slv) = &item;
+    if let Some((src, label, value)) = slv {
+      self.write(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label, id), &());
+      self.write(&format!("{prefix}.{name}.data.idx_label_value"), &(label, value, id), src);
+    }
+    self.write(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id), &());
+    self.write(&format!("{prefix}.{name}.data"), &id, &item);
+    Ok(())
+  }
+
+  fn id_label_value_by_src(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+  ) -> Result<BTreeMap<u128, (u64, Box<[u8]>)>, StoreError> {
+    self
+      .scan(&format!("{prefix}.{name}.data.idx_src_label"), &src)
+      .map(|entry| {
+        let ((_, label, id), ()): ((u128, u64, u128), ()) = entry?;
+        Ok((id, (label, read_indexed(self, prefix, name, id)?.2)))
+      })
       .collect()
   }
 
-  fn id_src_value_by_label(&self, prefix: &str, name: &str, label: u64) -> BTreeMap<u128, (u128, Box<[u8]>)> {
+  fn id_value_by_src_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, Box<[u8]>>, StoreError> {
     self
-      .prepare_cached(&format!(
-        "SELECT id, src, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label_value\"
-        WHERE label = ?"
-      ))
-      .unwrap()
-      .query_map((label.to_be_bytes(),), |row| Ok(read_row_id_src_value(row)))
-      .unwrap()
-      .map(Result::unwrap)
+      .scan(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label))
+      .map(|entry| {
+        let ((_, _, id), ()): ((u128, u64, u128), ()) = entry?;
+        Ok((id, read_indexed(self, prefix, name, id)?.2))
+      })
       .collect()
   }
 
-  fn id_src_by_label_value(&self, prefix: &str, name: &str, label: u64, value: &[u8]) -> BTreeMap<u128, u128> {
+  fn id_src_value_by_label(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+  ) -> Result<BTreeMap<u128, (u128, Box<[u8]>)>, StoreError> {
     self
-      .prepare_cached(&format!(
-        "SELECT id, src FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label_value\"
-        WHERE label = ? AND value = ?"
-      ))
-      .unwrap()
-      .query_map((label.to_be_bytes(), value), |row| Ok(read_row_id_src(row)))
-      .unwrap()
-      .map(Result::unwrap)
+      .scan(&format!("{prefix}.{name}.data.idx_label_value"), &label)
+      .map(|entry| entry.map(|((_, value, id), src): ((u64, Box<[u8]>, u128), u128)| (id, (src, value))))
       .collect()
   }
 
-  fn by_bucket_clock_range(&self, prefix: &str, name: &str, bucket: u64, lower: Option<u64>) -> BTreeMap<u128, Item> {
+  fn id_src_by_label_value(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    value: &[u8],
+  ) -> Result<BTreeMap<u128, u128>, StoreError> {
     self
-      .prepare_cached(&format!(
-        "SELECT id, bucket, clock, src, label, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_bucket_clock\"
-        WHERE bucket = ? AND clock > ?"
-      ))
-      .unwrap()
-      .query_map((bucket.to_be_bytes(), lower.map(u64::to_be_bytes)), |row| Ok(read_row(row)))
-      .unwrap()
-      .map(Result::unwrap)
+      .scan(&format!("{prefix}.{name}.data.idx_label_value"), &(label, value))
+      .map(|entry| entry.map(|((_, _, id), src): ((u64, Box<[u8]>, u128), u128)| (id, src)))
       .collect()
   }
+
+  fn id_src_value_by_label_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    label: u64,
+    (lower, upper): (Bound<&[u8]>, Bound<&[u8]>),
+    offset: u64,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, (u128, Box<[u8]>))>, StoreError> {
+    // Keys are not ordered by value here, since values are length-prefixed.
+    let mut res = Vec::new();
+    for entry in self.scan(&format!("{prefix}.{name}.data.idx_label_value"), &label) {
+      let ((_, value, id), src): ((u64, Box<[u8]>, u128), u128) = entry?;
+      if (lower, upper).contains(&value.as_ref()) {
+        res.push((id, (src, value)));
+      }
+    }
+    res.sort_by(|(lhs, (_, lvalue)), (rhs, (_, rvalue))| (lvalue, lhs).cmp(&(rvalue, rhs)));
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    let limit = limit.and_then(|limit| usize::try_from(limit).ok()).unwrap_or(usize::MAX);
+    Ok(res.into_iter().skip(offset).take(limit).collect())
+  }
+
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let ids = self.by_bucket_clock(&format!("{prefix}.{name}.data.idx_bucket_clock"), bucket, lower, limit)?;
+    ids.into_iter().map(|id| Ok((id, self.read_indexed(&format!("{prefix}.{name}.data"), &id)?))).collect()
+  }
+
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let ids: Vec<u128> = AtomSetTransactor::by_bucket_clock_range(self, prefix, name, bucket, None, None)?
+      .into_iter()
+      .take_while(|(_, (_, clock, _))| *clock <= upper)
+      .filter_map(|(id, (_, _, slv))| slv.is_none().then_some(id))
+      .collect();
+    for &id in &ids {
+      unindex(self, prefix, name, id)?;
+    }
+    Ok(ids.len() as u64)
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    let mut res: BTreeMap<Option<u64>, (u64, u64)> = BTreeMap::new();
+    for entry in self.scan::<u128, Item>(&format!("{prefix}.{name}.data"), &()) {
+      let (_, (_, _, slv)) = entry?;
+      let entry = res.entry(slv.as_ref().map(|(_, label, _)| *label)).or_default();
+      entry.0 += 1;
+      entry.1 += slv.map_or(0, |(_, _, value)| value.len() as u64);
+    }
+    Ok(res)
+  }
 }
@@ -0,0 +1,69 @@
+// Copyright 2024 ParkourLabs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{deserialize, fnv64_hash, serialize, StoreError};
+
+/// Magic bytes at the start of every bundle.
+pub const BUNDLE_MAGIC: [u8; 8] = *b"DUSTSYNC";
+
+/// Bundle format version. Bump this if the layout below changes.
+pub const BUNDLE_VERSION: u64 = 1;
+
+/// Describes the contents of an offline sync bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleHeader {
+  /// Sync protocol version of the payload.
+  pub protocol: u64,
+  /// Schema version of the sender.
+  pub schema: u64,
+  /// Oldest schema version the sender can sync with.
+  pub oldest: u64,
+  /// Workspace ID of the sender.
+  pub workspace: u128,
+  /// Bucket of the sender.
+  pub sender: u64,
+  /// Bucket clocks of the sender for each structure, at the time of export.
+  pub clocks: BTreeMap<String, BTreeMap<u64, u64>>,
+}
+
+/// Encodes a bundle as: magic bytes, format version, header and payload, then
+/// a checksum of everything before.
+pub fn encode(header: &BundleHeader, payload: &[u8]) -> Box<[u8]> {
+  let mut res = BUNDLE_MAGIC.to_vec();
+  res.extend_from_slice(&BUNDLE_VERSION.to_be_bytes());
+  res.extend(serialize(&(header, payload)).unwrap());
+  res.extend_from_slice(&fnv64_hash(&res).to_be_bytes());
+  res.into()
+}
+
+/// Verifies and decodes a bundle made by [`encode`], returning its header and
+/// payload.
+pub fn decode(bytes: &[u8]) -> Result<(BundleHeader, &[u8]), StoreError> {
+  let err = |what: &str| StoreError::MalformedBundle(what.to_owned());
+  if bytes.len() < 24 || bytes[..8] != BUNDLE_MAGIC {
+    return Err(err("not a sync bundle"));
+  }
+  let (content, checksum) = bytes.split_at(bytes.len() - 8);
+  if fnv64_hash(content).to_be_bytes() != checksum {
+    return Err(err("checksum mismatch"));
+  }
+  let format = u64::from_be_bytes(content[8..16].try_into().unwrap());
+  if format != BUNDLE_VERSION {
+    return Err(StoreError::MalformedBundle(format!("unsupported format version {format}")));
+  }
+  deserialize(&content[16..]).map_err(|err| StoreError::MalformedBundle(err.to_string()))
+}
@@ -0,0 +1,69 @@
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS ".version" (
+          version BLOB NOT NULL,
+          PRIMARY KEY (version)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".version" VALUES(X'0000000000000001');
+CREATE TABLE IF NOT EXISTS ".this" (
+          this BLOB NOT NULL,
+          PRIMARY KEY (this)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".this" VALUES(X'8f4140c7033f3139');
+CREATE TABLE IF NOT EXISTS ".nodes.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".nodes.buckets" VALUES(X'8f4140c7033f3139',X'18df98679ec72f86');
+CREATE TABLE IF NOT EXISTS ".nodes.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          label BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".nodes.data" VALUES(X'00000000000000000000000000000001',X'8f4140c7033f3139',X'18df98679ec6402d',X'000000000000000a');
+INSERT INTO ".nodes.data" VALUES(X'00000000000000000000000000000002',X'8f4140c7033f3139',X'18df98679ec72f86',X'0000000000000014');
+CREATE TABLE IF NOT EXISTS ".atoms.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".atoms.buckets" VALUES(X'8f4140c7033f3139',X'18df98679ecdbb93');
+CREATE TABLE IF NOT EXISTS ".atoms.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB,
+          label BLOB,
+          value BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".atoms.data" VALUES(X'00000000000000000000000000000003',X'8f4140c7033f3139',X'18df98679ec7d524',X'00000000000000000000000000000001',X'000000000000001e',X'010203');
+INSERT INTO ".atoms.data" VALUES(X'00000000000000000000000000000005',X'8f4140c7033f3139',X'18df98679ecdbb93',NULL,NULL,NULL);
+CREATE TABLE IF NOT EXISTS ".edges.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".edges.buckets" VALUES(X'8f4140c7033f3139',X'18df98679ec8891f');
+CREATE TABLE IF NOT EXISTS ".edges.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB,
+          label BLOB,
+          dst BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".edges.data" VALUES(X'00000000000000000000000000000004',X'8f4140c7033f3139',X'18df98679ec8891f',X'00000000000000000000000000000001',X'0000000000000028',X'00000000000000000000000000000002');
+CREATE INDEX ".nodes.data.idx_label" ON ".nodes.data" (label);
+CREATE INDEX ".nodes.data.idx_bucket_clock" ON ".nodes.data" (bucket, clock);
+CREATE INDEX ".atoms.data.idx_src_label" ON ".atoms.data" (src, label);
+CREATE INDEX ".atoms.data.idx_label_value" ON ".atoms.data" (label, value);
+CREATE INDEX ".atoms.data.idx_bucket_clock" ON ".atoms.data" (bucket, clock);
+CREATE INDEX ".edges.data.idx_src_label" ON ".edges.data" (src, label);
+CREATE INDEX ".edges.data.idx_dst_label" ON ".edges.data" (dst, label);
+CREATE INDEX ".edges.data.idx_bucket_clock" ON ".edges.data" (bucket, clock);
+COMMIT;
@@ -0,0 +1,234 @@
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS ".version" (
+          version BLOB NOT NULL,
+          PRIMARY KEY (version)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".version" VALUES(X'0000000000000002');
+CREATE TABLE IF NOT EXISTS ".this" (
+          this BLOB NOT NULL,
+          PRIMARY KEY (this)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".this" VALUES(X'447caa7c24e8f0f7');
+CREATE TABLE IF NOT EXISTS ".id" (
+          id BLOB NOT NULL,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".id" VALUES(X'44d7d0b3c3ed95372509c8a1e6ad3f1b');
+CREATE TABLE IF NOT EXISTS ".peers" (
+          peer BLOB NOT NULL,
+          PRIMARY KEY (peer)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".acks" (
+          peer BLOB NOT NULL,
+          name TEXT NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (peer, name, bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".fingerprint" (
+          fingerprint BLOB NOT NULL,
+          PRIMARY KEY (fingerprint)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".forks" (
+          bucket BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".nodes.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".nodes.buckets" VALUES(X'447caa7c24e8f0f7',X'18dfa07c724f0000');
+CREATE TABLE IF NOT EXISTS ".nodes.filters" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".nodes.compacted" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".nodes.ranges" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".nodes.seen" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".nodes.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          label BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".nodes.data" VALUES(X'00000000000000000000000000000001',X'447caa7c24e8f0f7',X'18dfa07c724e0000',X'000000000000000a');
+INSERT INTO ".nodes.data" VALUES(X'00000000000000000000000000000002',X'447caa7c24e8f0f7',X'18dfa07c724f0000',X'0000000000000014');
+CREATE TABLE IF NOT EXISTS ".atoms.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".atoms.buckets" VALUES(X'447caa7c24e8f0f7',X'18dfa07c72580000');
+CREATE TABLE IF NOT EXISTS ".atoms.filters" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".atoms.compacted" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".atoms.ranges" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".atoms.seen" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".atoms.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB,
+          label BLOB,
+          value BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".atoms.data" VALUES(X'00000000000000000000000000000003',X'447caa7c24e8f0f7',X'18dfa07c72500000',X'00000000000000000000000000000001',X'000000000000001e',X'010203');
+INSERT INTO ".atoms.data" VALUES(X'00000000000000000000000000000005',X'447caa7c24e8f0f7',X'18dfa07c72580000',NULL,NULL,NULL);
+CREATE TABLE IF NOT EXISTS ".edges.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".edges.buckets" VALUES(X'447caa7c24e8f0f7',X'18dfa07c72510000');
+CREATE TABLE IF NOT EXISTS ".edges.filters" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".edges.compacted" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".edges.ranges" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".edges.seen" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".edges.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB,
+          label BLOB,
+          dst BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".edges.data" VALUES(X'00000000000000000000000000000004',X'447caa7c24e8f0f7',X'18dfa07c72510000',X'00000000000000000000000000000001',X'0000000000000028',X'00000000000000000000000000000002');
+CREATE TABLE IF NOT EXISTS ".lists.buckets" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".lists.filters" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".lists.compacted" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".lists.ranges" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".lists.seen" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".lists.data" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB NOT NULL,
+          label BLOB NOT NULL,
+          left BLOB,
+          right BLOB,
+          value BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".txns" (
+          bucket BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          name TEXT NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, txn, name)
+        ) STRICT, WITHOUT ROWID;
+INSERT INTO ".txns" VALUES(X'447caa7c24e8f0f7',X'18dfa07c72520000','atoms',X'18dfa07c72500000',X'18dfa07c72520000');
+INSERT INTO ".txns" VALUES(X'447caa7c24e8f0f7',X'18dfa07c72520000','edges',X'18dfa07c72510000',X'18dfa07c72510000');
+INSERT INTO ".txns" VALUES(X'447caa7c24e8f0f7',X'18dfa07c72520000','nodes',X'18dfa07c724e0000',X'18dfa07c724f0000');
+CREATE TABLE IF NOT EXISTS ".staged" (
+          name TEXT NOT NULL,
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          item BLOB NOT NULL,
+          PRIMARY KEY (name, bucket, clock, id)
+        ) STRICT, WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS ".staged_at" (
+          bucket BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, txn)
+        ) STRICT, WITHOUT ROWID;
+CREATE UNIQUE INDEX ".version.idx_single" ON ".version" ((0));
+CREATE INDEX ".nodes.data.idx_label" ON ".nodes.data" (label);
+CREATE INDEX ".nodes.data.idx_bucket_clock" ON ".nodes.data" (bucket, clock);
+CREATE INDEX ".atoms.data.idx_src_label" ON ".atoms.data" (src, label);
+CREATE INDEX ".atoms.data.idx_label_value" ON ".atoms.data" (label, value);
+CREATE INDEX ".atoms.data.idx_bucket_clock" ON ".atoms.data" (bucket, clock);
+CREATE INDEX ".edges.data.idx_src_label" ON ".edges.data" (src, label);
+CREATE INDEX ".edges.data.idx_dst_label" ON ".edges.data" (dst, label);
+CREATE INDEX ".edges.data.idx_bucket_clock" ON ".edges.data" (bucket, clock);
+CREATE INDEX ".lists.data.idx_src_label" ON ".lists.data" (src, label);
+CREATE INDEX ".lists.data.idx_bucket_clock" ON ".lists.data" (bucket, clock);
+CREATE INDEX ".lists.data.idx_left" ON ".lists.data" (left);
+CREATE INDEX ".lists.data.idx_right" ON ".lists.data" (right);
+CREATE INDEX ".txns.idx_bucket_name_upper" ON ".txns" (bucket, name, upper);
+CREATE INDEX ".staged.idx_bucket_txn" ON ".staged" (bucket, txn);
+COMMIT;
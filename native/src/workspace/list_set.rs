@@ -14,28 +14,709 @@
 
 //! YATA sequence sets.
 
-/*
-/// A base class for YATA sequence sets.
+use rusqlite::{OptionalExtension, Result, Row};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use super::{
+  metadata::{StructureMetadata, StructureMetadataTransactor},
+  PAGE_CHUNK,
+};
+use crate::{memory::MemoryTransactor, StoreError, Transactor};
+
+/// A last-writer-wins element set for storing list items. Each item remembers
+/// its neighbours at the time of insertion (its "origins"), from which a
+/// consistent ordering can be recovered on every replica.
 #[derive(Debug, Clone)]
-pub struct ListSet<T: Ord + Clone> {
-  version: Version<u64>,
-  nodes: BTreeMap<(u64, u64), Option<Item<T>>>, // All loaded nodes.
-  roots: BTreeSet<(u64, u64)>,                  // All loaded roots.
+pub struct ListSet {
+  metadata: StructureMetadata,
+  mods: BTreeMap<u128, (Option<Item>, Item)>,
 }
 
-/// Item type for YATA sequence sets.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Item<T: Ord + Clone> {
-  parent: Option<(u64, u64)>, // Root nodes have `parent == None`.
-  right: Option<(u64, u64)>,  // Rightmost child nodes have `right == None`.
-  data: Option<Vec<T>>,       // Removed nodes have `data == None`.
+/// `(src, label, left, right)`: the list an item belongs to, and its origins.
+/// These are fixed on insertion.
+type Pos = (u128, u64, Option<u128>, Option<u128>);
+
+/// `(bucket, clock, pos, value)`. Removed items have `value == None`.
+type Item = (u64, u64, Pos, Option<Box<[u8]>>);
+
+fn item_lt(lhs: &Item, rhs: &Item) -> bool {
+  (lhs.1, lhs.0) < (rhs.1, rhs.0)
 }
 
 /// Database interface for [`ListSet`].
-pub trait ListSetStore<T: Ord + Clone>: VersionStore<u64> {
-  fn init_data(&mut self, name: &str);
-  fn get_data(&mut self, name: &str, bucket: u64, serial: u64) -> Option<Item<T>>;
-  fn set_data(&mut self, name: &str, bucket: u64, serial: u64, value: &Item<T>);
-  fn query_data(&mut self, name: &str, bucket: u64, lower: u64) -> Vec<((u64, u64), Item<T>)>; // Inclusive.
+pub trait ListSetTransactor: StructureMetadataTransactor {
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError>;
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError>;
+  fn by_src_label(&self, prefix: &str, name: &str, src: u128, label: u64) -> Result<BTreeMap<u128, Item>, StoreError>;
+  fn by_src(&self, prefix: &str, name: &str, src: u128) -> Result<BTreeMap<u128, Item>, StoreError>;
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError>;
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError>;
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError>;
+}
+
+impl ListSet {
+  /// Creates or loads data.
+  pub fn new(prefix: &str, name: &'static str, txr: &mut impl ListSetTransactor) -> Result<Self, StoreError> {
+    let metadata = StructureMetadata::new(prefix, name, txr)?;
+    let mods = BTreeMap::new();
+    txr.init(prefix, name)?;
+    Ok(Self { metadata, mods })
+  }
+
+  /// Returns a copy without pending modifications, for reading the saved
+  /// state through another connection.
+  pub fn committed(&self) -> Self {
+    Self { metadata: self.metadata.committed(), mods: BTreeMap::new() }
+  }
+
+  /// Returns the name of the workspace.
+  pub fn prefix(&self) -> &str {
+    self.metadata.prefix()
+  }
+
+  /// Returns the name of the structure.
+  pub fn name(&self) -> &'static str {
+    self.metadata.name()
+  }
+
+  /// Returns the current clock values for each bucket.
+  pub fn buckets(&self) -> BTreeMap<u64, u64> {
+    self.metadata.buckets()
+  }
+
+  /// Returns the clock value for the next local write.
+  pub fn next(&self) -> u64 {
+    self.metadata.next()
+  }
+
+  /// Returns whether all items in `bucket` up to clock `lower` have been received.
+  pub fn covers(&self, bucket: u64, lower: Option<u64>) -> bool {
+    self.metadata.covers(bucket, lower)
+  }
+
+  /// Records that all items in `bucket` with clock in `(lower, upper]` have been received.
+  pub fn extend(&mut self, bucket: u64, lower: Option<u64>, upper: u64) {
+    self.metadata.extend(bucket, lower, upper)
+  }
+
+  /// Returns whether no bucket has been received, with or without a filter.
+  pub fn is_pristine(&self) -> bool {
+    self.metadata.is_pristine()
+  }
+
+  /// Returns the current clock values for each bucket, for items matching `filter`.
+  pub fn filtered_buckets(&self, filter: u64) -> BTreeMap<u64, u64> {
+    self.metadata.filtered_buckets(filter)
+  }
+
+  /// Records that all items in `bucket` matching `filter` with clock in `(lower, upper]` have been received.
+  /// If `complete`, no item in the range was filtered out.
+  pub fn filtered_extend(&mut self, filter: u64, bucket: u64, lower: Option<u64>, upper: u64, complete: bool) {
+    self.metadata.filtered_extend(filter, bucket, lower, upper, complete)
+  }
+
+  /// Returns when buckets were received, see [`StructureMetadata::seen`].
+  pub fn seen(&self, since: Option<u64>) -> BTreeMap<u64, BTreeMap<u64, u64>> {
+    self.metadata.seen(since)
+  }
+
+  /// Forgets when buckets were received before local clock value `before`.
+  pub fn forget(&mut self, txr: &mut impl ListSetTransactor, before: u64) -> Result<(), StoreError> {
+    self.metadata.forget(txr, before)
+  }
+
+  /// Returns pending modifications as `(id, prev, curr)`, where removed items
+  /// are `None`.
+  pub fn mods(&self) -> Vec<(u128, Option<(u128, u64, Box<[u8]>)>, Option<(u128, u64, Box<[u8]>)>)> {
+    let present = |item: &Item| {
+      let (_, _, (src, label, _, _), value) = item;
+      value.clone().map(|value| (*src, *label, value))
+    };
+    let mut res = Vec::new();
+    for (id, (prev, curr)) in &self.mods {
+      res.push((*id, prev.as_ref().and_then(present), present(curr)));
+    }
+    res
+  }
+
+  /// Returns clock values of pending modifications in `bucket`.
+  pub fn pending(&self, bucket: u64) -> Vec<u64> {
+    self.mods.values().filter(|(_, curr)| curr.0 == bucket).map(|(_, curr)| curr.1).collect()
+  }
+
+  /// Returns the number of saved items and the total size of their values by label, where removed items
+  /// which have not been compacted are counted under `None`.
+  pub fn count_by_label(&self, txr: &impl ListSetTransactor) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    txr.count_by_label(self.prefix(), self.name())
+  }
+
+  pub fn get(&self, txr: &impl ListSetTransactor, id: u128) -> Result<Option<Item>, StoreError> {
+    self.mods.get(&id).map_or_else(|| txr.get(self.prefix(), self.name(), id), |(_, curr)| Ok(Some(curr.clone())))
+  }
+
+  /// Returns all items in the list, including removed ones.
+  pub fn by_src_label(
+    &self,
+    txr: &impl ListSetTransactor,
+    src: u128,
+    label: u64,
+  ) -> Result<BTreeMap<u128, Item>, StoreError> {
+    let mut res = txr.by_src_label(self.prefix(), self.name(), src, label)?;
+    for (id, (_, item)) in &self.mods {
+      let (_, _, (src_, label_, _, _), _) = item;
+      if src_ == &src && label_ == &label {
+        res.insert(*id, item.clone());
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns all items in all lists of `src`, including removed ones.
+  pub fn by_src(&self, txr: &impl ListSetTransactor, src: u128) -> Result<BTreeMap<u128, Item>, StoreError> {
+    let mut res = txr.by_src(self.prefix(), self.name(), src)?;
+    for (id, (_, item)) in &self.mods {
+      let (_, _, (src_, _, _, _), _) = item;
+      if src_ == &src {
+        res.insert(*id, item.clone());
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns the ids of all items in the list in order, including removed ones.
+  pub fn id_by_src_label(&self, txr: &impl ListSetTransactor, src: u128, label: u64) -> Result<Vec<u128>, StoreError> {
+    Ok(order(&self.by_src_label(txr, src, label)?))
+  }
+
+  /// Returns the ids and values of all present items in the list in order.
+  pub fn id_value_by_src_label(
+    &self,
+    txr: &impl ListSetTransactor,
+    src: u128,
+    label: u64,
+  ) -> Result<Vec<(u128, Box<[u8]>)>, StoreError> {
+    let mut items = self.by_src_label(txr, src, label)?;
+    let mut res = Vec::new();
+    for id in order(&items) {
+      if let Some((_, _, _, Some(value))) = items.remove(&id) {
+        res.push((id, value));
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns all actions strictly later than given clock values.
+  /// Absent entries are assumed to be `None`.
+  pub fn actions(
+    &self,
+    txr: &impl ListSetTransactor,
+    version: BTreeMap<u64, u64>,
+  ) -> Result<BTreeMap<u128, Item>, StoreError> {
+    let mut res = BTreeMap::new();
+    for &bucket in self.buckets().keys() {
+      let lower = version.get(&bucket).copied();
+      for (id, item) in txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, None)? {
+        res.insert(id, item);
+      }
+    }
+    for (id, (_, item)) in &self.mods {
+      let (bucket, clock, _, _) = item;
+      if Some(clock) > version.get(bucket) {
+        res.insert(*id, item.clone());
+      } else {
+        res.remove(id);
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns at most `limit` actions in `bucket` strictly later than `lower`,
+  /// in clock order.
+  pub fn bucket_actions(
+    &self,
+    txr: &impl ListSetTransactor,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let mut res = txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, Some(limit))?;
+    res.retain(|(id, _)| !self.mods.contains_key(id));
+    for (id, (_, item)) in &self.mods {
+      if item.0 == bucket && Some(item.1) > lower {
+        res.push((*id, item.clone()));
+      }
+    }
+    res.sort_by_key(|(_, item)| item.1);
+    res.truncate(limit as usize);
+    Ok(res)
+  }
+
+  /// Modifies item.
+  pub fn set(
+    &mut self,
+    txr: &impl ListSetTransactor,
+    id: u128,
+    bucket: u64,
+    clock: u64,
+    pos: Pos,
+    value: Option<Box<[u8]>>,
+  ) -> Result<bool, StoreError> {
+    Ok(self.metadata.update(bucket, clock) && self.merge(txr, id, bucket, clock, pos, value)?)
+  }
+
+  /// Modifies item without checking or advancing the bucket clock. Used for
+  /// items received out of order.
+  pub fn merge(
+    &mut self,
+    txr: &impl ListSetTransactor,
+    id: u128,
+    bucket: u64,
+    clock: u64,
+    pos: Pos,
+    value: Option<Box<[u8]>>,
+  ) -> Result<bool, StoreError> {
+    self.metadata.observe(clock);
+    let item = (bucket, clock, pos, value);
+    match self.mods.entry(id) {
+      Entry::Vacant(entry) => {
+        let prev = txr.get(self.metadata.prefix(), self.metadata.name(), id)?;
+        if prev.is_none() || item_lt(prev.as_ref().unwrap(), &item) {
+          entry.insert((prev, item));
+          return Ok(true);
+        }
+      }
+      Entry::Occupied(mut entry) => {
+        if item_lt(&entry.get().1, &item) {
+          entry.get_mut().1 = item;
+          return Ok(true);
+        }
+      }
+    }
+    Ok(false)
+  }
+
+  /// Moves item to `bucket`, keeping its clock value and contents. Used when
+  /// this replica forks, see [`crate::workspace::Workspace::fork`].
+  pub fn relocate(&mut self, txr: &impl ListSetTransactor, id: u128, bucket: u64) -> Result<(), StoreError> {
+    let (prev, mut curr) = match self.mods.remove(&id) {
+      Some(entry) => entry,
+      None => match txr.get(self.metadata.prefix(), self.metadata.name(), id)? {
+        Some(item) => (Some(item.clone()), item),
+        None => return Ok(()),
+      },
+    };
+    curr.0 = bucket;
+    self.metadata.update(bucket, curr.1);
+    self.mods.insert(id, (prev, curr));
+    Ok(())
+  }
+
+  /// Returns, for each bucket, the clock value up to which removed items may
+  /// have been physically deleted.
+  pub fn compacted(&self) -> &BTreeMap<u64, u64> {
+    self.metadata.compacted()
+  }
+
+  /// Records that removed items in `bucket` up to clock `clock` may have been
+  /// physically deleted elsewhere.
+  pub fn mark_compacted(
+    &mut self,
+    txr: &mut impl ListSetTransactor,
+    bucket: u64,
+    clock: u64,
+  ) -> Result<(), StoreError> {
+    self.metadata.compact(txr, bucket, clock)
+  }
+
+  /// Physically deletes saved removed items in `bucket` up to clock `upper`,
+  /// which no other item refers to as an origin, so that the ordering of the
+  /// remaining items does not change. Returns the number of deleted items.
+  pub fn compact(&mut self, txr: &mut impl ListSetTransactor, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let res = txr.purge(self.prefix(), self.name(), bucket, upper)?;
+    if res > 0 {
+      self.metadata.compact(txr, bucket, upper)?;
+    }
+    Ok(res)
+  }
+
+  /// Returns present items in `bucket` with clock in `(lower, upper]` which
+  /// satisfy `matches` but are not in `live`, together with their clocks. Used
+  /// when a remote peer which sent all of its items in that range no longer
+  /// has the removals of these items.
+  pub fn missing(
+    &self,
+    txr: &impl ListSetTransactor,
+    bucket: u64,
+    (lower, upper): (Option<u64>, u64),
+    live: &BTreeSet<u128>,
+    matches: impl Fn(&Item) -> bool,
+  ) -> Result<Vec<(u128, u64)>, StoreError> {
+    let mut res = Vec::new();
+    let mut lower = lower;
+    while lower < Some(upper) {
+      let chunk = self.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, item) in chunk {
+        if item.1 <= upper && item.3.is_some() && !live.contains(&id) && matches(&item) {
+          res.push((id, item.1));
+        }
+      }
+    }
+    Ok(res)
+  }
+
+  /// Saves all pending modifications.
+  pub fn save(&mut self, txr: &mut impl ListSetTransactor) -> Result<(), StoreError> {
+    self.metadata.save(txr)?;
+    for (id, (_, curr)) in std::mem::take(&mut self.mods) {
+      txr.set(self.prefix(), self.name(), id, curr)?;
+    }
+    Ok(())
+  }
+}
+
+/// Orders items in a list by integrating them one by one, using the rules
+/// from the YATA paper. The result does not depend on the integration order,
+/// as long as each item comes after its origins. Items with missing origins
+/// (i.e. not yet synchronised) are left out.
+fn order(items: &BTreeMap<u128, Item>) -> Vec<u128> {
+  let origins = |id: &u128| {
+    let (_, _, (_, _, left, right), _) = &items[id];
+    [*left, *right].into_iter().flatten()
+  };
+  let mut seq = BTreeMap::from([(None, None)]);
+  let mut waiting = BTreeMap::<u128, Vec<u128>>::new();
+  let mut ready = BTreeSet::new();
+  for id in items.keys() {
+    if origins(id).all(|origin| items.contains_key(&origin)) {
+      match origins(id).next() {
+        Some(origin) => waiting.entry(origin).or_default().push(*id),
+        None => {
+          ready.insert(*id);
+        }
+      }
+    }
+  }
+  while let Some(id) = ready.pop_first() {
+    let (_, _, (_, _, left, right), _) = &items[&id];
+    integrate(&mut seq, items, id, *left, *right);
+    for dependent in waiting.remove(&id).unwrap_or_default() {
+      match origins(&dependent).find(|origin| !seq.contains_key(&Some(*origin))) {
+        Some(origin) => waiting.entry(origin).or_default().push(dependent),
+        None => {
+          ready.insert(dependent);
+        }
+      }
+    }
+  }
+  let mut res = Vec::with_capacity(seq.len() - 1);
+  let mut curr = seq[&None];
+  while let Some(id) = curr {
+    res.push(id);
+    curr = seq[&Some(id)];
+  }
+  res
+}
+
+/// Inserts `id` into `seq` between its origins. `seq` maps each placed item
+/// to the one after it, with `None` standing for both ends, so that neither
+/// finding the origins nor inserting depends on the length of the list.
+fn integrate(
+  seq: &mut BTreeMap<Option<u128>, Option<u128>>,
+  items: &BTreeMap<u128, Item>,
+  id: u128,
+  left: Option<u128>,
+  right: Option<u128>,
+) {
+  let origins = |id: &u128| -> (Option<u128>, Option<u128>) {
+    let (_, _, (_, _, left, right), _) = &items[id];
+    (*left, *right)
+  };
+  let mut dest = left;
+  let mut before = BTreeSet::new();
+  let mut conflicting = BTreeSet::new();
+  let mut curr = seq[&left];
+  while let Some(other) = curr.filter(|_| curr != right) {
+    let (other_left, other_right) = origins(&other);
+    before.insert(other);
+    conflicting.insert(other);
+    if other_left == left {
+      if other < id {
+        dest = Some(other);
+        conflicting.clear();
+      } else if other_right == right {
+        break;
+      }
+    } else if let Some(other_left) = other_left.filter(|x| before.contains(x)) {
+      if !conflicting.contains(&other_left) {
+        dest = Some(other);
+        conflicting.clear();
+      }
+    } else {
+      break;
+    }
+    curr = seq[&Some(other)];
+  }
+  let after = seq.insert(dest, Some(id)).flatten();
+  seq.insert(Some(id), after);
+}
+
+fn read_row(row: &Row<'_>) -> Result<(u128, Item)> {
+  let id = row.get(0)?;
+  let bucket = row.get(1)?;
+  let clock = row.get(2)?;
+  let src = row.get(3)?;
+  let label = row.get(4)?;
+  let left: Option<_> = row.get(5)?;
+  let right: Option<_> = row.get(6)?;
+  let value: Option<Vec<u8>> = row.get(7)?;
+  Ok((
+    u128::from_be_bytes(id),
+    (
+      u64::from_be_bytes(bucket),
+      u64::from_be_bytes(clock),
+      (
+        u128::from_be_bytes(src),
+        u64::from_be_bytes(label),
+        left.map(u128::from_be_bytes),
+        right.map(u128::from_be_bytes),
+      ),
+      value.map(Into::into),
+    ),
+  ))
+}
+
+fn make_row(
+  id: u128,
+  item: Item,
+) -> ([u8; 16], [u8; 8], [u8; 8], [u8; 16], [u8; 8], Option<[u8; 16]>, Option<[u8; 16]>, Option<Box<[u8]>>) {
+  let (bucket, clock, (src, label, left, right), value) = item;
+  (
+    id.to_be_bytes(),
+    bucket.to_be_bytes(),
+    clock.to_be_bytes(),
+    src.to_be_bytes(),
+    label.to_be_bytes(),
+    left.map(u128::to_be_bytes),
+    right.map(u128::to_be_bytes),
+    value,
+  )
+}
+
+fn read_row_label_count(row: &Row<'_>) -> Result<(Option<u64>, (u64, u64))> {
+  let label: Option<_> = row.get(0)?;
+  Ok((label.map(u64::from_be_bytes), (row.get(1)?, row.get(2)?)))
+}
+
+impl ListSetTransactor for Transactor {
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self
+      .execute_batch(&format!(
+        "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.data\" (
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          src BLOB NOT NULL,
+          label BLOB NOT NULL,
+          left BLOB,
+          right BLOB,
+          value BLOB,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_src_label\" ON \"{prefix}.{name}.data\" (src, label);
+        CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_bucket_clock\" ON \"{prefix}.{name}.data\" (bucket, clock);
+        CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_left\" ON \"{prefix}.{name}.data\" (left);
+        CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_right\" ON \"{prefix}.{name}.data\" (right);
+        "
+      ))?;
+    Ok(())
+  }
+
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, bucket, clock, src, label, left, right, value FROM \"{prefix}.{name}.data\"
+        WHERE id = ?"
+        ))?
+        .query_row((id.to_be_bytes(),), read_row)
+        .optional()?
+        .map(|(_, item)| item),
+    )
+  }
+
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
+    self
+      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.data\" VALUES (?, ?, ?, ?, ?, ?, ?, ?)"))?
+      .execute(make_row(id, item))?;
+    Ok(())
+  }
+
+  fn by_src_label(&self, prefix: &str, name: &str, src: u128, label: u64) -> Result<BTreeMap<u128, Item>, StoreError> {
+    Ok(self
+      .prepare_cached(&format!(
+        "SELECT id, bucket, clock, src, label, left, right, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
+        WHERE src = ? AND label = ?"
+      ))?
+      .query_map((src.to_be_bytes(), label.to_be_bytes()), read_row)?
+      .collect::<Result<_>>()?)
+  }
+
+  fn by_src(&self, prefix: &str, name: &str, src: u128) -> Result<BTreeMap<u128, Item>, StoreError> {
+    Ok(self
+      .prepare_cached(&format!(
+        "SELECT id, bucket, clock, src, label, left, right, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_src_label\"
+        WHERE src = ?"
+      ))?
+      .query_map((src.to_be_bytes(),), read_row)?
+      .collect::<Result<_>>()?)
+  }
+
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    Ok(self
+      .prepare_cached(&format!(
+        "SELECT id, bucket, clock, src, label, left, right, value FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_bucket_clock\"
+        WHERE bucket = ?1 AND (?2 IS NULL OR clock > ?2) ORDER BY clock ASC LIMIT IFNULL(?3, -1)"
+      ))?
+      .query_map((bucket.to_be_bytes(), lower.map(u64::to_be_bytes), limit.and_then(|limit| i64::try_from(limit).ok())), read_row)?
+      .collect::<Result<_>>()?)
+  }
+
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "DELETE FROM \"{prefix}.{name}.data\" AS item WHERE bucket = ? AND clock <= ? AND value IS NULL
+          AND NOT EXISTS (SELECT 1 FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_left\" WHERE left = item.id)
+          AND NOT EXISTS (SELECT 1 FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_right\" WHERE right = item.id)"
+        ))?
+        .execute((bucket.to_be_bytes(), upper.to_be_bytes()))? as u64,
+    )
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT CASE WHEN value IS NOT NULL THEN label END AS live, COUNT(*), IFNULL(SUM(LENGTH(value)), 0)
+          FROM \"{prefix}.{name}.data\" GROUP BY live"
+        ))?
+        .query_map((), read_row_label_count)?
+        .collect::<Result<_>>()?,
+    )
+  }
+}
+
+/// Removes an item and its index entries, for [`MemoryTransactor`].
+fn unindex(txr: &mut MemoryTransactor, prefix: &str, name: &str, id: u128) -> Result<(), StoreError> {
+  let Some((bucket, clock, (src, label, left, right), _)) = txr.read::<Item>(&format!("{prefix}.{name}.data"), &id)?
+  else {
+    return Ok(());
+  };
+  txr.remove(&format!("{prefix}.{name}.data"), &id);
+  txr.remove(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label, id));
+  txr.remove(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id));
+  for origin in [left, right].into_iter().flatten() {
+    txr.remove(&format!("{prefix}.{name}.data.idx_origin"), &(origin, id));
+  }
+  Ok(())
+}
+
+impl ListSetTransactor for MemoryTransactor {
+  fn init(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    self.read(&format!("{prefix}.{name}.data"), &id)
+  }
+
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
+    unindex(self, prefix, name, id)?;
+    let (bucket, clock, (src, label, left, right), _) = item;
+    self.write(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label, id), &());
+    self.write(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id), &());
+    for origin in [left, right].into_iter().flatten() {
+      self.write(&format!("{prefix}.{name}.data.idx_origin"), &(origin, id), &());
+    }
+    self.write(&format!("{prefix}.{name}.data"), &id, &item);
+    Ok(())
+  }
+
+  fn by_src_label(&self, prefix: &str, name: &str, src: u128, label: u64) -> Result<BTreeMap<u128, Item>, StoreError> {
+    self
+      .scan(&format!("{prefix}.{name}.data.idx_src_label"), &(src, label))
+      .map(|entry| {
+        let ((_, _, id), ()): ((u128, u64, u128), ()) = entry?;
+        Ok((id, self.read_indexed(&format!("{prefix}.{name}.data"), &id)?))
+      })
+      .collect()
+  }
+
+  fn by_src(&self, prefix: &str, name: &str, src: u128) -> Result<BTreeMap<u128, Item>, StoreError> {
+    self
+      .scan(&format!("{prefix}.{name}.data.idx_src_label"), &src)
+      .map(|entry| {
+        let ((_, _, id), ()): ((u128, u64, u128), ()) = entry?;
+        Ok((id, self.read_indexed(&format!("{prefix}.{name}.data"), &id)?))
+      })
+      .collect()
+  }
+
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let ids = self.by_bucket_clock(&format!("{prefix}.{name}.data.idx_bucket_clock"), bucket, lower, limit)?;
+    ids.into_iter().map(|id| Ok((id, self.read_indexed(&format!("{prefix}.{name}.data"), &id)?))).collect()
+  }
+
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let ids: Vec<u128> = ListSetTransactor::by_bucket_clock_range(self, prefix, name, bucket, None, None)?
+      .into_iter()
+      .take_while(|(_, (_, clock, _, _))| *clock <= upper)
+      .filter_map(|(id, (_, _, _, value))| value.is_none().then_some(id))
+      .collect();
+    let mut res = 0;
+    for id in ids {
+      if self.scan::<(u128, u128), ()>(&format!("{prefix}.{name}.data.idx_origin"), &id).next().transpose()?.is_none() {
+        unindex(self, prefix, name, id)?;
+        res += 1;
+      }
+    }
+    Ok(res)
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    let mut res: BTreeMap<Option<u64>, (u64, u64)> = BTreeMap::new();
+    for entry in self.scan::<u128, Item>(&format!("{prefix}.{name}.data"), &()) {
+      let (_, (_, _, (_, label, _, _), value)) = entry?;
+      let entry = res.entry(value.as_ref().map(|_| label)).or_default();
+      entry.0 += 1;
+      entry.1 += value.map_or(0, |value| value.len() as u64);
+    }
+    Ok(res)
+  }
 }
-*/
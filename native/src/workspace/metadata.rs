@@ -15,162 +15,806 @@
 use rand::Rng;
 use rusqlite::OptionalExtension;
 use std::{
-  collections::BTreeMap,
+  collections::{btree_map, BTreeMap, BTreeSet},
+  sync::Arc,
   time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::Transactor;
+use crate::{memory::MemoryTransactor, StoreError, Transactor};
 
-/// Base schema version.
-pub const CURRENT_VERSION: u64 = 1;
+/// Current schema version.
+pub const CURRENT_VERSION: u64 = 2;
+
+/// Oldest schema version this one can still sync with. Raise it when sync
+/// payloads change in a way older versions cannot read, or vice versa.
+pub const OLDEST_COMPATIBLE_VERSION: u64 = 2;
+
+/// Upgrades the tables of a workspace in an SQLite database from one schema
+/// version to the next.
+pub type Migration = fn(&Transactor, &str) -> rusqlite::Result<()>;
+
+/// Schema migrations, in order: the one at index `i` upgrades from version
+/// `i + 1` to `i + 2`. To change the table layout, append a migration here and
+/// bump [`CURRENT_VERSION`], then add a fixture of the previous version to the
+/// tests. Migrations run inside the transaction which opens the database, so
+/// a failed upgrade leaves it unchanged.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize - 1] = [migrate_v1];
+
+/// Adds the tables and indexes introduced in version 2: workspace ID, peers
+/// and acknowledgements, fingerprint and forks, per-structure sync state,
+/// lists and transactions. Existing tables keep their layout.
+fn migrate_v1(txr: &Transactor, prefix: &str) -> rusqlite::Result<()> {
+  txr.execute_batch(&format!(
+    "
+      CREATE UNIQUE INDEX IF NOT EXISTS \"{prefix}.version.idx_single\" ON \"{prefix}.version\" ((0));
+      CREATE TABLE IF NOT EXISTS \"{prefix}.id\" (
+        id BLOB NOT NULL,
+        PRIMARY KEY (id)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.peers\" (
+        peer BLOB NOT NULL,
+        PRIMARY KEY (peer)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.acks\" (
+        peer BLOB NOT NULL,
+        name TEXT NOT NULL,
+        bucket BLOB NOT NULL,
+        clock BLOB NOT NULL,
+        PRIMARY KEY (peer, name, bucket)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.fingerprint\" (
+        fingerprint BLOB NOT NULL,
+        PRIMARY KEY (fingerprint)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.forks\" (
+        bucket BLOB NOT NULL,
+        PRIMARY KEY (bucket)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.lists.buckets\" (
+        bucket BLOB NOT NULL,
+        clock BLOB NOT NULL,
+        PRIMARY KEY (bucket)
+      ) STRICT, WITHOUT ROWID;
+      CREATE TABLE IF NOT EXISTS \"{prefix}.lists.data\" (
+        id BLOB NOT NULL,
+        bucket BLOB NOT NULL,
+        clock BLOB NOT NULL,
+        src BLOB NOT NULL,
+        label BLOB NOT NULL,
+        left BLOB,
+        right BLOB,
+        value BLOB,
+        PRIMARY KEY (id)
+      ) STRICT, WITHOUT ROWID;
+      CREATE INDEX IF NOT EXISTS \"{prefix}.lists.data.idx_src_label\" ON \"{prefix}.lists.data\" (src, label);
+      CREATE INDEX IF NOT EXISTS \"{prefix}.lists.data.idx_bucket_clock\" ON \"{prefix}.lists.data\" (bucket, clock);
+      CREATE INDEX IF NOT EXISTS \"{prefix}.lists.data.idx_left\" ON \"{prefix}.lists.data\" (left);
+      CREATE INDEX IF NOT EXISTS \"{prefix}.lists.data.idx_right\" ON \"{prefix}.lists.data\" (right);
+      CREATE TABLE IF NOT EXISTS \"{prefix}.txns\" (
+        bucket BLOB NOT NULL,
+        txn BLOB NOT NULL,
+        name TEXT NOT NULL,
+        lower BLOB NOT NULL,
+        upper BLOB NOT NULL,
+        PRIMARY KEY (bucket, txn, name)
+      ) STRICT, WITHOUT ROWID;
+      CREATE INDEX IF NOT EXISTS \"{prefix}.txns.idx_bucket_name_upper\" ON \"{prefix}.txns\" (bucket, name, upper);
+      CREATE TABLE IF NOT EXISTS \"{prefix}.staged\" (
+        name TEXT NOT NULL,
+        id BLOB NOT NULL,
+        bucket BLOB NOT NULL,
+        clock BLOB NOT NULL,
+        txn BLOB NOT NULL,
+        item BLOB NOT NULL,
+        PRIMARY KEY (name, bucket, clock, id)
+      ) STRICT, WITHOUT ROWID;
+      CREATE INDEX IF NOT EXISTS \"{prefix}.staged.idx_bucket_txn\" ON \"{prefix}.staged\" (bucket, txn);
+      CREATE TABLE IF NOT EXISTS \"{prefix}.staged_at\" (
+        bucket BLOB NOT NULL,
+        txn BLOB NOT NULL,
+        at BLOB NOT NULL,
+        PRIMARY KEY (bucket, txn)
+      ) STRICT, WITHOUT ROWID;
+      "
+  ))?;
+  for name in ["nodes", "atoms", "edges", "lists"] {
+    txr.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.filters\" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.compacted\" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.ranges\" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.seen\" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+  }
+  Ok(())
+}
+
+/// Number of low bits of a clock value which hold the logical counter of the
+/// hybrid logical clock. The other bits hold the physical time in nanoseconds,
+/// so clock values can still be compared with [`now`].
+pub const LOGICAL_BITS: u32 = 16;
+
+/// Returns the physical time in nanoseconds since the Unix epoch.
+pub fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).ok().and_then(|d| u64::try_from(d.as_nanos()).ok()).unwrap_or(0)
+}
+
+/// Returns the physical part of a clock value, with the logical counter cleared.
+pub fn physical(clock: u64) -> u64 {
+  clock >> LOGICAL_BITS << LOGICAL_BITS
+}
+
+/// Returns the largest clock value accepted from remote peers: the physical
+/// time plus `max_drift` nanoseconds, with any logical counter. Accepting
+/// larger values would drag the local clock along into the future.
+pub fn drift_limit(max_drift: u64) -> u64 {
+  physical(now().saturating_add(max_drift)) | ((1 << LOGICAL_BITS) - 1)
+}
 
 /// Stores the metadata for workspaces.
 #[derive(Debug, Clone)]
 pub struct WorkspaceMetadata {
-  prefix: &'static str,
+  prefix: Arc<str>,
   this: u64,
+  id: u128,
+  peers: BTreeMap<u64, BTreeMap<(String, u64), u64>>,
+  fingerprint: Option<u64>,
+  forks: BTreeSet<u64>,
 }
 
 /// Database interface for [`WorkspaceMetadata`].
 pub trait WorkspaceMetadataTransactor {
-  fn init_version(&mut self, prefix: &str);
-  fn init_this(&mut self, prefix: &str);
-  fn get_version(&self, prefix: &str) -> Option<u64>;
-  fn get_this(&self, prefix: &str) -> Option<u64>;
-  fn put_version(&mut self, prefix: &str, version: u64);
-  fn put_this(&mut self, prefix: &str, this: u64);
+  fn init_version(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn init_this(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn init_id(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn get_version(&self, prefix: &str) -> Result<Option<u64>, StoreError>;
+  fn get_this(&self, prefix: &str) -> Result<Option<u64>, StoreError>;
+  fn get_id(&self, prefix: &str) -> Result<Option<u128>, StoreError>;
+  fn put_version(&mut self, prefix: &str, version: u64) -> Result<(), StoreError>;
+  /// Upgrades the tables of workspace `prefix` from schema `version` to `version + 1`.
+  fn migrate(&mut self, prefix: &str, version: u64) -> Result<(), StoreError>;
+  fn put_this(&mut self, prefix: &str, this: u64) -> Result<(), StoreError>;
+  fn put_id(&mut self, prefix: &str, id: u128) -> Result<(), StoreError>;
+  fn init_peers(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn get_peers(&self, prefix: &str) -> Result<BTreeMap<u64, BTreeMap<(String, u64), u64>>, StoreError>;
+  fn put_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError>;
+  fn del_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError>;
+  fn put_ack(&mut self, prefix: &str, peer: u64, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError>;
+  fn init_fingerprint(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn get_fingerprint(&self, prefix: &str) -> Result<Option<u64>, StoreError>;
+  fn put_fingerprint(&mut self, prefix: &str, fingerprint: u64) -> Result<(), StoreError>;
+  fn init_forks(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn get_forks(&self, prefix: &str) -> Result<BTreeSet<u64>, StoreError>;
+  fn put_fork(&mut self, prefix: &str, bucket: u64) -> Result<(), StoreError>;
+}
+
+/// Brings the tables of workspace `prefix` to schema version `current`,
+/// running `migrate` once for each version between the stored one and
+/// `current`. New workspaces are stamped with `current` directly.
+fn upgrade<T: WorkspaceMetadataTransactor>(
+  txr: &mut T,
+  prefix: &str,
+  current: u64,
+  mut migrate: impl FnMut(&mut T, u64) -> Result<(), StoreError>,
+) -> Result<(), StoreError> {
+  txr.init_version(prefix)?;
+  match txr.get_version(prefix)? {
+    None => txr.put_version(prefix, current)?,
+    Some(version) if version == 0 || version > current => {
+      return Err(StoreError::UnsupportedSchema(version, current));
+    }
+    Some(version) => {
+      for version in version..current {
+        migrate(txr, version)?;
+        txr.put_version(prefix, version + 1)?;
+      }
+    }
+  }
+  Ok(())
 }
 
 impl WorkspaceMetadata {
   /// Creates or loads metadata.
-  pub fn new(prefix: &'static str, txr: &mut impl WorkspaceMetadataTransactor) -> Self {
-    txr.init_version(prefix);
-    txr.init_this(prefix);
-    let version = txr.get_version(prefix).unwrap_or_else(|| {
-      txr.put_version(prefix, CURRENT_VERSION);
-      CURRENT_VERSION
-    });
-    let this = txr.get_this(prefix).unwrap_or_else(|| {
-      let random = rand::thread_rng().gen();
-      txr.put_this(prefix, random);
-      random
-    });
-    if version != CURRENT_VERSION {
-      // Reserved for future use.
-      panic!("Unsupported schema version {version}.");
-    }
-    Self { prefix, this }
+  pub fn new(prefix: &str, txr: &mut impl WorkspaceMetadataTransactor) -> Result<Self, StoreError> {
+    upgrade(txr, prefix, CURRENT_VERSION, |txr, version| txr.migrate(prefix, version))?;
+    txr.init_this(prefix)?;
+    txr.init_id(prefix)?;
+    txr.init_peers(prefix)?;
+    txr.init_fingerprint(prefix)?;
+    txr.init_forks(prefix)?;
+    let this = match txr.get_this(prefix)? {
+      Some(this) => this,
+      None => {
+        let random = rand::thread_rng().gen();
+        txr.put_this(prefix, random)?;
+        random
+      }
+    };
+    let id = match txr.get_id(prefix)? {
+      Some(id) => id,
+      None => {
+        let random = rand::thread_rng().gen();
+        txr.put_id(prefix, random)?;
+        random
+      }
+    };
+    let peers = txr.get_peers(prefix)?;
+    let fingerprint = txr.get_fingerprint(prefix)?;
+    let forks = txr.get_forks(prefix)?;
+    Ok(Self { prefix: prefix.into(), this, id, peers, fingerprint, forks })
   }
 
   /// Returns the name of the workspace.
-  pub fn prefix(&self) -> &'static str {
-    self.prefix
+  pub fn prefix(&self) -> &str {
+    &self.prefix
   }
 
   /// Returns this client's ID.
   pub fn this(&self) -> u64 {
     self.this
   }
+
+  /// Switches to a fresh random client ID, returning it.
+  pub fn rotate(&mut self, txr: &mut impl WorkspaceMetadataTransactor) -> Result<u64, StoreError> {
+    self.this = rand::thread_rng().gen();
+    txr.put_this(&self.prefix, self.this)?;
+    Ok(self.this)
+  }
+
+  /// Returns the workspace ID, which is shared by all replicas of the same workspace.
+  pub fn id(&self) -> u128 {
+    self.id
+  }
+
+  /// Adopts the workspace ID of another replica.
+  pub fn set_id(&mut self, txr: &mut impl WorkspaceMetadataTransactor, id: u128) -> Result<(), StoreError> {
+    txr.put_id(&self.prefix, id)?;
+    self.id = id;
+    Ok(())
+  }
+
+  /// Returns the identity of the database file recorded when it was last
+  /// opened, if any.
+  pub fn fingerprint(&self) -> Option<u64> {
+    self.fingerprint
+  }
+
+  /// Records the identity of the database file.
+  pub fn set_fingerprint(
+    &mut self,
+    txr: &mut impl WorkspaceMetadataTransactor,
+    fingerprint: u64,
+  ) -> Result<(), StoreError> {
+    txr.put_fingerprint(&self.prefix, fingerprint)?;
+    self.fingerprint = Some(fingerprint);
+    Ok(())
+  }
+
+  /// Returns the buckets known to have been written by more than one replica.
+  pub fn forks(&self) -> &BTreeSet<u64> {
+    &self.forks
+  }
+
+  /// Records that `bucket` has been written by more than one replica.
+  pub fn add_fork(&mut self, txr: &mut impl WorkspaceMetadataTransactor, bucket: u64) -> Result<(), StoreError> {
+    if self.forks.insert(bucket) {
+      txr.put_fork(&self.prefix, bucket)?;
+    }
+    Ok(())
+  }
+
+  /// Returns the registered peers.
+  pub fn peers(&self) -> Vec<u64> {
+    self.peers.keys().copied().collect()
+  }
+
+  /// Registers a peer. Removed items are only compacted after all registered
+  /// peers have acknowledged them.
+  pub fn add_peer(&mut self, txr: &mut impl WorkspaceMetadataTransactor, peer: u64) -> Result<(), StoreError> {
+    if !self.peers.contains_key(&peer) {
+      txr.put_peer(&self.prefix, peer)?;
+      self.peers.insert(peer, BTreeMap::new());
+    }
+    Ok(())
+  }
+
+  /// Unregisters a peer, together with its acknowledgements.
+  pub fn remove_peer(&mut self, txr: &mut impl WorkspaceMetadataTransactor, peer: u64) -> Result<(), StoreError> {
+    if self.peers.contains_key(&peer) {
+      txr.del_peer(&self.prefix, peer)?;
+      self.peers.remove(&peer);
+    }
+    Ok(())
+  }
+
+  /// Records that a registered peer has received all items of structure `name`
+  /// up to the given clock values.
+  pub fn acknowledge(
+    &mut self,
+    txr: &mut impl WorkspaceMetadataTransactor,
+    peer: u64,
+    name: &str,
+    version: &BTreeMap<u64, u64>,
+  ) -> Result<(), StoreError> {
+    if let Some(acks) = self.peers.get_mut(&peer) {
+      for (&bucket, &clock) in version {
+        let entry = acks.entry((name.to_owned(), bucket)).or_default();
+        if *entry < clock {
+          txr.put_ack(&self.prefix, peer, name, bucket, clock)?;
+          *entry = clock;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns the clock values up to which all registered peers have received
+  /// all items of structure `name`, or `None` if there are no registered peers.
+  pub fn horizon(&self, name: &str) -> Option<BTreeMap<u64, u64>> {
+    let mut peers = self.peers.values();
+    let acks = |acks: &BTreeMap<(String, u64), u64>| -> BTreeMap<u64, u64> {
+      acks.iter().filter(|((name_, _), _)| name_ == name).map(|(&(_, bucket), &clock)| (bucket, clock)).collect()
+    };
+    let mut res = acks(peers.next()?);
+    for peer in peers {
+      let other = acks(peer);
+      res.retain(|bucket, clock| {
+        other.get(bucket).is_some_and(|&other| {
+          *clock = (*clock).min(other);
+          true
+        })
+      });
+    }
+    Some(res)
+  }
 }
 
 impl WorkspaceMetadataTransactor for Transactor {
-  fn init_version(&mut self, prefix: &str) {
-    self
-      .execute_batch(&format!(
-        "
+  fn init_version(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
         CREATE TABLE IF NOT EXISTS \"{prefix}.version\" (
           version BLOB NOT NULL,
           PRIMARY KEY (version)
         ) STRICT, WITHOUT ROWID;
+        CREATE UNIQUE INDEX IF NOT EXISTS \"{prefix}.version.idx_single\" ON \"{prefix}.version\" ((0));
         "
-      ))
-      .unwrap();
+    ))?;
+    Ok(())
   }
 
-  fn init_this(&mut self, prefix: &str) {
-    self
-      .execute_batch(&format!(
-        "
+  fn init_this(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
         CREATE TABLE IF NOT EXISTS \"{prefix}.this\" (
           this BLOB NOT NULL,
           PRIMARY KEY (this)
         ) STRICT, WITHOUT ROWID;
         "
-      ))
-      .unwrap();
+    ))?;
+    Ok(())
   }
 
-  fn get_version(&self, prefix: &str) -> Option<u64> {
-    self
-      .prepare_cached(&format!("SELECT version FROM \"{prefix}.version\""))
-      .unwrap()
-      .query_row((), |row| {
-        let version = row.get(0).unwrap();
-        Ok(u64::from_be_bytes(version))
-      })
-      .optional()
-      .unwrap()
+  fn init_id(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.id\" (
+          id BLOB NOT NULL,
+          PRIMARY KEY (id)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
   }
 
-  fn get_this(&self, prefix: &str) -> Option<u64> {
-    self
-      .prepare_cached(&format!("SELECT this FROM \"{prefix}.this\""))
-      .unwrap()
-      .query_row((), |row| {
-        let this = row.get(0).unwrap();
-        Ok(u64::from_be_bytes(this))
-      })
-      .optional()
-      .unwrap()
+  fn get_version(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT version FROM \"{prefix}.version\""))?
+        .query_row((), |row| {
+          let version = row.get(0)?;
+          Ok(u64::from_be_bytes(version))
+        })
+        .optional()?,
+    )
   }
 
-  fn put_version(&mut self, prefix: &str, version: u64) {
+  fn get_this(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT this FROM \"{prefix}.this\""))?
+        .query_row((), |row| {
+          let this = row.get(0)?;
+          Ok(u64::from_be_bytes(this))
+        })
+        .optional()?,
+    )
+  }
+
+  fn get_id(&self, prefix: &str) -> Result<Option<u128>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT id FROM \"{prefix}.id\""))?
+        .query_row((), |row| {
+          let id = row.get(0)?;
+          Ok(u128::from_be_bytes(id))
+        })
+        .optional()?,
+    )
+  }
+
+  fn put_version(&mut self, prefix: &str, version: u64) -> Result<(), StoreError> {
+    // The index on a constant keeps the table to a single row, which the upsert replaces.
     self
-      .prepare_cached(&format!("REPLACE INTO \"{prefix}.version\" VALUES (?)"))
-      .unwrap()
-      .execute((version.to_be_bytes(),))
-      .unwrap();
+      .prepare_cached(&format!(
+        "INSERT INTO \"{prefix}.version\" VALUES (?) ON CONFLICT DO UPDATE SET version = excluded.version"
+      ))?
+      .execute((version.to_be_bytes(),))?;
+    Ok(())
+  }
+
+  fn migrate(&mut self, prefix: &str, version: u64) -> Result<(), StoreError> {
+    MIGRATIONS[version as usize - 1](self, prefix)?;
+    Ok(())
+  }
+
+  fn put_this(&mut self, prefix: &str, this: u64) -> Result<(), StoreError> {
+    self.execute(&format!("DELETE FROM \"{prefix}.this\""), ())?;
+    self.prepare_cached(&format!("INSERT INTO \"{prefix}.this\" VALUES (?)"))?.execute((this.to_be_bytes(),))?;
+    Ok(())
+  }
+
+  fn put_id(&mut self, prefix: &str, id: u128) -> Result<(), StoreError> {
+    self.execute(&format!("DELETE FROM \"{prefix}.id\""), ())?;
+    self.prepare_cached(&format!("INSERT INTO \"{prefix}.id\" VALUES (?)"))?.execute((id.to_be_bytes(),))?;
+    Ok(())
   }
 
-  fn put_this(&mut self, prefix: &str, this: u64) {
+  fn init_peers(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.peers\" (
+          peer BLOB NOT NULL,
+          PRIMARY KEY (peer)
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE TABLE IF NOT EXISTS \"{prefix}.acks\" (
+          peer BLOB NOT NULL,
+          name TEXT NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (peer, name, bucket)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_peers(&self, prefix: &str) -> Result<BTreeMap<u64, BTreeMap<(String, u64), u64>>, StoreError> {
+    let mut res: BTreeMap<u64, BTreeMap<(String, u64), u64>> = self
+      .prepare_cached(&format!("SELECT peer FROM \"{prefix}.peers\""))?
+      .query_map((), |row| {
+        let peer = row.get(0)?;
+        Ok((u64::from_be_bytes(peer), BTreeMap::new()))
+      })?
+      .collect::<rusqlite::Result<_>>()?;
+    let acks = self
+      .prepare_cached(&format!("SELECT peer, name, bucket, clock FROM \"{prefix}.acks\""))?
+      .query_map((), |row| {
+        let peer = row.get(0)?;
+        let name = row.get(1)?;
+        let bucket = row.get(2)?;
+        let clock = row.get(3)?;
+        Ok((u64::from_be_bytes(peer), name, u64::from_be_bytes(bucket), u64::from_be_bytes(clock)))
+      })?
+      .collect::<rusqlite::Result<Vec<(u64, String, u64, u64)>>>()?;
+    for (peer, name, bucket, clock) in acks {
+      if let Some(acks) = res.get_mut(&peer) {
+        acks.insert((name, bucket), clock);
+      }
+    }
+    Ok(res)
+  }
+
+  fn put_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.peers\" VALUES (?)"))?.execute((peer.to_be_bytes(),))?;
+    Ok(())
+  }
+
+  fn del_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("DELETE FROM \"{prefix}.peers\" WHERE peer = ?"))?.execute((peer.to_be_bytes(),))?;
+    self.prepare_cached(&format!("DELETE FROM \"{prefix}.acks\" WHERE peer = ?"))?.execute((peer.to_be_bytes(),))?;
+    Ok(())
+  }
+
+  fn put_ack(&mut self, prefix: &str, peer: u64, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.acks\" VALUES (?, ?, ?, ?)"))?.execute((
+      peer.to_be_bytes(),
+      name,
+      bucket.to_be_bytes(),
+      clock.to_be_bytes(),
+    ))?;
+    Ok(())
+  }
+
+  fn init_fingerprint(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.fingerprint\" (
+          fingerprint BLOB NOT NULL,
+          PRIMARY KEY (fingerprint)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_fingerprint(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT fingerprint FROM \"{prefix}.fingerprint\""))?
+        .query_row((), |row| {
+          let fingerprint = row.get(0)?;
+          Ok(u64::from_be_bytes(fingerprint))
+        })
+        .optional()?,
+    )
+  }
+
+  fn put_fingerprint(&mut self, prefix: &str, fingerprint: u64) -> Result<(), StoreError> {
+    self.execute(&format!("DELETE FROM \"{prefix}.fingerprint\""), ())?;
     self
-      .prepare_cached(&format!("REPLACE INTO \"{prefix}.this\" VALUES (?)"))
-      .unwrap()
-      .execute((this.to_be_bytes(),))
-      .unwrap();
+      .prepare_cached(&format!("INSERT INTO \"{prefix}.fingerprint\" VALUES (?)"))?
+      .execute((fingerprint.to_be_bytes(),))?;
+    Ok(())
+  }
+
+  fn init_forks(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.forks\" (
+          bucket BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_forks(&self, prefix: &str) -> Result<BTreeSet<u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT bucket FROM \"{prefix}.forks\""))?
+        .query_map((), |row| {
+          let bucket = row.get(0)?;
+          Ok(u64::from_be_bytes(bucket))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn put_fork(&mut self, prefix: &str, bucket: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.forks\" VALUES (?)"))?.execute((bucket.to_be_bytes(),))?;
+    Ok(())
+  }
+}
+
+impl WorkspaceMetadataTransactor for MemoryTransactor {
+  fn init_version(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn init_this(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn init_id(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_version(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    self.read(&format!("{prefix}.version"), &())
+  }
+
+  fn get_this(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    self.read(&format!("{prefix}.this"), &())
+  }
+
+  fn get_id(&self, prefix: &str) -> Result<Option<u128>, StoreError> {
+    self.read(&format!("{prefix}.id"), &())
+  }
+
+  fn put_version(&mut self, prefix: &str, version: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.version"), &(), &version);
+    Ok(())
+  }
+
+  fn migrate(&mut self, _: &str, _: u64) -> Result<(), StoreError> {
+    // Nothing is persisted, so data is always created at the current version.
+    Ok(())
+  }
+
+  fn put_this(&mut self, prefix: &str, this: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.this"), &(), &this);
+    Ok(())
+  }
+
+  fn put_id(&mut self, prefix: &str, id: u128) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.id"), &(), &id);
+    Ok(())
+  }
+
+  fn init_peers(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_peers(&self, prefix: &str) -> Result<BTreeMap<u64, BTreeMap<(String, u64), u64>>, StoreError> {
+    let mut res: BTreeMap<u64, BTreeMap<(String, u64), u64>> = self
+      .scan(&format!("{prefix}.peers"), &())
+      .map(|entry| entry.map(|(peer, ())| (peer, BTreeMap::new())))
+      .collect::<Result<_, _>>()?;
+    for entry in self.scan::<(u64, String, u64), u64>(&format!("{prefix}.acks"), &()) {
+      let ((peer, name, bucket), clock) = entry?;
+      if let Some(acks) = res.get_mut(&peer) {
+        acks.insert((name, bucket), clock);
+      }
+    }
+    Ok(res)
+  }
+
+  fn put_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.peers"), &peer, &());
+    Ok(())
+  }
+
+  fn del_peer(&mut self, prefix: &str, peer: u64) -> Result<(), StoreError> {
+    self.remove(&format!("{prefix}.peers"), &peer);
+    self.remove_prefix(&format!("{prefix}.acks"), &peer);
+    Ok(())
+  }
+
+  fn put_ack(&mut self, prefix: &str, peer: u64, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.acks"), &(peer, name, bucket), &clock);
+    Ok(())
+  }
+
+  fn init_fingerprint(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_fingerprint(&self, prefix: &str) -> Result<Option<u64>, StoreError> {
+    self.read(&format!("{prefix}.fingerprint"), &())
+  }
+
+  fn put_fingerprint(&mut self, prefix: &str, fingerprint: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.fingerprint"), &(), &fingerprint);
+    Ok(())
+  }
+
+  fn init_forks(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_forks(&self, prefix: &str) -> Result<BTreeSet<u64>, StoreError> {
+    self.scan(&format!("{prefix}.forks"), &()).map(|entry| entry.map(|(bucket, ())| bucket)).collect()
+  }
+
+  fn put_fork(&mut self, prefix: &str, bucket: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.forks"), &bucket, &());
+    Ok(())
   }
 }
 
 /// Stores the metadata for individual Γ-joinable structures.
 #[derive(Debug, Clone)]
 pub struct StructureMetadata {
-  prefix: &'static str,
+  prefix: Arc<str>,
   name: &'static str,
-  buckets: BTreeMap<u64, u64>, // Saved, exhaustive
-  mods: BTreeMap<u64, u64>,    // Pending, exhaustive
-  next: u64,
+  buckets: BTreeMap<u64, u64>,                   // Saved, exhaustive
+  mods: BTreeMap<u64, u64>,                      // Pending, exhaustive
+  ranges: BTreeMap<u64, BTreeMap<u64, u64>>,     // Saved, received out of order
+  range_mods: BTreeMap<u64, BTreeMap<u64, u64>>, // Pending, replacing whole buckets
+  filters: BTreeMap<(u64, u64), u64>,            // Saved, keyed by filter and bucket
+  filter_mods: BTreeMap<(u64, u64), u64>,        // Pending
+  compacted: BTreeMap<u64, u64>,                 // Saved immediately
+  seen: BTreeMap<u64, BTreeMap<u64, u64>>,       // Saved, bucket clock -> local clock when received
+  seen_mods: BTreeMap<(u64, u64), u64>,          // Pending
+  last: u64,                                     // Largest clock value written or received
 }
 
 /// Database interface for [`StructureMetadata`].
 pub trait StructureMetadataTransactor {
-  fn init_buckets(&mut self, prefix: &str, name: &str);
-  fn get_buckets(&self, prefix: &str, name: &str) -> BTreeMap<u64, u64>;
-  fn set_bucket(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64);
+  fn init_buckets(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get_buckets(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError>;
+  fn set_bucket(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError>;
+  fn init_filters(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get_filters(&self, prefix: &str, name: &str) -> Result<BTreeMap<(u64, u64), u64>, StoreError>;
+  fn set_filter(&mut self, prefix: &str, name: &str, filter: u64, bucket: u64, clock: u64) -> Result<(), StoreError>;
+  fn init_compacted(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get_compacted(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError>;
+  fn set_compacted(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError>;
+  fn init_ranges(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get_ranges(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError>;
+  fn set_ranges(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    ranges: &BTreeMap<u64, u64>,
+  ) -> Result<(), StoreError>;
+  fn init_seen(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get_seen(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError>;
+  fn set_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64, at: u64) -> Result<(), StoreError>;
+  fn del_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError>;
 }
 
 impl StructureMetadata {
   /// Creates or loads metadata.
-  pub fn new(prefix: &'static str, name: &'static str, txr: &mut impl StructureMetadataTransactor) -> Self {
-    txr.init_buckets(prefix, name);
-    let buckets = txr.get_buckets(prefix, name);
+  pub fn new(prefix: &str, name: &'static str, txr: &mut impl StructureMetadataTransactor) -> Result<Self, StoreError> {
+    txr.init_buckets(prefix, name)?;
+    txr.init_filters(prefix, name)?;
+    txr.init_compacted(prefix, name)?;
+    txr.init_ranges(prefix, name)?;
+    txr.init_seen(prefix, name)?;
+    let buckets = txr.get_buckets(prefix, name)?;
     let mods = BTreeMap::new();
-    let next = buckets.values().fold(0, |acc, &clock| acc.max(clock + 1));
-    Self { prefix, name, buckets, mods, next }
+    let ranges = txr.get_ranges(prefix, name)?;
+    let range_mods = BTreeMap::new();
+    let filters = txr.get_filters(prefix, name)?;
+    let filter_mods = BTreeMap::new();
+    let compacted = txr.get_compacted(prefix, name)?;
+    let seen = txr.get_seen(prefix, name)?;
+    let seen_mods = BTreeMap::new();
+    let last = buckets.values().copied().max().unwrap_or(0);
+    Ok(Self {
+      prefix: prefix.into(),
+      name,
+      buckets,
+      mods,
+      ranges,
+      range_mods,
+      filters,
+      filter_mods,
+      compacted,
+      seen,
+      seen_mods,
+      last,
+    })
+  }
+
+  /// Returns a copy without pending modifications, i.e. as last saved.
+  pub fn committed(&self) -> Self {
+    let mut res = self.clone();
+    res.mods.clear();
+    res.range_mods.clear();
+    res.filter_mods.clear();
+    res.seen_mods.clear();
+    res
   }
 
   /// Returns the name of the workspace.
-  pub fn prefix(&self) -> &'static str {
-    self.prefix
+  pub fn prefix(&self) -> &str {
+    &self.prefix
   }
 
   /// Returns the name of the structure.
@@ -196,66 +840,479 @@ impl StructureMetadata {
     res
   }
 
-  /// Returns the largest clock value across all buckets plus one.
+  /// Returns the clock value for the next local write. This is a hybrid
+  /// logical clock: the physical time with a zero logical counter if it is
+  /// ahead of every clock value written or received, otherwise the largest of
+  /// those with its logical counter incremented. Once the physical time passes
+  /// it again, the counter starts over.
   pub fn next(&self) -> u64 {
-    let measured = SystemTime::now().duration_since(UNIX_EPOCH).ok().and_then(|d| u64::try_from(d.as_nanos()).ok());
-    self.next.max(measured.unwrap_or(0))
+    let physical = physical(now());
+    match physical > self.last {
+      true => physical,
+      false => self.last.saturating_add(1),
+    }
   }
 
   /// Updates clock for one bucket.
   pub fn update(&mut self, bucket: u64, clock: u64) -> bool {
     if self.get(bucket) < Some(clock) {
       self.mods.insert(bucket, clock);
-      self.next = self.next.max(clock + 1);
+      self.last = self.last.max(clock);
       return true;
     }
     false
   }
 
+  /// Advances the clock past a received clock value without updating any
+  /// bucket. Callers reject values beyond [`drift_limit`] beforehand.
+  pub fn observe(&mut self, clock: u64) {
+    self.last = self.last.max(clock);
+  }
+
+  /// Returns whether all items in `bucket` up to clock `lower` have been received.
+  pub fn covers(&self, bucket: u64, lower: Option<u64>) -> bool {
+    lower.is_none() || self.get(bucket) >= lower
+  }
+
+  /// Records that all items in `bucket` with clock in `(lower, upper]` have
+  /// been received. If this connects to the current clock value, the bucket is
+  /// advanced (together with any adjacent ranges received earlier); otherwise
+  /// the range is kept until the gap is filled.
+  pub fn extend(&mut self, bucket: u64, lower: Option<u64>, upper: u64) {
+    let covered = self.covers(bucket, lower);
+    let saved = self.ranges.get(&bucket);
+    let ranges = self.range_mods.entry(bucket).or_insert_with(|| saved.cloned().unwrap_or_default());
+    match lower {
+      Some(lower) if !covered => {
+        let entry = ranges.entry(lower).or_insert(upper);
+        *entry = (*entry).max(upper);
+      }
+      _ => {
+        let mut upper = upper;
+        while let Some((&lower, &upper_)) = ranges.first_key_value() {
+          if lower > upper {
+            break;
+          }
+          ranges.remove(&lower);
+          upper = upper.max(upper_);
+        }
+        self.update(bucket, upper);
+        if let Some(clock) = self.get(bucket) {
+          let at = self.next();
+          self.seen_mods.entry((bucket, clock)).or_insert(at);
+        }
+      }
+    }
+  }
+
+  /// Returns, for each bucket, the clock values up to which it had been
+  /// received when the local clock was at a given value, i.e. which writes
+  /// the next local write (at a larger clock value) could have seen. Only
+  /// entries which may be needed by a peer whose clock value for our own
+  /// bucket is `since` are returned.
+  pub fn seen(&self, since: Option<u64>) -> BTreeMap<u64, BTreeMap<u64, u64>> {
+    let mut all = self.seen.clone();
+    for (&(bucket, clock), &at) in &self.seen_mods {
+      all.entry(bucket).or_default().entry(clock).or_insert(at);
+    }
+    for entries in all.values_mut() {
+      // The last entry received before `since` tells about all later writes
+      // received by the peer; earlier ones are not needed.
+      let last = entries.iter().filter(|(_, &at)| Some(at) <= since).map(|(&clock, _)| clock).next_back();
+      entries.retain(|&clock, &mut at| Some(at) > since || Some(clock) == last);
+    }
+    all
+  }
+
+  /// Forgets when buckets were received before local clock value `before`,
+  /// except for the latest entry of each bucket.
+  pub fn forget(&mut self, txr: &mut impl StructureMetadataTransactor, before: u64) -> Result<(), StoreError> {
+    for (&bucket, entries) in &mut self.seen {
+      let old: Vec<u64> = entries.iter().filter(|(_, &at)| at < before).map(|(&clock, _)| clock).collect();
+      for clock in old.iter().rev().skip(1) {
+        txr.del_seen(&self.prefix, self.name, bucket, *clock)?;
+        entries.remove(clock);
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns whether no bucket has been received, with or without a filter.
+  pub fn is_pristine(&self) -> bool {
+    self.buckets.is_empty() && self.mods.is_empty() && self.filters.is_empty() && self.filter_mods.is_empty()
+  }
+
+  /// Returns the current clock values for each bucket, as seen by a replica
+  /// which only receives items matching `filter`.
+  pub fn filtered_buckets(&self, filter: u64) -> BTreeMap<u64, u64> {
+    let mut res = self.buckets();
+    for (&(_, bucket), &clock) in self.filters.range((filter, 0)..=(filter, u64::MAX)) {
+      let entry = res.entry(bucket).or_insert(clock);
+      *entry = (*entry).max(clock);
+    }
+    for (&(_, bucket), &clock) in self.filter_mods.range((filter, 0)..=(filter, u64::MAX)) {
+      let entry = res.entry(bucket).or_insert(clock);
+      *entry = (*entry).max(clock);
+    }
+    res
+  }
+
+  /// Returns whether any bucket has been received with a filter.
+  pub fn is_filtered(&self) -> bool {
+    !self.filters.is_empty() || !self.filter_mods.is_empty()
+  }
+
+  /// Records that all items in `bucket` matching `filter` with clock in
+  /// `(lower, upper]` have been received. Unlike [`Self::extend`], ranges
+  /// which do not connect to the current clock value are discarded.
+  ///
+  /// If `complete`, i.e. no item in the range was filtered out, and the range
+  /// also connects to the unfiltered clock value, that advances as well.
+  pub fn filtered_extend(&mut self, filter: u64, bucket: u64, lower: Option<u64>, upper: u64, complete: bool) {
+    let current = self.filtered_buckets(filter).get(&bucket).copied();
+    if (lower.is_none() || current >= lower) && current < Some(upper) {
+      self.filter_mods.insert((filter, bucket), upper);
+    }
+    if complete && self.covers(bucket, lower) {
+      self.extend(bucket, lower, upper);
+    }
+  }
+
+  /// Returns, for each bucket, the clock value up to which removed items may
+  /// have been physically deleted.
+  pub fn compacted(&self) -> &BTreeMap<u64, u64> {
+    &self.compacted
+  }
+
+  /// Records that removed items in `bucket` up to clock `clock` may have been
+  /// physically deleted.
+  pub fn compact(
+    &mut self,
+    txr: &mut impl StructureMetadataTransactor,
+    bucket: u64,
+    clock: u64,
+  ) -> Result<(), StoreError> {
+    if self.compacted.get(&bucket) < Some(&clock) {
+      txr.set_compacted(&self.prefix, self.name, bucket, clock)?;
+      self.compacted.insert(bucket, clock);
+    }
+    Ok(())
+  }
+
   /// Saves all pending modifications.
-  pub fn save(&mut self, txr: &mut impl StructureMetadataTransactor) {
+  pub fn save(&mut self, txr: &mut impl StructureMetadataTransactor) -> Result<(), StoreError> {
     for (key, value) in std::mem::take(&mut self.mods) {
       self.buckets.insert(key, value);
-      txr.set_bucket(self.prefix, self.name, key, value);
+      txr.set_bucket(&self.prefix, self.name, key, value)?;
+    }
+    for ((filter, bucket), value) in std::mem::take(&mut self.filter_mods) {
+      self.filters.insert((filter, bucket), value);
+      txr.set_filter(&self.prefix, self.name, filter, bucket, value)?;
+    }
+    for (bucket, ranges) in std::mem::take(&mut self.range_mods) {
+      txr.set_ranges(&self.prefix, self.name, bucket, &ranges)?;
+      if ranges.is_empty() {
+        self.ranges.remove(&bucket);
+      } else {
+        self.ranges.insert(bucket, ranges);
+      }
     }
+    for ((bucket, clock), at) in std::mem::take(&mut self.seen_mods) {
+      if let btree_map::Entry::Vacant(entry) = self.seen.entry(bucket).or_default().entry(clock) {
+        entry.insert(at);
+        txr.set_seen(&self.prefix, self.name, bucket, clock, at)?;
+      }
+    }
+    Ok(())
   }
 }
 
 impl StructureMetadataTransactor for Transactor {
-  fn init_buckets(&mut self, prefix: &str, name: &str) {
+  fn init_buckets(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.buckets\" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (bucket)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_buckets(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT bucket, clock FROM \"{prefix}.{name}.buckets\""))?
+        .query_map((), |row| {
+          let bucket = row.get(0)?;
+          let clock = row.get(1)?;
+          Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(clock)))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn set_bucket(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
     self
-      .execute_batch(&format!(
+      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.buckets\" VALUES (?, ?)"))?
+      .execute((bucket.to_be_bytes(), clock.to_be_bytes()))?;
+    Ok(())
+  }
+
+  fn init_filters(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.filters\" (
+          filter BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          PRIMARY KEY (filter, bucket)
+        ) STRICT, WITHOUT ROWID;
         "
-        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.buckets\" (
+    ))?;
+    Ok(())
+  }
+
+  fn get_filters(&self, prefix: &str, name: &str) -> Result<BTreeMap<(u64, u64), u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT filter, bucket, clock FROM \"{prefix}.{name}.filters\""))?
+        .query_map((), |row| {
+          let filter = row.get(0)?;
+          let bucket = row.get(1)?;
+          let clock = row.get(2)?;
+          Ok(((u64::from_be_bytes(filter), u64::from_be_bytes(bucket)), u64::from_be_bytes(clock)))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn set_filter(&mut self, prefix: &str, name: &str, filter: u64, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.filters\" VALUES (?, ?, ?)"))?.execute((
+      filter.to_be_bytes(),
+      bucket.to_be_bytes(),
+      clock.to_be_bytes(),
+    ))?;
+    Ok(())
+  }
+
+  fn init_compacted(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.compacted\" (
           bucket BLOB NOT NULL,
           clock BLOB NOT NULL,
           PRIMARY KEY (bucket)
         ) STRICT, WITHOUT ROWID;
         "
-      ))
-      .unwrap();
+    ))?;
+    Ok(())
+  }
+
+  fn get_compacted(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT bucket, clock FROM \"{prefix}.{name}.compacted\""))?
+        .query_map((), |row| {
+          let bucket = row.get(0)?;
+          let clock = row.get(1)?;
+          Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(clock)))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
   }
 
-  fn get_buckets(&self, prefix: &str, name: &str) -> BTreeMap<u64, u64> {
+  fn set_compacted(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
     self
-      .prepare_cached(&format!("SELECT bucket, clock FROM \"{prefix}.{name}.buckets\""))
-      .unwrap()
-      .query_map((), |row| {
-        let bucket = row.get(0).unwrap();
-        let clock = row.get(1).unwrap();
-        Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(clock)))
-      })
-      .unwrap()
-      .map(Result::unwrap)
-      .collect()
+      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.compacted\" VALUES (?, ?)"))?
+      .execute((bucket.to_be_bytes(), clock.to_be_bytes()))?;
+    Ok(())
+  }
+
+  fn init_ranges(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.ranges\" (
+          bucket BLOB NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, lower)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_ranges(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError> {
+    let mut res = BTreeMap::<u64, BTreeMap<u64, u64>>::new();
+    let mut stmt = self.prepare_cached(&format!("SELECT bucket, lower, upper FROM \"{prefix}.{name}.ranges\""))?;
+    let rows = stmt.query_map((), |row| {
+      let bucket = row.get(0)?;
+      let lower = row.get(1)?;
+      let upper = row.get(2)?;
+      Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(lower), u64::from_be_bytes(upper)))
+    })?;
+    for row in rows {
+      let (bucket, lower, upper) = row?;
+      res.entry(bucket).or_default().insert(lower, upper);
+    }
+    Ok(res)
+  }
+
+  fn set_ranges(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    ranges: &BTreeMap<u64, u64>,
+  ) -> Result<(), StoreError> {
+    self
+      .prepare_cached(&format!("DELETE FROM \"{prefix}.{name}.ranges\" WHERE bucket = ?"))?
+      .execute((bucket.to_be_bytes(),))?;
+    let mut stmt = self.prepare_cached(&format!("INSERT INTO \"{prefix}.{name}.ranges\" VALUES (?, ?, ?)"))?;
+    for (lower, upper) in ranges {
+      stmt.execute((bucket.to_be_bytes(), lower.to_be_bytes(), upper.to_be_bytes()))?;
+    }
+    Ok(())
+  }
+
+  fn init_seen(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.{name}.seen\" (
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, clock)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_seen(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError> {
+    let mut res = BTreeMap::<u64, BTreeMap<u64, u64>>::new();
+    let mut stmt = self.prepare_cached(&format!("SELECT bucket, clock, at FROM \"{prefix}.{name}.seen\""))?;
+    let rows = stmt.query_map((), |row| {
+      let bucket = row.get(0)?;
+      let clock = row.get(1)?;
+      let at = row.get(2)?;
+      Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(clock), u64::from_be_bytes(at)))
+    })?;
+    for row in rows {
+      let (bucket, clock, at) = row?;
+      res.entry(bucket).or_default().insert(clock, at);
+    }
+    Ok(res)
+  }
+
+  fn set_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64, at: u64) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.seen\" VALUES (?, ?, ?)"))?.execute((
+      bucket.to_be_bytes(),
+      clock.to_be_bytes(),
+      at.to_be_bytes(),
+    ))?;
+    Ok(())
   }
 
-  fn set_bucket(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) {
+  fn del_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
     self
-      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.buckets\" VALUES (?, ?)"))
-      .unwrap()
-      .execute((bucket.to_be_bytes(), clock.to_be_bytes()))
-      .unwrap();
+      .prepare_cached(&format!("DELETE FROM \"{prefix}.{name}.seen\" WHERE bucket = ? AND clock = ?"))?
+      .execute((bucket.to_be_bytes(), clock.to_be_bytes()))?;
+    Ok(())
+  }
+}
+
+impl StructureMetadataTransactor for MemoryTransactor {
+  fn init_buckets(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_buckets(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    self.scan(&format!("{prefix}.{name}.buckets"), &()).collect()
+  }
+
+  fn set_bucket(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.{name}.buckets"), &bucket, &clock);
+    Ok(())
+  }
+
+  fn init_filters(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_filters(&self, prefix: &str, name: &str) -> Result<BTreeMap<(u64, u64), u64>, StoreError> {
+    self.scan(&format!("{prefix}.{name}.filters"), &()).collect()
+  }
+
+  fn set_filter(&mut self, prefix: &str, name: &str, filter: u64, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.{name}.filters"), &(filter, bucket), &clock);
+    Ok(())
+  }
+
+  fn init_compacted(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_compacted(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    self.scan(&format!("{prefix}.{name}.compacted"), &()).collect()
+  }
+
+  fn set_compacted(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.{name}.compacted"), &bucket, &clock);
+    Ok(())
+  }
+
+  fn init_ranges(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_ranges(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError> {
+    let mut res = BTreeMap::<u64, BTreeMap<u64, u64>>::new();
+    for entry in self.scan::<(u64, u64), u64>(&format!("{prefix}.{name}.ranges"), &()) {
+      let ((bucket, lower), upper) = entry?;
+      res.entry(bucket).or_default().insert(lower, upper);
+    }
+    Ok(res)
+  }
+
+  fn set_ranges(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    ranges: &BTreeMap<u64, u64>,
+  ) -> Result<(), StoreError> {
+    self.remove_prefix(&format!("{prefix}.{name}.ranges"), &bucket);
+    for (lower, upper) in ranges {
+      self.write(&format!("{prefix}.{name}.ranges"), &(bucket, lower), upper);
+    }
+    Ok(())
+  }
+
+  fn init_seen(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_seen(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, BTreeMap<u64, u64>>, StoreError> {
+    let mut res = BTreeMap::<u64, BTreeMap<u64, u64>>::new();
+    for entry in self.scan::<(u64, u64), u64>(&format!("{prefix}.{name}.seen"), &()) {
+      let ((bucket, clock), at) = entry?;
+      res.entry(bucket).or_default().insert(clock, at);
+    }
+    Ok(res)
+  }
+
+  fn set_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64, at: u64) -> Result<(), StoreError> {
+    self.write(&format!("{prefix}.{name}.seen"), &(bucket, clock), &at);
+    Ok(())
+  }
+
+  fn del_seen(&mut self, prefix: &str, name: &str, bucket: u64, clock: u64) -> Result<(), StoreError> {
+    self.remove(&format!("{prefix}.{name}.seen"), &(bucket, clock));
+    Ok(())
   }
 }
 
@@ -268,24 +1325,77 @@ mod tests {
   fn workspace_metadata_simple() {
     let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
 
-    let workspace = WorkspaceMetadata::new("workspace", &mut txr);
+    let workspace = WorkspaceMetadata::new("workspace", &mut txr).unwrap();
     assert_eq!(workspace.prefix(), "workspace");
     let this = workspace.this();
+    let id = workspace.id();
 
-    let another_workspace = WorkspaceMetadata::new("another_workspace", &mut txr);
+    let mut another_workspace = WorkspaceMetadata::new("another_workspace", &mut txr).unwrap();
     assert_eq!(another_workspace.prefix(), "another_workspace");
     assert_ne!(another_workspace.this(), this);
+    assert_ne!(another_workspace.id(), id);
 
-    let workspace = WorkspaceMetadata::new("workspace", &mut txr);
+    let workspace = WorkspaceMetadata::new("workspace", &mut txr).unwrap();
     assert_eq!(workspace.prefix(), "workspace");
     assert_eq!(workspace.this(), this);
+    assert_eq!(workspace.id(), id);
+
+    another_workspace.set_id(&mut txr, id).unwrap();
+    let another_workspace = WorkspaceMetadata::new("another_workspace", &mut txr).unwrap();
+    assert_ne!(another_workspace.this(), this);
+    assert_eq!(another_workspace.id(), id);
+  }
+
+  #[test]
+  fn workspace_metadata_version() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    WorkspaceMetadata::new("workspace", &mut txr).unwrap();
+    assert_eq!(txr.get_version("workspace").unwrap(), Some(CURRENT_VERSION));
+
+    // Databases written by a newer library are not touched.
+    txr.put_version("workspace", CURRENT_VERSION + 1).unwrap();
+    let err = WorkspaceMetadata::new("workspace", &mut txr).unwrap_err();
+    assert!(matches!(err, StoreError::UnsupportedSchema(version, CURRENT_VERSION) if version == CURRENT_VERSION + 1));
+    assert_eq!(txr.get_version("workspace").unwrap(), Some(CURRENT_VERSION + 1));
+
+    txr.put_version("workspace", 0).unwrap();
+    assert!(matches!(WorkspaceMetadata::new("workspace", &mut txr), Err(StoreError::UnsupportedSchema(0, _))));
+  }
+
+  #[test]
+  fn workspace_metadata_migrate() {
+    let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
+    WorkspaceMetadata::new("workspace", &mut txr).unwrap();
+    txr.put_version("workspace", 1).unwrap();
+
+    // A dummy upgrade from version 1 to 2, run through the same loop as the real ones.
+    fn add_note(txr: &Transactor, prefix: &str) -> rusqlite::Result<()> {
+      txr.execute_batch(&format!("ALTER TABLE \"{prefix}.this\" ADD COLUMN note TEXT DEFAULT 'migrated'"))
+    }
+    let migrations: [Migration; 1] = [add_note];
+    let mut runs = Vec::new();
+    let migrate = |txr: &mut Transactor, version: u64| -> Result<(), StoreError> {
+      runs.push(version);
+      migrations[version as usize - 1](txr, "workspace")?;
+      Ok(())
+    };
+    upgrade(&mut txr, "workspace", 2, migrate).unwrap();
+    assert_eq!(runs, [1]);
+    assert_eq!(txr.get_version("workspace").unwrap(), Some(2));
+    let note: String = txr.query_row("SELECT note FROM \"workspace.this\"", (), |row| row.get(0)).unwrap();
+    assert_eq!(note, "migrated");
+
+    // Upgraded databases are not migrated again.
+    upgrade(&mut txr, "workspace", 2, |_, _| unreachable!()).unwrap();
+    let count: u64 = txr.query_row("SELECT COUNT(*) FROM \"workspace.version\"", (), |row| row.get(0)).unwrap();
+    assert_eq!(count, 1);
   }
 
   #[test]
   fn structure_metadata_simple() {
     let mut txr: Transactor = Connection::open_in_memory().unwrap().try_into().unwrap();
 
-    let mut structure = StructureMetadata::new("workspace", "name", &mut txr);
+    let mut structure = StructureMetadata::new("workspace", "name", &mut txr).unwrap();
     assert_eq!(structure.prefix(), "workspace");
     assert_eq!(structure.name(), "name");
     assert_eq!(structure.buckets().len(), 0);
@@ -303,11 +1413,11 @@ mod tests {
     structure.update(2, 2u64);
     assert_eq!(structure.buckets().get(&2).unwrap(), &3);
 
-    structure.save(&mut txr);
+    structure.save(&mut txr).unwrap();
     assert_eq!(structure.buckets().get(&1).unwrap(), &4);
     assert_eq!(structure.buckets().get(&2).unwrap(), &3);
 
-    let mut structure = StructureMetadata::new("workspace", "name", &mut txr);
+    let mut structure = StructureMetadata::new("workspace", "name", &mut txr).unwrap();
     assert_eq!(structure.prefix(), "workspace");
     assert_eq!(structure.name(), "name");
     assert_eq!(structure.buckets(), BTreeMap::from([(1, 4u64), (2, 3u64)]));
@@ -315,12 +1425,85 @@ mod tests {
     structure.update(3, 3u64);
     assert_eq!(structure.buckets(), BTreeMap::from([(1, 4u64), (2, 3u64), (3, 3u64)]));
 
-    let structure = StructureMetadata::new("workspace", "name", &mut txr);
+    let structure = StructureMetadata::new("workspace", "name", &mut txr).unwrap();
     assert_eq!(structure.buckets(), BTreeMap::from([(1, 4u64), (2, 3u64)]));
 
-    let structure = StructureMetadata::new("workspace", "another_name", &mut txr);
+    let structure = StructureMetadata::new("workspace", "another_name", &mut txr).unwrap();
     assert_eq!(structure.prefix(), "workspace");
     assert_eq!(structure.name(), "another_name");
     assert_eq!(structure.buckets().len(), 0);
   }
+
+  #[test]
+  fn structure_metadata_ranges() {
+    fn check(txr: &mut impl StructureMetadataTransactor) {
+      let mut structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      structure.extend(1, None, 2);
+      structure.extend(1, Some(5), 7);
+      structure.extend(1, Some(9), 10);
+      assert_eq!(structure.buckets(), BTreeMap::from([(1, 2)]));
+      structure.save(txr).unwrap();
+
+      // Out-of-order ranges survive reopening, so filling the gap later
+      // advances the bucket over them.
+      let mut structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      structure.extend(1, Some(2), 5);
+      assert_eq!(structure.buckets(), BTreeMap::from([(1, 7)]));
+      assert_eq!(structure.committed().buckets(), BTreeMap::from([(1, 2)]));
+      structure.save(txr).unwrap();
+
+      let mut structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      structure.extend(1, Some(7), 9);
+      assert_eq!(structure.buckets(), BTreeMap::from([(1, 10)]));
+    }
+
+    check(&mut TryInto::<Transactor>::try_into(Connection::open_in_memory().unwrap()).unwrap());
+    check(&mut MemoryTransactor::new());
+  }
+
+  #[test]
+  fn structure_metadata_clock() {
+    let mut txr = MemoryTransactor::new();
+    let mut structure = StructureMetadata::new("workspace", "name", &mut txr).unwrap();
+    let clock = structure.next();
+    assert_eq!(physical(clock), clock);
+    assert!(structure.update(1, clock));
+
+    // A received clock value ahead of the physical time is followed by
+    // incrementing the logical counter, until the physical time passes it.
+    let hour = 60 * 60 * 1_000_000_000;
+    let future = physical(now() + hour);
+    assert!(future <= drift_limit(hour) && future + hour > drift_limit(hour));
+    structure.observe(future);
+    assert_eq!(structure.next(), future + 1);
+    assert!(structure.update(1, future + 1));
+    assert_eq!(structure.next(), future + 2);
+    assert_eq!(physical(structure.next()), future);
+  }
+
+  #[test]
+  fn structure_metadata_seen() {
+    fn check(txr: &mut impl StructureMetadataTransactor) {
+      let mut structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      structure.extend(1, None, 2);
+      structure.extend(1, Some(2), 4);
+      structure.extend(1, Some(5), 6);
+      let seen = structure.seen(None).remove(&1).unwrap();
+      assert_eq!(seen.keys().copied().collect::<Vec<_>>(), [2, 4]);
+      let (first, second) = (seen[&2], seen[&4]);
+      assert!(4 < first && first <= second && second <= structure.next());
+      assert!(structure.committed().seen(None).is_empty());
+      structure.save(txr).unwrap();
+
+      // Peers which have our writes up to `second` only need the last entry before it.
+      let mut structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      assert_eq!(structure.seen(Some(second)), BTreeMap::from([(1, BTreeMap::from([(4, second)]))]));
+      structure.forget(txr, u64::MAX).unwrap();
+      let structure = StructureMetadata::new("workspace", "name", txr).unwrap();
+      assert_eq!(structure.seen(None), BTreeMap::from([(1, BTreeMap::from([(4, second)]))]));
+    }
+
+    check(&mut TryInto::<Transactor>::try_into(Connection::open_in_memory().unwrap()).unwrap());
+    check(&mut MemoryTransactor::new());
+  }
 }
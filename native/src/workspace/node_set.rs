@@ -13,13 +13,16 @@
 // limitations under the License.
 
 use rusqlite::{OptionalExtension, Result, Row};
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
 
-use super::metadata::{StructureMetadata, StructureMetadataTransactor};
-use crate::Transactor;
+use super::{
+  metadata::{StructureMetadata, StructureMetadataTransactor},
+  PAGE_CHUNK,
+};
+use crate::{memory::MemoryTransactor, StoreError, Transactor};
 
 /// A last-writer-wins element set for storing nodes.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NodeSet {
   metadata: StructureMetadata,
   mods: BTreeMap<u128, (Option<Item>, Item)>,
@@ -34,24 +37,39 @@ fn item_lt(lhs: &Item, rhs: &Item) -> bool {
 
 /// Database interface for [`NodeSet`].
 pub trait NodeSetTransactor: StructureMetadataTransactor {
-  fn init(&mut self, prefix: &str, name: &str);
-  fn get(&self, prefix: &str, name: &str, id: u128) -> Option<Item>;
-  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item);
-  fn id_by_label(&self, prefix: &str, name: &str, label: u64) -> BTreeMap<u128, ()>;
-  fn by_bucket_clock_range(&self, prefix: &str, name: &str, bucket: u64, lower: Option<u64>) -> BTreeMap<u128, Item>;
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError>;
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError>;
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError>;
+  fn id_by_label(&self, prefix: &str, name: &str, label: u64) -> Result<BTreeMap<u128, ()>, StoreError>;
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError>;
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError>;
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError>;
 }
 
 impl NodeSet {
   /// Creates or loads data.
-  pub fn new(prefix: &'static str, name: &'static str, txr: &mut impl NodeSetTransactor) -> Self {
-    let metadata = StructureMetadata::new(prefix, name, txr);
+  pub fn new(prefix: &str, name: &'static str, txr: &mut impl NodeSetTransactor) -> Result<Self, StoreError> {
+    let metadata = StructureMetadata::new(prefix, name, txr)?;
     let mods = BTreeMap::new();
-    txr.init(prefix, name);
-    Self { metadata, mods }
+    txr.init(prefix, name)?;
+    Ok(Self { metadata, mods })
+  }
+
+  /// Returns a copy without pending modifications, for reading the saved
+  /// state through another connection.
+  pub fn committed(&self) -> Self {
+    Self { metadata: self.metadata.committed(), mods: BTreeMap::new() }
   }
 
   /// Returns the name of the workspace.
-  pub fn prefix(&self) -> &'static str {
+  pub fn prefix(&self) -> &str {
     self.metadata.prefix()
   }
 
@@ -65,11 +83,52 @@ impl NodeSet {
     self.metadata.buckets()
   }
 
-  /// Returns the largest clock value across all buckets plus one.
+  /// Returns the clock value for the next local write.
   pub fn next(&self) -> u64 {
     self.metadata.next()
   }
 
+  /// Returns whether all items in `bucket` up to clock `lower` have been received.
+  pub fn covers(&self, bucket: u64, lower: Option<u64>) -> bool {
+    self.metadata.covers(bucket, lower)
+  }
+
+  /// Records that all items in `bucket` with clock in `(lower, upper]` have been received.
+  pub fn extend(&mut self, bucket: u64, lower: Option<u64>, upper: u64) {
+    self.metadata.extend(bucket, lower, upper)
+  }
+
+  /// Returns whether no bucket has been received, with or without a filter.
+  pub fn is_pristine(&self) -> bool {
+    self.metadata.is_pristine()
+  }
+
+  /// Returns whether any bucket has been received with a filter.
+  pub fn is_filtered(&self) -> bool {
+    self.metadata.is_filtered()
+  }
+
+  /// Returns the current clock values for each bucket, for items matching `filter`.
+  pub fn filtered_buckets(&self, filter: u64) -> BTreeMap<u64, u64> {
+    self.metadata.filtered_buckets(filter)
+  }
+
+  /// Records that all items in `bucket` matching `filter` with clock in `(lower, upper]` have been received.
+  /// If `complete`, no item in the range was filtered out.
+  pub fn filtered_extend(&mut self, filter: u64, bucket: u64, lower: Option<u64>, upper: u64, complete: bool) {
+    self.metadata.filtered_extend(filter, bucket, lower, upper, complete)
+  }
+
+  /// Returns when buckets were received, see [`StructureMetadata::seen`].
+  pub fn seen(&self, since: Option<u64>) -> BTreeMap<u64, BTreeMap<u64, u64>> {
+    self.metadata.seen(since)
+  }
+
+  /// Forgets when buckets were received before local clock value `before`.
+  pub fn forget(&mut self, txr: &mut impl NodeSetTransactor, before: u64) -> Result<(), StoreError> {
+    self.metadata.forget(txr, before)
+  }
+
   /// Returns pending modifications.
   pub fn mods(&self) -> Vec<(u128, Option<u64>, Option<u64>)> {
     let mut res = Vec::new();
@@ -79,32 +138,47 @@ impl NodeSet {
     res
   }
 
-  pub fn exists(&self, txr: &impl NodeSetTransactor, id: u128) -> bool {
-    self.get(txr, id).and_then(|(_, _, label)| label).is_some()
+  /// Returns clock values of pending modifications in `bucket`.
+  pub fn pending(&self, bucket: u64) -> Vec<u64> {
+    self.mods.values().filter(|(_, curr)| curr.0 == bucket).map(|(_, curr)| curr.1).collect()
   }
 
-  pub fn get(&self, txr: &impl NodeSetTransactor, id: u128) -> Option<Item> {
-    self.mods.get(&id).map_or_else(|| txr.get(self.prefix(), self.name(), id), |(_, curr)| Some(*curr))
+  /// Returns the number of saved items by label, where removed items
+  /// which have not been compacted are counted under `None`.
+  pub fn count_by_label(&self, txr: &impl NodeSetTransactor) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    txr.count_by_label(self.prefix(), self.name())
   }
 
-  pub fn id_by_label(&self, txr: &impl NodeSetTransactor, label: u64) -> BTreeMap<u128, ()> {
-    let mut res = txr.id_by_label(self.prefix(), self.name(), label);
+  pub fn exists(&self, txr: &impl NodeSetTransactor, id: u128) -> Result<bool, StoreError> {
+    Ok(self.get(txr, id)?.and_then(|(_, _, label)| label).is_some())
+  }
+
+  pub fn get(&self, txr: &impl NodeSetTransactor, id: u128) -> Result<Option<Item>, StoreError> {
+    self.mods.get(&id).map_or_else(|| txr.get(self.prefix(), self.name(), id), |(_, curr)| Ok(Some(*curr)))
+  }
+
+  pub fn id_by_label(&self, txr: &impl NodeSetTransactor, label: u64) -> Result<BTreeMap<u128, ()>, StoreError> {
+    let mut res = txr.id_by_label(self.prefix(), self.name(), label)?;
     for (id, (_, (_, _, l))) in &self.mods {
       match l {
         Some(label_) if label_ == &label => res.insert(*id, ()),
         _ => res.remove(id),
       };
     }
-    res
+    Ok(res)
   }
 
   /// Returns all actions strictly later than given clock values.
   /// Absent entries are assumed to be `None`.
-  pub fn actions(&self, txr: &impl NodeSetTransactor, version: BTreeMap<u64, u64>) -> BTreeMap<u128, Item> {
+  pub fn actions(
+    &self,
+    txr: &impl NodeSetTransactor,
+    version: BTreeMap<u64, u64>,
+  ) -> Result<BTreeMap<u128, Item>, StoreError> {
     let mut res = BTreeMap::new();
     for &bucket in self.buckets().keys() {
       let lower = version.get(&bucket).copied();
-      for (id, item) in txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower) {
+      for (id, item) in txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, None)? {
         res.insert(id, item);
       }
     }
@@ -116,52 +190,163 @@ impl NodeSet {
         res.remove(id);
       }
     }
-    res
+    Ok(res)
+  }
+
+  /// Returns at most `limit` actions in `bucket` strictly later than `lower`,
+  /// in clock order.
+  pub fn bucket_actions(
+    &self,
+    txr: &impl NodeSetTransactor,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let mut res = txr.by_bucket_clock_range(self.prefix(), self.name(), bucket, lower, Some(limit))?;
+    res.retain(|(id, _)| !self.mods.contains_key(id));
+    for (id, (_, item)) in &self.mods {
+      if item.0 == bucket && Some(item.1) > lower {
+        res.push((*id, *item));
+      }
+    }
+    res.sort_by_key(|(_, item)| item.1);
+    res.truncate(limit as usize);
+    Ok(res)
   }
 
   /// Modifies item.
-  pub fn set(&mut self, txr: &impl NodeSetTransactor, id: u128, bucket: u64, clock: u64, l: Option<u64>) -> bool {
-    if self.metadata.update(bucket, clock) {
-      let item = (bucket, clock, l);
-      match self.mods.entry(id) {
-        Entry::Vacant(entry) => {
-          let prev = txr.get(self.metadata.prefix(), self.metadata.name(), id);
-          if prev.is_none() || item_lt(prev.as_ref().unwrap(), &item) {
-            entry.insert((prev, item));
-            return true;
-          }
+  pub fn set(
+    &mut self,
+    txr: &impl NodeSetTransactor,
+    id: u128,
+    bucket: u64,
+    clock: u64,
+    l: Option<u64>,
+  ) -> Result<bool, StoreError> {
+    Ok(self.metadata.update(bucket, clock) && self.merge(txr, id, bucket, clock, l)?)
+  }
+
+  /// Modifies item without checking or advancing the bucket clock. Used for
+  /// items received out of order.
+  pub fn merge(
+    &mut self,
+    txr: &impl NodeSetTransactor,
+    id: u128,
+    bucket: u64,
+    clock: u64,
+    l: Option<u64>,
+  ) -> Result<bool, StoreError> {
+    self.metadata.observe(clock);
+    let item = (bucket, clock, l);
+    match self.mods.entry(id) {
+      Entry::Vacant(entry) => {
+        let prev = txr.get(self.metadata.prefix(), self.metadata.name(), id)?;
+        if prev.is_none() || item_lt(prev.as_ref().unwrap(), &item) {
+          entry.insert((prev, item));
+          return Ok(true);
         }
-        Entry::Occupied(mut entry) => {
-          if item_lt(&entry.get().1, &item) {
-            entry.get_mut().1 = item;
-            return true;
-          }
+      }
+      Entry::Occupied(mut entry) => {
+        if item_lt(&entry.get().1, &item) {
+          entry.get_mut().1 = item;
+          return Ok(true);
         }
       }
     }
-    false
+    Ok(false)
+  }
+
+  /// Moves item to `bucket`, keeping its clock value and contents. Used when
+  /// this replica forks, see [`crate::workspace::Workspace::fork`].
+  pub fn relocate(&mut self, txr: &impl NodeSetTransactor, id: u128, bucket: u64) -> Result<(), StoreError> {
+    let (prev, mut curr) = match self.mods.remove(&id) {
+      Some(entry) => entry,
+      None => match txr.get(self.metadata.prefix(), self.metadata.name(), id)? {
+        Some(item) => (Some(item), item),
+        None => return Ok(()),
+      },
+    };
+    curr.0 = bucket;
+    self.metadata.update(bucket, curr.1);
+    self.mods.insert(id, (prev, curr));
+    Ok(())
+  }
+
+  /// Returns, for each bucket, the clock value up to which removed items may
+  /// have been physically deleted.
+  pub fn compacted(&self) -> &BTreeMap<u64, u64> {
+    self.metadata.compacted()
+  }
+
+  /// Records that removed items in `bucket` up to clock `clock` may have been
+  /// physically deleted elsewhere.
+  pub fn mark_compacted(
+    &mut self,
+    txr: &mut impl NodeSetTransactor,
+    bucket: u64,
+    clock: u64,
+  ) -> Result<(), StoreError> {
+    self.metadata.compact(txr, bucket, clock)
+  }
+
+  /// Physically deletes saved removed items in `bucket` up to clock `upper`.
+  /// Returns the number of deleted items.
+  pub fn compact(&mut self, txr: &mut impl NodeSetTransactor, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let res = txr.purge(self.prefix(), self.name(), bucket, upper)?;
+    if res > 0 {
+      self.metadata.compact(txr, bucket, upper)?;
+    }
+    Ok(res)
+  }
+
+  /// Returns present items in `bucket` with clock in `(lower, upper]` which
+  /// satisfy `matches` but are not in `live`, together with their clocks. Used
+  /// when a remote peer which sent all of its items in that range no longer
+  /// has the removals of these items.
+  pub fn missing(
+    &self,
+    txr: &impl NodeSetTransactor,
+    bucket: u64,
+    (lower, upper): (Option<u64>, u64),
+    live: &BTreeSet<u128>,
+    matches: impl Fn(&Item) -> bool,
+  ) -> Result<Vec<(u128, u64)>, StoreError> {
+    let mut res = Vec::new();
+    let mut lower = lower;
+    while lower < Some(upper) {
+      let chunk = self.bucket_actions(txr, bucket, lower, PAGE_CHUNK)?;
+      let Some((_, last)) = chunk.last() else { break };
+      lower = Some(last.1);
+      for (id, item) in chunk {
+        if item.1 <= upper && item.2.is_some() && !live.contains(&id) && matches(&item) {
+          res.push((id, item.1));
+        }
+      }
+    }
+    Ok(res)
   }
 
   /// Saves all pending modifications.
-  pub fn save(&mut self, txr: &mut impl NodeSetTransactor) {
-    self.metadata.save(txr);
+  pub fn save(&mut self, txr: &mut impl NodeSetTransactor) -> Result<(), StoreError> {
+    self.metadata.save(txr)?;
     for (id, (_, curr)) in std::mem::take(&mut self.mods) {
-      txr.set(self.prefix(), self.name(), id, curr);
+      txr.set(self.prefix(), self.name(), id, curr)?;
     }
+    Ok(())
   }
 }
 
-fn read_row(row: &Row<'_>) -> (u128, Item) {
-  let id = row.get(0).unwrap();
-  let bucket = row.get(1).unwrap();
-  let clock = row.get(2).unwrap();
-  let label: Option<_> = row.get(3).unwrap();
-  (u128::from_be_bytes(id), (u64::from_be_bytes(bucket), u64::from_be_bytes(clock), label.map(u64::from_be_bytes)))
+fn read_row(row: &Row<'_>) -> Result<(u128, Item)> {
+  let id = row.get(0)?;
+  let bucket = row.get(1)?;
+  let clock = row.get(2)?;
+  let label: Option<_> = row.get(3)?;
+  Ok((u128::from_be_bytes(id), (u64::from_be_bytes(bucket), u64::from_be_bytes(clock), label.map(u64::from_be_bytes))))
 }
 
-fn read_row_id(row: &Row<'_>) -> (u128, ()) {
-  let id = row.get(0).unwrap();
-  (u128::from_be_bytes(id), ())
+fn read_row_id(row: &Row<'_>) -> Result<(u128, ())> {
+  let id = row.get(0)?;
+  Ok((u128::from_be_bytes(id), ()))
 }
 
 fn make_row(id: u128, item: Item) -> ([u8; 16], [u8; 8], [u8; 8], Option<[u8; 8]>) {
@@ -169,8 +354,13 @@ fn make_row(id: u128, item: Item) -> ([u8; 16], [u8; 8], [u8; 8], Option<[u8; 8]
   (id.to_be_bytes(), bucket.to_be_bytes(), clock.to_be_bytes(), l.map(|label| label.to_be_bytes()))
 }
 
+fn read_row_label_count(row: &Row<'_>) -> Result<(Option<u64>, (u64, u64))> {
+  let label: Option<_> = row.get(0)?;
+  Ok((label.map(u64::from_be_bytes), (row.get(1)?, row.get(2)?)))
+}
+
 impl NodeSetTransactor for Transactor {
-  fn init(&mut self, prefix: &str, name: &str) {
+  fn init(&mut self, prefix: &str, name: &str) -> Result<(), StoreError> {
     self
       .execute_batch(&format!(
         "
@@ -185,54 +375,148 @@ impl NodeSetTransactor for Transactor {
         CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_label\" ON \"{prefix}.{name}.data\" (label);
         CREATE INDEX IF NOT EXISTS \"{prefix}.{name}.data.idx_bucket_clock\" ON \"{prefix}.{name}.data\" (bucket, clock);
         "
-      ))
-      .unwrap();
+      ))?;
+    Ok(())
   }
 
-  fn get(&self, prefix: &str, name: &str, id: u128) -> Option<Item> {
-    self
-      .prepare_cached(&format!(
-        "SELECT id, bucket, clock, label FROM \"{prefix}.{name}.data\"
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, bucket, clock, label FROM \"{prefix}.{name}.data\"
         WHERE id = ?"
-      ))
-      .unwrap()
-      .query_row((id.to_be_bytes(),), |row| Ok(read_row(row)))
-      .optional()
-      .unwrap()
-      .map(|(_, item)| item)
+        ))?
+        .query_row((id.to_be_bytes(),), read_row)
+        .optional()?
+        .map(|(_, item)| item),
+    )
   }
 
-  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) {
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
     self
-      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.data\" VALUES (?, ?, ?, ?)"))
-      .unwrap()
-      .execute(make_row(id, item))
-      .unwrap();
+      .prepare_cached(&format!("REPLACE INTO \"{prefix}.{name}.data\" VALUES (?, ?, ?, ?)"))?
+      .execute(make_row(id, item))?;
+    Ok(())
   }
 
-  fn id_by_label(&self, prefix: &str, name: &str, label: u64) -> BTreeMap<u128, ()> {
-    self
-      .prepare_cached(&format!(
-        "SELECT id FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label\"
+  fn id_by_label(&self, prefix: &str, name: &str, label: u64) -> Result<BTreeMap<u128, ()>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_label\"
         WHERE label = ?"
-      ))
-      .unwrap()
-      .query_map((label.to_be_bytes(),), |row| Ok(read_row_id(row)))
-      .unwrap()
-      .map(Result::unwrap)
-      .collect()
+        ))?
+        .query_map((label.to_be_bytes(),), read_row_id)?
+        .collect::<Result<_>>()?,
+    )
   }
 
-  fn by_bucket_clock_range(&self, prefix: &str, name: &str, bucket: u64, lower: Option<u64>) -> BTreeMap<u128, Item> {
-    self
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    Ok(self
       .prepare_cached(&format!(
         "SELECT id, bucket, clock, label FROM \"{prefix}.{name}.data\" INDEXED BY \"{prefix}.{name}.data.idx_bucket_clock\"
-        WHERE bucket = ? AND clock > ?"
-      ))
-      .unwrap()
-      .query_map((bucket.to_be_bytes(), lower.map(u64::to_be_bytes)), |row| Ok(read_row(row)))
-      .unwrap()
-      .map(Result::unwrap)
+        WHERE bucket = ?1 AND (?2 IS NULL OR clock > ?2) ORDER BY clock ASC LIMIT IFNULL(?3, -1)"
+      ))?
+      .query_map((bucket.to_be_bytes(), lower.map(u64::to_be_bytes), limit.and_then(|limit| i64::try_from(limit).ok())), read_row)?
+      .collect::<Result<_>>()?)
+  }
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "DELETE FROM \"{prefix}.{name}.data\" WHERE bucket = ? AND clock <= ? AND label IS NULL"
+        ))?
+        .execute((bucket.to_be_bytes(), upper.to_be_bytes()))? as u64,
+    )
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT label, COUNT(*), 0 FROM \"{prefix}.{name}.data\" GROUP BY label"))?
+        .query_map((), read_row_label_count)?
+        .collect::<Result<_>>()?,
+    )
+  }
+}
+
+/// Removes an item and its index entries, for [`MemoryTransactor`]. Returns
+/// the removed item.
+fn unindex(txr: &mut MemoryTransactor, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+  let Some(item): Option<Item> = txr.read(&format!("{prefix}.{name}.data"), &id)? else { return Ok(None) };
+  let (bucket, clock, l) = item;
+  txr.remove(&format!("{prefix}.{name}.data"), &id);
+  if let Some(label) = l {
+    txr.remove(&format!("{prefix}.{name}.data.idx_label"), &(label, id));
+  }
+  txr.remove(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id));
+  Ok(Some(item))
+}
+
+impl NodeSetTransactor for MemoryTransactor {
+  fn init(&mut self, _: &str, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get(&self, prefix: &str, name: &str, id: u128) -> Result<Option<Item>, StoreError> {
+    self.read(&format!("{prefix}.{name}.data"), &id)
+  }
+
+  fn set(&mut self, prefix: &str, name: &str, id: u128, item: Item) -> Result<(), StoreError> {
+    unindex(self, prefix, name, id)?;
+    let (bucket, clock, l) = item;
+    if let Some(label) = l {
+      self.write(&format!("{prefix}.{name}.data.idx_label"), &(label, id), &());
+    }
+    self.write(&format!("{prefix}.{name}.data.idx_bucket_clock"), &(bucket, clock, id), &());
+    self.write(&format!("{prefix}.{name}.data"), &id, &item);
+    Ok(())
+  }
+
+  fn id_by_label(&self, prefix: &str, name: &str, label: u64) -> Result<BTreeMap<u128, ()>, StoreError> {
+    self
+      .scan(&format!("{prefix}.{name}.data.idx_label"), &label)
+      .map(|entry| entry.map(|((_, id), ()): ((u64, u128), ())| (id, ())))
       .collect()
   }
+
+  fn by_bucket_clock_range(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: Option<u64>,
+  ) -> Result<Vec<(u128, Item)>, StoreError> {
+    let ids = self.by_bucket_clock(&format!("{prefix}.{name}.data.idx_bucket_clock"), bucket, lower, limit)?;
+    ids.into_iter().map(|id| Ok((id, self.read_indexed(&format!("{prefix}.{name}.data"), &id)?))).collect()
+  }
+
+  fn purge(&mut self, prefix: &str, name: &str, bucket: u64, upper: u64) -> Result<u64, StoreError> {
+    let ids: Vec<u128> = NodeSetTransactor::by_bucket_clock_range(self, prefix, name, bucket, None, None)?
+      .into_iter()
+      .take_while(|(_, (_, clock, _))| *clock <= upper)
+      .filter_map(|(id, (_, _, l))| l.is_none().then_some(id))
+      .collect();
+    for &id in &ids {
+      unindex(self, prefix, name, id)?;
+    }
+    Ok(ids.len() as u64)
+  }
+
+  fn count_by_label(&self, prefix: &str, name: &str) -> Result<BTreeMap<Option<u64>, (u64, u64)>, StoreError> {
+    let mut res: BTreeMap<Option<u64>, (u64, u64)> = BTreeMap::new();
+    for entry in self.scan::<u128, Item>(&format!("{prefix}.{name}.data"), &()) {
+      let (_, (_, _, l)) = entry?;
+      res.entry(l).or_default().0 += 1;
+    }
+    Ok(res)
+  }
 }
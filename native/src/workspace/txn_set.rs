@@ -0,0 +1,484 @@
+// Copyright 2024 ParkourLabs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{memory::MemoryTransactor, StoreError, Transactor};
+
+/// Stores transaction manifests, and remote items staged until all items of
+/// their transaction have been received.
+///
+/// A transaction consists of the writes of one barrier. Its ID is the largest
+/// clock value among them, and its manifest records, for each structure, the
+/// range of clock values written (all in the same bucket).
+#[derive(Debug, Clone)]
+pub struct TxnSet {
+  prefix: Arc<str>,
+}
+
+/// `name -> (lower, upper)`.
+pub type Manifest = BTreeMap<String, (u64, u64)>;
+
+/// Database interface for [`TxnSet`].
+pub trait TxnSetTransactor {
+  fn init_txns(&mut self, prefix: &str) -> Result<(), StoreError>;
+  fn get_txn(&self, prefix: &str, bucket: u64, txn: u64) -> Result<Manifest, StoreError>;
+  fn find_txn(&self, prefix: &str, bucket: u64, name: &str, clock: u64) -> Result<Option<u64>, StoreError>;
+  fn put_txn(
+    &mut self,
+    prefix: &str,
+    bucket: u64,
+    txn: u64,
+    name: &str,
+    lower: u64,
+    upper: u64,
+  ) -> Result<(), StoreError>;
+  fn prune_txns(&mut self, prefix: &str, bucket: u64, name: &str, upper: u64) -> Result<(), StoreError>;
+  fn put_staged(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    id: u128,
+    key: (u64, u64, u64),
+    item: &[u8],
+    at: u64,
+  ) -> Result<(), StoreError>;
+  fn get_staged(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Vec<u8>)>, StoreError>;
+  fn staged_txns(&self, prefix: &str) -> Result<Vec<(u64, u64, u64)>, StoreError>;
+  fn staged_lower(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError>;
+  fn take_staged(&mut self, prefix: &str, bucket: u64, txn: u64) -> Result<Vec<(String, u128, Vec<u8>)>, StoreError>;
+}
+
+impl TxnSet {
+  /// Creates or loads data.
+  pub fn new(prefix: &str, txr: &mut impl TxnSetTransactor) -> Result<Self, StoreError> {
+    txr.init_txns(prefix)?;
+    Ok(Self { prefix: prefix.into() })
+  }
+
+  /// Returns the manifest of a transaction.
+  pub fn manifest(&self, txr: &impl TxnSetTransactor, bucket: u64, txn: u64) -> Result<Manifest, StoreError> {
+    txr.get_txn(&self.prefix, bucket, txn)
+  }
+
+  /// Returns the transaction containing the given item, if it is known to be
+  /// part of a transaction with multiple items.
+  pub fn find(
+    &self,
+    txr: &impl TxnSetTransactor,
+    bucket: u64,
+    name: &str,
+    clock: u64,
+  ) -> Result<Option<u64>, StoreError> {
+    txr.find_txn(&self.prefix, bucket, name, clock)
+  }
+
+  /// Records that the items of structure `name` in `bucket` with clock in
+  /// `[lower, upper]` belong to a transaction.
+  pub fn record(
+    &mut self,
+    txr: &mut impl TxnSetTransactor,
+    bucket: u64,
+    txn: u64,
+    name: &str,
+    lower: u64,
+    upper: u64,
+  ) -> Result<(), StoreError> {
+    txr.put_txn(&self.prefix, bucket, txn, name, lower, upper)
+  }
+
+  /// Forgets manifest entries of structure `name` in `bucket` up to clock `upper`.
+  pub fn prune(
+    &mut self,
+    txr: &mut impl TxnSetTransactor,
+    bucket: u64,
+    name: &str,
+    upper: u64,
+  ) -> Result<(), StoreError> {
+    txr.prune_txns(&self.prefix, bucket, name, upper)
+  }
+
+  /// Stages a serialized item with key `(bucket, clock, txn)` until its
+  /// transaction is complete. `at` is the local time, which is kept for the
+  /// first item of each transaction.
+  pub fn stage(
+    &mut self,
+    txr: &mut impl TxnSetTransactor,
+    name: &str,
+    id: u128,
+    key: (u64, u64, u64),
+    item: &[u8],
+    at: u64,
+  ) -> Result<(), StoreError> {
+    txr.put_staged(&self.prefix, name, id, key, item, at)
+  }
+
+  /// Returns at most `limit` serialized staged items of structure `name` in
+  /// `bucket` strictly later than `lower`, in clock order.
+  pub fn staged(
+    &self,
+    txr: &impl TxnSetTransactor,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Vec<u8>)>, StoreError> {
+    txr.get_staged(&self.prefix, name, bucket, lower, limit)
+  }
+
+  /// Returns all transactions `(bucket, txn, at)` with staged items, where
+  /// `at` is the local time their first item was staged.
+  pub fn pending(&self, txr: &impl TxnSetTransactor) -> Result<Vec<(u64, u64, u64)>, StoreError> {
+    txr.staged_txns(&self.prefix)
+  }
+
+  /// Returns, for each bucket, the smallest clock value of staged items of
+  /// structure `name`.
+  pub fn staged_lower(&self, txr: &impl TxnSetTransactor, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    txr.staged_lower(&self.prefix, name)
+  }
+
+  /// Removes and returns all staged items `(name, id, item)` of a transaction.
+  pub fn release(
+    &mut self,
+    txr: &mut impl TxnSetTransactor,
+    bucket: u64,
+    txn: u64,
+  ) -> Result<Vec<(String, u128, Vec<u8>)>, StoreError> {
+    txr.take_staged(&self.prefix, bucket, txn)
+  }
+}
+
+impl TxnSetTransactor for Transactor {
+  fn init_txns(&mut self, prefix: &str) -> Result<(), StoreError> {
+    self.execute_batch(&format!(
+      "
+        CREATE TABLE IF NOT EXISTS \"{prefix}.txns\" (
+          bucket BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          name TEXT NOT NULL,
+          lower BLOB NOT NULL,
+          upper BLOB NOT NULL,
+          PRIMARY KEY (bucket, txn, name)
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS \"{prefix}.txns.idx_bucket_name_upper\" ON \"{prefix}.txns\" (bucket, name, upper);
+
+        CREATE TABLE IF NOT EXISTS \"{prefix}.staged\" (
+          name TEXT NOT NULL,
+          id BLOB NOT NULL,
+          bucket BLOB NOT NULL,
+          clock BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          item BLOB NOT NULL,
+          PRIMARY KEY (name, bucket, clock, id)
+        ) STRICT, WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS \"{prefix}.staged.idx_bucket_txn\" ON \"{prefix}.staged\" (bucket, txn);
+
+        CREATE TABLE IF NOT EXISTS \"{prefix}.staged_at\" (
+          bucket BLOB NOT NULL,
+          txn BLOB NOT NULL,
+          at BLOB NOT NULL,
+          PRIMARY KEY (bucket, txn)
+        ) STRICT, WITHOUT ROWID;
+        "
+    ))?;
+    Ok(())
+  }
+
+  fn get_txn(&self, prefix: &str, bucket: u64, txn: u64) -> Result<Manifest, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT name, lower, upper FROM \"{prefix}.txns\" WHERE bucket = ? AND txn = ?"))?
+        .query_map((bucket.to_be_bytes(), txn.to_be_bytes()), |row| {
+          let name = row.get(0)?;
+          let lower = row.get(1)?;
+          let upper = row.get(2)?;
+          Ok((name, (u64::from_be_bytes(lower), u64::from_be_bytes(upper))))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn find_txn(&self, prefix: &str, bucket: u64, name: &str, clock: u64) -> Result<Option<u64>, StoreError> {
+    Ok(self
+      .prepare_cached(&format!(
+        "SELECT txn, lower FROM \"{prefix}.txns\" WHERE bucket = ? AND name = ? AND upper >= ? ORDER BY upper ASC LIMIT 1"
+      ))?
+      .query_map((bucket.to_be_bytes(), name, clock.to_be_bytes()), |row| {
+        let txn = row.get(0)?;
+        let lower = row.get(1)?;
+        Ok((u64::from_be_bytes(txn), u64::from_be_bytes(lower)))
+      })?
+      .collect::<rusqlite::Result<Vec<_>>>()?
+      .into_iter()
+      .find_map(|(txn, lower)| (lower <= clock).then_some(txn)))
+  }
+
+  fn put_txn(
+    &mut self,
+    prefix: &str,
+    bucket: u64,
+    txn: u64,
+    name: &str,
+    lower: u64,
+    upper: u64,
+  ) -> Result<(), StoreError> {
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.txns\" VALUES (?, ?, ?, ?, ?)"))?.execute((
+      bucket.to_be_bytes(),
+      txn.to_be_bytes(),
+      name,
+      lower.to_be_bytes(),
+      upper.to_be_bytes(),
+    ))?;
+    Ok(())
+  }
+
+  fn prune_txns(&mut self, prefix: &str, bucket: u64, name: &str, upper: u64) -> Result<(), StoreError> {
+    self
+      .prepare_cached(&format!("DELETE FROM \"{prefix}.txns\" WHERE bucket = ? AND name = ? AND upper <= ?"))?
+      .execute((bucket.to_be_bytes(), name, upper.to_be_bytes()))?;
+    Ok(())
+  }
+
+  fn put_staged(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    id: u128,
+    key: (u64, u64, u64),
+    item: &[u8],
+    at: u64,
+  ) -> Result<(), StoreError> {
+    let (bucket, clock, txn) = key;
+    self.prepare_cached(&format!("REPLACE INTO \"{prefix}.staged\" VALUES (?, ?, ?, ?, ?, ?)"))?.execute((
+      name,
+      id.to_be_bytes(),
+      bucket.to_be_bytes(),
+      clock.to_be_bytes(),
+      txn.to_be_bytes(),
+      item,
+    ))?;
+    self.prepare_cached(&format!("INSERT OR IGNORE INTO \"{prefix}.staged_at\" VALUES (?, ?, ?)"))?.execute((
+      bucket.to_be_bytes(),
+      txn.to_be_bytes(),
+      at.to_be_bytes(),
+    ))?;
+    Ok(())
+  }
+
+  fn get_staged(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Vec<u8>)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!(
+          "SELECT id, item FROM \"{prefix}.staged\"
+        WHERE name = ?1 AND bucket = ?2 AND (?3 IS NULL OR clock > ?3) ORDER BY clock ASC LIMIT IFNULL(?4, -1)"
+        ))?
+        .query_map((name, bucket.to_be_bytes(), lower.map(u64::to_be_bytes), i64::try_from(limit).ok()), |row| {
+          let id = row.get(0)?;
+          let item = row.get(1)?;
+          Ok((u128::from_be_bytes(id), item))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn staged_txns(&self, prefix: &str) -> Result<Vec<(u64, u64, u64)>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT bucket, txn, at FROM \"{prefix}.staged_at\""))?
+        .query_map((), |row| {
+          let bucket = row.get(0)?;
+          let txn = row.get(1)?;
+          let at = row.get(2)?;
+          Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(txn), u64::from_be_bytes(at)))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn staged_lower(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    Ok(
+      self
+        .prepare_cached(&format!("SELECT bucket, MIN(clock) FROM \"{prefix}.staged\" WHERE name = ? GROUP BY bucket"))?
+        .query_map((name,), |row| {
+          let bucket = row.get(0)?;
+          let clock = row.get(1)?;
+          Ok((u64::from_be_bytes(bucket), u64::from_be_bytes(clock)))
+        })?
+        .collect::<rusqlite::Result<_>>()?,
+    )
+  }
+
+  fn take_staged(&mut self, prefix: &str, bucket: u64, txn: u64) -> Result<Vec<(String, u128, Vec<u8>)>, StoreError> {
+    let res = self
+      .prepare_cached(&format!(
+        "SELECT name, id, item FROM \"{prefix}.staged\" WHERE bucket = ? AND txn = ? ORDER BY clock ASC"
+      ))?
+      .query_map((bucket.to_be_bytes(), txn.to_be_bytes()), |row| {
+        let name = row.get(0)?;
+        let id = row.get(1)?;
+        let item = row.get(2)?;
+        Ok((name, u128::from_be_bytes(id), item))
+      })?
+      .collect::<rusqlite::Result<_>>()?;
+    self
+      .prepare_cached(&format!("DELETE FROM \"{prefix}.staged\" WHERE bucket = ? AND txn = ?"))?
+      .execute((bucket.to_be_bytes(), txn.to_be_bytes()))?;
+    self
+      .prepare_cached(&format!("DELETE FROM \"{prefix}.staged_at\" WHERE bucket = ? AND txn = ?"))?
+      .execute((bucket.to_be_bytes(), txn.to_be_bytes()))?;
+    Ok(res)
+  }
+}
+
+impl TxnSetTransactor for MemoryTransactor {
+  fn init_txns(&mut self, _: &str) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  fn get_txn(&self, prefix: &str, bucket: u64, txn: u64) -> Result<Manifest, StoreError> {
+    self
+      .scan(&format!("{prefix}.txns"), &(bucket, txn))
+      .map(|entry| entry.map(|((_, _, name), range): ((u64, u64, String), _)| (name, range)))
+      .collect()
+  }
+
+  fn find_txn(&self, prefix: &str, bucket: u64, name: &str, clock: u64) -> Result<Option<u64>, StoreError> {
+    for entry in self.range(&format!("{prefix}.txns.idx_bucket_name_upper"), &(bucket, name), &(bucket, name, clock)) {
+      let ((_, _, _, txn), lower): ((u64, String, u64, u64), u64) = entry?;
+      if lower <= clock {
+        return Ok(Some(txn));
+      }
+    }
+    Ok(None)
+  }
+
+  fn put_txn(
+    &mut self,
+    prefix: &str,
+    bucket: u64,
+    txn: u64,
+    name: &str,
+    lower: u64,
+    upper: u64,
+  ) -> Result<(), StoreError> {
+    if let Some((_, upper)) = self.read::<(u64, u64)>(&format!("{prefix}.txns"), &(bucket, txn, name))? {
+      self.remove(&format!("{prefix}.txns.idx_bucket_name_upper"), &(bucket, name, upper, txn));
+    }
+    self.write(&format!("{prefix}.txns"), &(bucket, txn, name), &(lower, upper));
+    self.write(&format!("{prefix}.txns.idx_bucket_name_upper"), &(bucket, name, upper, txn), &lower);
+    Ok(())
+  }
+
+  fn prune_txns(&mut self, prefix: &str, bucket: u64, name: &str, upper: u64) -> Result<(), StoreError> {
+    let mut txns: Vec<(u64, u64)> = Vec::new();
+    for entry in self.scan(&format!("{prefix}.txns.idx_bucket_name_upper"), &(bucket, name)) {
+      let ((_, _, upper_, txn), _): ((u64, String, u64, u64), u64) = entry?;
+      if upper_ > upper {
+        break;
+      }
+      txns.push((upper_, txn));
+    }
+    for (upper, txn) in txns {
+      self.remove(&format!("{prefix}.txns"), &(bucket, txn, name));
+      self.remove(&format!("{prefix}.txns.idx_bucket_name_upper"), &(bucket, name, upper, txn));
+    }
+    Ok(())
+  }
+
+  fn put_staged(
+    &mut self,
+    prefix: &str,
+    name: &str,
+    id: u128,
+    key: (u64, u64, u64),
+    item: &[u8],
+    at: u64,
+  ) -> Result<(), StoreError> {
+    let (bucket, clock, txn) = key;
+    if self.read::<u64>(&format!("{prefix}.staged_at"), &(bucket, txn))?.is_none() {
+      self.write(&format!("{prefix}.staged_at"), &(bucket, txn), &at);
+    }
+    if let Some((txn, _)) = self.read::<(u64, Vec<u8>)>(&format!("{prefix}.staged"), &(name, bucket, clock, id))? {
+      self.remove(&format!("{prefix}.staged.idx_bucket_txn"), &(bucket, txn, clock, name, id));
+    }
+    self.write(&format!("{prefix}.staged"), &(name, bucket, clock, id), &(txn, item));
+    self.write(&format!("{prefix}.staged.idx_bucket_txn"), &(bucket, txn, clock, name, id), &());
+    Ok(())
+  }
+
+  fn get_staged(
+    &self,
+    prefix: &str,
+    name: &str,
+    bucket: u64,
+    lower: Option<u64>,
+    limit: u64,
+  ) -> Result<Vec<(u128, Vec<u8>)>, StoreError> {
+    let Some(start) = lower.map_or(Some(0), |lower| lower.checked_add(1)) else { return Ok(Vec::new()) };
+    self
+      .range(&format!("{prefix}.staged"), &(name, bucket), &(name, bucket, start))
+      .take(usize::try_from(limit).unwrap_or(usize::MAX))
+      .map(|entry| entry.map(|((_, _, _, id), (_, item)): ((String, u64, u64, u128), (u64, Vec<u8>))| (id, item)))
+      .collect()
+  }
+
+  fn staged_txns(&self, prefix: &str) -> Result<Vec<(u64, u64, u64)>, StoreError> {
+    self
+      .scan(&format!("{prefix}.staged_at"), &())
+      .map(|entry| entry.map(|((bucket, txn), at): ((u64, u64), u64)| (bucket, txn, at)))
+      .collect()
+  }
+
+  fn staged_lower(&self, prefix: &str, name: &str) -> Result<BTreeMap<u64, u64>, StoreError> {
+    let mut res = BTreeMap::new();
+    for entry in self.scan::<(String, u64, u64, u128), (u64, Vec<u8>)>(&format!("{prefix}.staged"), &name) {
+      let ((_, bucket, clock, _), _) = entry?;
+      res.entry(bucket).or_insert(clock);
+    }
+    Ok(res)
+  }
+
+  fn take_staged(&mut self, prefix: &str, bucket: u64, txn: u64) -> Result<Vec<(String, u128, Vec<u8>)>, StoreError> {
+    let keys: Vec<(u64, String, u128)> = self
+      .scan(&format!("{prefix}.staged.idx_bucket_txn"), &(bucket, txn))
+      .map(|entry| entry.map(|((_, _, clock, name, id), ()): ((u64, u64, u64, String, u128), ())| (clock, name, id)))
+      .collect::<Result<_, _>>()?;
+    let mut res = Vec::new();
+    for (clock, name, id) in keys {
+      let (_, item): (u64, Vec<u8>) = self.read_indexed(&format!("{prefix}.staged"), &(&name, bucket, clock, id))?;
+      self.remove(&format!("{prefix}.staged"), &(&name, bucket, clock, id));
+      self.remove(&format!("{prefix}.staged.idx_bucket_txn"), &(bucket, txn, clock, &name, id));
+      res.push((name, id, item));
+    }
+    self.remove(&format!("{prefix}.staged_at"), &(bucket, txn));
+    Ok(res)
+  }
+}